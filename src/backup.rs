@@ -0,0 +1,766 @@
+use std::{
+    collections::BTreeMap,
+    fs::OpenOptions,
+    io::Write,
+    path::{Component, Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use rusqlite::{Connection, MAIN_DB};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{db, events::EVENT_MIGRATIONS, migrate, store::CAPSULE_MIGRATIONS, tls::sha256_hex};
+
+pub const BACKUP_FORMAT_VERSION: u32 = 1;
+pub const MANIFEST_ENTRY: &str = "manifest.json";
+const CAPSULE_DB_ENTRY: &str = "capsules.sqlite3";
+const EVENTS_DB_ENTRY: &str = "events.sqlite3";
+const FLAGS_ENTRY: &str = "flags.toml";
+const TLS_PREFIX: &str = "tls/";
+const BLOCK: usize = 512;
+
+/// Where each piece of Nexum state lives. `create_backup` skips paths that do
+/// not exist; `restore_backup` needs a target for every component in the
+/// bundle.
+#[derive(Debug, Clone, Default)]
+pub struct BackupPaths {
+    pub capsule_db: Option<PathBuf>,
+    pub events_db: Option<PathBuf>,
+    pub flags: Option<PathBuf>,
+    pub tls_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackupComponent {
+    CapsuleDb,
+    EventsDb,
+    Flags,
+    Tls,
+}
+
+impl BackupComponent {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::CapsuleDb => "capsule_db",
+            Self::EventsDb => "events_db",
+            Self::Flags => "flags",
+            Self::Tls => "tls",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupFile {
+    pub path: String,
+    pub component: BackupComponent,
+    pub bytes: u64,
+    pub sha256: String,
+    pub mode: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub format_version: u32,
+    pub nexum_version: String,
+    pub created_unix_ms: u64,
+    pub capsule_schema_version: Option<u32>,
+    pub events_schema_version: Option<u32>,
+    pub files: Vec<BackupFile>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupReport {
+    pub bundle: String,
+    pub manifest: BackupManifest,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RestoredComponent {
+    pub component: BackupComponent,
+    pub target: String,
+    pub files: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RestoreReport {
+    pub bundle: String,
+    pub dry_run: bool,
+    pub created_unix_ms: u64,
+    pub restored: Vec<RestoredComponent>,
+}
+
+#[derive(Debug, Error)]
+pub enum BackupError {
+    #[error("db: {0}")]
+    Db(#[from] rusqlite::Error),
+    #[error("io: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("manifest: {0}")]
+    Manifest(#[from] serde_json::Error),
+    #[error("nothing to back up: none of the given paths exist")]
+    NothingToBackUp,
+    #[error("invalid bundle: {0}")]
+    InvalidBundle(String),
+    #[error("bundle has no {MANIFEST_ENTRY}")]
+    MissingManifest,
+    #[error("unsupported bundle format version {0}; upgrade nexum")]
+    UnsupportedFormat(u32),
+    #[error("bundle is missing '{0}' listed in its manifest")]
+    MissingEntry(String),
+    #[error("bundle contains '{0}' which is not in its manifest")]
+    UnexpectedEntry(String),
+    #[error("checksum mismatch for '{0}'")]
+    ChecksumMismatch(String),
+    #[error("unsafe path in bundle: {0}")]
+    UnsafePath(String),
+    #[error("{database} schema version {found} is newer than supported version {supported}")]
+    NewerSchema {
+        database: String,
+        found: u32,
+        supported: u32,
+    },
+    #[error("bundle contains {0} but no target path was given for it")]
+    MissingTarget(&'static str),
+    #[error("restored database '{0}' failed integrity_check")]
+    CorruptDatabase(String),
+}
+
+struct Entry {
+    path: String,
+    mode: u32,
+    data: Vec<u8>,
+}
+
+/// Writes a tar bundle of every existing path in `paths` plus a manifest with
+/// per-file SHA-256. Databases are copied with the SQLite online backup API,
+/// so the snapshot is consistent even while other processes write to them.
+pub fn create_backup(paths: &BackupPaths, out: &Path) -> Result<BackupReport, BackupError> {
+    let mut entries = Vec::new();
+    let mut files = Vec::new();
+    let mut capsule_schema_version = None;
+    let mut events_schema_version = None;
+
+    for (component, source, entry, schema_version) in [
+        (
+            BackupComponent::CapsuleDb,
+            &paths.capsule_db,
+            CAPSULE_DB_ENTRY,
+            &mut capsule_schema_version,
+        ),
+        (
+            BackupComponent::EventsDb,
+            &paths.events_db,
+            EVENTS_DB_ENTRY,
+            &mut events_schema_version,
+        ),
+    ] {
+        let Some(source) = source.as_ref().filter(|path| path.exists()) else {
+            continue;
+        };
+        let (version, data) = snapshot_database(source, &temporary_path(out, entry))?;
+        *schema_version = Some(version);
+        push_entry(&mut entries, &mut files, component, entry, 0o600, data);
+    }
+
+    if let Some(flags) = paths.flags.as_ref().filter(|path| path.is_file()) {
+        let data = std::fs::read(flags)?;
+        push_entry(
+            &mut entries,
+            &mut files,
+            BackupComponent::Flags,
+            FLAGS_ENTRY,
+            file_mode(flags)?,
+            data,
+        );
+    }
+
+    if let Some(tls_dir) = paths.tls_dir.as_ref().filter(|path| path.is_dir()) {
+        for relative in walk_files(tls_dir)? {
+            let source = tls_dir.join(&relative);
+            let data = std::fs::read(&source)?;
+            push_entry(
+                &mut entries,
+                &mut files,
+                BackupComponent::Tls,
+                &format!("{TLS_PREFIX}{relative}"),
+                file_mode(&source)?,
+                data,
+            );
+        }
+    }
+
+    if files.is_empty() {
+        return Err(BackupError::NothingToBackUp);
+    }
+
+    let manifest = BackupManifest {
+        format_version: BACKUP_FORMAT_VERSION,
+        nexum_version: env!("CARGO_PKG_VERSION").to_string(),
+        created_unix_ms: now_unix_ms(),
+        capsule_schema_version,
+        events_schema_version,
+        files,
+    };
+    entries.insert(
+        0,
+        Entry {
+            path: MANIFEST_ENTRY.to_string(),
+            mode: 0o644,
+            data: serde_json::to_vec_pretty(&manifest)?,
+        },
+    );
+
+    if let Some(parent) = out.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mtime = manifest.created_unix_ms / 1000;
+    write_atomic(out, &write_tar(&entries, mtime)?, 0o600)?;
+
+    Ok(BackupReport {
+        bundle: out.display().to_string(),
+        manifest,
+    })
+}
+
+/// Validates `bundle` and puts each component back at its target. Every
+/// component is first staged beside its target, with databases passing
+/// `PRAGMA integrity_check`; only when all of them staged cleanly are they
+/// swapped in, so a bad component leaves the live state untouched. Processes
+/// that hold the databases open (such as `nexumd`) should be stopped first.
+pub fn restore_backup(
+    bundle: &Path,
+    targets: &BackupPaths,
+    dry_run: bool,
+) -> Result<RestoreReport, BackupError> {
+    let (manifest, mut entries) = read_bundle(bundle)?;
+
+    let mut counts = BTreeMap::<BackupComponent, u32>::new();
+    for file in &manifest.files {
+        *counts.entry(file.component).or_default() += 1;
+    }
+    let mut restored = Vec::new();
+    for (component, files) in counts {
+        let target = match component {
+            BackupComponent::CapsuleDb => &targets.capsule_db,
+            BackupComponent::EventsDb => &targets.events_db,
+            BackupComponent::Flags => &targets.flags,
+            BackupComponent::Tls => &targets.tls_dir,
+        }
+        .clone()
+        .ok_or(BackupError::MissingTarget(component.as_str()))?;
+        restored.push((component, target, files));
+    }
+
+    if !dry_run {
+        let mut staged = Vec::new();
+        let result = (|| -> Result<(), BackupError> {
+            for (component, target, _) in &restored {
+                staged.push(match component {
+                    BackupComponent::CapsuleDb => stage_database(
+                        &entries.remove(CAPSULE_DB_ENTRY).unwrap_or_default(),
+                        target,
+                    )?,
+                    BackupComponent::EventsDb => stage_database(
+                        &entries.remove(EVENTS_DB_ENTRY).unwrap_or_default(),
+                        target,
+                    )?,
+                    BackupComponent::Flags => {
+                        let (mode, data) = entries.remove(FLAGS_ENTRY).unwrap_or_default();
+                        stage_file(&data, mode, target)?
+                    }
+                    BackupComponent::Tls => {
+                        let tls = entries
+                            .iter()
+                            .filter_map(|(path, entry)| {
+                                path.strip_prefix(TLS_PREFIX)
+                                    .map(|relative| (relative.to_string(), entry.clone()))
+                            })
+                            .collect::<Vec<_>>();
+                        stage_directory(&tls, target)?
+                    }
+                });
+            }
+            Ok(())
+        })();
+        if let Err(error) = result {
+            staged.iter().for_each(Staged::discard);
+            return Err(error);
+        }
+        let mut pending = staged.into_iter();
+        while let Some(next) = pending.next() {
+            if let Err(error) = next.commit() {
+                pending.for_each(|rest| rest.discard());
+                return Err(error);
+            }
+        }
+    }
+
+    Ok(RestoreReport {
+        bundle: bundle.display().to_string(),
+        dry_run,
+        created_unix_ms: manifest.created_unix_ms,
+        restored: restored
+            .into_iter()
+            .map(|(component, target, files)| RestoredComponent {
+                component,
+                target: target.display().to_string(),
+                files,
+            })
+            .collect(),
+    })
+}
+
+type BundleEntries = BTreeMap<String, (u32, Vec<u8>)>;
+
+fn read_bundle(bundle: &Path) -> Result<(BackupManifest, BundleEntries), BackupError> {
+    let mut entries = BundleEntries::new();
+    for entry in read_tar(&std::fs::read(bundle)?)? {
+        if !is_safe_relative(&entry.path) {
+            return Err(BackupError::UnsafePath(entry.path));
+        }
+        entries.insert(entry.path, (entry.mode, entry.data));
+    }
+
+    let (_, manifest) = entries
+        .remove(MANIFEST_ENTRY)
+        .ok_or(BackupError::MissingManifest)?;
+    let manifest: BackupManifest = serde_json::from_slice(&manifest)?;
+    if manifest.format_version != BACKUP_FORMAT_VERSION {
+        return Err(BackupError::UnsupportedFormat(manifest.format_version));
+    }
+    check_schema(
+        "capsules",
+        manifest.capsule_schema_version,
+        CAPSULE_MIGRATIONS,
+    )?;
+    check_schema("events", manifest.events_schema_version, EVENT_MIGRATIONS)?;
+
+    for file in &manifest.files {
+        let (_, data) = entries
+            .get(&file.path)
+            .ok_or_else(|| BackupError::MissingEntry(file.path.clone()))?;
+        if data.len() as u64 != file.bytes || sha256_hex(data) != file.sha256 {
+            return Err(BackupError::ChecksumMismatch(file.path.clone()));
+        }
+    }
+    if let Some(extra) = entries
+        .keys()
+        .find(|path| !manifest.files.iter().any(|file| &file.path == *path))
+    {
+        return Err(BackupError::UnexpectedEntry(extra.clone()));
+    }
+
+    Ok((manifest, entries))
+}
+
+fn check_schema(
+    database: &str,
+    found: Option<u32>,
+    migrations: &[migrate::Migration],
+) -> Result<(), BackupError> {
+    let supported = migrations
+        .iter()
+        .map(|migration| migration.version)
+        .max()
+        .unwrap_or(0);
+    match found {
+        Some(found) if found > supported => Err(BackupError::NewerSchema {
+            database: database.to_string(),
+            found,
+            supported,
+        }),
+        _ => Ok(()),
+    }
+}
+
+fn snapshot_database(source: &Path, temp: &Path) -> Result<(u32, Vec<u8>), BackupError> {
+    let result = (|| -> Result<(u32, Vec<u8>), BackupError> {
+        let conn = db::open(source)?;
+        let version = migrate::schema_version(&conn).map_err(|error| match error {
+            migrate::MigrationError::Db(error) => BackupError::Db(error),
+            other => BackupError::InvalidBundle(other.to_string()),
+        })?;
+        conn.backup(MAIN_DB, temp, None)?;
+        Ok((version, std::fs::read(temp)?))
+    })();
+    let _ = std::fs::remove_file(temp);
+    result
+}
+
+/// A component written beside its target, waiting to be swapped in.
+enum Staged {
+    File {
+        temp: PathBuf,
+        target: PathBuf,
+        database: bool,
+    },
+    Directory {
+        staging: PathBuf,
+        target: PathBuf,
+    },
+}
+
+impl Staged {
+    fn commit(&self) -> Result<(), BackupError> {
+        match self {
+            Self::File {
+                temp,
+                target,
+                database,
+            } => {
+                std::fs::rename(temp, target)?;
+                if *database {
+                    // The live WAL and shared memory belong to the replaced
+                    // file; drop them only once the new one is in place.
+                    for suffix in ["-wal", "-shm"] {
+                        let sidecar = PathBuf::from(format!("{}{suffix}", target.display()));
+                        if sidecar.exists() {
+                            std::fs::remove_file(sidecar)?;
+                        }
+                    }
+                }
+            }
+            Self::Directory { staging, target } => {
+                if target.exists() {
+                    let retired = temporary_path(target, "old");
+                    std::fs::rename(target, &retired)?;
+                    std::fs::rename(staging, target)?;
+                    std::fs::remove_dir_all(retired)?;
+                } else {
+                    std::fs::rename(staging, target)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn discard(&self) {
+        match self {
+            Self::File { temp, .. } => {
+                let _ = std::fs::remove_file(temp);
+            }
+            Self::Directory { staging, .. } => {
+                let _ = std::fs::remove_dir_all(staging);
+            }
+        }
+    }
+}
+
+fn stage_file(data: &[u8], mode: u32, target: &Path) -> Result<Staged, BackupError> {
+    Ok(Staged::File {
+        temp: write_staged(data, mode, target)?,
+        target: target.to_path_buf(),
+        database: false,
+    })
+}
+
+fn stage_database((mode, data): &(u32, Vec<u8>), target: &Path) -> Result<Staged, BackupError> {
+    let temp = write_staged(data, *mode, target)?;
+    let checked = (|| -> Result<(), BackupError> {
+        let ok = Connection::open(&temp)?
+            .query_row("PRAGMA integrity_check", [], |row| row.get::<_, String>(0))?;
+        if ok != "ok" {
+            return Err(BackupError::CorruptDatabase(target.display().to_string()));
+        }
+        Ok(())
+    })();
+    if let Err(error) = checked {
+        let _ = std::fs::remove_file(&temp);
+        return Err(error);
+    }
+    Ok(Staged::File {
+        temp,
+        target: target.to_path_buf(),
+        database: true,
+    })
+}
+
+fn write_staged(data: &[u8], mode: u32, target: &Path) -> Result<PathBuf, BackupError> {
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let temp = temporary_path(target, "restore");
+    if let Err(error) = write_new(&temp, data, mode) {
+        let _ = std::fs::remove_file(&temp);
+        return Err(error);
+    }
+    Ok(temp)
+}
+
+/// Builds the directory beside `target` so it can replace it whole, and files
+/// created after the backup do not survive the restore.
+fn stage_directory(
+    files: &[(String, (u32, Vec<u8>))],
+    target: &Path,
+) -> Result<Staged, BackupError> {
+    let staging = temporary_path(target, "restore");
+    let result = (|| -> Result<(), BackupError> {
+        for (relative, (mode, data)) in files {
+            let path = staging.join(relative);
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            write_new(&path, data, *mode)?;
+        }
+        std::fs::create_dir_all(&staging)?;
+        Ok(())
+    })();
+    if let Err(error) = result {
+        let _ = std::fs::remove_dir_all(&staging);
+        return Err(error);
+    }
+    Ok(Staged::Directory {
+        staging,
+        target: target.to_path_buf(),
+    })
+}
+
+fn push_entry(
+    entries: &mut Vec<Entry>,
+    files: &mut Vec<BackupFile>,
+    component: BackupComponent,
+    path: &str,
+    mode: u32,
+    data: Vec<u8>,
+) {
+    files.push(BackupFile {
+        path: path.to_string(),
+        component,
+        bytes: data.len() as u64,
+        sha256: sha256_hex(&data),
+        mode,
+    });
+    entries.push(Entry {
+        path: path.to_string(),
+        mode,
+        data,
+    });
+}
+
+/// Regular files under `dir` as sorted `/`-separated relative paths. Dotfiles
+/// (lock and temp files) are skipped.
+fn walk_files(dir: &Path) -> Result<Vec<String>, BackupError> {
+    let mut files = Vec::new();
+    let mut pending = vec![PathBuf::new()];
+    while let Some(relative) = pending.pop() {
+        for entry in std::fs::read_dir(dir.join(&relative))? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name.starts_with('.') {
+                continue;
+            }
+            let path = relative.join(&name);
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                pending.push(path);
+            } else if file_type.is_file() {
+                files.push(
+                    path.components()
+                        .map(|component| component.as_os_str().to_string_lossy())
+                        .collect::<Vec<_>>()
+                        .join("/"),
+                );
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn is_safe_relative(path: &str) -> bool {
+    !path.is_empty()
+        && Path::new(path)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+}
+
+fn file_mode(path: &Path) -> Result<u32, BackupError> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        Ok(std::fs::metadata(path)?.permissions().mode() & 0o777)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        Ok(0o644)
+    }
+}
+
+/// Minimal ustar writer: regular files only, names split into prefix/name
+/// when longer than 100 bytes.
+fn write_tar(entries: &[Entry], mtime: u64) -> Result<Vec<u8>, BackupError> {
+    let mut out = Vec::new();
+    for entry in entries {
+        let mut header = [0u8; BLOCK];
+        let (prefix, name) = split_tar_path(&entry.path)?;
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        write_octal(&mut header[100..108], u64::from(entry.mode));
+        write_octal(&mut header[108..116], 0);
+        write_octal(&mut header[116..124], 0);
+        write_octal(&mut header[124..136], entry.data.len() as u64);
+        write_octal(&mut header[136..148], mtime);
+        header[156] = b'0';
+        header[257..263].copy_from_slice(b"ustar\0");
+        header[263..265].copy_from_slice(b"00");
+        header[345..345 + prefix.len()].copy_from_slice(prefix.as_bytes());
+        let checksum = tar_checksum(&header);
+        header[148..156].copy_from_slice(format!("{checksum:06o}\0 ").as_bytes());
+
+        out.extend_from_slice(&header);
+        out.extend_from_slice(&entry.data);
+        out.resize(out.len().next_multiple_of(BLOCK), 0);
+    }
+    out.resize(out.len() + 2 * BLOCK, 0);
+    Ok(out)
+}
+
+fn read_tar(bytes: &[u8]) -> Result<Vec<Entry>, BackupError> {
+    let mut entries = Vec::new();
+    let mut offset = 0;
+    while offset + BLOCK <= bytes.len() {
+        let header = &bytes[offset..offset + BLOCK];
+        if header.iter().all(|byte| *byte == 0) {
+            return Ok(entries);
+        }
+        let expected = read_octal(&header[148..156])?;
+        if tar_checksum(header) != expected {
+            return Err(BackupError::InvalidBundle(format!(
+                "bad header checksum at offset {offset}"
+            )));
+        }
+
+        let name = read_str(&header[..100])?;
+        let prefix = read_str(&header[345..500])?;
+        let path = if prefix.is_empty() {
+            name
+        } else {
+            format!("{prefix}/{name}")
+        };
+        let size = read_octal(&header[124..136])? as usize;
+        let start = offset + BLOCK;
+        let end = start
+            .checked_add(size)
+            .filter(|end| *end <= bytes.len())
+            .ok_or_else(|| BackupError::InvalidBundle(format!("'{path}' is truncated")))?;
+        match header[156] {
+            b'0' | 0 => entries.push(Entry {
+                path,
+                mode: read_octal(&header[100..108])? as u32,
+                data: bytes[start..end].to_vec(),
+            }),
+            b'5' => {}
+            other => {
+                return Err(BackupError::InvalidBundle(format!(
+                    "'{path}' has unsupported entry type '{}'",
+                    other as char
+                )));
+            }
+        }
+        offset = start + size.next_multiple_of(BLOCK);
+    }
+    Err(BackupError::InvalidBundle(
+        "missing end-of-archive marker".to_string(),
+    ))
+}
+
+fn split_tar_path(path: &str) -> Result<(&str, &str), BackupError> {
+    if path.len() <= 100 {
+        return Ok(("", path));
+    }
+    path.char_indices()
+        .filter(|(_, ch)| *ch == '/')
+        .map(|(index, _)| (&path[..index], &path[index + 1..]))
+        .find(|(prefix, name)| prefix.len() <= 155 && name.len() <= 100 && !name.is_empty())
+        .ok_or_else(|| BackupError::InvalidBundle(format!("path too long for tar: {path}")))
+}
+
+fn tar_checksum(header: &[u8]) -> u64 {
+    header
+        .iter()
+        .enumerate()
+        .map(|(index, byte)| {
+            if (148..156).contains(&index) {
+                u64::from(b' ')
+            } else {
+                u64::from(*byte)
+            }
+        })
+        .sum()
+}
+
+fn write_octal(field: &mut [u8], value: u64) {
+    let width = field.len() - 1;
+    field[..width].copy_from_slice(format!("{value:0width$o}").as_bytes());
+    field[width] = 0;
+}
+
+fn read_octal(field: &[u8]) -> Result<u64, BackupError> {
+    let text = std::str::from_utf8(field)
+        .map_err(|_| BackupError::InvalidBundle("non-ascii numeric field".to_string()))?
+        .trim_matches(|ch: char| ch == '\0' || ch == ' ');
+    if text.is_empty() {
+        return Ok(0);
+    }
+    u64::from_str_radix(text, 8)
+        .map_err(|_| BackupError::InvalidBundle(format!("bad octal field '{text}'")))
+}
+
+fn read_str(field: &[u8]) -> Result<String, BackupError> {
+    let end = field
+        .iter()
+        .position(|byte| *byte == 0)
+        .unwrap_or(field.len());
+    String::from_utf8(field[..end].to_vec())
+        .map_err(|_| BackupError::InvalidBundle("non-utf8 entry name".to_string()))
+}
+
+fn write_atomic(path: &Path, bytes: &[u8], mode: u32) -> Result<(), BackupError> {
+    let temp = temporary_path(path, "tmp");
+    let result = write_new(&temp, bytes, mode).and_then(|()| Ok(std::fs::rename(&temp, path)?));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp);
+    }
+    result
+}
+
+fn write_new(path: &Path, bytes: &[u8], mode: u32) -> Result<(), BackupError> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(mode);
+    }
+    #[cfg(not(unix))]
+    let _ = mode;
+    let mut file = options.open(path)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    Ok(())
+}
+
+fn temporary_path(path: &Path, purpose: &str) -> PathBuf {
+    let file_name = path
+        .file_name()
+        .and_then(|value| value.to_str())
+        .unwrap_or("backup");
+    let stamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|value| value.as_nanos())
+        .unwrap_or(0);
+    path.with_file_name(format!(
+        ".{file_name}.{purpose}.{}.{}",
+        std::process::id(),
+        stamp
+    ))
+}
+
+fn now_unix_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|value| value.as_millis() as u64)
+        .unwrap_or(0)
+}
@@ -1,19 +1,44 @@
-use std::path::PathBuf;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
 
 use nexum::{
     attention::{AttentionEvent, AttentionPolicy, AttentionPriority, RoutedAttention},
-    capsule::{Capsule, CapsuleMode, CapsuleState, mode_to_str, parse_state, state_to_str},
+    backup::{BackupPaths, create_backup, restore_backup},
+    capsule::{
+        Capsule, CapsuleMode, CapsuleState, generate_capsule_id, mode_to_str, parse_state,
+        state_to_str, validate_capsule_id,
+    },
+    cleanup::{CleanupAction, CleanupInput, cleanup_capsule},
+    crypto::to_hex,
     cutover::{CutoverInput, apply_cutover, evaluate_cutover, parse_capability},
     events::EventStore,
     flags::{CutoverFlags, FlagName},
-    restore::SignalType,
-    routing::{RouteCommand, default_socket_path, send_command},
+    fork::{ForkInput, UnforkInput, fork_capsule, unfork_capsule},
+    host_ports::audit_capsule_ports,
+    labels::{Selector, parse_label},
+    manifest::{RestoreOverrides, resolve_restore},
+    migrate::MigrationPlan,
+    onboard::{FromRepoInput, create_from_repo},
+    ports::{DEFAULT_PORT_POOL, DEFAULT_PORT_QUOTA, DEFAULT_SERVICE, PortAllocator, port_env_var},
+    restore::{SignalType, signal_to_str},
+    routing::{
+        RouteCommand, RouteOutcome, caller_proof_message, default_socket_path, send_command,
+    },
     runflow::{RestoreRunInput, run_restore_flow},
+    scene::{SceneRestoreInput, restore_scene},
     shadow::{ExecutionResult, compare_execution},
     shell::{NiriShellCommand, NiriShellPlan, render_shell_script},
     stead::{DispatchEvent, parse_dispatch_event, parse_dispatch_events},
-    store::CapsuleStore,
-    tls::{ensure_self_signed_cert, rotate_if_expiring},
+    store::{
+        CapsuleStore, ImportPlan, PortPool, PortReservation, SECRETS_PASSPHRASE_ENV, Scene,
+        SceneMember, StoreError, WorkspaceCollision, parse_capsules_yaml,
+    },
+    tls::{
+        ensure_cert_with_sans, ensure_client_cert, inventory, prune_orphans, rotate_all_expiring,
+        rotate_if_expiring, sign_with_client_key, verify, verify_all, verify_client_cert,
+    },
 };
 use serde::Serialize;
 
@@ -26,6 +51,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     match args[0].as_str() {
         "capsule" => capsule_command(&args[1..])?,
+        "scene" => scene_command(&args[1..])?,
         "flags" => flags_command(&args[1..])?,
         "parity" => parity_command(&args[1..])?,
         "events" => events_command(&args[1..])?,
@@ -34,6 +60,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         "stead" => stead_command(&args[1..])?,
         "supervisor" => supervisor_command(&args[1..])?,
         "tls" => tls_command(&args[1..])?,
+        "db" => db_command(&args[1..])?,
+        "backup" => backup_command(&args[1..])?,
         "cutover" => cutover_command(&args[1..])?,
         "run" => run_command(&args[1..])?,
         _ => {
@@ -57,6 +85,9 @@ struct SupervisorCapsuleStatus {
     last_event_level: Option<String>,
     last_event_message: Option<String>,
     last_event_ts_unix_ms: Option<u64>,
+    last_restored_unix_ms: Option<u64>,
+    restore_count: u32,
+    last_signal: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -66,9 +97,31 @@ struct SupervisorStatusReport {
     degraded_capsules: u32,
     archived_capsules: u32,
     critical_events: u32,
+    workspace_collisions: Vec<WorkspaceCollision>,
     capsules: Vec<SupervisorCapsuleStatus>,
 }
 
+#[derive(Debug, Serialize)]
+struct CapsuleImportReport {
+    applied: bool,
+    #[serde(flatten)]
+    plan: ImportPlan,
+}
+
+#[derive(Debug, Serialize)]
+struct DbMigrationEntry {
+    database: String,
+    path: String,
+    #[serde(flatten)]
+    plan: MigrationPlan,
+}
+
+#[derive(Debug, Serialize)]
+struct DbMigrationReport {
+    dry_run: bool,
+    databases: Vec<DbMigrationEntry>,
+}
+
 #[derive(Debug, Serialize)]
 struct SupervisorBlocker {
     capsule_id: String,
@@ -101,7 +154,11 @@ fn supervisor_status(args: &[String]) -> Result<(), Box<dyn std::error::Error>>
     let store = CapsuleStore::open(&capsule_db)?;
     let events = EventStore::open(&events_db)?;
     let flags = CutoverFlags::load_or_default(&flags_file)?;
-    let listed = store.list()?;
+    let listed = select_capsules(&store, store.list()?, selector_arg(args)?.as_ref())?;
+    let selected = listed
+        .iter()
+        .map(|capsule| capsule.capsule_id.clone())
+        .collect::<Vec<_>>();
 
     let mut degraded_capsules = 0u32;
     let mut archived_capsules = 0u32;
@@ -123,6 +180,7 @@ fn supervisor_status(args: &[String]) -> Result<(), Box<dyn std::error::Error>>
             .list_recent(Some(&capsule.capsule_id), None, Some(1))?
             .into_iter()
             .next();
+        let activity = store.activity(&capsule.capsule_id)?;
 
         capsules.push(SupervisorCapsuleStatus {
             capsule_id: capsule.capsule_id,
@@ -135,6 +193,9 @@ fn supervisor_status(args: &[String]) -> Result<(), Box<dyn std::error::Error>>
             last_event_level: last.as_ref().map(|value| value.level.clone()),
             last_event_message: last.as_ref().map(|value| value.message.clone()),
             last_event_ts_unix_ms: last.as_ref().map(|value| value.ts_unix_ms),
+            last_restored_unix_ms: activity.last_restored_unix_ms,
+            restore_count: activity.restore_count,
+            last_signal: activity.last_signal,
         });
     }
 
@@ -144,6 +205,16 @@ fn supervisor_status(args: &[String]) -> Result<(), Box<dyn std::error::Error>>
         degraded_capsules,
         archived_capsules,
         critical_events,
+        workspace_collisions: store
+            .workspace_collisions()?
+            .into_iter()
+            .filter(|collision| {
+                collision
+                    .capsule_ids
+                    .iter()
+                    .any(|capsule_id| selected.contains(capsule_id))
+            })
+            .collect(),
         capsules,
     };
 
@@ -161,7 +232,7 @@ fn supervisor_blockers(args: &[String]) -> Result<(), Box<dyn std::error::Error>
 
     let store = CapsuleStore::open(&capsule_db)?;
     let events = EventStore::open(&events_db)?;
-    let listed = store.list()?;
+    let listed = select_capsules(&store, store.list()?, selector_arg(args)?.as_ref())?;
 
     let mut blockers = Vec::new();
     for capsule in listed {
@@ -198,12 +269,26 @@ fn capsule_command(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
     match args[0].as_str() {
         "create" => capsule_create(&args[1..]),
         "list" => capsule_list(&args[1..]),
+        "which" => capsule_which(&args[1..]),
         "export" => capsule_export(&args[1..]),
+        "import" => capsule_import(&args[1..]),
         "rename" => capsule_rename(&args[1..]),
         "set-repo" => capsule_set_repo(&args[1..]),
         "set-state" => capsule_set_state(&args[1..]),
+        "history" => capsule_history(&args[1..]),
+        "archive" => capsule_cleanup(&args[1..], CleanupAction::Archive),
+        "unarchive" => capsule_unarchive(&args[1..]),
+        "delete" => capsule_cleanup(&args[1..], CleanupAction::Delete),
+        "fork" => capsule_fork(&args[1..]),
+        "unfork" => capsule_unfork(&args[1..]),
         "allocate-port" => capsule_allocate_port(&args[1..]),
         "release-ports" => capsule_release_ports(&args[1..]),
+        "ports" => capsule_ports_command(&args[1..]),
+        "env" => capsule_env_command(&args[1..]),
+        "label" => capsule_label(&args[1..]),
+        "deps" => capsule_deps_command(&args[1..]),
+        "workspaces" => capsule_workspaces(&args[1..]),
+        "share-workspace" => capsule_share_workspace(&args[1..]),
         _ => {
             usage();
             std::process::exit(2);
@@ -212,48 +297,170 @@ fn capsule_command(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
 }
 
 fn capsule_create(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    if optional_arg(args, "--from-repo").is_some() {
+        return capsule_create_from_repo(args);
+    }
     let db = required_arg(args, "--db")?;
-    let id = required_arg(args, "--id")?;
+    let id = match optional_arg(args, "--id") {
+        Some(id) => {
+            validate_capsule_id(&id).map_err(|error| error.to_string())?;
+            id
+        }
+        None => generate_capsule_id()?,
+    };
     let name = required_arg(args, "--name")?;
-    let workspace = parse_u16_arg(args, "--workspace")?;
     let mode = required_arg(args, "--mode")?;
     let repo_path = optional_arg(args, "--repo-path").unwrap_or_default();
+    let json = optional_arg(args, "--json")
+        .map(|value| parse_bool(&value))
+        .transpose()?
+        .unwrap_or(false);
 
     let mode = parse_mode(&mode)?;
 
     let mut store = CapsuleStore::open(&PathBuf::from(db))?;
+    let workspace = match optional_arg(args, "--workspace") {
+        Some(_) => {
+            let workspace = parse_u16_arg(args, "--workspace")?;
+            store
+                .check_workspace(&id, workspace)
+                .map_err(|error| error.to_string())?;
+            workspace
+        }
+        None => match store.get(&id)? {
+            Some(existing) => existing.workspace,
+            None => store.next_free_workspace()?,
+        },
+    };
     let capsule = Capsule::new(&id, &name, mode, workspace).with_repo_path(&repo_path);
     store.upsert(capsule)?;
 
-    println!("created {}", id);
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string(&serde_json::json!({
+                "capsule_id": id,
+                "workspace": workspace,
+            }))?
+        );
+    } else {
+        println!("created {}", id);
+    }
+    Ok(())
+}
+
+fn capsule_create_from_repo(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let workspace = match optional_arg(args, "--workspace") {
+        Some(_) => Some(parse_u16_arg(args, "--workspace")?),
+        None => None,
+    };
+    let report = create_from_repo(&FromRepoInput {
+        capsule_db: PathBuf::from(required_arg(args, "--db")?),
+        repo_path: PathBuf::from(required_arg(args, "--from-repo")?),
+        capsule_id: optional_arg(args, "--id"),
+        display_name: optional_arg(args, "--name"),
+        mode: optional_arg(args, "--mode")
+            .map(|mode| parse_mode(&mode))
+            .transpose()?,
+        workspace,
+        pool: optional_arg(args, "--pool").unwrap_or_else(|| DEFAULT_PORT_POOL.to_string()),
+    })
+    .map_err(|error| error.to_string())?;
+    println!("{}", serde_json::to_string(&report)?);
     Ok(())
 }
 
 fn capsule_list(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
     let db = required_arg(args, "--db")?;
     let store = CapsuleStore::open(&PathBuf::from(db))?;
-    let listed = store.list()?;
+    let listed = match optional_arg(args, "--sort").as_deref() {
+        None | Some("id") => store.list()?,
+        Some("recent") => store.list_recent()?,
+        Some(other) => return Err(format!("invalid sort: {other} (expected id|recent)").into()),
+    };
+    let listed = select_capsules(&store, listed, selector_arg(args)?.as_ref())?;
 
     let mut payload = Vec::with_capacity(listed.len());
     for capsule in listed {
-        let allocated_ports = store.list_ports(&capsule.capsule_id)?;
-        payload.push(serde_json::json!({
-            "capsule_id": capsule.capsule_id,
-            "slug": capsule.slug,
-            "domain": capsule.domain(),
-            "display_name": capsule.display_name,
-            "repo_path": capsule.repo_path,
-            "mode": mode_to_str(capsule.mode),
-            "state": state_to_str(capsule.state),
-            "workspace": capsule.workspace,
-            "allocated_ports": allocated_ports,
-        }));
+        payload.push(capsule_json(&store, capsule)?);
     }
 
     println!("{}", serde_json::to_string(&payload)?);
     Ok(())
 }
 
+/// Keeps the capsules matched by `selector`, preserving order.
+fn select_capsules(
+    store: &CapsuleStore,
+    capsules: Vec<Capsule>,
+    selector: Option<&Selector>,
+) -> Result<Vec<Capsule>, Box<dyn std::error::Error>> {
+    let Some(selector) = selector else {
+        return Ok(capsules);
+    };
+    let labels = store.all_labels()?;
+    let empty = BTreeMap::new();
+    Ok(capsules
+        .into_iter()
+        .filter(|capsule| {
+            selector.matches(capsule, labels.get(&capsule.capsule_id).unwrap_or(&empty))
+        })
+        .collect())
+}
+
+fn selector_arg(args: &[String]) -> Result<Option<Selector>, Box<dyn std::error::Error>> {
+    Ok(optional_arg(args, "--selector")
+        .map(|selector| selector.parse::<Selector>())
+        .transpose()
+        .map_err(|error| error.to_string())?)
+}
+
+fn capsule_which(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let db = required_arg(args, "--db")?;
+    let path = match optional_arg(args, "--path") {
+        Some(path) => PathBuf::from(path),
+        None => std::env::current_dir()?,
+    };
+    let store = CapsuleStore::open(&PathBuf::from(db))?;
+
+    let Some(capsule) = store.find_by_path(&path)? else {
+        eprintln!("no capsule matches {}", path.display());
+        std::process::exit(1);
+    };
+    println!(
+        "{}",
+        serde_json::to_string(&capsule_json(&store, capsule)?)?
+    );
+    Ok(())
+}
+
+fn capsule_json(
+    store: &CapsuleStore,
+    capsule: Capsule,
+) -> Result<serde_json::Value, Box<dyn std::error::Error>> {
+    let allocated_ports = store.list_ports(&capsule.capsule_id)?;
+    let service_ports = store.list_service_ports(&capsule.capsule_id)?;
+    let activity = store.activity(&capsule.capsule_id)?;
+    Ok(serde_json::json!({
+        "capsule_id": capsule.capsule_id,
+        "slug": capsule.slug,
+        "domain": capsule.domain(),
+        "display_name": capsule.display_name,
+        "repo_path": capsule.repo_path,
+        "mode": mode_to_str(capsule.mode),
+        "state": state_to_str(capsule.state),
+        "workspace": capsule.workspace,
+        "allocated_ports": allocated_ports,
+        "service_ports": service_ports,
+        "env": store.list_env(&capsule.capsule_id)?,
+        "labels": store.labels(&capsule.capsule_id)?,
+        "dependencies": store.dependencies(&capsule.capsule_id)?,
+        "last_restored_unix_ms": activity.last_restored_unix_ms,
+        "restore_count": activity.restore_count,
+        "last_signal": activity.last_signal,
+    }))
+}
+
 fn capsule_export(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
     let db = required_arg(args, "--db")?;
     let format = required_arg(args, "--format")?;
@@ -268,6 +475,37 @@ fn capsule_export(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
     }
 }
 
+fn capsule_import(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let db = required_arg(args, "--db")?;
+    let file = required_arg(args, "--file")?;
+    let apply = optional_arg(args, "--apply")
+        .map(|value| parse_bool(&value))
+        .transpose()?
+        .unwrap_or(false);
+    let capsules = parse_capsules_yaml(&std::fs::read_to_string(&file)?)?;
+
+    let mut store = CapsuleStore::open(&PathBuf::from(db))?;
+    let plan = store.plan_import(&capsules)?;
+    let has_conflicts = !plan.conflicts.is_empty();
+    let plan = if apply && !has_conflicts {
+        store.import(&capsules)?
+    } else {
+        plan
+    };
+
+    println!(
+        "{}",
+        serde_json::to_string(&CapsuleImportReport {
+            applied: apply && !has_conflicts,
+            plan,
+        })?
+    );
+    if has_conflicts {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
 fn capsule_rename(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
     let db = required_arg(args, "--db")?;
     let id = required_arg(args, "--id")?;
@@ -286,38 +524,619 @@ fn capsule_set_state(args: &[String]) -> Result<(), Box<dyn std::error::Error>>
     let state =
         parse_state(&required_arg(args, "--state")?).ok_or_else(|| "invalid state".to_string())?;
 
-    let mut store = CapsuleStore::open(&PathBuf::from(db))?;
-    store.transition_state(&id, state)?;
+    let reason = optional_arg(args, "--reason").unwrap_or_else(|| "manual".to_string());
+
+    let mut store = CapsuleStore::open(&PathBuf::from(db))?;
+    store
+        .transition_state(&id, state, "nexumctl", &reason)
+        .map_err(|error| error.to_string())?;
+
+    println!("state_updated {}", id);
+    Ok(())
+}
+
+fn capsule_unarchive(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let db = required_arg(args, "--db")?;
+    let id = required_arg(args, "--id")?;
+    let reason = optional_arg(args, "--reason").unwrap_or_else(|| "unarchived".to_string());
+
+    let mut store = CapsuleStore::open(&PathBuf::from(db))?;
+    let capsule = store
+        .get(&id)?
+        .ok_or_else(|| format!("capsule not found: {id}"))?;
+    if capsule.state != CapsuleState::Archived {
+        return Err(format!("capsule is not archived: {id}").into());
+    }
+    store
+        .transition_state(&id, CapsuleState::Ready, "nexumctl", &reason)
+        .map_err(|error| error.to_string())?;
+
+    println!("unarchived {}", id);
+    Ok(())
+}
+
+fn capsule_history(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let db = required_arg(args, "--db")?;
+    let id = required_arg(args, "--id")?;
+
+    let store = CapsuleStore::open(&PathBuf::from(db))?;
+    if store.get(&id)?.is_none() {
+        return Err(format!("capsule not found: {id}").into());
+    }
+    println!("{}", serde_json::to_string(&store.history(&id)?)?);
+    Ok(())
+}
+
+fn capsule_cleanup(
+    args: &[String],
+    action: CleanupAction,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let report = cleanup_capsule(&CleanupInput {
+        capsule_db: PathBuf::from(required_arg(args, "--db")?),
+        capsule_id: required_arg(args, "--id")?,
+        action,
+        routing_socket: Some(socket_arg_or_default(args)),
+        tls_dir: optional_arg(args, "--tls-dir").map(PathBuf::from),
+        events_db: optional_arg(args, "--events-db").map(PathBuf::from),
+        dry_run: optional_arg(args, "--dry-run")
+            .map(|value| parse_bool(&value))
+            .transpose()?
+            .unwrap_or(false),
+    })
+    .map_err(|error| error.to_string())?;
+
+    println!("{}", serde_json::to_string(&report)?);
+    Ok(())
+}
+
+fn capsule_fork(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let report = fork_capsule(&ForkInput {
+        capsule_db: PathBuf::from(required_arg(args, "--db")?),
+        source_id: required_arg(args, "--from")?,
+        branch: required_arg(args, "--branch")?,
+        pool: optional_arg(args, "--pool").unwrap_or_else(|| DEFAULT_PORT_POOL.to_string()),
+    })
+    .map_err(|error| error.to_string())?;
+
+    println!("{}", serde_json::to_string(&report)?);
+    Ok(())
+}
+
+fn capsule_unfork(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let report = unfork_capsule(&UnforkInput {
+        capsule_db: PathBuf::from(required_arg(args, "--db")?),
+        capsule_id: required_arg(args, "--id")?,
+        routing_socket: Some(socket_arg_or_default(args)),
+        tls_dir: optional_arg(args, "--tls-dir").map(PathBuf::from),
+        events_db: optional_arg(args, "--events-db").map(PathBuf::from),
+        force: optional_arg(args, "--force")
+            .map(|value| parse_bool(&value))
+            .transpose()?
+            .unwrap_or(false),
+        dry_run: optional_arg(args, "--dry-run")
+            .map(|value| parse_bool(&value))
+            .transpose()?
+            .unwrap_or(false),
+    })
+    .map_err(|error| error.to_string())?;
+
+    println!("{}", serde_json::to_string(&report)?);
+    Ok(())
+}
+
+fn capsule_set_repo(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let db = required_arg(args, "--db")?;
+    let id = required_arg(args, "--id")?;
+    let repo_path = required_arg(args, "--repo-path")?;
+
+    let mut store = CapsuleStore::open(&PathBuf::from(db))?;
+    store.set_repo_path(&id, &repo_path)?;
+
+    println!("repo_updated {}", id);
+    Ok(())
+}
+
+fn scene_command(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    if args.is_empty() {
+        usage();
+        std::process::exit(2);
+    }
+
+    match args[0].as_str() {
+        "set" => scene_set(&args[1..]),
+        "show" => scene_show(&args[1..]),
+        "list" => scene_list(&args[1..]),
+        "delete" => scene_delete(&args[1..]),
+        _ => {
+            usage();
+            std::process::exit(2);
+        }
+    }
+}
+
+/// `--members cap-web:2,cap-api` pins `cap-web` to workspace 2 within the
+/// scene; `cap-api` keeps its own workspace.
+fn scene_set(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let db = required_arg(args, "--db")?;
+    let name = required_arg(args, "--name")?;
+    let members = parse_csv(&required_arg(args, "--members")?)
+        .into_iter()
+        .map(|member| match member.split_once(':') {
+            Some((capsule_id, workspace)) => workspace
+                .parse::<u16>()
+                .map(|workspace| SceneMember {
+                    capsule_id: capsule_id.to_string(),
+                    workspace: Some(workspace),
+                })
+                .map_err(|error| format!("invalid workspace for {capsule_id}: {error}")),
+            None => Ok(SceneMember {
+                capsule_id: member,
+                workspace: None,
+            }),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut store = CapsuleStore::open(&PathBuf::from(db))?;
+    let scene = Scene { name, members };
+    store.set_scene(&scene).map_err(|error| error.to_string())?;
+    println!("{}", serde_json::to_string(&scene_json(&store, scene)?)?);
+    Ok(())
+}
+
+fn scene_show(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let db = required_arg(args, "--db")?;
+    let name = required_arg(args, "--name")?;
+    let store = CapsuleStore::open(&PathBuf::from(db))?;
+    let scene = store
+        .scene(&name)?
+        .ok_or_else(|| format!("scene not found: {name}"))?;
+    println!("{}", serde_json::to_string(&scene_json(&store, scene)?)?);
+    Ok(())
+}
+
+fn scene_list(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let db = required_arg(args, "--db")?;
+    let store = CapsuleStore::open(&PathBuf::from(db))?;
+    let mut payload = Vec::new();
+    for scene in store.list_scenes()? {
+        payload.push(scene_json(&store, scene)?);
+    }
+    println!("{}", serde_json::to_string(&payload)?);
+    Ok(())
+}
+
+fn scene_delete(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let db = required_arg(args, "--db")?;
+    let name = required_arg(args, "--name")?;
+    let mut store = CapsuleStore::open(&PathBuf::from(db))?;
+    let deleted = store.delete_scene(&name)?;
+    println!(
+        "{}",
+        serde_json::to_string(&serde_json::json!({
+            "name": name,
+            "deleted": deleted,
+        }))?
+    );
+    Ok(())
+}
+
+/// Scene members with the workspace each one restores onto.
+fn scene_json(
+    store: &CapsuleStore,
+    scene: Scene,
+) -> Result<serde_json::Value, Box<dyn std::error::Error>> {
+    let mut members = Vec::with_capacity(scene.members.len());
+    for member in scene.members {
+        let capsule = store
+            .get(&member.capsule_id)?
+            .ok_or_else(|| format!("unknown capsule: {}", member.capsule_id))?;
+        members.push(serde_json::json!({
+            "capsule_id": member.capsule_id,
+            "workspace": member.workspace.unwrap_or(capsule.workspace),
+            "workspace_override": member.workspace,
+            "state": state_to_str(capsule.state),
+        }));
+    }
+    Ok(serde_json::json!({
+        "name": scene.name,
+        "members": members,
+    }))
+}
+
+fn capsule_label(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let db = required_arg(args, "--db")?;
+    let id = required_arg(args, "--id")?;
+    let set = optional_arg(args, "--set")
+        .map(|labels| {
+            labels
+                .split(',')
+                .map(parse_label)
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .transpose()
+        .map_err(|error| error.to_string())?
+        .unwrap_or_default();
+    let remove = optional_arg(args, "--remove")
+        .map(|keys| keys.split(',').map(ToString::to_string).collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    let mut store = CapsuleStore::open(&PathBuf::from(db))?;
+    let labels = store
+        .update_labels(&id, &set, &remove)
+        .map_err(|error| error.to_string())?;
+    println!(
+        "{}",
+        serde_json::to_string(&serde_json::json!({
+            "capsule_id": id,
+            "labels": labels,
+        }))?
+    );
+    Ok(())
+}
+
+fn capsule_deps_command(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    if args.is_empty() {
+        usage();
+        std::process::exit(2);
+    }
+
+    match args[0].as_str() {
+        "add" => capsule_deps_add(&args[1..]),
+        "remove" => capsule_deps_remove(&args[1..]),
+        "list" => capsule_deps_list(&args[1..]),
+        _ => {
+            usage();
+            std::process::exit(2);
+        }
+    }
+}
+
+fn capsule_deps_add(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let db = required_arg(args, "--db")?;
+    let id = required_arg(args, "--id")?;
+    let on = required_arg(args, "--on")?;
+    let route = optional_arg(args, "--route");
+
+    let mut store = CapsuleStore::open(&PathBuf::from(db))?;
+    store
+        .add_dependency(&id, &on, route.as_deref())
+        .map_err(|error| error.to_string())?;
+    println!("{}", serde_json::to_string(&store.dependencies(&id)?)?);
+    Ok(())
+}
+
+fn capsule_deps_remove(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let db = required_arg(args, "--db")?;
+    let id = required_arg(args, "--id")?;
+    let on = required_arg(args, "--on")?;
+    let route = optional_arg(args, "--route");
+
+    let mut store = CapsuleStore::open(&PathBuf::from(db))?;
+    let removed = store.remove_dependency(&id, &on, route.as_deref())?;
+    println!(
+        "{}",
+        serde_json::to_string(&serde_json::json!({
+            "capsule_id": id,
+            "depends_on": on,
+            "route": route,
+            "removed": removed,
+        }))?
+    );
+    Ok(())
+}
+
+fn capsule_deps_list(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let db = required_arg(args, "--db")?;
+    let id = required_arg(args, "--id")?;
+    let store = CapsuleStore::open(&PathBuf::from(db))?;
+
+    println!("{}", serde_json::to_string(&store.dependencies(&id)?)?);
+    Ok(())
+}
+
+fn capsule_env_command(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    if args.is_empty() {
+        usage();
+        std::process::exit(2);
+    }
+
+    match args[0].as_str() {
+        "set" => capsule_env_set(&args[1..]),
+        "unset" => capsule_env_unset(&args[1..]),
+        "list" => capsule_env_list(&args[1..]),
+        "reveal" => capsule_env_reveal(&args[1..]),
+        _ => {
+            usage();
+            std::process::exit(2);
+        }
+    }
+}
+
+fn capsule_env_set(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let db = required_arg(args, "--db")?;
+    let id = required_arg(args, "--id")?;
+    let key = required_arg(args, "--key")?;
+    let secret = optional_arg(args, "--secret")
+        .map(|value| parse_bool(&value))
+        .transpose()?
+        .unwrap_or(false);
+    let value = if secret {
+        secret_value_arg(args)?
+    } else {
+        required_arg(args, "--value")?
+    };
+
+    let mut store = CapsuleStore::open(&PathBuf::from(db))?;
+    if store.get(&id)?.is_none() {
+        return Err(format!("capsule not found: {id}").into());
+    }
+    if secret {
+        let passphrase = secrets_passphrase()?;
+        store.set_secret(&id, &key, &value, &passphrase)
+    } else {
+        store.set_env(&id, &key, &value)
+    }
+    .map_err(|error| error.to_string())?;
+    println!(
+        "{}",
+        serde_json::to_string(&serde_json::json!({
+            "capsule_id": id,
+            "key": key,
+            "secret": secret,
+        }))?
+    );
+    Ok(())
+}
+
+fn capsule_env_unset(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let db = required_arg(args, "--db")?;
+    let id = required_arg(args, "--id")?;
+    let key = required_arg(args, "--key")?;
+
+    let mut store = CapsuleStore::open(&PathBuf::from(db))?;
+    let removed = store.unset_env(&id, &key)?;
+    println!(
+        "{}",
+        serde_json::to_string(&serde_json::json!({
+            "capsule_id": id,
+            "key": key,
+            "removed": removed,
+        }))?
+    );
+    Ok(())
+}
+
+fn capsule_env_list(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let db = required_arg(args, "--db")?;
+    let id = required_arg(args, "--id")?;
+    let store = CapsuleStore::open(&PathBuf::from(db))?;
+
+    println!("{}", serde_json::to_string(&store.list_env(&id)?)?);
+    Ok(())
+}
+
+/// Prints a secret's plaintext with no trailing newline; restore scripts call
+/// this so secret values never appear in rendered output.
+fn capsule_env_reveal(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let db = required_arg(args, "--db")?;
+    let id = required_arg(args, "--id")?;
+    let key = required_arg(args, "--key")?;
+
+    let store = CapsuleStore::open(&PathBuf::from(db))?;
+    let value = store
+        .reveal_secret(&id, &key, &secrets_passphrase()?)
+        .map_err(|error| error.to_string())?
+        .ok_or_else(|| format!("secret not found: {id}/{key}"))?;
+    print!("{value}");
+    Ok(())
+}
+
+/// Secret values never come from argv, where `ps` and shell history would
+/// see them: they are read from a named env var or from stdin.
+fn secret_value_arg(args: &[String]) -> Result<String, Box<dyn std::error::Error>> {
+    if optional_arg(args, "--value").is_some() {
+        return Err(
+            "--value is not accepted with --secret true; use --value-env <VAR> or --value-stdin true"
+                .into(),
+        );
+    }
+    if let Some(var) = optional_arg(args, "--value-env") {
+        return std::env::var(&var).map_err(|_| format!("env var {var} is not set").into());
+    }
+    let from_stdin = optional_arg(args, "--value-stdin")
+        .map(|value| parse_bool(&value))
+        .transpose()?
+        .unwrap_or(false);
+    if !from_stdin {
+        return Err("secret value required: use --value-env <VAR> or --value-stdin true".into());
+    }
+    let mut value = String::new();
+    std::io::Read::read_to_string(&mut std::io::stdin(), &mut value)?;
+    let trimmed = value
+        .strip_suffix('\n')
+        .map(|rest| rest.strip_suffix('\r').unwrap_or(rest))
+        .unwrap_or(&value);
+    Ok(trimmed.to_string())
+}
+
+fn secrets_passphrase() -> Result<String, Box<dyn std::error::Error>> {
+    std::env::var(SECRETS_PASSPHRASE_ENV)
+        .ok()
+        .filter(|value| !value.is_empty())
+        .ok_or_else(|| StoreError::SecretsLocked.to_string().into())
+}
+
+fn capsule_workspaces(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let db = required_arg(args, "--db")?;
+    let store = CapsuleStore::open(&PathBuf::from(db))?;
+
+    println!(
+        "{}",
+        serde_json::to_string(&serde_json::json!({
+            "next_free": store.next_free_workspace().ok(),
+            "shared": store.shared_workspaces()?,
+            "collisions": store.workspace_collisions()?,
+        }))?
+    );
+    Ok(())
+}
+
+fn capsule_share_workspace(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let db = required_arg(args, "--db")?;
+    let workspace = parse_u16_arg(args, "--workspace")?;
+    let shared = parse_bool(&required_arg(args, "--shared")?)?;
+
+    let mut store = CapsuleStore::open(&PathBuf::from(db))?;
+    store.set_workspace_shared(workspace, shared)?;
+    println!(
+        "{}",
+        serde_json::to_string(&serde_json::json!({
+            "workspace": workspace,
+            "shared": shared,
+        }))?
+    );
+    Ok(())
+}
+
+fn capsule_allocate_port(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let db = required_arg(args, "--db")?;
+    let id = required_arg(args, "--id")?;
+    let service = optional_arg(args, "--service").unwrap_or_else(|| DEFAULT_SERVICE.to_string());
+    let quota = optional_arg(args, "--quota")
+        .map(|value| {
+            value
+                .parse::<u32>()
+                .map_err(|error| format!("failed to parse --quota as u32: {error}"))
+        })
+        .transpose()?
+        .unwrap_or(DEFAULT_PORT_QUOTA);
+
+    let mut store = CapsuleStore::open(&PathBuf::from(db))?;
+    let port = match optional_arg(args, "--start") {
+        Some(_) => store.allocate_service_port(
+            &id,
+            &service,
+            parse_u16_arg(args, "--start")?,
+            parse_u16_arg(args, "--end")?,
+            quota,
+        ),
+        None => {
+            let pool =
+                optional_arg(args, "--pool").unwrap_or_else(|| DEFAULT_PORT_POOL.to_string());
+            PortAllocator::open(&mut store, &pool)
+                .and_then(|allocator| allocator.with_quota(quota).allocate_service(&id, &service))
+        }
+    }
+    .map_err(|error| error.to_string())?;
+    println!(
+        "{}",
+        serde_json::to_string(&serde_json::json!({
+            "capsule_id": id,
+            "service": service,
+            "port": port,
+            "env": port.map(|_| port_env_var(&service)),
+        }))?
+    );
+    Ok(())
+}
+
+fn capsule_ports_command(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    if args.is_empty() {
+        usage();
+        std::process::exit(2);
+    }
+
+    match args[0].as_str() {
+        "audit" => capsule_ports_audit(&args[1..]),
+        "set-pool" => capsule_ports_set_pool(&args[1..]),
+        "pools" => capsule_ports_pools(&args[1..]),
+        "reserve" => capsule_ports_reserve(&args[1..]),
+        "unreserve" => capsule_ports_unreserve(&args[1..]),
+        _ => {
+            usage();
+            std::process::exit(2);
+        }
+    }
+}
+
+fn capsule_ports_audit(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let db = required_arg(args, "--db")?;
+    let store = CapsuleStore::open(&PathBuf::from(db))?;
+
+    let audit = audit_capsule_ports(&store.list_all_ports()?);
+    println!("{}", serde_json::to_string(&audit)?);
+    if !audit.conflicts.is_empty() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn capsule_ports_set_pool(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let db = required_arg(args, "--db")?;
+    let name = optional_arg(args, "--name").unwrap_or_else(|| DEFAULT_PORT_POOL.to_string());
+    let start = parse_u16_arg(args, "--start")?;
+    let end = parse_u16_arg(args, "--end")?;
+
+    let mut store = CapsuleStore::open(&PathBuf::from(db))?;
+    store
+        .set_port_pool(&name, start, end)
+        .map_err(|error| error.to_string())?;
+    println!("{}", serde_json::to_string(&PortPool { name, start, end })?);
+    Ok(())
+}
+
+fn capsule_ports_pools(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let db = required_arg(args, "--db")?;
+    let store = CapsuleStore::open(&PathBuf::from(db))?;
 
-    println!("state_updated {}", id);
+    let mut pools = Vec::new();
+    for pool in store.list_port_pools()? {
+        let usage = store.pool_usage(&pool.name)?;
+        pools.push(serde_json::json!({
+            "name": pool.name,
+            "start": pool.start,
+            "end": pool.end,
+            "used": usage.used,
+            "available": usage.available,
+        }));
+    }
+    println!(
+        "{}",
+        serde_json::to_string(&serde_json::json!({
+            "pools": pools,
+            "reservations": store.list_port_reservations()?,
+        }))?
+    );
     Ok(())
 }
 
-fn capsule_set_repo(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+fn capsule_ports_reserve(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
     let db = required_arg(args, "--db")?;
-    let id = required_arg(args, "--id")?;
-    let repo_path = required_arg(args, "--repo-path")?;
+    let port = parse_u16_arg(args, "--port")?;
+    let reason = optional_arg(args, "--reason").unwrap_or_default();
 
     let mut store = CapsuleStore::open(&PathBuf::from(db))?;
-    store.set_repo_path(&id, &repo_path)?;
-
-    println!("repo_updated {}", id);
+    store
+        .reserve_port(port, &reason)
+        .map_err(|error| error.to_string())?;
+    println!(
+        "{}",
+        serde_json::to_string(&PortReservation { port, reason })?
+    );
     Ok(())
 }
 
-fn capsule_allocate_port(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+fn capsule_ports_unreserve(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
     let db = required_arg(args, "--db")?;
-    let id = required_arg(args, "--id")?;
-    let start = parse_u16_arg(args, "--start")?;
-    let end = parse_u16_arg(args, "--end")?;
+    let port = parse_u16_arg(args, "--port")?;
 
     let mut store = CapsuleStore::open(&PathBuf::from(db))?;
-    let port = store.allocate_port(&id, start, end)?;
+    let removed = store.unreserve_port(port)?;
     println!(
         "{}",
         serde_json::to_string(&serde_json::json!({
-            "capsule_id": id,
             "port": port,
+            "removed": removed,
         }))?
     );
     Ok(())
@@ -328,7 +1147,10 @@ fn capsule_release_ports(args: &[String]) -> Result<(), Box<dyn std::error::Erro
     let id = required_arg(args, "--id")?;
 
     let mut store = CapsuleStore::open(&PathBuf::from(db))?;
-    let released = store.release_ports(&id)?;
+    let released = match optional_arg(args, "--service") {
+        Some(service) => store.release_service_port(&id, &service)?,
+        None => store.release_ports(&id)?,
+    };
     println!(
         "{}",
         serde_json::to_string(&serde_json::json!({
@@ -462,6 +1284,8 @@ fn routing_command(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
         "resolve" => routing_resolve(&args[1..]),
         "remove" => routing_remove(&args[1..]),
         "list" => routing_list(&args[1..]),
+        "set-policy" => routing_set_policy(&args[1..]),
+        "authorize" => routing_authorize(&args[1..]),
         _ => {
             usage();
             std::process::exit(2);
@@ -516,6 +1340,60 @@ fn routing_list(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+fn routing_set_policy(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let outcome = route_request(
+        socket_arg_or_default(args),
+        RouteCommand::SetCallerPolicy {
+            domain: required_arg(args, "--domain")?,
+            allowed_callers: optional_arg(args, "--allow")
+                .map(|value| parse_csv(&value))
+                .unwrap_or_default(),
+        },
+    )?;
+    println!("{}", serde_json::to_string(&outcome)?);
+    Ok(())
+}
+
+fn routing_authorize(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let socket = socket_arg_or_default(args);
+    let domain = required_arg(args, "--domain")?;
+    let client_cert_pem = optional_arg(args, "--client-cert")
+        .map(std::fs::read_to_string)
+        .transpose()?;
+
+    let (nonce, signature) = match &client_cert_pem {
+        Some(_) => {
+            let key = PathBuf::from(required_arg(args, "--client-key")?);
+            let challenge = route_request(
+                socket.clone(),
+                RouteCommand::CallerChallenge {
+                    domain: domain.clone(),
+                },
+            )?;
+            let RouteOutcome::CallerChallenge { nonce, .. } = challenge else {
+                println!("{}", serde_json::to_string(&challenge)?);
+                return Ok(());
+            };
+            let signature =
+                sign_with_client_key(&key, caller_proof_message(&domain, &nonce).as_bytes())?;
+            (Some(nonce), Some(to_hex(&signature)))
+        }
+        None => (None, None),
+    };
+
+    let outcome = route_request(
+        socket,
+        RouteCommand::AuthorizeCaller {
+            domain,
+            client_cert_pem,
+            nonce,
+            signature,
+        },
+    )?;
+    println!("{}", serde_json::to_string(&outcome)?);
+    Ok(())
+}
+
 fn socket_arg_or_default(args: &[String]) -> PathBuf {
     optional_arg(args, "--socket")
         .map(PathBuf::from)
@@ -525,7 +1403,7 @@ fn socket_arg_or_default(args: &[String]) -> PathBuf {
 fn route_request(
     socket: PathBuf,
     command: RouteCommand,
-) -> Result<nexum::routing::RouteOutcome, Box<dyn std::error::Error>> {
+) -> Result<RouteOutcome, Box<dyn std::error::Error>> {
     let runtime = tokio::runtime::Builder::new_current_thread()
         .enable_io()
         .enable_time()
@@ -570,6 +1448,107 @@ fn shell_render(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+fn db_command(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    if args.is_empty() {
+        usage();
+        std::process::exit(2);
+    }
+
+    match args[0].as_str() {
+        "migrate" => db_migrate(&args[1..]),
+        _ => {
+            usage();
+            std::process::exit(2);
+        }
+    }
+}
+
+fn db_migrate(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let capsule_db = optional_arg(args, "--capsule-db").map(PathBuf::from);
+    let events_db = optional_arg(args, "--events-db").map(PathBuf::from);
+    if capsule_db.is_none() && events_db.is_none() {
+        return Err("db migrate requires --capsule-db and/or --events-db".into());
+    }
+    let dry_run = optional_arg(args, "--dry-run")
+        .map(|value| parse_bool(&value))
+        .transpose()?
+        .unwrap_or(false);
+
+    let mut databases = Vec::new();
+    if let Some(path) = capsule_db {
+        let plan = CapsuleStore::migration_plan(&path)?;
+        if !dry_run {
+            CapsuleStore::open(&path)?;
+        }
+        databases.push(DbMigrationEntry {
+            database: "capsules".to_string(),
+            path: path.display().to_string(),
+            plan,
+        });
+    }
+    if let Some(path) = events_db {
+        let plan = EventStore::migration_plan(&path)?;
+        if !dry_run {
+            EventStore::open(&path)?;
+        }
+        databases.push(DbMigrationEntry {
+            database: "events".to_string(),
+            path: path.display().to_string(),
+            plan,
+        });
+    }
+
+    println!(
+        "{}",
+        serde_json::to_string(&DbMigrationReport { dry_run, databases })?
+    );
+    Ok(())
+}
+
+fn backup_command(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    if args.is_empty() {
+        usage();
+        std::process::exit(2);
+    }
+
+    match args[0].as_str() {
+        "create" => backup_create(&args[1..]),
+        "restore" => backup_restore(&args[1..]),
+        _ => {
+            usage();
+            std::process::exit(2);
+        }
+    }
+}
+
+fn backup_paths(args: &[String]) -> BackupPaths {
+    BackupPaths {
+        capsule_db: optional_arg(args, "--capsule-db").map(PathBuf::from),
+        events_db: optional_arg(args, "--events-db").map(PathBuf::from),
+        flags: optional_arg(args, "--flags").map(PathBuf::from),
+        tls_dir: optional_arg(args, "--tls-dir").map(PathBuf::from),
+    }
+}
+
+fn backup_create(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let out = PathBuf::from(required_arg(args, "--out")?);
+    let report = create_backup(&backup_paths(args), &out).map_err(|error| error.to_string())?;
+    println!("{}", serde_json::to_string(&report)?);
+    Ok(())
+}
+
+fn backup_restore(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let bundle = PathBuf::from(required_arg(args, "--bundle")?);
+    let dry_run = optional_arg(args, "--dry-run")
+        .map(|value| parse_bool(&value))
+        .transpose()?
+        .unwrap_or(false);
+    let report =
+        restore_backup(&bundle, &backup_paths(args), dry_run).map_err(|error| error.to_string())?;
+    println!("{}", serde_json::to_string(&report)?);
+    Ok(())
+}
+
 fn tls_command(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
     if args.is_empty() {
         usage();
@@ -579,6 +1558,12 @@ fn tls_command(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
     match args[0].as_str() {
         "ensure" => tls_ensure(&args[1..]),
         "rotate" => tls_rotate(&args[1..]),
+        "list" => tls_list(&args[1..]),
+        "rotate-all" => tls_rotate_all(&args[1..]),
+        "prune" => tls_prune(&args[1..]),
+        "verify" => tls_verify(&args[1..]),
+        "issue-client" => tls_issue_client(&args[1..]),
+        "verify-client" => tls_verify_client(&args[1..]),
         _ => {
             usage();
             std::process::exit(2);
@@ -593,7 +1578,11 @@ fn tls_ensure(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
         .unwrap_or_else(|| "30".to_string())
         .parse::<u64>()?;
 
-    let record = ensure_self_signed_cert(&dir, &domain, validity_days)?;
+    let sans = optional_arg(args, "--sans")
+        .map(|value| parse_csv(&value))
+        .unwrap_or_default();
+
+    let record = ensure_cert_with_sans(&dir, &domain, &sans, validity_days)?;
     println!("{}", serde_json::to_string(&record)?);
     Ok(())
 }
@@ -608,6 +1597,89 @@ fn tls_rotate(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+fn tls_list(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let dir = PathBuf::from(required_arg(args, "--dir")?);
+    let capsules = match optional_arg(args, "--capsule-db") {
+        Some(db) => CapsuleStore::open(&PathBuf::from(db))?.list()?,
+        None => Vec::new(),
+    };
+
+    let entries = inventory(&dir, &capsules)?;
+    println!("{}", serde_json::to_string(&entries)?);
+    Ok(())
+}
+
+fn tls_rotate_all(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let dir = PathBuf::from(required_arg(args, "--dir")?);
+    let threshold_days = required_arg(args, "--threshold-days")?.parse::<u64>()?;
+
+    let outcomes = rotate_all_expiring(&dir, threshold_days)?;
+    let rotated = outcomes.iter().filter(|outcome| outcome.rotated).count();
+    println!(
+        "{}",
+        serde_json::to_string(&serde_json::json!({
+            "checked": outcomes.len(),
+            "rotated": rotated,
+            "outcomes": outcomes,
+        }))?
+    );
+    Ok(())
+}
+
+fn tls_prune(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let dir = PathBuf::from(required_arg(args, "--dir")?);
+    let capsule_db = PathBuf::from(required_arg(args, "--capsule-db")?);
+    let dry_run = optional_arg(args, "--dry-run")
+        .map(|value| parse_bool(&value))
+        .transpose()?
+        .unwrap_or(false);
+
+    let capsules = CapsuleStore::open(&capsule_db)?.list()?;
+    let report = prune_orphans(&dir, &capsules, dry_run)?;
+    println!("{}", serde_json::to_string(&report)?);
+    Ok(())
+}
+
+fn tls_verify(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let dir = PathBuf::from(required_arg(args, "--dir")?);
+    let repair = optional_arg(args, "--repair")
+        .map(|value| parse_bool(&value))
+        .transpose()?
+        .unwrap_or(false);
+
+    let reports = match optional_arg(args, "--domain") {
+        Some(domain) => vec![verify(&dir, &domain, repair)?],
+        None => verify_all(&dir, repair)?,
+    };
+    println!("{}", serde_json::to_string(&reports)?);
+
+    if reports.iter().any(|report| !report.ok) {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn tls_issue_client(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let dir = PathBuf::from(required_arg(args, "--dir")?);
+    let capsule_id = required_arg(args, "--capsule-id")?;
+    let validity_days = optional_arg(args, "--validity-days")
+        .unwrap_or_else(|| "30".to_string())
+        .parse::<u64>()?;
+
+    let record = ensure_client_cert(&dir, &capsule_id, validity_days)?;
+    println!("{}", serde_json::to_string(&record)?);
+    Ok(())
+}
+
+fn tls_verify_client(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let dir = PathBuf::from(required_arg(args, "--dir")?);
+    let cert = std::fs::read_to_string(required_arg(args, "--cert")?)?;
+
+    let verified = verify_client_cert(&dir, &cert)?;
+    println!("{}", serde_json::to_string(&verified)?);
+    Ok(())
+}
+
 fn cutover_command(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
     if args.is_empty() {
         usage();
@@ -755,6 +1827,7 @@ fn run_command(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
     match args[0].as_str() {
         "restore" => run_restore(&args[1..]),
         "restore-capsule" => run_restore_capsule(&args[1..]),
+        "restore-scene" => run_restore_scene(&args[1..]),
         _ => {
             usage();
             std::process::exit(2);
@@ -814,6 +1887,7 @@ struct SteadBatchReport {
     failed: u32,
     attention_plan: SteadAttentionPlan,
     results: Vec<SteadBatchResult>,
+    skipped: Vec<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -830,6 +1904,10 @@ fn stead_dispatch_batch(args: &[String]) -> Result<(), Box<dyn std::error::Error
     let capsule_db = PathBuf::from(required_arg(args, "--capsule-db")?);
     let events = parse_dispatch_events(&required_arg(args, "--events-json")?)
         .map_err(|error| error.to_string())?;
+    let (events, skipped) = match selector_arg(args)? {
+        Some(selector) => select_dispatch_events(&capsule_db, events, &selector)?,
+        None => (events, Vec::new()),
+    };
     let attention_plan = build_stead_attention_plan(&events);
     let dry_run = optional_arg(args, "--dry-run")
         .map(|value| parse_bool(&value))
@@ -925,6 +2003,7 @@ fn stead_dispatch_batch(args: &[String]) -> Result<(), Box<dyn std::error::Error
         failed,
         attention_plan,
         results,
+        skipped,
     };
     let payload = serde_json::to_string(&report)?;
     if let Some(report_file) = report_file {
@@ -945,7 +2024,7 @@ fn collect_event_capsule_ids(events: &[DispatchEvent]) -> Vec<String> {
 }
 
 fn find_missing_capsule_ids(
-    capsule_db: &PathBuf,
+    capsule_db: &Path,
     events: &[DispatchEvent],
 ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
     let store = CapsuleStore::open(capsule_db)?;
@@ -958,8 +2037,36 @@ fn find_missing_capsule_ids(
     Ok(missing_capsule_ids)
 }
 
+/// Splits a batch into events to dispatch and the capsule ids of events whose
+/// capsule does not match `selector`. Events for unknown capsules are kept so
+/// they still fail (or trip `--fail-on-missing-capsules`) as before.
+fn select_dispatch_events(
+    capsule_db: &Path,
+    events: Vec<DispatchEvent>,
+    selector: &Selector,
+) -> Result<(Vec<DispatchEvent>, Vec<String>), Box<dyn std::error::Error>> {
+    let store = CapsuleStore::open(capsule_db)?;
+    let labels = store.all_labels()?;
+    let empty = BTreeMap::new();
+    let mut selected = Vec::with_capacity(events.len());
+    let mut skipped = Vec::new();
+    for event in events {
+        match store.get(&event.capsule_id)? {
+            Some(capsule)
+                if !selector
+                    .matches(&capsule, labels.get(&capsule.capsule_id).unwrap_or(&empty)) =>
+            {
+                skipped.push(event.capsule_id);
+            }
+            _ => selected.push(event),
+        }
+    }
+    Ok((selected, skipped))
+}
+
+#[allow(clippy::too_many_arguments)]
 fn dispatch_stead_event(
-    capsule_db: &PathBuf,
+    capsule_db: &Path,
     event: DispatchEvent,
     terminal_override: Option<String>,
     editor_override: Option<String>,
@@ -968,48 +2075,37 @@ fn dispatch_stead_event(
     tls_dir: PathBuf,
     events_db: PathBuf,
 ) -> Result<nexum::runflow::RestoreRunSummary, Box<dyn std::error::Error>> {
-    let store = CapsuleStore::open(capsule_db)?;
+    let mut store = CapsuleStore::open(capsule_db)?;
     let capsule = store
         .get(&event.capsule_id)?
         .ok_or_else(|| format!("unknown capsule: {}", event.capsule_id))?;
 
-    let terminal_cmd = if let Some(terminal) = terminal_override {
-        terminal
-    } else if !capsule.repo_path.is_empty() {
-        format!("cd {} && nix develop", capsule.repo_path)
-    } else {
-        return Err(
-            "missing restore surfaces: provide --terminal and --editor or set capsule repo_path"
-                .into(),
-        );
-    };
-
-    let editor_target = if let Some(editor) = editor_override {
-        editor
-    } else if !capsule.repo_path.is_empty() {
-        capsule.repo_path.clone()
-    } else {
-        return Err(
-            "missing restore surfaces: provide --terminal and --editor or set capsule repo_path"
-                .into(),
-        );
-    };
-
-    let browser_url = browser_override.unwrap_or_else(|| format!("https://{}", capsule.domain()));
+    let resolved = resolve_restore(
+        &mut store,
+        &capsule,
+        RestoreOverrides {
+            terminal: terminal_override,
+            editor: editor_override,
+            browser: browser_override,
+            upstream: Some(event.upstream),
+            force_isolated_mode: event.force_isolated_mode.then_some(true),
+        },
+    )
+    .map_err(|error| error.to_string())?;
 
     run_restore_flow(RestoreRunInput {
         capsule_id: capsule.capsule_id,
-        display_name: capsule.display_name,
-        workspace: capsule.workspace,
+        display_name: resolved.display_name,
+        workspace: resolved.workspace,
         signal: event.signal,
-        terminal_cmd,
-        editor_target,
-        browser_url,
-        route_upstream: event.upstream,
+        terminal_cmd: resolved.terminal_cmd,
+        editor_target: resolved.editor_target,
+        browser_url: resolved.browser_url,
+        route_upstream: resolved.route_upstream,
         routing_socket,
         identity_collision: event.identity_collision,
         high_risk_secret_workflow: event.high_risk_secret_workflow,
-        force_isolated_mode: event.force_isolated_mode,
+        force_isolated_mode: resolved.force_isolated_mode,
         capsule_db: Some(capsule_db.to_path_buf()),
         tls_dir,
         events_db,
@@ -1105,14 +2201,6 @@ fn attention_priority_rank(value: AttentionPriority) -> u8 {
     }
 }
 
-fn signal_to_str(value: SignalType) -> &'static str {
-    match value {
-        SignalType::NeedsDecision => "needs_decision",
-        SignalType::CriticalFailure => "critical_failure",
-        SignalType::PassiveCompletion => "passive_completion",
-    }
-}
-
 fn run_restore(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
     let signal = parse_signal(&required_arg(args, "--signal")?)?;
     let identity_collision = optional_arg(args, "--identity-collision")
@@ -1154,7 +2242,6 @@ fn run_restore_capsule(args: &[String]) -> Result<(), Box<dyn std::error::Error>
     let capsule_db = PathBuf::from(required_arg(args, "--capsule-db")?);
     let capsule_id = required_arg(args, "--capsule-id")?;
     let signal = parse_signal(&required_arg(args, "--signal")?)?;
-    let route_upstream = required_arg(args, "--upstream")?;
     let routing_socket = optional_arg(args, "--routing-socket").map(PathBuf::from);
     let identity_collision = optional_arg(args, "--identity-collision")
         .map(|value| parse_bool(&value))
@@ -1166,52 +2253,39 @@ fn run_restore_capsule(args: &[String]) -> Result<(), Box<dyn std::error::Error>
         .unwrap_or(false);
     let force_isolated_mode = optional_arg(args, "--force-isolated")
         .map(|value| parse_bool(&value))
-        .transpose()?
-        .unwrap_or(false);
+        .transpose()?;
 
-    let store = CapsuleStore::open(&capsule_db)?;
+    let mut store = CapsuleStore::open(&capsule_db)?;
     let capsule = store
         .get(&capsule_id)?
         .ok_or_else(|| format!("unknown capsule: {capsule_id}"))?;
 
-    let terminal_cmd = if let Some(terminal) = optional_arg(args, "--terminal") {
-        terminal
-    } else if !capsule.repo_path.is_empty() {
-        format!("cd {} && nix develop", capsule.repo_path)
-    } else {
-        return Err(
-            "missing restore surfaces: provide --terminal and --editor or set capsule repo_path"
-                .into(),
-        );
-    };
-
-    let editor_target = if let Some(editor) = optional_arg(args, "--editor") {
-        editor
-    } else if !capsule.repo_path.is_empty() {
-        capsule.repo_path.clone()
-    } else {
-        return Err(
-            "missing restore surfaces: provide --terminal and --editor or set capsule repo_path"
-                .into(),
-        );
-    };
-
-    let browser_url =
-        optional_arg(args, "--browser").unwrap_or_else(|| format!("https://{}", capsule.domain()));
+    let resolved = resolve_restore(
+        &mut store,
+        &capsule,
+        RestoreOverrides {
+            terminal: optional_arg(args, "--terminal"),
+            editor: optional_arg(args, "--editor"),
+            browser: optional_arg(args, "--browser"),
+            upstream: optional_arg(args, "--upstream"),
+            force_isolated_mode,
+        },
+    )
+    .map_err(|error| error.to_string())?;
 
     let summary = run_restore_flow(RestoreRunInput {
         capsule_id: capsule.capsule_id,
-        display_name: capsule.display_name,
-        workspace: capsule.workspace,
+        display_name: resolved.display_name,
+        workspace: resolved.workspace,
         signal,
-        terminal_cmd,
-        editor_target,
-        browser_url,
-        route_upstream,
+        terminal_cmd: resolved.terminal_cmd,
+        editor_target: resolved.editor_target,
+        browser_url: resolved.browser_url,
+        route_upstream: resolved.route_upstream,
         routing_socket,
         identity_collision,
         high_risk_secret_workflow,
-        force_isolated_mode,
+        force_isolated_mode: resolved.force_isolated_mode,
         capsule_db: Some(capsule_db),
         tls_dir: PathBuf::from(required_arg(args, "--tls-dir")?),
         events_db: PathBuf::from(required_arg(args, "--events-db")?),
@@ -1221,6 +2295,29 @@ fn run_restore_capsule(args: &[String]) -> Result<(), Box<dyn std::error::Error>
     Ok(())
 }
 
+fn run_restore_scene(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let summary = restore_scene(&SceneRestoreInput {
+        capsule_db: PathBuf::from(required_arg(args, "--capsule-db")?),
+        scene: required_arg(args, "--scene")?,
+        signal: parse_signal(&required_arg(args, "--signal")?)?,
+        routing_socket: optional_arg(args, "--routing-socket").map(PathBuf::from),
+        identity_collision: optional_arg(args, "--identity-collision")
+            .map(|value| parse_bool(&value))
+            .transpose()?
+            .unwrap_or(false),
+        high_risk_secret_workflow: optional_arg(args, "--high-risk-secret")
+            .map(|value| parse_bool(&value))
+            .transpose()?
+            .unwrap_or(false),
+        tls_dir: PathBuf::from(required_arg(args, "--tls-dir")?),
+        events_db: PathBuf::from(required_arg(args, "--events-db")?),
+    })
+    .map_err(|error| error.to_string())?;
+
+    println!("{}", serde_json::to_string(&summary)?);
+    Ok(())
+}
+
 fn required_arg(args: &[String], key: &str) -> Result<String, Box<dyn std::error::Error>> {
     let pos = args
         .iter()
@@ -1262,6 +2359,15 @@ fn write_output_file(path: &PathBuf, payload: &str) -> Result<(), Box<dyn std::e
     Ok(())
 }
 
+fn parse_csv(input: &str) -> Vec<String> {
+    input
+        .split(',')
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(ToString::to_string)
+        .collect()
+}
+
 fn parse_bool(input: &str) -> Result<bool, Box<dyn std::error::Error>> {
     match input {
         "true" => Ok(true),
@@ -1281,17 +2387,68 @@ fn parse_signal(input: &str) -> Result<SignalType, Box<dyn std::error::Error>> {
 
 fn usage() {
     eprintln!(
-        "nexumctl capsule create --db <path> --id <id> --name <name> --workspace <n> --mode <host_default|isolated_nix_shell> [--repo-path <path>]"
+        "nexumctl capsule create --db <path> [--id <id>] --name <name> [--workspace <n>] --mode <host_default|isolated_nix_shell> [--repo-path <path>] [--json <bool>]"
+    );
+    eprintln!(
+        "nexumctl capsule create --db <path> --from-repo <path> [--id <id>] [--name <name>] [--mode <host_default|isolated_nix_shell>] [--workspace <n>] [--pool <name>]"
     );
-    eprintln!("nexumctl capsule list --db <path>");
+    eprintln!("nexumctl capsule list --db <path> [--sort <id|recent>] [--selector <selector>]");
     eprintln!("nexumctl capsule export --db <path> --format <yaml>");
+    eprintln!("nexumctl capsule import --db <path> --file <capsules.yaml> [--apply <bool>]");
     eprintln!("nexumctl capsule rename --db <path> --id <id> --name <name>");
     eprintln!("nexumctl capsule set-repo --db <path> --id <id> --repo-path <path>");
     eprintln!(
-        "nexumctl capsule set-state --db <path> --id <id> --state <creating|ready|restoring|degraded|archived>"
+        "nexumctl capsule set-state --db <path> --id <id> --state <creating|ready|restoring|degraded|archived> [--reason <text>]"
     );
-    eprintln!("nexumctl capsule allocate-port --db <path> --id <id> --start <u16> --end <u16>");
-    eprintln!("nexumctl capsule release-ports --db <path> --id <id>");
+    eprintln!("nexumctl capsule history --db <path> --id <id>");
+    eprintln!(
+        "nexumctl capsule archive --db <path> --id <id> [--socket <path>] [--tls-dir <path>] [--dry-run <bool>]"
+    );
+    eprintln!("nexumctl capsule unarchive --db <path> --id <id> [--reason <text>]");
+    eprintln!(
+        "nexumctl capsule delete --db <path> --id <id> [--socket <path>] [--tls-dir <path>] [--events-db <path>] [--dry-run <bool>]"
+    );
+    eprintln!(
+        "nexumctl capsule fork --db <path> --from <capsule_id> --branch <name> [--pool <name>]"
+    );
+    eprintln!(
+        "nexumctl capsule unfork --db <path> --id <id> [--socket <path>] [--tls-dir <path>] [--events-db <path>] [--force <bool>] [--dry-run <bool>]"
+    );
+    eprintln!(
+        "nexumctl capsule allocate-port --db <path> --id <id> [--pool <name> | --start <u16> --end <u16>] [--service <name>] [--quota <n>]"
+    );
+    eprintln!("nexumctl capsule release-ports --db <path> --id <id> [--service <name>]");
+    eprintln!("nexumctl capsule env set --db <path> --id <id> --key <KEY> --value <value>");
+    eprintln!(
+        "nexumctl capsule env set --db <path> --id <id> --key <KEY> --secret true (--value-env <VAR> | --value-stdin true)"
+    );
+    eprintln!("nexumctl capsule env unset --db <path> --id <id> --key <KEY>");
+    eprintln!("nexumctl capsule env list --db <path> --id <id>");
+    eprintln!("nexumctl capsule env reveal --db <path> --id <id> --key <KEY>");
+    eprintln!(
+        "nexumctl capsule label --db <path> --id <id> [--set <key=value>[,<key=value>...]] [--remove <key>[,<key>...]]"
+    );
+    eprintln!("nexumctl capsule deps add --db <path> --id <id> --on <capsule_id> [--route <name>]");
+    eprintln!(
+        "nexumctl capsule deps remove --db <path> --id <id> --on <capsule_id> [--route <name>]"
+    );
+    eprintln!("nexumctl capsule deps list --db <path> --id <id>");
+    eprintln!("nexumctl capsule which --db <path> [--path <dir>]");
+    eprintln!("nexumctl capsule workspaces --db <path>");
+    eprintln!("nexumctl capsule share-workspace --db <path> --workspace <n> --shared <true|false>");
+    eprintln!("nexumctl capsule ports audit --db <path>");
+    eprintln!(
+        "nexumctl capsule ports set-pool --db <path> [--name <name>] --start <u16> --end <u16>"
+    );
+    eprintln!("nexumctl capsule ports pools --db <path>");
+    eprintln!("nexumctl capsule ports reserve --db <path> --port <u16> [--reason <text>]");
+    eprintln!("nexumctl capsule ports unreserve --db <path> --port <u16>");
+    eprintln!(
+        "nexumctl scene set --db <path> --name <name> --members <capsule_id>[:<workspace>][,<capsule_id>[:<workspace>]...]"
+    );
+    eprintln!("nexumctl scene show --db <path> --name <name>");
+    eprintln!("nexumctl scene list --db <path>");
+    eprintln!("nexumctl scene delete --db <path> --name <name>");
     eprintln!(
         "nexumctl flags set --file <path> [--shadow true|false] [--routing true|false] [--restore true|false] [--attention true|false]"
     );
@@ -1308,6 +2465,12 @@ fn usage() {
     eprintln!("nexumctl routing resolve --domain <domain> [--socket <path>]");
     eprintln!("nexumctl routing remove --domain <domain> [--socket <path>]");
     eprintln!("nexumctl routing list [--socket <path>]");
+    eprintln!(
+        "nexumctl routing set-policy --domain <domain> [--allow <capsule-id>[,<capsule-id>...]] [--socket <path>]"
+    );
+    eprintln!(
+        "nexumctl routing authorize --domain <domain> [--client-cert <path> --client-key <path>] [--socket <path>]"
+    );
     eprintln!(
         "nexumctl shell render --workspace <n> --terminal <cmd> --editor <path> --browser <url> --attention <level>"
     );
@@ -1315,18 +2478,33 @@ fn usage() {
         "nexumctl stead dispatch --capsule-db <path> --event-json <json> [--terminal <cmd>] [--editor <path>] [--browser <url>] [--routing-socket <path>] --tls-dir <path> --events-db <path>"
     );
     eprintln!(
-        "nexumctl stead dispatch-batch --capsule-db <path> --events-json <json-array> [--terminal <cmd>] [--editor <path>] [--browser <url>] [--routing-socket <path>] [--dry-run <bool>] [--fail-on-missing-capsules <bool>] [--report-file <path>] --tls-dir <path> --events-db <path>"
+        "nexumctl stead dispatch-batch --capsule-db <path> --events-json <json-array> [--terminal <cmd>] [--editor <path>] [--browser <url>] [--routing-socket <path>] [--dry-run <bool>] [--fail-on-missing-capsules <bool>] [--report-file <path>] [--selector <selector>] --tls-dir <path> --events-db <path>"
     );
     eprintln!("nexumctl stead validate-events --events-json <json-array> [--capsule-db <path>]");
     eprintln!("nexumctl stead attention-plan --events-json <json-array>");
     eprintln!(
-        "nexumctl supervisor status --capsule-db <path> --events-db <path> --flags-file <path>"
+        "nexumctl supervisor status --capsule-db <path> --events-db <path> --flags-file <path> [--selector <selector>]"
     );
     eprintln!(
-        "nexumctl supervisor blockers --capsule-db <path> --events-db <path> [--critical-threshold <u32>]"
+        "nexumctl supervisor blockers --capsule-db <path> --events-db <path> [--critical-threshold <u32>] [--selector <selector>]"
+    );
+    eprintln!(
+        "nexumctl tls ensure --dir <path> --domain <domain> [--validity-days <days>] [--sans <name|ip>[,<name|ip>...]]"
     );
-    eprintln!("nexumctl tls ensure --dir <path> --domain <domain> [--validity-days <days>]");
     eprintln!("nexumctl tls rotate --dir <path> --domain <domain> --threshold-days <days>");
+    eprintln!("nexumctl tls list --dir <path> [--capsule-db <path>]");
+    eprintln!("nexumctl tls rotate-all --dir <path> --threshold-days <days>");
+    eprintln!("nexumctl tls prune --dir <path> --capsule-db <path> [--dry-run <bool>]");
+    eprintln!("nexumctl tls verify --dir <path> [--domain <domain>] [--repair <bool>]");
+    eprintln!("nexumctl tls issue-client --dir <path> --capsule-id <id> [--validity-days <days>]");
+    eprintln!("nexumctl tls verify-client --dir <path> --cert <path>");
+    eprintln!("nexumctl db migrate [--capsule-db <path>] [--events-db <path>] [--dry-run <bool>]");
+    eprintln!(
+        "nexumctl backup create --out <bundle.tar> [--capsule-db <path>] [--events-db <path>] [--flags <path>] [--tls-dir <path>]"
+    );
+    eprintln!(
+        "nexumctl backup restore --bundle <bundle.tar> [--capsule-db <path>] [--events-db <path>] [--flags <path>] [--tls-dir <path>] [--dry-run <bool>]"
+    );
     eprintln!(
         "nexumctl cutover apply --file <path> --capability <routing|restore|attention> --parity-score <f64> --min-parity-score <f64> --critical-events <u32> --max-critical-events <u32> --shadow-mode <true|false>"
     );
@@ -1341,6 +2519,9 @@ fn usage() {
         "nexumctl run restore --capsule-id <id> --name <name> --workspace <n> --signal <needs_decision|critical_failure|passive_completion> --terminal <cmd> --editor <path> --browser <url> --upstream <host:port> [--routing-socket <path>] [--identity-collision true|false] [--high-risk-secret true|false] [--force-isolated true|false] [--capsule-db <path>] --tls-dir <path> --events-db <path>"
     );
     eprintln!(
-        "nexumctl run restore-capsule --capsule-db <path> --capsule-id <id> --signal <needs_decision|critical_failure|passive_completion> --upstream <host:port> [--terminal <cmd>] [--editor <path>] [--browser <url>] [--routing-socket <path>] [--identity-collision true|false] [--high-risk-secret true|false] [--force-isolated true|false] --tls-dir <path> --events-db <path>"
+        "nexumctl run restore-capsule --capsule-db <path> --capsule-id <id> --signal <needs_decision|critical_failure|passive_completion> [--upstream <host:port>] [--terminal <cmd>] [--editor <path>] [--browser <url>] [--routing-socket <path>] [--identity-collision true|false] [--high-risk-secret true|false] [--force-isolated true|false] --tls-dir <path> --events-db <path>"
+    );
+    eprintln!(
+        "nexumctl run restore-scene --capsule-db <path> --scene <name> --signal <needs_decision|critical_failure|passive_completion> [--routing-socket <path>] [--identity-collision true|false] [--high-risk-secret true|false] --tls-dir <path> --events-db <path>"
     );
 }
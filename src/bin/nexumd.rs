@@ -1,6 +1,8 @@
 use std::path::PathBuf;
 
-use nexum::routing::{default_socket_path, serve_unix_socket};
+use nexum::routing::{
+    RouterState, default_policy_path, default_socket_path, serve_unix_socket_with_state,
+};
 use tokio::sync::oneshot;
 
 #[tokio::main(flavor = "multi_thread")]
@@ -18,6 +20,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
 
     let mut socket = default_socket_path();
+    let mut client_ca_dir = None;
+    let mut policy_file = None;
+    let mut require_client_cert = false;
     while let Some(arg) = args.next() {
         if arg == "--help" || arg == "-h" {
             usage();
@@ -31,6 +36,33 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             eprintln!("--socket requires a path");
             std::process::exit(2);
         }
+        if arg == "--client-ca-dir" {
+            if let Some(path) = args.next() {
+                client_ca_dir = Some(PathBuf::from(path));
+                continue;
+            }
+            eprintln!("--client-ca-dir requires a path");
+            std::process::exit(2);
+        }
+        if arg == "--policy-file" {
+            if let Some(path) = args.next() {
+                policy_file = Some(PathBuf::from(path));
+                continue;
+            }
+            eprintln!("--policy-file requires a path");
+            std::process::exit(2);
+        }
+        if arg == "--require-client-cert" {
+            match args.next().as_deref() {
+                Some("true") => require_client_cert = true,
+                Some("false") => require_client_cert = false,
+                _ => {
+                    eprintln!("--require-client-cert requires true|false");
+                    std::process::exit(2);
+                }
+            }
+            continue;
+        }
 
         eprintln!("unknown arg: {arg}");
         std::process::exit(2);
@@ -38,8 +70,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
     let serve_socket = socket.clone();
-    let mut serve_task =
-        tokio::spawn(async move { serve_unix_socket(&serve_socket, shutdown_rx).await });
+    let mut state = RouterState::with_caller_attestation(client_ca_dir, require_client_cert);
+    match policy_file.or_else(default_policy_path) {
+        Some(path) => state = state.with_policy_file(path)?,
+        None => eprintln!("no --policy-file and no HOME: caller policies will not persist"),
+    }
+    let mut serve_task = tokio::spawn(async move {
+        serve_unix_socket_with_state(&serve_socket, state, shutdown_rx).await
+    });
 
     tokio::select! {
         serve_result = &mut serve_task => {
@@ -75,5 +113,7 @@ async fn wait_for_shutdown_signal() -> Result<(), Box<dyn std::error::Error>> {
 }
 
 fn usage() {
-    println!("nexumd serve [--socket <path>]");
+    println!(
+        "nexumd serve [--socket <path>] [--client-ca-dir <tls-dir>] [--require-client-cert <true|false>] [--policy-file <path>]"
+    );
 }
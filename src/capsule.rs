@@ -1,6 +1,19 @@
-use std::{fmt, str::FromStr};
+use std::{
+    fmt,
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::crypto::{CryptoError, random_bytes};
+
+/// Longest accepted `capsule_id`.
+pub const CAPSULE_ID_MAX_LEN: usize = 64;
+/// Prefix of IDs produced by [`generate_capsule_id`].
+pub const GENERATED_ID_PREFIX: &str = "cap-";
+const CROCKFORD_LOWER: &[u8; 32] = b"0123456789abcdefghjkmnpqrstvwxyz";
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -37,6 +50,43 @@ pub enum CapsuleState {
     Archived,
 }
 
+impl CapsuleState {
+    /// States reachable from `self` in one step. Staying in the same state is
+    /// always permitted and is not listed here.
+    pub fn allowed_transitions(self) -> &'static [CapsuleState] {
+        match self {
+            Self::Creating => &[Self::Ready, Self::Degraded, Self::Archived],
+            Self::Ready => &[Self::Restoring, Self::Degraded, Self::Archived],
+            Self::Restoring => &[Self::Ready, Self::Degraded],
+            Self::Degraded => &[Self::Restoring, Self::Ready, Self::Archived],
+            Self::Archived => &[Self::Ready],
+        }
+    }
+
+    pub fn can_transition_to(self, next: CapsuleState) -> bool {
+        self == next || self.allowed_transitions().contains(&next)
+    }
+}
+
+impl fmt::Display for CapsuleState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(state_to_str(*self))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+#[error("illegal capsule state transition: {from} -> {to}")]
+pub struct TransitionError {
+    pub from: CapsuleState,
+    pub to: CapsuleState,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error(
+    "invalid capsule id '{0}': use 1-64 lowercase letters, digits, '-' or '_', starting with a letter or digit"
+)]
+pub struct InvalidCapsuleId(pub String);
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Capsule {
     pub capsule_id: String,
@@ -69,8 +119,15 @@ impl Capsule {
         format!("{}.nexum.local", self.slug)
     }
 
-    pub fn transition_state(&mut self, state: CapsuleState) {
+    pub fn transition_state(&mut self, state: CapsuleState) -> Result<(), TransitionError> {
+        if !self.state.can_transition_to(state) {
+            return Err(TransitionError {
+                from: self.state,
+                to: state,
+            });
+        }
         self.state = state;
+        Ok(())
     }
 
     pub fn with_repo_path(mut self, repo_path: &str) -> Self {
@@ -83,6 +140,50 @@ impl Capsule {
     }
 }
 
+/// Capsule IDs end up in file names, process labels and shell exports, so
+/// they are limited to `[a-z0-9][a-z0-9_-]*` and at most
+/// [`CAPSULE_ID_MAX_LEN`] characters.
+pub fn validate_capsule_id(capsule_id: &str) -> Result<(), InvalidCapsuleId> {
+    let valid = capsule_id.len() <= CAPSULE_ID_MAX_LEN
+        && capsule_id
+            .bytes()
+            .next()
+            .is_some_and(|first| first.is_ascii_lowercase() || first.is_ascii_digit())
+        && capsule_id.bytes().all(|byte| {
+            byte.is_ascii_lowercase() || byte.is_ascii_digit() || byte == b'-' || byte == b'_'
+        });
+    if valid {
+        Ok(())
+    } else {
+        Err(InvalidCapsuleId(capsule_id.to_string()))
+    }
+}
+
+/// A new `cap-<ulid>` ID. IDs generated later sort after earlier ones.
+pub fn generate_capsule_id() -> Result<String, CryptoError> {
+    let now_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|value| value.as_millis() as u64)
+        .unwrap_or(0);
+    Ok(format!(
+        "{GENERATED_ID_PREFIX}{}",
+        ulid(now_ms, random_bytes::<10>()?)
+    ))
+}
+
+/// 26-character ULID in lowercase Crockford base32: 48 bits of milliseconds
+/// followed by 80 random bits.
+pub fn ulid(ts_unix_ms: u64, random: [u8; 10]) -> String {
+    let mut value = u128::from(ts_unix_ms & 0xffff_ffff_ffff) << 80;
+    for (index, byte) in random.iter().enumerate() {
+        value |= u128::from(*byte) << (72 - 8 * index);
+    }
+    (0..26)
+        .rev()
+        .map(|index| CROCKFORD_LOWER[((value >> (5 * index)) & 0x1f) as usize] as char)
+        .collect()
+}
+
 pub fn normalize_slug(input: &str) -> String {
     let mut slug = String::with_capacity(input.len());
     let mut previous_dash = false;
@@ -0,0 +1,208 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    capsule::{Capsule, CapsuleState, TransitionError},
+    events::{EventError, EventStore},
+    identity::profile_dir_for_capsule,
+    routing::{RouteCommand, RouteOutcome, send_command},
+    store::{CapsuleStore, StoreError},
+    tls::{PrunedMaterial, TlsError, retire_capsule_material},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CleanupAction {
+    Archive,
+    Delete,
+}
+
+#[derive(Debug, Clone)]
+pub struct CleanupInput {
+    pub capsule_db: PathBuf,
+    pub capsule_id: String,
+    pub action: CleanupAction,
+    pub routing_socket: Option<PathBuf>,
+    pub tls_dir: Option<PathBuf>,
+    pub events_db: Option<PathBuf>,
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CleanupReport {
+    pub capsule_id: String,
+    pub action: CleanupAction,
+    pub dry_run: bool,
+    pub released_ports: Vec<u16>,
+    pub removed_routes: Vec<String>,
+    pub routing_error: Option<String>,
+    pub retired_tls: Vec<PrunedMaterial>,
+    pub removed_profile_dir: Option<String>,
+    pub removed_events: u32,
+}
+
+#[derive(Debug, Error)]
+pub enum CleanupError {
+    #[error("store: {0}")]
+    Store(#[from] StoreError),
+    #[error("events: {0}")]
+    Events(#[from] EventError),
+    #[error("tls: {0}")]
+    Tls(#[from] TlsError),
+    #[error("io: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("capsule not found: {0}")]
+    CapsuleNotFound(String),
+    #[error("capsule '{capsule_id}': {source}")]
+    IllegalTransition {
+        capsule_id: String,
+        source: TransitionError,
+    },
+}
+
+/// Archives or deletes a capsule and releases everything it holds: ports,
+/// router entries, TLS material and, on delete, its browser profile and
+/// events. Deleting a capsule that others depend on is refused. Routing
+/// failures are reported rather than aborting the cleanup.
+pub fn cleanup_capsule(input: &CleanupInput) -> Result<CleanupReport, CleanupError> {
+    let mut store = CapsuleStore::open(&input.capsule_db)?;
+    let capsule = store
+        .get(&input.capsule_id)?
+        .ok_or_else(|| CleanupError::CapsuleNotFound(input.capsule_id.clone()))?;
+
+    if input.action == CleanupAction::Archive
+        && !capsule.state.can_transition_to(CapsuleState::Archived)
+    {
+        return Err(CleanupError::IllegalTransition {
+            capsule_id: capsule.capsule_id.clone(),
+            source: TransitionError {
+                from: capsule.state,
+                to: CapsuleState::Archived,
+            },
+        });
+    }
+
+    if input.action == CleanupAction::Delete {
+        let dependents = store.dependents(&capsule.capsule_id)?;
+        if !dependents.is_empty() {
+            return Err(StoreError::HasDependents {
+                capsule_id: capsule.capsule_id.clone(),
+                dependents,
+            }
+            .into());
+        }
+    }
+
+    let released_ports = store.list_ports(&capsule.capsule_id)?;
+    let capsules = store.list()?;
+
+    // The store change is committed first so a failure in the filesystem and
+    // router steps below never leaves a capsule whose resources are gone.
+    if !input.dry_run {
+        store.release_ports(&capsule.capsule_id)?;
+        match input.action {
+            CleanupAction::Archive => {
+                store.transition_state(
+                    &capsule.capsule_id,
+                    CapsuleState::Archived,
+                    "cleanup",
+                    "capsule archived",
+                )?;
+            }
+            CleanupAction::Delete => {
+                store.delete(&capsule.capsule_id)?;
+            }
+        }
+    }
+
+    let (removed_routes, routing_error) = match &input.routing_socket {
+        Some(socket) => match remove_routes(socket, &capsule.capsule_id, input.dry_run) {
+            Ok(domains) => (domains, None),
+            Err(error) => (Vec::new(), Some(error)),
+        },
+        None => (Vec::new(), None),
+    };
+    let retired_tls = match &input.tls_dir {
+        Some(dir) => retire_capsule_material(dir, &capsule, &capsules, input.dry_run)?,
+        None => Vec::new(),
+    };
+
+    let mut removed_profile_dir = None;
+    let mut removed_events = 0;
+    if input.action == CleanupAction::Delete {
+        removed_profile_dir = remove_profile_dir(&capsule, input.dry_run)?;
+        if let Some(path) = &input.events_db {
+            removed_events = remove_events(path, &capsule.capsule_id, input.dry_run)?;
+        }
+    }
+
+    Ok(CleanupReport {
+        capsule_id: capsule.capsule_id,
+        action: input.action,
+        dry_run: input.dry_run,
+        released_ports,
+        removed_routes,
+        routing_error,
+        retired_tls,
+        removed_profile_dir,
+        removed_events,
+    })
+}
+
+fn remove_routes(socket: &Path, capsule_id: &str, dry_run: bool) -> Result<Vec<String>, String> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_io()
+        .enable_time()
+        .build()
+        .map_err(|error| error.to_string())?;
+
+    runtime.block_on(async {
+        let routes = match send_command(socket, RouteCommand::List)
+            .await
+            .map_err(|error| error.to_string())?
+        {
+            RouteOutcome::Listed { routes } => routes,
+            other => return Err(format!("unexpected routing outcome: {other:?}")),
+        };
+
+        let mut removed = Vec::new();
+        for route in routes
+            .into_iter()
+            .filter(|route| route.capsule_id == capsule_id)
+        {
+            if !dry_run {
+                send_command(
+                    socket,
+                    RouteCommand::Remove {
+                        domain: route.domain.clone(),
+                    },
+                )
+                .await
+                .map_err(|error| error.to_string())?;
+            }
+            removed.push(route.domain);
+        }
+        Ok(removed)
+    })
+}
+
+fn remove_profile_dir(capsule: &Capsule, dry_run: bool) -> Result<Option<String>, CleanupError> {
+    let dir = profile_dir_for_capsule(&capsule.capsule_id);
+    if !dir.exists() {
+        return Ok(None);
+    }
+    if !dry_run {
+        std::fs::remove_dir_all(&dir)?;
+    }
+    Ok(Some(dir.display().to_string()))
+}
+
+fn remove_events(path: &Path, capsule_id: &str, dry_run: bool) -> Result<u32, CleanupError> {
+    let mut events = EventStore::open(path)?;
+    if dry_run {
+        return Ok(events.list_for_capsule(capsule_id)?.len() as u32);
+    }
+    Ok(events.delete_for_capsule(capsule_id)?)
+}
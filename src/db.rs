@@ -0,0 +1,78 @@
+use std::{path::Path, thread, time::Duration};
+
+use rusqlite::{Connection, ErrorCode, Transaction, TransactionBehavior};
+
+/// How long SQLite itself waits on a locked database before reporting busy.
+pub const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+/// Extra attempts made by [`write`] after SQLite gives up waiting.
+pub const BUSY_RETRIES: u32 = 5;
+const RETRY_BACKOFF: Duration = Duration::from_millis(20);
+
+/// Opens a database for use by several processes at once: WAL journaling so
+/// readers never block the writer, and a busy timeout instead of failing
+/// immediately with `database is locked`.
+pub fn open(path: &Path) -> rusqlite::Result<Connection> {
+    let conn = Connection::open(path)?;
+    conn.busy_timeout(BUSY_TIMEOUT)?;
+    retry_busy(|| conn.pragma_update_and_check(None, "journal_mode", "WAL", |_| Ok(())))?;
+    conn.pragma_update(None, "synchronous", "NORMAL")?;
+    Ok(conn)
+}
+
+/// Runs `op` in an `IMMEDIATE` transaction and commits it. Taking the write
+/// lock up front means read-then-write sequences cannot interleave with
+/// another writer; beginning or committing is retried while the database
+/// stays busy. Returning an error from `op` rolls the transaction back.
+pub fn write<T, E>(
+    conn: &mut Connection,
+    mut op: impl FnMut(&Transaction<'_>) -> Result<T, E>,
+) -> Result<T, E>
+where
+    E: From<rusqlite::Error>,
+{
+    let mut attempt = 0;
+    loop {
+        let tx = match conn.transaction_with_behavior(TransactionBehavior::Immediate) {
+            Ok(tx) => tx,
+            Err(error) if is_busy(&error) && attempt < BUSY_RETRIES => {
+                attempt += 1;
+                backoff(attempt);
+                continue;
+            }
+            Err(error) => return Err(error.into()),
+        };
+        let value = op(&tx)?;
+        match tx.commit() {
+            Ok(()) => return Ok(value),
+            Err(error) if is_busy(&error) && attempt < BUSY_RETRIES => {
+                attempt += 1;
+                backoff(attempt);
+            }
+            Err(error) => return Err(error.into()),
+        }
+    }
+}
+
+pub fn is_busy(error: &rusqlite::Error) -> bool {
+    matches!(
+        error.sqlite_error_code(),
+        Some(ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked)
+    )
+}
+
+fn retry_busy<T>(mut op: impl FnMut() -> rusqlite::Result<T>) -> rusqlite::Result<T> {
+    let mut attempt = 0;
+    loop {
+        match op() {
+            Err(error) if is_busy(&error) && attempt < BUSY_RETRIES => {
+                attempt += 1;
+                backoff(attempt);
+            }
+            result => return result,
+        }
+    }
+}
+
+fn backoff(attempt: u32) {
+    thread::sleep(RETRY_BACKOFF * attempt);
+}
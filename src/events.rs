@@ -1,9 +1,28 @@
 use std::path::Path;
 
-use rusqlite::{Connection, params, params_from_iter};
+use rusqlite::{Connection, Transaction, params, params_from_iter};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+use crate::{
+    db,
+    migrate::{self, Migration, MigrationError, MigrationPlan},
+};
+
+/// Ordered schema history for the runtime event database.
+pub const EVENT_MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "create runtime_events",
+        apply: create_runtime_events_table,
+    },
+    Migration {
+        version: 2,
+        description: "index runtime_events by capsule and time",
+        apply: create_runtime_events_index,
+    },
+];
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct RuntimeEvent {
     pub capsule_id: String,
@@ -33,6 +52,8 @@ pub enum EventError {
     Db(#[from] rusqlite::Error),
     #[error("io: {0}")]
     Io(#[from] std::io::Error),
+    #[error("migration: {0}")]
+    Migration(#[from] MigrationError),
 }
 
 #[derive(Debug)]
@@ -46,37 +67,37 @@ impl EventStore {
             std::fs::create_dir_all(parent)?;
         }
 
-        let conn = Connection::open(path)?;
-        conn.execute_batch(
-            "
-            CREATE TABLE IF NOT EXISTS runtime_events (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                capsule_id TEXT NOT NULL,
-                component TEXT NOT NULL,
-                level TEXT NOT NULL,
-                message TEXT NOT NULL,
-                ts_unix_ms INTEGER NOT NULL
-            );
-            ",
-        )?;
+        let mut conn = db::open(path)?;
+        migrate::apply(&mut conn, EVENT_MIGRATIONS)?;
 
         Ok(Self { conn })
     }
 
+    /// Reports pending event schema migrations without touching the file.
+    pub fn migration_plan(path: &Path) -> Result<MigrationPlan, EventError> {
+        Ok(migrate::plan_path(path, EVENT_MIGRATIONS)?)
+    }
+
+    pub fn schema_version(&self) -> Result<u32, EventError> {
+        Ok(migrate::schema_version(&self.conn)?)
+    }
+
     pub fn append(&mut self, event: RuntimeEvent) -> Result<(), EventError> {
-        self.conn.execute(
-            "
-            INSERT INTO runtime_events (capsule_id, component, level, message, ts_unix_ms)
-            VALUES (?1, ?2, ?3, ?4, ?5)
-            ",
-            params![
-                event.capsule_id,
-                event.component,
-                event.level,
-                event.message,
-                event.ts_unix_ms,
-            ],
-        )?;
+        db::write(&mut self.conn, |tx| {
+            tx.execute(
+                "
+                INSERT INTO runtime_events (capsule_id, component, level, message, ts_unix_ms)
+                VALUES (?1, ?2, ?3, ?4, ?5)
+                ",
+                params![
+                    event.capsule_id,
+                    event.component,
+                    event.level,
+                    event.message,
+                    event.ts_unix_ms,
+                ],
+            )
+        })?;
 
         Ok(())
     }
@@ -106,6 +127,16 @@ impl EventStore {
         Ok(rows)
     }
 
+    pub fn delete_for_capsule(&mut self, capsule_id: &str) -> Result<u32, EventError> {
+        let deleted = db::write(&mut self.conn, |tx| {
+            tx.execute(
+                "DELETE FROM runtime_events WHERE capsule_id = ?1",
+                params![capsule_id],
+            )
+        })?;
+        Ok(deleted as u32)
+    }
+
     pub fn count_for_capsule_level(
         &self,
         capsule_id: &str,
@@ -209,3 +240,27 @@ impl EventStore {
         Ok(rows)
     }
 }
+
+fn create_runtime_events_table(tx: &Transaction<'_>) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS runtime_events (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            capsule_id TEXT NOT NULL,
+            component TEXT NOT NULL,
+            level TEXT NOT NULL,
+            message TEXT NOT NULL,
+            ts_unix_ms INTEGER NOT NULL
+        );
+        ",
+    )
+}
+
+fn create_runtime_events_index(tx: &Transaction<'_>) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "
+        CREATE INDEX IF NOT EXISTS runtime_events_capsule_ts
+        ON runtime_events (capsule_id, ts_unix_ms);
+        ",
+    )
+}
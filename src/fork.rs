@@ -0,0 +1,294 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    capsule::{Capsule, normalize_slug, validate_capsule_id},
+    cleanup::{CleanupAction, CleanupError, CleanupInput, CleanupReport, cleanup_capsule},
+    ports::{DEFAULT_PORT_QUOTA, DEFAULT_SERVICE},
+    store::{CapsuleFork, CapsuleStore, StoreError},
+};
+
+#[derive(Debug, Clone)]
+pub struct ForkInput {
+    pub capsule_db: PathBuf,
+    pub source_id: String,
+    pub branch: String,
+    pub pool: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ForkReport {
+    pub capsule_id: String,
+    pub source_id: String,
+    pub branch: String,
+    pub created_branch: bool,
+    pub repo_path: String,
+    pub slug: String,
+    pub domain: String,
+    pub workspace: u16,
+    pub service_ports: BTreeMap<String, u16>,
+}
+
+#[derive(Debug, Clone)]
+pub struct UnforkInput {
+    pub capsule_db: PathBuf,
+    pub capsule_id: String,
+    pub routing_socket: Option<PathBuf>,
+    pub tls_dir: Option<PathBuf>,
+    pub events_db: Option<PathBuf>,
+    /// Removes the worktree even if it has uncommitted changes.
+    pub force: bool,
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnforkReport {
+    pub capsule_id: String,
+    pub source_id: String,
+    pub branch: String,
+    pub worktree: String,
+    pub removed_worktree: bool,
+    pub cleanup: CleanupReport,
+}
+
+#[derive(Debug, Error)]
+pub enum ForkError {
+    #[error("store: {0}")]
+    Store(#[from] StoreError),
+    #[error("{0}")]
+    Cleanup(#[from] CleanupError),
+    #[error("io: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("capsule not found: {0}")]
+    CapsuleNotFound(String),
+    #[error("capsule '{0}' has no repo_path to fork from")]
+    MissingRepoPath(String),
+    #[error("invalid branch name '{0}'")]
+    InvalidBranch(String),
+    #[error("capsule '{0}' already exists")]
+    CapsuleExists(String),
+    #[error("worktree path already exists: {0}")]
+    WorktreeExists(String),
+    #[error("capsule '{0}' is not a fork")]
+    NotAFork(String),
+    #[error("worktree has uncommitted changes: {0}; commit them or pass --force true")]
+    DirtyWorktree(String),
+    #[error("port pool '{0}' has no free port for the fork")]
+    PortsExhausted(String),
+    #[error("git {args}: {stderr}")]
+    Git { args: String, stderr: String },
+}
+
+/// Adds a `git worktree` for `branch` next to the source capsule's repo and
+/// registers it as a new capsule with a derived slug, the next free workspace
+/// and fresh ports for each of the source's services.
+pub fn fork_capsule(input: &ForkInput) -> Result<ForkReport, ForkError> {
+    let mut store = CapsuleStore::open(&input.capsule_db)?;
+    let source = store
+        .get(&input.source_id)?
+        .ok_or_else(|| ForkError::CapsuleNotFound(input.source_id.clone()))?;
+    if source.repo_path.is_empty() {
+        return Err(ForkError::MissingRepoPath(source.capsule_id));
+    }
+    let branch_slug = normalize_slug(&input.branch);
+    if branch_slug.is_empty() {
+        return Err(ForkError::InvalidBranch(input.branch.clone()));
+    }
+
+    let capsule_id = format!("{}-{branch_slug}", source.capsule_id);
+    validate_capsule_id(&capsule_id).map_err(StoreError::from)?;
+    if store.get(&capsule_id)?.is_some() {
+        return Err(ForkError::CapsuleExists(capsule_id));
+    }
+    store.effective_port_pool(&input.pool)?;
+    let mut services = store
+        .list_service_ports(&source.capsule_id)?
+        .into_keys()
+        .collect::<Vec<_>>();
+    if services.is_empty() {
+        services.push(DEFAULT_SERVICE.to_string());
+    }
+
+    let source_repo = PathBuf::from(&source.repo_path);
+    let worktree = worktree_path(&source_repo, &branch_slug);
+    if worktree.exists() {
+        return Err(ForkError::WorktreeExists(worktree.display().to_string()));
+    }
+    let created_branch = add_worktree(&source_repo, &worktree, &input.branch)?;
+
+    let capsule = Capsule::new(
+        &capsule_id,
+        &format!("{} {}", source.display_name, input.branch),
+        source.mode,
+        store.next_free_workspace()?,
+    )
+    .with_repo_path(&worktree.display().to_string());
+    let fork = CapsuleFork {
+        capsule_id: capsule_id.clone(),
+        source_id: source.capsule_id.clone(),
+        branch: input.branch.clone(),
+        source_repo_path: source.repo_path.clone(),
+    };
+
+    let registered = register_fork(&mut store, &capsule, &fork, &services, &input.pool);
+    let service_ports = match registered {
+        Ok(ports) => ports,
+        Err(error) => {
+            let _ = store.delete(&capsule_id);
+            let _ = git(
+                &source_repo,
+                &[
+                    "worktree",
+                    "remove",
+                    "--force",
+                    &worktree.display().to_string(),
+                ],
+            );
+            if created_branch {
+                let _ = git(&source_repo, &["branch", "-D", &input.branch]);
+            }
+            return Err(error);
+        }
+    };
+
+    Ok(ForkReport {
+        capsule_id,
+        source_id: source.capsule_id,
+        branch: input.branch.clone(),
+        created_branch,
+        repo_path: capsule.repo_path.clone(),
+        slug: capsule.slug.clone(),
+        domain: capsule.domain(),
+        workspace: capsule.workspace,
+        service_ports,
+    })
+}
+
+/// Deletes a fork's capsule with the same cleanup as `capsule delete` and
+/// then removes its worktree, so a failed cleanup leaves the checkout intact.
+/// A dirty worktree is refused up front unless `force` is set. The branch
+/// itself is kept.
+pub fn unfork_capsule(input: &UnforkInput) -> Result<UnforkReport, ForkError> {
+    let (capsule, fork) = {
+        let store = CapsuleStore::open(&input.capsule_db)?;
+        let capsule = store
+            .get(&input.capsule_id)?
+            .ok_or_else(|| ForkError::CapsuleNotFound(input.capsule_id.clone()))?;
+        let fork = store
+            .fork_of(&input.capsule_id)?
+            .ok_or_else(|| ForkError::NotAFork(input.capsule_id.clone()))?;
+        (capsule, fork)
+    };
+
+    let worktree = PathBuf::from(&capsule.repo_path);
+    let removed_worktree = worktree.exists();
+    if removed_worktree && !input.force && !git(&worktree, &["status", "--porcelain"])?.is_empty() {
+        return Err(ForkError::DirtyWorktree(worktree.display().to_string()));
+    }
+
+    let cleanup = cleanup_capsule(&CleanupInput {
+        capsule_db: input.capsule_db.clone(),
+        capsule_id: capsule.capsule_id.clone(),
+        action: CleanupAction::Delete,
+        routing_socket: input.routing_socket.clone(),
+        tls_dir: input.tls_dir.clone(),
+        events_db: input.events_db.clone(),
+        dry_run: input.dry_run,
+    })?;
+
+    if !input.dry_run {
+        let source_repo = PathBuf::from(&fork.source_repo_path);
+        if removed_worktree {
+            let target = worktree.display().to_string();
+            let mut args = vec!["worktree", "remove"];
+            if input.force {
+                args.push("--force");
+            }
+            args.push(&target);
+            git(&source_repo, &args)?;
+        } else {
+            git(&source_repo, &["worktree", "prune"])?;
+        }
+    }
+
+    Ok(UnforkReport {
+        capsule_id: capsule.capsule_id,
+        source_id: fork.source_id,
+        branch: fork.branch,
+        worktree: worktree.display().to_string(),
+        removed_worktree,
+        cleanup,
+    })
+}
+
+/// `<parent>/<repo-dir>-<branch-slug>`, a sibling of the source checkout.
+pub fn worktree_path(source_repo: &Path, branch_slug: &str) -> PathBuf {
+    let name = source_repo
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "repo".to_string());
+    source_repo
+        .parent()
+        .unwrap_or(source_repo)
+        .join(format!("{name}-{branch_slug}"))
+}
+
+fn register_fork(
+    store: &mut CapsuleStore,
+    capsule: &Capsule,
+    fork: &CapsuleFork,
+    services: &[String],
+    pool: &str,
+) -> Result<BTreeMap<String, u16>, ForkError> {
+    store.insert_fork(capsule, fork)?;
+    let mut ports = BTreeMap::new();
+    for service in services {
+        let port = store
+            .allocate_pool_port(&capsule.capsule_id, service, pool, DEFAULT_PORT_QUOTA)?
+            .ok_or_else(|| ForkError::PortsExhausted(pool.to_string()))?;
+        ports.insert(service.clone(), port);
+    }
+    Ok(ports)
+}
+
+/// Returns whether `branch` had to be created.
+fn add_worktree(source_repo: &Path, worktree: &Path, branch: &str) -> Result<bool, ForkError> {
+    let target = worktree.display().to_string();
+    let exists = git(
+        source_repo,
+        &[
+            "rev-parse",
+            "--verify",
+            "--quiet",
+            &format!("refs/heads/{branch}"),
+        ],
+    )
+    .is_ok();
+    if exists {
+        git(source_repo, &["worktree", "add", &target, branch])?;
+    } else {
+        git(source_repo, &["worktree", "add", "-b", branch, &target])?;
+    }
+    Ok(!exists)
+}
+
+fn git(repo: &Path, args: &[&str]) -> Result<String, ForkError> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(args)
+        .output()?;
+    if !output.status.success() {
+        return Err(ForkError::Git {
+            args: args.join(" "),
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        });
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
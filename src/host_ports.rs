@@ -0,0 +1,246 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    net::{Ipv4Addr, TcpListener},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::store::CapsulePort;
+
+const PROC_NET_TCP: [&str; 2] = ["/proc/net/tcp", "/proc/net/tcp6"];
+const TCP_LISTEN: &str = "0A";
+
+/// A TCP socket in LISTEN state as reported by `/proc/net/tcp{,6}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HostListener {
+    pub port: u16,
+    pub inode: u64,
+}
+
+/// A host process holding a listening socket.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PortOwner {
+    pub pid: u32,
+    pub command: String,
+    pub capsule_id: Option<String>,
+    /// False when the process environment could not be read, so it is not
+    /// known whether the process runs inside a capsule.
+    pub environ_readable: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PortStatus {
+    /// Held by a process outside the capsule.
+    Conflict,
+    /// In use, but no owning process could be identified.
+    OwnerUnknown,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PortConflict {
+    pub capsule_id: String,
+    pub service: String,
+    pub port: u16,
+    pub status: PortStatus,
+    pub owners: Vec<PortOwner>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PortAudit {
+    pub checked: u32,
+    pub conflicts: Vec<PortConflict>,
+    /// Ports in use whose owner is unknown; reported, not counted as
+    /// conflicts.
+    pub unknown_owners: Vec<PortConflict>,
+}
+
+/// Point-in-time view of which host ports are taken. Candidates are rejected
+/// if they appear as listeners in procfs or cannot be bound.
+#[derive(Debug, Clone, Default)]
+pub struct HostPortProbe {
+    listening: BTreeSet<u16>,
+}
+
+impl HostPortProbe {
+    pub fn snapshot() -> Self {
+        Self {
+            listening: host_listeners()
+                .into_iter()
+                .map(|listener| listener.port)
+                .collect(),
+        }
+    }
+
+    pub fn is_free(&self, port: u16) -> bool {
+        !self.listening.contains(&port) && can_bind(port)
+    }
+}
+
+pub fn can_bind(port: u16) -> bool {
+    TcpListener::bind((Ipv4Addr::UNSPECIFIED, port)).is_ok()
+}
+
+/// Listening sockets from `/proc/net/tcp{,6}`; empty where procfs is absent.
+pub fn host_listeners() -> Vec<HostListener> {
+    PROC_NET_TCP
+        .iter()
+        .filter_map(|path| std::fs::read_to_string(path).ok())
+        .flat_map(|contents| parse_proc_net_tcp(&contents))
+        .collect()
+}
+
+pub fn parse_proc_net_tcp(contents: &str) -> Vec<HostListener> {
+    contents
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields = line.split_whitespace().collect::<Vec<_>>();
+            if fields.get(3) != Some(&TCP_LISTEN) {
+                return None;
+            }
+            let (_, port_hex) = fields.get(1)?.rsplit_once(':')?;
+            Some(HostListener {
+                port: u16::from_str_radix(port_hex, 16).ok()?,
+                inode: fields.get(9)?.parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+/// Status of a port recorded for `capsule_id` that is in use by `owners`, or
+/// `None` when every owner runs inside that capsule. An owner outside it is a
+/// conflict; with none visible, or only some identified and all inside, the
+/// owner is unknown.
+pub fn port_status(capsule_id: &str, owners: &[PortOwner]) -> Option<PortStatus> {
+    let outside = owners
+        .iter()
+        .any(|owner| owner.environ_readable && owner.capsule_id.as_deref() != Some(capsule_id));
+    if outside {
+        return Some(PortStatus::Conflict);
+    }
+    if owners.is_empty() || owners.iter().any(|owner| !owner.environ_readable) {
+        return Some(PortStatus::OwnerUnknown);
+    }
+    None
+}
+
+/// Reports recorded capsule ports that are listened on by a process which is
+/// not running inside that capsule (per its `NEXUM_CAPSULE_ID`), and,
+/// separately, ports in use whose owner cannot be identified.
+pub fn audit_capsule_ports(ports: &[CapsulePort]) -> PortAudit {
+    let listeners = host_listeners();
+    let owners = socket_owners(
+        &listeners
+            .iter()
+            .map(|listener| listener.inode)
+            .collect::<BTreeSet<_>>(),
+    );
+
+    let mut conflicts = Vec::new();
+    let mut unknown_owners = Vec::new();
+    for recorded in ports {
+        let inodes = listeners
+            .iter()
+            .filter(|listener| listener.port == recorded.port)
+            .map(|listener| listener.inode)
+            .collect::<Vec<_>>();
+        let occupied = !inodes.is_empty() || !can_bind(recorded.port);
+        if !occupied {
+            continue;
+        }
+
+        let port_owners = inodes
+            .iter()
+            .filter_map(|inode| owners.get(inode))
+            .flatten()
+            .cloned()
+            .collect::<Vec<_>>();
+        let Some(status) = port_status(&recorded.capsule_id, &port_owners) else {
+            continue;
+        };
+        let finding = PortConflict {
+            capsule_id: recorded.capsule_id.clone(),
+            service: recorded.service.clone(),
+            port: recorded.port,
+            status,
+            owners: port_owners,
+        };
+        match status {
+            PortStatus::Conflict => conflicts.push(finding),
+            PortStatus::OwnerUnknown => unknown_owners.push(finding),
+        }
+    }
+
+    PortAudit {
+        checked: ports.len() as u32,
+        conflicts,
+        unknown_owners,
+    }
+}
+
+fn socket_owners(inodes: &BTreeSet<u64>) -> BTreeMap<u64, Vec<PortOwner>> {
+    let mut owners: BTreeMap<u64, Vec<PortOwner>> = BTreeMap::new();
+    let Ok(processes) = std::fs::read_dir("/proc") else {
+        return owners;
+    };
+
+    for process in processes.flatten() {
+        let Some(pid) = process
+            .file_name()
+            .to_str()
+            .and_then(|name| name.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        let Ok(fds) = std::fs::read_dir(process.path().join("fd")) else {
+            continue;
+        };
+        let held = fds
+            .flatten()
+            .filter_map(|fd| std::fs::read_link(fd.path()).ok())
+            .filter_map(|target| socket_inode(&target))
+            .filter(|inode| inodes.contains(inode))
+            .collect::<BTreeSet<_>>();
+        if held.is_empty() {
+            continue;
+        }
+
+        let environ = process_environ(&process.path());
+        let owner = PortOwner {
+            pid,
+            command: std::fs::read_to_string(process.path().join("comm"))
+                .map(|comm| comm.trim().to_string())
+                .unwrap_or_default(),
+            capsule_id: environ.as_deref().and_then(environ_capsule_id),
+            environ_readable: environ.is_some(),
+        };
+        for inode in held {
+            owners.entry(inode).or_default().push(owner.clone());
+        }
+    }
+
+    owners
+}
+
+fn socket_inode(target: &Path) -> Option<u64> {
+    target
+        .to_str()?
+        .strip_prefix("socket:[")?
+        .strip_suffix(']')?
+        .parse()
+        .ok()
+}
+
+fn process_environ(process_dir: &Path) -> Option<Vec<u8>> {
+    std::fs::read(process_dir.join("environ")).ok()
+}
+
+fn environ_capsule_id(environ: &[u8]) -> Option<String> {
+    environ
+        .split(|byte| *byte == 0)
+        .filter_map(|entry| std::str::from_utf8(entry).ok())
+        .find_map(|entry| entry.strip_prefix("NEXUM_CAPSULE_ID="))
+        .map(ToString::to_string)
+}
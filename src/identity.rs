@@ -1,4 +1,19 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::shell::shell_quote;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProfileTrust {
+    pub profile_dir: String,
+    pub trusted_cert_path: String,
+    pub user_js_path: String,
+    pub policies_path: String,
+    pub certutil_command: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
 
 pub fn profile_dir_for_capsule(capsule_id: &str) -> PathBuf {
     PathBuf::from(format!("/tmp/nexum/profiles/{capsule_id}"))
@@ -14,3 +29,84 @@ pub fn browser_launch_command(url: &str, capsule_id: &str, collision_detected: b
 
     format!("xdg-open {url}")
 }
+
+/// Makes an isolated browser profile trust a Nexum certificate. The cert is
+/// copied into the profile and the returned `certutil_command` imports it
+/// into the profile's NSS store when `certutil` is available at restore time.
+/// As a fallback the profile gets a `user.js` enabling enterprise roots and a
+/// `policies.json` with a `Certificates.Install` entry for the copied cert;
+/// without `certutil` the report carries a warning, since neither fallback
+/// takes effect until the cert is in the OS store or the policy is installed
+/// into Firefox's `distribution/` directory.
+pub fn provision_profile_trust(
+    profile_dir: &Path,
+    cert_pem_path: &Path,
+    nickname: &str,
+) -> Result<ProfileTrust, std::io::Error> {
+    let trust_dir = profile_dir.join("nexum-trust");
+    std::fs::create_dir_all(&trust_dir)?;
+
+    let trusted_cert_path = trust_dir.join(format!("{nickname}.crt.pem"));
+    std::fs::copy(cert_pem_path, &trusted_cert_path)?;
+
+    let user_js_path = profile_dir.join("user.js");
+    std::fs::write(
+        &user_js_path,
+        [
+            "// Managed by Nexum: trust Nexum-issued capsule certificates.",
+            "user_pref(\"security.enterprise_roots.enabled\", true);",
+            "",
+        ]
+        .join("\n"),
+    )?;
+
+    let policies_path = trust_dir.join("policies.json");
+    let policies = serde_json::json!({
+        "policies": {
+            "Certificates": {
+                "Install": [trusted_cert_path.display().to_string()],
+            }
+        }
+    });
+    std::fs::write(
+        &policies_path,
+        serde_json::to_vec_pretty(&policies).map_err(std::io::Error::other)?,
+    )?;
+
+    let profile = shell_quote(&profile_dir.display().to_string());
+    let database = shell_quote(&format!("sql:{}", profile_dir.display()));
+    let certutil_command = format!(
+        "if command -v certutil >/dev/null; then [ -f {profile}/cert9.db ] || certutil -N --empty-password -d {database}; certutil -A -d {database} -n {nickname} -t P,, -i {cert}; else echo {warning} >&2; fi",
+        nickname = shell_quote(nickname),
+        cert = shell_quote(&trusted_cert_path.display().to_string()),
+        warning = shell_quote(&format!(
+            "nexum: certutil not found; {profile_path} relies on user.js and {policies}",
+            profile_path = profile_dir.display(),
+            policies = policies_path.display(),
+        )),
+    );
+
+    let mut warnings = Vec::new();
+    if !certutil_available() {
+        warnings.push(format!(
+            "certutil not found: {nickname} is not imported into {}; user.js only helps once the cert is in the OS trust store and {} must be installed into Firefox's distribution directory",
+            profile_dir.display(),
+            policies_path.display(),
+        ));
+    }
+
+    Ok(ProfileTrust {
+        profile_dir: profile_dir.display().to_string(),
+        trusted_cert_path: trusted_cert_path.display().to_string(),
+        user_js_path: user_js_path.display().to_string(),
+        policies_path: policies_path.display().to_string(),
+        certutil_command,
+        warnings,
+    })
+}
+
+/// Whether `certutil` is on `PATH` for the current process.
+pub fn certutil_available() -> bool {
+    std::env::var_os("PATH")
+        .is_some_and(|path| std::env::split_paths(&path).any(|dir| dir.join("certutil").is_file()))
+}
@@ -0,0 +1,185 @@
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::capsule::{Capsule, mode_to_str, parse_state, state_to_str};
+
+pub const LABEL_MAX_LEN: usize = 63;
+/// Selector keys that refer to capsule fields rather than labels.
+pub const RESERVED_LABEL_KEYS: [&str; 2] = ["state", "mode"];
+
+pub type Labels = BTreeMap<String, String>;
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum LabelError {
+    #[error(
+        "invalid label key '{0}': use up to 63 lowercase letters, digits, '-', '_', '.' or '/', starting with a letter or digit"
+    )]
+    InvalidKey(String),
+    #[error("label key '{0}' is reserved")]
+    ReservedKey(String),
+    #[error("invalid label value '{0}': use up to 63 letters, digits, '-', '_' or '.'")]
+    InvalidValue(String),
+    #[error("invalid label '{0}': expected key=value")]
+    InvalidLabel(String),
+    #[error("invalid selector term '{0}'")]
+    InvalidSelector(String),
+}
+
+/// One comma-separated term of a [`Selector`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "op")]
+pub enum Requirement {
+    Equals { key: String, value: String },
+    NotEquals { key: String, value: String },
+    Exists { key: String },
+    Absent { key: String },
+}
+
+/// A conjunction of requirements such as `team=infra,tier!=db,!archived`.
+///
+/// Terms are `key=value`, `key!=value`, `key` (label present) and `!key`
+/// (label absent). The keys `state` and `mode` match the capsule's own fields,
+/// and a bare state name is shorthand for `state=<name>`, so `!archived`
+/// selects every capsule that is not archived. An empty selector matches
+/// everything.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Selector {
+    pub requirements: Vec<Requirement>,
+}
+
+impl Selector {
+    pub fn matches(&self, capsule: &Capsule, labels: &Labels) -> bool {
+        self.requirements.iter().all(|requirement| {
+            let field = |key: &str| match key {
+                "state" => Some(state_to_str(capsule.state).to_string()),
+                "mode" => Some(mode_to_str(capsule.mode).to_string()),
+                _ => labels.get(key).cloned(),
+            };
+            match requirement {
+                Requirement::Equals { key, value } => field(key).as_ref() == Some(value),
+                Requirement::NotEquals { key, value } => field(key).as_ref() != Some(value),
+                Requirement::Exists { key } => field(key).is_some(),
+                Requirement::Absent { key } => field(key).is_none(),
+            }
+        })
+    }
+}
+
+impl FromStr for Selector {
+    type Err = LabelError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let requirements = input
+            .split(',')
+            .map(str::trim)
+            .filter(|term| !term.is_empty())
+            .map(parse_requirement)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { requirements })
+    }
+}
+
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let terms = self
+            .requirements
+            .iter()
+            .map(|requirement| match requirement {
+                Requirement::Equals { key, value } => format!("{key}={value}"),
+                Requirement::NotEquals { key, value } => format!("{key}!={value}"),
+                Requirement::Exists { key } => key.clone(),
+                Requirement::Absent { key } => format!("!{key}"),
+            })
+            .collect::<Vec<_>>();
+        f.write_str(&terms.join(","))
+    }
+}
+
+pub fn validate_label_key(key: &str) -> Result<(), LabelError> {
+    let valid = key.len() <= LABEL_MAX_LEN
+        && key
+            .bytes()
+            .next()
+            .is_some_and(|first| first.is_ascii_lowercase() || first.is_ascii_digit())
+        && key.bytes().all(|byte| {
+            byte.is_ascii_lowercase() || byte.is_ascii_digit() || b"-_./".contains(&byte)
+        });
+    if !valid {
+        return Err(LabelError::InvalidKey(key.to_string()));
+    }
+    if RESERVED_LABEL_KEYS.contains(&key) || parse_state(key).is_some() {
+        return Err(LabelError::ReservedKey(key.to_string()));
+    }
+    Ok(())
+}
+
+pub fn validate_label_value(value: &str) -> Result<(), LabelError> {
+    let valid = value.len() <= LABEL_MAX_LEN
+        && value
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || b"-_.".contains(&byte));
+    if valid {
+        Ok(())
+    } else {
+        Err(LabelError::InvalidValue(value.to_string()))
+    }
+}
+
+/// Parses `key=value` and validates both halves.
+pub fn parse_label(input: &str) -> Result<(String, String), LabelError> {
+    let (key, value) = input
+        .split_once('=')
+        .ok_or_else(|| LabelError::InvalidLabel(input.to_string()))?;
+    validate_label_key(key)?;
+    validate_label_value(value)?;
+    Ok((key.to_string(), value.to_string()))
+}
+
+fn parse_requirement(term: &str) -> Result<Requirement, LabelError> {
+    let invalid = || LabelError::InvalidSelector(term.to_string());
+    if let Some((key, value)) = term.split_once("!=") {
+        check_selector_key(key).map_err(|_| invalid())?;
+        validate_label_value(value).map_err(|_| invalid())?;
+        return Ok(Requirement::NotEquals {
+            key: key.to_string(),
+            value: value.to_string(),
+        });
+    }
+    if let Some((key, value)) = term.split_once('=') {
+        check_selector_key(key).map_err(|_| invalid())?;
+        validate_label_value(value).map_err(|_| invalid())?;
+        return Ok(Requirement::Equals {
+            key: key.to_string(),
+            value: value.to_string(),
+        });
+    }
+    let (negated, key) = match term.strip_prefix('!') {
+        Some(key) => (true, key),
+        None => (false, term),
+    };
+    if let Some(state) = parse_state(key) {
+        let key = "state".to_string();
+        let value = state_to_str(state).to_string();
+        return Ok(if negated {
+            Requirement::NotEquals { key, value }
+        } else {
+            Requirement::Equals { key, value }
+        });
+    }
+    validate_label_key(key).map_err(|_| invalid())?;
+    let key = key.to_string();
+    Ok(if negated {
+        Requirement::Absent { key }
+    } else {
+        Requirement::Exists { key }
+    })
+}
+
+fn check_selector_key(key: &str) -> Result<(), LabelError> {
+    if RESERVED_LABEL_KEYS.contains(&key) {
+        return Ok(());
+    }
+    validate_label_key(key)
+}
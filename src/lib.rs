@@ -1,16 +1,27 @@
 pub mod attention;
+pub mod backup;
 pub mod capsule;
+pub mod cleanup;
 pub mod control_plane;
+pub mod crypto;
 pub mod cutover;
+pub mod db;
 pub mod events;
 pub mod flags;
+pub mod fork;
+pub mod host_ports;
 pub mod identity;
 pub mod isolation;
+pub mod labels;
+pub mod manifest;
+pub mod migrate;
+pub mod onboard;
 pub mod ports;
 pub mod restore;
 pub mod routing;
 pub mod runflow;
 pub mod runtime_meta;
+pub mod scene;
 pub mod shadow;
 pub mod shell;
 pub mod stead;
@@ -0,0 +1,219 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    capsule::{Capsule, CapsuleMode, normalize_slug},
+    store::{CapsuleStore, StoreError},
+};
+
+pub const MANIFEST_FILE: &str = "nexum.toml";
+
+/// Repo-local capsule defaults read from `<repo_path>/nexum.toml`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CapsuleManifest {
+    pub display_name: Option<String>,
+    pub mode: Option<CapsuleMode>,
+    pub workspace: Option<u16>,
+    pub upstream: Option<String>,
+    #[serde(default)]
+    pub surfaces: ManifestSurfaces,
+    #[serde(default)]
+    pub services: BTreeMap<String, ManifestService>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ManifestSurfaces {
+    /// Commands run in order inside the repo, joined with `&&`.
+    #[serde(default)]
+    pub terminal: Vec<String>,
+    /// Editor target; relative paths resolve against the repo.
+    pub editor: Option<String>,
+    pub browser: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ManifestService {
+    pub port: Option<u16>,
+}
+
+/// Values given explicitly on the command line or in a dispatch event.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RestoreOverrides {
+    pub terminal: Option<String>,
+    pub editor: Option<String>,
+    pub browser: Option<String>,
+    pub upstream: Option<String>,
+    pub force_isolated_mode: Option<bool>,
+}
+
+/// Restore inputs after merging CLI overrides, the manifest and the stored
+/// capsule, in that order of precedence.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResolvedRestore {
+    pub display_name: String,
+    pub workspace: u16,
+    pub terminal_cmd: String,
+    pub editor_target: String,
+    pub browser_url: String,
+    pub route_upstream: String,
+    pub force_isolated_mode: bool,
+    pub manifest_path: Option<String>,
+}
+
+#[derive(Debug, Error)]
+pub enum ManifestError {
+    #[error("io: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("{path}: {source}")]
+    Parse {
+        path: String,
+        source: toml::de::Error,
+    },
+    #[error(
+        "missing restore surfaces: provide --terminal and --editor, set capsule repo_path, or add {MANIFEST_FILE}"
+    )]
+    MissingSurfaces,
+    #[error("missing upstream: provide --upstream or declare upstream in {MANIFEST_FILE}")]
+    MissingUpstream,
+    #[error("{0}")]
+    Store(#[from] StoreError),
+}
+
+impl CapsuleManifest {
+    pub fn parse(input: &str, path: &Path) -> Result<Self, ManifestError> {
+        toml::from_str(input).map_err(|source| ManifestError::Parse {
+            path: path.display().to_string(),
+            source,
+        })
+    }
+
+    /// Loads `nexum.toml` from `repo_path`; a missing file is not an error.
+    pub fn load_from_repo(repo_path: &Path) -> Result<Option<Self>, ManifestError> {
+        let path = manifest_path(repo_path);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        Ok(Some(Self::parse(&std::fs::read_to_string(&path)?, &path)?))
+    }
+
+    /// Upstream declared directly, or the first service with a fixed port.
+    pub fn default_upstream(&self) -> Option<String> {
+        self.upstream.clone().or_else(|| {
+            self.services
+                .values()
+                .find_map(|service| service.port)
+                .map(|port| format!("127.0.0.1:{port}"))
+        })
+    }
+}
+
+pub fn manifest_path(repo_path: &Path) -> PathBuf {
+    repo_path.join(MANIFEST_FILE)
+}
+
+/// Resolves restore inputs for a stored capsule. As in onboarding, the
+/// manifest display name wins over the fallback, here the stored name; it may
+/// not change the slug. A manifest workspace must pass the store's collision
+/// check, and services with a fixed port are recorded in the store so they
+/// are exported like allocated ports.
+pub fn resolve_restore(
+    store: &mut CapsuleStore,
+    capsule: &Capsule,
+    overrides: RestoreOverrides,
+) -> Result<ResolvedRestore, ManifestError> {
+    let repo = (!capsule.repo_path.is_empty()).then(|| PathBuf::from(&capsule.repo_path));
+    let manifest = match &repo {
+        Some(repo) => CapsuleManifest::load_from_repo(repo)?,
+        None => None,
+    };
+    let manifest_ref = manifest.as_ref();
+
+    let terminal_cmd = match (overrides.terminal, &repo) {
+        (Some(terminal), _) => terminal,
+        (None, Some(repo)) => {
+            let commands = manifest_ref
+                .map(|manifest| manifest.surfaces.terminal.clone())
+                .filter(|commands| !commands.is_empty())
+                .unwrap_or_else(|| vec!["nix develop".to_string()]);
+            format!("cd {} && {}", repo.display(), commands.join(" && "))
+        }
+        (None, None) => return Err(ManifestError::MissingSurfaces),
+    };
+
+    let editor_target = match (overrides.editor, &repo) {
+        (Some(editor), _) => editor,
+        (None, Some(repo)) => {
+            match manifest_ref.and_then(|manifest| manifest.surfaces.editor.as_deref()) {
+                Some(".") | None => repo.display().to_string(),
+                Some(editor) => repo.join(editor).display().to_string(),
+            }
+        }
+        (None, None) => return Err(ManifestError::MissingSurfaces),
+    };
+
+    let browser_url = overrides
+        .browser
+        .or_else(|| manifest_ref.and_then(|manifest| manifest.surfaces.browser.clone()))
+        .unwrap_or_else(|| format!("https://{}", capsule.domain()));
+
+    let route_upstream = overrides
+        .upstream
+        .or_else(|| manifest_ref.and_then(CapsuleManifest::default_upstream))
+        .ok_or(ManifestError::MissingUpstream)?;
+
+    let force_isolated_mode = overrides.force_isolated_mode.unwrap_or_else(|| {
+        manifest_ref.and_then(|manifest| manifest.mode) == Some(CapsuleMode::IsolatedNixShell)
+    });
+
+    let display_name = match manifest_ref.and_then(|manifest| manifest.display_name.clone()) {
+        Some(name) if normalize_slug(&name) != capsule.slug => {
+            return Err(StoreError::ImmutableSlug {
+                capsule_id: capsule.capsule_id.clone(),
+                existing_slug: capsule.slug.clone(),
+                attempted_slug: normalize_slug(&name),
+            }
+            .into());
+        }
+        Some(name) => name,
+        None => capsule.display_name.clone(),
+    };
+
+    let workspace = match manifest_ref.and_then(|manifest| manifest.workspace) {
+        Some(workspace) => {
+            store.check_workspace(&capsule.capsule_id, workspace)?;
+            workspace
+        }
+        None => capsule.workspace,
+    };
+
+    for (service, port) in manifest_ref
+        .into_iter()
+        .flat_map(|manifest| &manifest.services)
+        .filter_map(|(service, declared)| declared.port.map(|port| (service, port)))
+    {
+        store.record_service_port(&capsule.capsule_id, service, port)?;
+    }
+
+    Ok(ResolvedRestore {
+        display_name,
+        workspace,
+        terminal_cmd,
+        editor_target,
+        browser_url,
+        route_upstream,
+        force_isolated_mode,
+        manifest_path: manifest
+            .is_some()
+            .then(|| repo.as_deref().map(manifest_path))
+            .flatten()
+            .map(|path| path.display().to_string()),
+    })
+}
@@ -0,0 +1,132 @@
+use std::path::Path;
+
+use rusqlite::{Connection, OpenFlags, Transaction};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::db;
+
+/// One schema step. `version` is the `PRAGMA user_version` the database
+/// reaches once `apply` has run; versions must be contiguous from 1.
+#[derive(Debug, Clone, Copy)]
+pub struct Migration {
+    pub version: u32,
+    pub description: &'static str,
+    pub apply: fn(&Transaction<'_>) -> rusqlite::Result<()>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingMigration {
+    pub version: u32,
+    pub description: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MigrationPlan {
+    pub current_version: u32,
+    pub target_version: u32,
+    pub pending: Vec<PendingMigration>,
+}
+
+#[derive(Debug, Error)]
+pub enum MigrationError {
+    #[error("db: {0}")]
+    Db(#[from] rusqlite::Error),
+    #[error(
+        "database schema version {found} is newer than supported version {supported}; upgrade nexum"
+    )]
+    NewerSchema { found: u32, supported: u32 },
+}
+
+pub fn schema_version(conn: &Connection) -> Result<u32, MigrationError> {
+    Ok(conn.query_row("PRAGMA user_version", [], |row| row.get::<_, u32>(0))?)
+}
+
+pub fn plan(conn: &Connection, migrations: &[Migration]) -> Result<MigrationPlan, MigrationError> {
+    plan_from_version(schema_version(conn)?, migrations)
+}
+
+/// Plans against the database at `path` without creating or modifying it.
+pub fn plan_path(path: &Path, migrations: &[Migration]) -> Result<MigrationPlan, MigrationError> {
+    if !path.exists() {
+        return plan_from_version(0, migrations);
+    }
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    plan(&conn, migrations)
+}
+
+/// Applies every pending migration in order, each in its own immediate
+/// transaction together with the `user_version` bump, and returns what was
+/// planned. A step another process applied in the meantime is skipped.
+pub fn apply(
+    conn: &mut Connection,
+    migrations: &[Migration],
+) -> Result<MigrationPlan, MigrationError> {
+    let plan = plan(conn, migrations)?;
+    for migration in migrations
+        .iter()
+        .filter(|migration| migration.version > plan.current_version)
+    {
+        db::write(conn, |tx| {
+            if schema_version(tx)? >= migration.version {
+                return Ok(());
+            }
+            (migration.apply)(tx)?;
+            tx.pragma_update(None, "user_version", migration.version)?;
+            Ok::<_, MigrationError>(())
+        })?;
+    }
+    Ok(plan)
+}
+
+/// Adds a column unless it is already present, so migrations can adopt
+/// databases created before schema versioning existed.
+pub fn add_column_if_missing(
+    tx: &Transaction<'_>,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> rusqlite::Result<()> {
+    let exists = tx
+        .prepare(&format!("PRAGMA table_info({table})"))?
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<Result<Vec<_>, _>>()?
+        .iter()
+        .any(|name| name == column);
+    if !exists {
+        tx.execute_batch(&format!(
+            "ALTER TABLE {table} ADD COLUMN {column} {definition}"
+        ))?;
+    }
+    Ok(())
+}
+
+fn plan_from_version(
+    current_version: u32,
+    migrations: &[Migration],
+) -> Result<MigrationPlan, MigrationError> {
+    let target_version = migrations
+        .iter()
+        .map(|migration| migration.version)
+        .max()
+        .unwrap_or(0);
+    if current_version > target_version {
+        return Err(MigrationError::NewerSchema {
+            found: current_version,
+            supported: target_version,
+        });
+    }
+
+    Ok(MigrationPlan {
+        current_version,
+        target_version,
+        pending: migrations
+            .iter()
+            .filter(|migration| migration.version > current_version)
+            .map(|migration| PendingMigration {
+                version: migration.version,
+                description: migration.description.to_string(),
+            })
+            .collect(),
+    })
+}
@@ -0,0 +1,250 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    capsule::{Capsule, CapsuleMode, InvalidCapsuleId, generate_capsule_id, validate_capsule_id},
+    crypto::CryptoError,
+    manifest::{CapsuleManifest, ManifestError, manifest_path},
+    ports::{DEFAULT_PORT_QUOTA, DEFAULT_SERVICE},
+    store::{CapsuleStore, StoreError},
+};
+
+#[derive(Debug, Clone)]
+pub struct FromRepoInput {
+    pub capsule_db: PathBuf,
+    pub repo_path: PathBuf,
+    /// Generated when absent.
+    pub capsule_id: Option<String>,
+    pub display_name: Option<String>,
+    pub mode: Option<CapsuleMode>,
+    pub workspace: Option<u16>,
+    pub pool: String,
+}
+
+/// Where the display name of a capsule created from a repo came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NameSource {
+    Flag,
+    Manifest,
+    GitRemote,
+    Directory,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FromRepoReport {
+    pub capsule_id: String,
+    pub display_name: String,
+    pub name_source: NameSource,
+    pub slug: String,
+    pub domain: String,
+    pub repo_path: String,
+    pub mode: CapsuleMode,
+    pub workspace: u16,
+    pub manifest_path: Option<String>,
+    pub service_ports: BTreeMap<String, u16>,
+}
+
+#[derive(Debug, Error)]
+pub enum OnboardError {
+    #[error("store: {0}")]
+    Store(#[from] StoreError),
+    #[error("manifest: {0}")]
+    Manifest(#[from] ManifestError),
+    #[error("{0}")]
+    InvalidCapsuleId(#[from] InvalidCapsuleId),
+    #[error("id generation: {0}")]
+    Crypto(#[from] CryptoError),
+    #[error("repo not found: {0}")]
+    RepoNotFound(String),
+    #[error("repo {repo_path} is already registered as capsule '{capsule_id}'")]
+    RepoRegistered {
+        repo_path: String,
+        capsule_id: String,
+    },
+    #[error("capsule '{0}' already exists")]
+    CapsuleExists(String),
+    #[error("cannot derive a display name for {0}; pass --name")]
+    NoDisplayName(String),
+    #[error("port pool '{0}' has no free port for the capsule")]
+    PortsExhausted(String),
+}
+
+/// Registers `repo_path` as a capsule in one step. The display name comes
+/// from the flag, `nexum.toml`, the `origin` remote or the directory name, in
+/// that order; mode and workspace from the flags or the manifest, falling back
+/// to host mode and the next free workspace. Manifest services with a fixed
+/// port are recorded as declared; every other service (or the default
+/// service) gets a port from `pool`.
+pub fn create_from_repo(input: &FromRepoInput) -> Result<FromRepoReport, OnboardError> {
+    let repo = input
+        .repo_path
+        .canonicalize()
+        .ok()
+        .filter(|path| path.is_dir())
+        .ok_or_else(|| OnboardError::RepoNotFound(input.repo_path.display().to_string()))?;
+    let repo_path = repo.display().to_string();
+    let manifest = CapsuleManifest::load_from_repo(&repo)?;
+    let manifest_ref = manifest.as_ref();
+
+    let (display_name, name_source) = derive_display_name(input, manifest_ref, &repo)?;
+    let capsule_id = match &input.capsule_id {
+        Some(capsule_id) => {
+            validate_capsule_id(capsule_id)?;
+            capsule_id.clone()
+        }
+        None => generate_capsule_id()?,
+    };
+
+    let mut store = CapsuleStore::open(&input.capsule_db)?;
+    if store.get(&capsule_id)?.is_some() {
+        return Err(OnboardError::CapsuleExists(capsule_id));
+    }
+    if let Some(existing) = store
+        .list()?
+        .into_iter()
+        .find(|capsule| capsule.repo_path == repo_path)
+    {
+        return Err(OnboardError::RepoRegistered {
+            repo_path,
+            capsule_id: existing.capsule_id,
+        });
+    }
+    store.effective_port_pool(&input.pool)?;
+
+    let mode = input
+        .mode
+        .or_else(|| manifest_ref.and_then(|manifest| manifest.mode))
+        .unwrap_or(CapsuleMode::HostDefault);
+    let workspace = match input
+        .workspace
+        .or_else(|| manifest_ref.and_then(|manifest| manifest.workspace))
+    {
+        Some(workspace) => {
+            store.check_workspace(&capsule_id, workspace)?;
+            workspace
+        }
+        None => store.next_free_workspace()?,
+    };
+
+    let mut services = manifest_ref
+        .map(|manifest| {
+            manifest
+                .services
+                .iter()
+                .map(|(name, service)| (name.clone(), service.port))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    if manifest_ref.is_none_or(|manifest| manifest.services.is_empty()) {
+        services.push((DEFAULT_SERVICE.to_string(), None));
+    }
+
+    let capsule =
+        Capsule::new(&capsule_id, &display_name, mode, workspace).with_repo_path(&repo_path);
+    store.upsert(capsule.clone())?;
+    let service_ports = match allocate_services(&mut store, &capsule_id, &services, &input.pool) {
+        Ok(ports) => ports,
+        Err(error) => {
+            let _ = store.delete(&capsule_id);
+            return Err(error);
+        }
+    };
+
+    Ok(FromRepoReport {
+        capsule_id,
+        display_name,
+        name_source,
+        slug: capsule.slug.clone(),
+        domain: capsule.domain(),
+        repo_path,
+        mode,
+        workspace,
+        manifest_path: manifest
+            .is_some()
+            .then(|| manifest_path(&repo).display().to_string()),
+        service_ports,
+    })
+}
+
+/// Repository name from a git remote URL, e.g. `billing-api` for
+/// `git@github.com:acme/billing-api.git`.
+pub fn repo_name_from_remote(url: &str) -> Option<String> {
+    let name = url
+        .trim()
+        .trim_end_matches('/')
+        .rsplit(['/', ':'])
+        .next()?
+        .trim_end_matches(".git");
+    has_slug(name).then(|| name.to_string())
+}
+
+fn derive_display_name(
+    input: &FromRepoInput,
+    manifest: Option<&CapsuleManifest>,
+    repo: &Path,
+) -> Result<(String, NameSource), OnboardError> {
+    if let Some(name) = &input.display_name {
+        return Ok((name.clone(), NameSource::Flag));
+    }
+    if let Some(name) = manifest.and_then(|manifest| manifest.display_name.clone()) {
+        return Ok((name, NameSource::Manifest));
+    }
+    if let Some(name) = origin_url(repo).as_deref().and_then(repo_name_from_remote) {
+        return Ok((name, NameSource::GitRemote));
+    }
+    repo.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .filter(|name| has_slug(name))
+        .map(|name| (name, NameSource::Directory))
+        .ok_or_else(|| OnboardError::NoDisplayName(repo.display().to_string()))
+}
+
+/// Whether `name` has any character that survives slug normalization.
+fn has_slug(name: &str) -> bool {
+    name.chars().any(|ch| ch.is_ascii_alphanumeric())
+}
+
+fn origin_url(repo: &Path) -> Option<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .arg("remote")
+        .arg("get-url")
+        .arg("origin")
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .filter(|url| !url.is_empty())
+}
+
+fn allocate_services(
+    store: &mut CapsuleStore,
+    capsule_id: &str,
+    services: &[(String, Option<u16>)],
+    pool: &str,
+) -> Result<BTreeMap<String, u16>, OnboardError> {
+    let mut ports = BTreeMap::new();
+    for (service, fixed) in services {
+        let port = match fixed {
+            Some(port) => {
+                store.record_service_port(capsule_id, service, *port)?;
+                *port
+            }
+            None => store
+                .allocate_pool_port(capsule_id, service, pool, DEFAULT_PORT_QUOTA)?
+                .ok_or_else(|| OnboardError::PortsExhausted(pool.to_string()))?,
+        };
+        ports.insert(service.clone(), port);
+    }
+    Ok(ports)
+}
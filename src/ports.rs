@@ -1,58 +1,114 @@
-use std::collections::{BTreeSet, HashMap};
-
-#[derive(Debug, Clone)]
-pub struct PortAllocator {
-    start: u16,
-    end: u16,
-    used: BTreeSet<u16>,
-    by_capsule: HashMap<String, u16>,
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::store::{CapsuleStore, PortPool, PortPoolUsage, StoreError};
+
+/// Service name used by single-port callers such as `allocate`.
+pub const DEFAULT_SERVICE: &str = "default";
+/// Pool used when callers do not name one.
+pub const DEFAULT_PORT_POOL: &str = "default";
+/// Range the default pool covers while no pool has been configured.
+pub const DEFAULT_PORT_RANGE: (u16, u16) = (4300, 4999);
+/// Maximum number of named ports a capsule may hold unless overridden.
+pub const DEFAULT_PORT_QUOTA: u32 = 8;
+
+/// A view over one persisted port pool. It holds no port state of its own:
+/// allocations, reservations and releases all go through `CapsuleStore`.
+#[derive(Debug)]
+pub struct PortAllocator<'a> {
+    store: &'a mut CapsuleStore,
+    pool: PortPool,
+    quota: u32,
 }
 
-impl PortAllocator {
-    pub fn new(start: u16, end: u16) -> Self {
-        assert!(start <= end, "port range start must be <= end");
-        Self {
-            start,
-            end,
-            used: BTreeSet::new(),
-            by_capsule: HashMap::new(),
-        }
+impl<'a> PortAllocator<'a> {
+    pub fn open(store: &'a mut CapsuleStore, pool: &str) -> Result<Self, StoreError> {
+        let pool = store
+            .port_pool(pool)?
+            .ok_or_else(|| StoreError::PortPoolNotFound(pool.to_string()))?;
+        Ok(Self {
+            store,
+            pool,
+            quota: DEFAULT_PORT_QUOTA,
+        })
     }
 
-    pub fn allocate(&mut self, capsule_id: &str) -> Option<u16> {
-        if let Some(existing) = self.by_capsule.get(capsule_id) {
-            return Some(*existing);
-        }
+    pub fn with_quota(mut self, quota: u32) -> Self {
+        self.quota = quota;
+        self
+    }
 
-        for candidate in self.start..=self.end {
-            if self.used.insert(candidate) {
-                self.by_capsule.insert(capsule_id.to_string(), candidate);
-                return Some(candidate);
-            }
-        }
+    pub fn allocate(&mut self, capsule_id: &str) -> Result<Option<u16>, StoreError> {
+        self.allocate_service(capsule_id, DEFAULT_SERVICE)
+    }
 
-        None
+    /// Returns the capsule's port for `service`, allocating one from the pool
+    /// if needed. `Ok(None)` means the pool is exhausted.
+    pub fn allocate_service(
+        &mut self,
+        capsule_id: &str,
+        service: &str,
+    ) -> Result<Option<u16>, StoreError> {
+        self.store
+            .allocate_pool_port(capsule_id, service, &self.pool.name, self.quota)
     }
 
-    pub fn reserve(&mut self, port: u16) {
-        assert!(
-            (self.start..=self.end).contains(&port),
-            "reserved port out of range"
-        );
-        self.used.insert(port);
+    /// The capsule's ports that lie inside this pool, keyed by service.
+    pub fn service_ports(&self, capsule_id: &str) -> Result<BTreeMap<String, u16>, StoreError> {
+        let mut ports = self.store.list_service_ports(capsule_id)?;
+        ports.retain(|_, port| self.contains(*port));
+        Ok(ports)
     }
 
-    pub fn release(&mut self, capsule_id: &str) {
-        if let Some(port) = self.by_capsule.remove(capsule_id) {
-            self.used.remove(&port);
+    pub fn reserve(&mut self, port: u16, reason: &str) -> Result<(), StoreError> {
+        if !self.contains(port) {
+            return Err(StoreError::PortOutsidePool {
+                port,
+                pool: self.pool.name.clone(),
+            });
         }
+        self.store.reserve_port(port, reason)
     }
 
-    pub fn used_ports(&self) -> &BTreeSet<u16> {
-        &self.used
+    /// Releases the capsule's ports in this pool and returns how many.
+    pub fn release(&mut self, capsule_id: &str) -> Result<u32, StoreError> {
+        let mut released = 0;
+        for service in self.service_ports(capsule_id)?.keys() {
+            released += self.store.release_service_port(capsule_id, service)?;
+        }
+        Ok(released)
+    }
+
+    pub fn usage(&self) -> Result<PortPoolUsage, StoreError> {
+        self.store.pool_usage(&self.pool.name)
+    }
+
+    pub fn used_ports(&self) -> Result<BTreeSet<u16>, StoreError> {
+        Ok(self.usage()?.used.into_iter().collect())
     }
 
     pub fn range(&self) -> (u16, u16) {
-        (self.start, self.end)
+        (self.pool.start, self.pool.end)
+    }
+
+    fn contains(&self, port: u16) -> bool {
+        (self.pool.start..=self.pool.end).contains(&port)
     }
 }
+
+/// Service names are lowercase ASCII letters, digits, `-` and `_` so they map
+/// onto `NEXUM_PORT_<SERVICE>`. `-` and `_` both become `_`, so the store
+/// rejects a second service of a capsule that would share a variable.
+pub fn is_valid_service_name(service: &str) -> bool {
+    !service.is_empty()
+        && service.len() <= 32
+        && service
+            .chars()
+            .all(|ch| ch.is_ascii_lowercase() || ch.is_ascii_digit() || ch == '-' || ch == '_')
+}
+
+pub fn port_env_var(service: &str) -> String {
+    format!(
+        "NEXUM_PORT_{}",
+        service.to_ascii_uppercase().replace('-', "_")
+    )
+}
@@ -10,6 +10,14 @@ pub enum SignalType {
     PassiveCompletion,
 }
 
+pub fn signal_to_str(value: SignalType) -> &'static str {
+    match value {
+        SignalType::NeedsDecision => "needs_decision",
+        SignalType::CriticalFailure => "critical_failure",
+        SignalType::PassiveCompletion => "passive_completion",
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct RestoreSurfaces {
     pub terminal_cmd: String,
@@ -2,6 +2,7 @@ use std::{
     collections::BTreeMap,
     path::{Path, PathBuf},
     sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
 };
 
 use serde::{Deserialize, Serialize};
@@ -13,14 +14,28 @@ use tokio::{
     time::{Duration, timeout},
 };
 
+use crate::{
+    crypto::{from_hex, random_bytes, to_hex},
+    tls::{verify_client_cert, verify_client_signature},
+};
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct RouteEntry {
     pub capsule_id: String,
     pub domain: String,
     pub upstream: String,
     pub tls_mode: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_callers: Vec<String>,
 }
 
+/// Header a fronting proxy sets to pass the attested caller capsule id upstream.
+pub const CAPSULE_ID_HEADER: &str = "x-nexum-capsule-id";
+/// How long a caller challenge nonce stays redeemable.
+pub const CALLER_CHALLENGE_TTL_MS: u64 = 30_000;
+/// Outstanding challenges beyond this are refused until older ones expire.
+pub const MAX_PENDING_CHALLENGES: usize = 1024;
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(tag = "cmd", rename_all = "snake_case")]
 pub enum RouteCommand {
@@ -37,25 +52,135 @@ pub enum RouteCommand {
         domain: String,
     },
     List,
+    SetCallerPolicy {
+        domain: String,
+        allowed_callers: Vec<String>,
+    },
+    CallerChallenge {
+        domain: String,
+    },
+    AuthorizeCaller {
+        domain: String,
+        #[serde(default)]
+        client_cert_pem: Option<String>,
+        /// Nonce from a prior `caller_challenge`, required with a certificate.
+        #[serde(default)]
+        nonce: Option<String>,
+        /// Hex signature over [`caller_proof_message`] made with the
+        /// certificate's private key.
+        #[serde(default)]
+        signature: Option<String>,
+    },
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(tag = "kind", rename_all = "snake_case")]
 pub enum RouteOutcome {
-    Health { status: String },
-    Registered { domain: String },
-    Resolved { route: Option<RouteEntry> },
-    Removed { removed: bool },
-    Listed { routes: Vec<RouteEntry> },
-    Error { code: String, message: String },
+    Health {
+        status: String,
+    },
+    Registered {
+        domain: String,
+    },
+    Resolved {
+        route: Option<RouteEntry>,
+    },
+    Removed {
+        removed: bool,
+    },
+    Listed {
+        routes: Vec<RouteEntry>,
+    },
+    PolicyUpdated {
+        domain: String,
+        allowed_callers: Vec<String>,
+    },
+    CallerChallenge {
+        domain: String,
+        nonce: String,
+    },
+    /// nexumd does not proxy traffic; `headers` are for the proxy in front
+    /// of `upstream` to set on the forwarded request.
+    CallerAuthorized {
+        domain: String,
+        upstream: String,
+        caller_capsule_id: Option<String>,
+        headers: BTreeMap<String, String>,
+    },
+    Error {
+        code: String,
+        message: String,
+    },
 }
 
 #[derive(Debug, Default, Clone)]
 pub struct RouterState {
     routes: BTreeMap<String, RouteEntry>,
+    client_ca_dir: Option<PathBuf>,
+    require_client_cert: bool,
+    /// Caller policies by domain, kept apart from the routes so they survive
+    /// route removal and daemon restarts.
+    caller_policies: BTreeMap<String, Vec<String>>,
+    policy_file: Option<PathBuf>,
+    /// Outstanding challenge nonces mapped to their domain and issue time.
+    challenges: BTreeMap<String, (String, u64)>,
+    /// Only socket peers with this uid may change caller policies.
+    owner_uid: Option<u32>,
 }
 
 impl RouterState {
+    /// Router that attests callers with capsule client certificates issued by
+    /// the local CA in `client_ca_dir`; with `require_client_cert` every caller
+    /// must attest. This is not mutual TLS: nexumd never sees the caller's
+    /// connection, only the certificate and a signed challenge.
+    pub fn with_caller_attestation(
+        client_ca_dir: Option<PathBuf>,
+        require_client_cert: bool,
+    ) -> Self {
+        Self {
+            client_ca_dir,
+            require_client_cert,
+            ..Self::default()
+        }
+    }
+
+    /// Persists caller policies to `path` and loads any saved there before,
+    /// so a restarted daemon keeps restricting the same routes. The parent
+    /// directory is created with mode 0700 and must not be group or world
+    /// accessible.
+    pub fn with_policy_file(mut self, path: PathBuf) -> Result<Self, RoutingError> {
+        if let Some(parent) = path.parent() {
+            ensure_private_dir(parent)?;
+        }
+        if path.exists() {
+            self.caller_policies = serde_json::from_slice(&std::fs::read(&path)?)?;
+        }
+        self.policy_file = Some(path);
+        Ok(self)
+    }
+
+    /// Restricts `set_caller_policy` to socket peers running as `uid`.
+    /// `serve_unix_socket_with_state` defaults it to the daemon's own uid.
+    pub fn with_owner_uid(mut self, uid: u32) -> Self {
+        self.owner_uid = Some(uid);
+        self
+    }
+
+    /// Handles a command received from a socket peer. Policy changes are only
+    /// accepted from the owner; everything else behaves like [`Self::handle`].
+    pub fn handle_from(&mut self, peer_uid: Option<u32>, command: RouteCommand) -> RouteOutcome {
+        if let RouteCommand::SetCallerPolicy { domain, .. } = &command
+            && let Some(owner) = self.owner_uid
+            && peer_uid != Some(owner)
+        {
+            return RouteOutcome::Error {
+                code: "policy_forbidden".to_string(),
+                message: format!("only uid {owner} may change the caller policy of '{domain}'"),
+            };
+        }
+        self.handle(command)
+    }
+
     pub fn handle(&mut self, command: RouteCommand) -> RouteOutcome {
         match command {
             RouteCommand::Health => RouteOutcome::Health {
@@ -66,18 +191,23 @@ impl RouterState {
                 domain,
                 upstream,
             } => {
-                if let Some(existing) = self.routes.get(&domain) {
-                    if existing.capsule_id != capsule_id {
-                        return RouteOutcome::Error {
-                            code: "domain_conflict".to_string(),
-                            message: format!(
-                                "domain '{}' already claimed by {}",
-                                domain, existing.capsule_id
-                            ),
-                        };
-                    }
+                if let Some(existing) = self.routes.get(&domain)
+                    && existing.capsule_id != capsule_id
+                {
+                    return RouteOutcome::Error {
+                        code: "domain_conflict".to_string(),
+                        message: format!(
+                            "domain '{}' already claimed by {}",
+                            domain, existing.capsule_id
+                        ),
+                    };
                 }
 
+                let allowed_callers = self
+                    .caller_policies
+                    .get(&domain)
+                    .cloned()
+                    .unwrap_or_default();
                 self.routes.insert(
                     domain.clone(),
                     RouteEntry {
@@ -85,6 +215,7 @@ impl RouterState {
                         domain: domain.clone(),
                         upstream,
                         tls_mode: "self_signed".to_string(),
+                        allowed_callers,
                     },
                 );
 
@@ -99,7 +230,257 @@ impl RouterState {
             RouteCommand::List => RouteOutcome::Listed {
                 routes: self.routes.values().cloned().collect(),
             },
+            RouteCommand::SetCallerPolicy {
+                domain,
+                mut allowed_callers,
+            } => {
+                if !self.routes.contains_key(&domain) {
+                    return route_not_found(&domain);
+                }
+                allowed_callers.sort();
+                allowed_callers.dedup();
+                let mut policies = self.caller_policies.clone();
+                if allowed_callers.is_empty() {
+                    policies.remove(&domain);
+                } else {
+                    policies.insert(domain.clone(), allowed_callers.clone());
+                }
+                if let Some(path) = &self.policy_file
+                    && let Err(error) = save_caller_policies(path, &policies)
+                {
+                    return RouteOutcome::Error {
+                        code: "policy_persist_failed".to_string(),
+                        message: error.to_string(),
+                    };
+                }
+                self.caller_policies = policies;
+                if let Some(route) = self.routes.get_mut(&domain) {
+                    route.allowed_callers = allowed_callers.clone();
+                }
+                RouteOutcome::PolicyUpdated {
+                    domain,
+                    allowed_callers,
+                }
+            }
+            RouteCommand::CallerChallenge { domain } => self.issue_challenge(domain),
+            RouteCommand::AuthorizeCaller {
+                domain,
+                client_cert_pem,
+                nonce,
+                signature,
+            } => self.authorize_caller(domain, client_cert_pem, nonce, signature),
+        }
+    }
+
+    fn issue_challenge(&mut self, domain: String) -> RouteOutcome {
+        if !self.routes.contains_key(&domain) {
+            return route_not_found(&domain);
+        }
+        let now = now_unix_ms();
+        self.sweep_challenges(now);
+        if self.challenges.len() >= MAX_PENDING_CHALLENGES {
+            return RouteOutcome::Error {
+                code: "challenge_limit".to_string(),
+                message: format!(
+                    "{MAX_PENDING_CHALLENGES} challenges are pending; retry once they expire"
+                ),
+            };
+        }
+        let nonce = match random_bytes::<32>() {
+            Ok(bytes) => to_hex(&bytes),
+            Err(error) => {
+                return RouteOutcome::Error {
+                    code: "challenge_unavailable".to_string(),
+                    message: error.to_string(),
+                };
+            }
+        };
+        self.challenges.insert(nonce.clone(), (domain.clone(), now));
+        RouteOutcome::CallerChallenge { domain, nonce }
+    }
+
+    fn sweep_challenges(&mut self, now: u64) {
+        self.challenges
+            .retain(|_, (_, issued)| now.saturating_sub(*issued) < CALLER_CHALLENGE_TTL_MS);
+    }
+
+    /// Admits a caller only after its certificate chains to the local CA and
+    /// it has signed a fresh challenge for this domain with the matching key.
+    fn authorize_caller(
+        &mut self,
+        domain: String,
+        client_cert_pem: Option<String>,
+        nonce: Option<String>,
+        signature: Option<String>,
+    ) -> RouteOutcome {
+        if !self.routes.contains_key(&domain) {
+            return route_not_found(&domain);
+        }
+
+        let caller_capsule_id = match (client_cert_pem, &self.client_ca_dir) {
+            (Some(pem), Some(ca_dir)) => match verify_client_cert(ca_dir, &pem) {
+                Ok(verified) => {
+                    if let Err(outcome) =
+                        self.check_proof(&domain, &pem, nonce.as_deref(), signature.as_deref())
+                    {
+                        return outcome;
+                    }
+                    Some(verified.capsule_id)
+                }
+                Err(error) => {
+                    return RouteOutcome::Error {
+                        code: "client_cert_invalid".to_string(),
+                        message: error.to_string(),
+                    };
+                }
+            },
+            (Some(_), None) => {
+                return RouteOutcome::Error {
+                    code: "client_auth_unconfigured".to_string(),
+                    message: "router has no client CA configured".to_string(),
+                };
+            }
+            (None, _) => None,
+        };
+
+        let Some(route) = self.routes.get(&domain) else {
+            return route_not_found(&domain);
+        };
+        let restricted = !route.allowed_callers.is_empty();
+        match &caller_capsule_id {
+            None if self.require_client_cert || restricted => {
+                return RouteOutcome::Error {
+                    code: "client_cert_required".to_string(),
+                    message: format!("route '{domain}' requires a capsule client certificate"),
+                };
+            }
+            Some(caller) if restricted && !route.allowed_callers.contains(caller) => {
+                return RouteOutcome::Error {
+                    code: "caller_forbidden".to_string(),
+                    message: format!("capsule '{caller}' may not call '{domain}'"),
+                };
+            }
+            _ => {}
+        }
+
+        let mut headers = BTreeMap::new();
+        if let Some(caller) = &caller_capsule_id {
+            headers.insert(CAPSULE_ID_HEADER.to_string(), caller.clone());
+        }
+
+        RouteOutcome::CallerAuthorized {
+            domain,
+            upstream: route.upstream.clone(),
+            caller_capsule_id,
+            headers,
+        }
+    }
+
+    /// Redeems `nonce` (one use, bound to `domain`) and checks `signature`
+    /// against the certificate's public key.
+    fn check_proof(
+        &mut self,
+        domain: &str,
+        cert_pem: &str,
+        nonce: Option<&str>,
+        signature: Option<&str>,
+    ) -> Result<(), RouteOutcome> {
+        let invalid = |message: String| RouteOutcome::Error {
+            code: "client_proof_invalid".to_string(),
+            message,
+        };
+        let (Some(nonce), Some(signature)) = (nonce, signature) else {
+            return Err(RouteOutcome::Error {
+                code: "client_proof_required".to_string(),
+                message: "request a caller_challenge and sign it with the client key".to_string(),
+            });
+        };
+
+        let now = now_unix_ms();
+        let fresh = match self.challenges.remove(nonce) {
+            Some((challenged, issued)) => {
+                challenged == domain && now.saturating_sub(issued) < CALLER_CHALLENGE_TTL_MS
+            }
+            None => false,
+        };
+        self.sweep_challenges(now);
+        if !fresh {
+            return Err(invalid(format!(
+                "unknown or expired challenge for '{domain}'"
+            )));
         }
+
+        let signature =
+            from_hex(signature).ok_or_else(|| invalid("signature is not hex".to_string()))?;
+        verify_client_signature(
+            cert_pem,
+            caller_proof_message(domain, nonce).as_bytes(),
+            &signature,
+        )
+        .map_err(|error| invalid(error.to_string()))
+    }
+}
+
+/// The bytes a caller signs to prove it holds the key for its certificate.
+pub fn caller_proof_message(domain: &str, nonce: &str) -> String {
+    format!("nexum-authorize-caller\n{domain}\n{nonce}")
+}
+
+fn save_caller_policies(
+    path: &Path,
+    policies: &BTreeMap<String, Vec<String>>,
+) -> Result<(), RoutingError> {
+    if let Some(parent) = path.parent() {
+        ensure_private_dir(parent)?;
+    }
+    let tmp = path.with_extension("tmp");
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(&tmp)?;
+    std::io::Write::write_all(&mut file, &serde_json::to_vec_pretty(policies)?)?;
+    file.sync_all()?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// Creates `dir` with mode 0700 if missing and refuses one that other users
+/// could write to, since they could otherwise replace the policy file.
+fn ensure_private_dir(dir: &Path) -> Result<(), RoutingError> {
+    if dir.as_os_str().is_empty() {
+        return Ok(());
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+        std::fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(dir)?;
+        if std::fs::metadata(dir)?.permissions().mode() & 0o077 != 0 {
+            return Err(RoutingError::InsecurePolicyDir(dir.to_path_buf()));
+        }
+    }
+    #[cfg(not(unix))]
+    std::fs::create_dir_all(dir)?;
+    Ok(())
+}
+
+fn now_unix_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+fn route_not_found(domain: &str) -> RouteOutcome {
+    RouteOutcome::Error {
+        code: "route_not_found".to_string(),
+        message: format!("no route registered for '{domain}'"),
     }
 }
 
@@ -111,6 +492,8 @@ pub enum RoutingError {
     Json(#[from] serde_json::Error),
     #[error("timeout waiting for route response")]
     Timeout,
+    #[error("policy directory {} must not be accessible to other users (chmod 700)", .0.display())]
+    InsecurePolicyDir(PathBuf),
 }
 
 pub async fn send_command(
@@ -135,6 +518,14 @@ pub async fn send_command(
 
 pub async fn serve_unix_socket(
     socket_path: &Path,
+    shutdown_rx: oneshot::Receiver<()>,
+) -> Result<(), RoutingError> {
+    serve_unix_socket_with_state(socket_path, RouterState::default(), shutdown_rx).await
+}
+
+pub async fn serve_unix_socket_with_state(
+    socket_path: &Path,
+    initial_state: RouterState,
     mut shutdown_rx: oneshot::Receiver<()>,
 ) -> Result<(), RoutingError> {
     if socket_path.exists() {
@@ -146,7 +537,13 @@ pub async fn serve_unix_socket(
     }
 
     let listener = UnixListener::bind(socket_path)?;
-    let state = Arc::new(Mutex::new(RouterState::default()));
+    let mut initial_state = initial_state;
+    #[cfg(unix)]
+    if initial_state.owner_uid.is_none() {
+        use std::os::unix::fs::MetadataExt;
+        initial_state.owner_uid = Some(std::fs::metadata(socket_path)?.uid());
+    }
+    let state = Arc::new(Mutex::new(initial_state));
 
     loop {
         tokio::select! {
@@ -173,6 +570,7 @@ async fn handle_connection(
     stream: UnixStream,
     state: Arc<Mutex<RouterState>>,
 ) -> Result<(), RoutingError> {
+    let peer_uid = stream.peer_cred().ok().map(|cred| cred.uid());
     let (reader, mut writer) = stream.into_split();
     let mut reader = BufReader::new(reader);
     let mut line = String::new();
@@ -185,7 +583,10 @@ async fn handle_connection(
 
         let command = serde_json::from_str::<RouteCommand>(line.trim_end());
         let outcome = match command {
-            Ok(command) => state.lock().expect("router mutex poisoned").handle(command),
+            Ok(command) => state
+                .lock()
+                .expect("router mutex poisoned")
+                .handle_from(peer_uid, command),
             Err(error) => RouteOutcome::Error {
                 code: "invalid_command".to_string(),
                 message: error.to_string(),
@@ -204,3 +605,18 @@ async fn handle_connection(
 pub fn default_socket_path() -> PathBuf {
     PathBuf::from("/tmp/nexumd.sock")
 }
+
+/// Caller policies live in the user's private state directory
+/// (`$XDG_STATE_HOME/nexum` or `~/.local/state/nexum`), never next to the
+/// socket in `/tmp`. `None` when neither variable is set.
+pub fn default_policy_path() -> Option<PathBuf> {
+    let state_home = std::env::var_os("XDG_STATE_HOME")
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME")
+                .filter(|value| !value.is_empty())
+                .map(|home| PathBuf::from(home).join(".local/state"))
+        })?;
+    Some(state_home.join("nexum").join("policies.json"))
+}
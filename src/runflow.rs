@@ -1,5 +1,6 @@
 use std::{
-    path::PathBuf,
+    collections::BTreeMap,
+    path::{Path, PathBuf},
     time::{SystemTime, UNIX_EPOCH},
 };
 
@@ -9,14 +10,18 @@ use thiserror::Error;
 use crate::{
     capsule::{Capsule, CapsuleMode, CapsuleState},
     events::{EventError, EventStore, RuntimeEvent},
-    identity::browser_launch_command,
+    identity::{browser_launch_command, profile_dir_for_capsule, provision_profile_trust},
     isolation::{IsolationInput, select_capsule_mode},
-    restore::{RestoreRequest, RestoreSurfaces, SignalType, build_restore_plan},
+    manifest::{CapsuleManifest, RestoreOverrides, resolve_restore},
+    restore::{RestoreRequest, RestoreSurfaces, SignalType, build_restore_plan, signal_to_str},
     routing::{RouteCommand, RouteOutcome, RouterState, send_command},
-    runtime_meta::{capsule_runtime_env, terminal_process_label},
+    runtime_meta::{
+        capsule_env_exports, capsule_runtime_env, secrets_permitted, service_port_env,
+        terminal_process_label,
+    },
     shell::{build_niri_shell_plan, render_shell_script},
-    store::StoreError,
-    tls::{TlsError, ensure_self_signed_cert},
+    store::{CapsuleDependency, CapsuleEnvVar, CapsuleStore, SECRET_MASK, StoreError},
+    tls::{TlsError, capsule_subject_alt_names, ensure_cert_with_sans},
 };
 
 #[derive(Debug, Clone)]
@@ -49,6 +54,23 @@ pub struct RestoreRunSummary {
     pub shell_script: String,
     pub tls_fingerprint_sha256: String,
     pub events_written: u32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dependencies: Vec<DependencyStatus>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+/// Readiness of one declared dependency, checked before the dependent
+/// restores.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DependencyStatus {
+    pub capsule_id: String,
+    pub route: Option<String>,
+    pub domain: String,
+    pub state: CapsuleState,
+    /// Whether the dependency was restored as part of this restore.
+    pub restored: bool,
+    pub degraded_reason: Option<String>,
 }
 
 #[derive(Debug, Error)]
@@ -61,15 +83,42 @@ pub enum RunFlowError {
     Store(#[from] StoreError),
     #[error("routing failed: {0}")]
     Routing(String),
+    #[error("profile trust: {0}")]
+    ProfileTrust(std::io::Error),
 }
 
+/// Restores a capsule. Declared dependencies come first: any that is not
+/// `Ready` or `Degraded` is restored, and routes of ready ones are registered
+/// with the routing daemon when missing. A degraded or unreachable dependency
+/// degrades this restore with the dependency's reason.
+///
+/// Once the capsule is `Restoring`, any failure moves it to `Degraded` with
+/// the error as reason so it never stays stuck mid-restore.
 pub fn run_restore_flow(input: RestoreRunInput) -> Result<RestoreRunSummary, RunFlowError> {
+    let dependencies = ensure_dependencies(&input)?;
     transition_capsule_state(
         input.capsule_db.as_ref(),
         &input.capsule_id,
         CapsuleState::Restoring,
+        "restore started",
     )?;
 
+    restore_capsule(&input, dependencies).inspect_err(|error| {
+        // The restore error is what the caller needs; a failing store here
+        // would only repeat it.
+        let _ = transition_capsule_state(
+            input.capsule_db.as_ref(),
+            &input.capsule_id,
+            CapsuleState::Degraded,
+            &error.to_string(),
+        );
+    })
+}
+
+fn restore_capsule(
+    input: &RestoreRunInput,
+    dependencies: Vec<DependencyStatus>,
+) -> Result<RestoreRunSummary, RunFlowError> {
     let mode = select_capsule_mode(&IsolationInput {
         identity_collision_detected: input.identity_collision,
         high_risk_secret_workflow: input.high_risk_secret_workflow,
@@ -85,7 +134,7 @@ pub fn run_restore_flow(input: RestoreRunInput) -> Result<RestoreRunSummary, Run
 
     let request = RestoreRequest {
         capsule: capsule.clone(),
-        signal: input.signal.clone(),
+        signal: input.signal,
         surfaces: RestoreSurfaces {
             terminal_cmd: input.terminal_cmd.clone(),
             editor_target: input.editor_target.clone(),
@@ -95,30 +144,51 @@ pub fn run_restore_flow(input: RestoreRunInput) -> Result<RestoreRunSummary, Run
 
     let restore = build_restore_plan(&request);
 
-    let tls = ensure_self_signed_cert(&input.tls_dir, &capsule.domain(), 30)?;
-
-    let route_status = match ensure_route(&capsule, &input) {
-        Ok(status) => status,
-        Err(error) => {
-            transition_capsule_state(
-                input.capsule_db.as_ref(),
-                &input.capsule_id,
-                CapsuleState::Degraded,
-            )?;
-            return Err(error);
-        }
-    };
+    let tls = ensure_cert_with_sans(
+        &input.tls_dir,
+        &capsule.domain(),
+        &capsule_subject_alt_names(&capsule.domain()),
+        30,
+    )?;
+
+    let route_status = ensure_route(&capsule, input)?;
+
+    let mut browser_launch = browser_launch_command(
+        &input.browser_url,
+        &input.capsule_id,
+        input.identity_collision,
+    );
+    let mut warnings = Vec::new();
+    if input.identity_collision {
+        let trust = provision_profile_trust(
+            &profile_dir_for_capsule(&input.capsule_id),
+            Path::new(&tls.cert_path),
+            &capsule.domain(),
+        )
+        .map_err(RunFlowError::ProfileTrust)?;
+        browser_launch = format!("{}\n{browser_launch}", trust.certutil_command);
+        warnings.extend(trust.warnings);
+    }
 
     let shell_script = apply_browser_launch_policy(
         render_shell_script(&build_niri_shell_plan(&restore)),
         &input.browser_url,
-        &browser_launch_command(
-            &input.browser_url,
-            &input.capsule_id,
-            input.identity_collision,
-        ),
+        &browser_launch,
     );
-    let shell_script = apply_runtime_metadata(shell_script, &capsule);
+    let (service_ports, user_env, env_message) = match &input.capsule_db {
+        Some(path) => {
+            let store = crate::store::CapsuleStore::open(path)?;
+            let vars = store.list_env(&capsule.capsule_id)?;
+            let include_secrets = secrets_permitted(capsule.mode, input.high_risk_secret_workflow);
+            (
+                store.list_service_ports(&capsule.capsule_id)?,
+                capsule_env_exports(path, &capsule.capsule_id, &vars, include_secrets),
+                (!vars.is_empty()).then(|| env_event_message(&vars, include_secrets)),
+            )
+        }
+        None => (BTreeMap::new(), BTreeMap::new(), None),
+    };
+    let shell_script = apply_runtime_metadata(shell_script, &capsule, &service_ports, &user_env);
 
     let (degraded, degraded_reason, routing_level, routing_message) = match route_status {
         RouteEnsureStatus::Ready => (
@@ -135,6 +205,15 @@ pub fn run_restore_flow(input: RestoreRunInput) -> Result<RestoreRunSummary, Run
         ),
     };
 
+    let mut degraded_reasons = degraded_reason.into_iter().collect::<Vec<_>>();
+    degraded_reasons.extend(
+        dependencies
+            .iter()
+            .filter_map(|dependency| dependency.degraded_reason.clone()),
+    );
+    let degraded = degraded || !degraded_reasons.is_empty();
+    let degraded_reason = degraded.then(|| degraded_reasons.join("; "));
+
     let mut events = EventStore::open(&input.events_db)?;
     events.append(RuntimeEvent {
         capsule_id: capsule.capsule_id.clone(),
@@ -150,6 +229,31 @@ pub fn run_restore_flow(input: RestoreRunInput) -> Result<RestoreRunSummary, Run
         message: routing_message,
         ts_unix_ms: now_unix_ms(),
     })?;
+    let mut events_written = 2;
+    if !dependencies.is_empty() {
+        events.append(dependency_event(&capsule.capsule_id, &dependencies))?;
+        events_written += 1;
+    }
+    for warning in &warnings {
+        events.append(RuntimeEvent {
+            capsule_id: capsule.capsule_id.clone(),
+            component: "identity".into(),
+            level: "warn".into(),
+            message: warning.clone(),
+            ts_unix_ms: now_unix_ms(),
+        })?;
+        events_written += 1;
+    }
+    if let Some(message) = env_message {
+        events.append(RuntimeEvent {
+            capsule_id: capsule.capsule_id.clone(),
+            component: "env".into(),
+            level: "info".into(),
+            message,
+            ts_unix_ms: now_unix_ms(),
+        })?;
+        events_written += 1;
+    }
     events.append(RuntimeEvent {
         capsule_id: capsule.capsule_id.clone(),
         component: "runflow".into(),
@@ -157,7 +261,9 @@ pub fn run_restore_flow(input: RestoreRunInput) -> Result<RestoreRunSummary, Run
         message: "restore plan ready".into(),
         ts_unix_ms: now_unix_ms(),
     })?;
+    events_written += 1;
 
+    record_restore_activity(input.capsule_db.as_ref(), &input.capsule_id, input.signal)?;
     transition_capsule_state(
         input.capsule_db.as_ref(),
         &input.capsule_id,
@@ -166,6 +272,7 @@ pub fn run_restore_flow(input: RestoreRunInput) -> Result<RestoreRunSummary, Run
         } else {
             CapsuleState::Ready
         },
+        degraded_reason.as_deref().unwrap_or("restore completed"),
     )?;
 
     Ok(RestoreRunSummary {
@@ -177,18 +284,242 @@ pub fn run_restore_flow(input: RestoreRunInput) -> Result<RestoreRunSummary, Run
         target_budget_ms: restore.target_budget_ms,
         shell_script,
         tls_fingerprint_sha256: tls.fingerprint_sha256,
-        events_written: 3,
+        events_written,
+        dependencies,
+        warnings,
     })
 }
 
+fn ensure_dependencies(input: &RestoreRunInput) -> Result<Vec<DependencyStatus>, RunFlowError> {
+    let Some(path) = &input.capsule_db else {
+        return Ok(Vec::new());
+    };
+    let dependencies = CapsuleStore::open(path)?.dependencies(&input.capsule_id)?;
+    dependencies
+        .iter()
+        .map(|dependency| ensure_dependency(input, path, dependency))
+        .collect()
+}
+
+fn ensure_dependency(
+    input: &RestoreRunInput,
+    capsule_db: &Path,
+    dependency: &CapsuleDependency,
+) -> Result<DependencyStatus, RunFlowError> {
+    let mut store = CapsuleStore::open(capsule_db)?;
+    let id = &dependency.depends_on;
+    let mut capsule = store
+        .get(id)?
+        .ok_or_else(|| StoreError::CapsuleNotFound(id.clone()))?;
+    let mut status = DependencyStatus {
+        capsule_id: id.clone(),
+        route: dependency.route.clone(),
+        domain: dependency_domain(&capsule, dependency.route.as_deref()),
+        state: capsule.state,
+        restored: false,
+        degraded_reason: None,
+    };
+
+    if capsule.state == CapsuleState::Archived {
+        status.degraded_reason = Some(format!("dependency_archived: {id}"));
+        return Ok(status);
+    }
+
+    if !matches!(capsule.state, CapsuleState::Ready | CapsuleState::Degraded) {
+        if let Err(reason) = restore_dependency(input, &mut store, &capsule) {
+            status.degraded_reason = Some(format!("dependency_failed: {id}: {reason}"));
+            return Ok(status);
+        }
+        status.restored = true;
+        capsule = store
+            .get(id)?
+            .ok_or_else(|| StoreError::CapsuleNotFound(id.clone()))?;
+        status.state = capsule.state;
+    }
+
+    if capsule.state == CapsuleState::Degraded {
+        let reason = store
+            .history(id)?
+            .into_iter()
+            .rev()
+            .find(|transition| transition.to_state == CapsuleState::Degraded)
+            .map(|transition| transition.reason)
+            .unwrap_or_else(|| "degraded".to_string());
+        status.degraded_reason = Some(format!("dependency_degraded: {id}: {reason}"));
+        return Ok(status);
+    }
+
+    if let Some(socket) = &input.routing_socket
+        && let Err(reason) = ensure_dependency_route(socket, &mut store, &capsule, &status)
+    {
+        status.degraded_reason = Some(format!(
+            "dependency_route_unavailable: {}: {reason}",
+            status.domain
+        ));
+    }
+    Ok(status)
+}
+
+/// Restores a dependency from its stored values and manifest, sharing the
+/// dependent's routing, TLS and event targets.
+fn restore_dependency(
+    input: &RestoreRunInput,
+    store: &mut CapsuleStore,
+    capsule: &Capsule,
+) -> Result<(), String> {
+    let resolved = resolve_restore(store, capsule, RestoreOverrides::default())
+        .map_err(|error| error.to_string())?;
+    run_restore_flow(RestoreRunInput {
+        capsule_id: capsule.capsule_id.clone(),
+        display_name: resolved.display_name,
+        workspace: resolved.workspace,
+        signal: SignalType::PassiveCompletion,
+        terminal_cmd: resolved.terminal_cmd,
+        editor_target: resolved.editor_target,
+        browser_url: resolved.browser_url,
+        route_upstream: resolved.route_upstream,
+        routing_socket: input.routing_socket.clone(),
+        identity_collision: false,
+        high_risk_secret_workflow: false,
+        force_isolated_mode: resolved.force_isolated_mode,
+        capsule_db: input.capsule_db.clone(),
+        tls_dir: input.tls_dir.clone(),
+        events_db: input.events_db.clone(),
+    })
+    .map(|_| ())
+    .map_err(|error| error.to_string())
+}
+
+/// Registers the dependency's route with the daemon unless it already
+/// resolves. Named routes point at the service port of the same name.
+fn ensure_dependency_route(
+    socket: &Path,
+    store: &mut CapsuleStore,
+    capsule: &Capsule,
+    status: &DependencyStatus,
+) -> Result<(), String> {
+    if let RouteOutcome::Resolved { route: Some(_) } = route_request(
+        socket,
+        RouteCommand::Resolve {
+            domain: status.domain.clone(),
+        },
+    )? {
+        return Ok(());
+    }
+
+    let upstream = match &status.route {
+        Some(route) => {
+            let port = match store
+                .list_service_ports(&capsule.capsule_id)
+                .map_err(|error| error.to_string())?
+                .get(route)
+            {
+                Some(port) => Some(*port),
+                None => (!capsule.repo_path.is_empty())
+                    .then(|| CapsuleManifest::load_from_repo(Path::new(&capsule.repo_path)))
+                    .transpose()
+                    .map_err(|error| error.to_string())?
+                    .flatten()
+                    .and_then(|manifest| {
+                        manifest
+                            .services
+                            .get(route)
+                            .and_then(|service| service.port)
+                    }),
+            };
+            port.map(|port| format!("127.0.0.1:{port}"))
+                .ok_or_else(|| format!("no port for route '{route}'"))?
+        }
+        None => {
+            resolve_restore(store, capsule, RestoreOverrides::default())
+                .map_err(|error| error.to_string())?
+                .route_upstream
+        }
+    };
+    match route_request(
+        socket,
+        RouteCommand::Register {
+            capsule_id: capsule.capsule_id.clone(),
+            domain: status.domain.clone(),
+            upstream,
+        },
+    )? {
+        RouteOutcome::Registered { .. } => Ok(()),
+        RouteOutcome::Error { code, message } => Err(format!("{code}: {message}")),
+        other => Err(format!("unexpected daemon outcome: {other:?}")),
+    }
+}
+
+fn route_request(socket: &Path, command: RouteCommand) -> Result<RouteOutcome, String> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_io()
+        .enable_time()
+        .build()
+        .map_err(|error| error.to_string())?;
+    runtime
+        .block_on(send_command(socket, command))
+        .map_err(|error| error.to_string())
+}
+
+fn dependency_domain(capsule: &Capsule, route: Option<&str>) -> String {
+    match route {
+        Some(route) => format!("{route}.{}", capsule.domain()),
+        None => capsule.domain(),
+    }
+}
+
+fn dependency_event(capsule_id: &str, dependencies: &[DependencyStatus]) -> RuntimeEvent {
+    let reasons = dependencies
+        .iter()
+        .filter_map(|dependency| dependency.degraded_reason.clone())
+        .collect::<Vec<_>>();
+    let (level, message) = if reasons.is_empty() {
+        let ready = dependencies
+            .iter()
+            .map(|dependency| dependency.domain.clone())
+            .collect::<Vec<_>>();
+        ("info", format!("dependencies ready: {}", ready.join(", ")))
+    } else {
+        (
+            "warn",
+            format!("dependencies degraded: {}", reasons.join("; ")),
+        )
+    };
+    RuntimeEvent {
+        capsule_id: capsule_id.to_string(),
+        component: "dependencies".into(),
+        level: level.into(),
+        message,
+        ts_unix_ms: now_unix_ms(),
+    }
+}
+
+fn record_restore_activity(
+    capsule_db: Option<&PathBuf>,
+    capsule_id: &str,
+    signal: SignalType,
+) -> Result<(), RunFlowError> {
+    if let Some(path) = capsule_db {
+        let mut store = crate::store::CapsuleStore::open(path)?;
+        if store.get(capsule_id)?.is_some() {
+            store.record_restore(capsule_id, signal_to_str(signal), now_unix_ms())?;
+        }
+    }
+    Ok(())
+}
+
 fn transition_capsule_state(
     capsule_db: Option<&PathBuf>,
     capsule_id: &str,
     state: CapsuleState,
+    reason: &str,
 ) -> Result<(), RunFlowError> {
     if let Some(path) = capsule_db {
         let mut store = crate::store::CapsuleStore::open(path)?;
-        store.transition_state(capsule_id, state)?;
+        if store.get(capsule_id)?.is_none() {
+            return Ok(());
+        }
+        store.transition_state(capsule_id, state, "runflow", reason)?;
     }
     Ok(())
 }
@@ -276,9 +607,41 @@ fn mode_to_str(mode: CapsuleMode) -> &'static str {
     }
 }
 
-fn apply_runtime_metadata(script: String, capsule: &Capsule) -> String {
+/// Names the injected variables; secret values never appear in events.
+fn env_event_message(vars: &[CapsuleEnvVar], include_secrets: bool) -> String {
+    let mut injected = Vec::new();
+    let mut withheld = Vec::new();
+    for var in vars {
+        match (var.secret, include_secrets) {
+            (false, _) => injected.push(var.key.clone()),
+            (true, true) => injected.push(format!("{}={SECRET_MASK}", var.key)),
+            (true, false) => withheld.push(var.key.clone()),
+        }
+    }
+    let mut message = if injected.is_empty() {
+        "env injected: none".to_string()
+    } else {
+        format!("env injected: {}", injected.join(", "))
+    };
+    if !withheld.is_empty() {
+        message.push_str(&format!(
+            "; secrets withheld outside isolated mode: {}",
+            withheld.join(", ")
+        ));
+    }
+    message
+}
+
+fn apply_runtime_metadata(
+    script: String,
+    capsule: &Capsule,
+    service_ports: &BTreeMap<String, u16>,
+    user_env: &BTreeMap<String, String>,
+) -> String {
     let mut lines = capsule_runtime_env(capsule)
         .into_iter()
+        .chain(service_port_env(service_ports))
+        .chain(user_env.clone())
         .map(|(key, value)| format!("export {key}={value}"))
         .collect::<Vec<_>>();
     lines.push(format!(
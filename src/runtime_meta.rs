@@ -1,6 +1,11 @@
-use std::collections::BTreeMap;
+use std::{collections::BTreeMap, path::Path};
 
-use crate::capsule::Capsule;
+use crate::{
+    capsule::{Capsule, CapsuleMode},
+    ports::port_env_var,
+    shell::shell_quote,
+    store::CapsuleEnvVar,
+};
 
 pub fn capsule_runtime_env(capsule: &Capsule) -> BTreeMap<String, String> {
     let mut env = BTreeMap::new();
@@ -14,6 +19,56 @@ pub fn capsule_runtime_env(capsule: &Capsule) -> BTreeMap<String, String> {
     env
 }
 
+/// `NEXUM_PORT_<SERVICE>` variables for a capsule's named ports.
+pub fn service_port_env(ports: &BTreeMap<String, u16>) -> BTreeMap<String, String> {
+    ports
+        .iter()
+        .map(|(service, port)| (port_env_var(service), port.to_string()))
+        .collect()
+}
+
+/// Shell-ready values for a capsule's stored env. Plain values are quoted
+/// inline; secrets are resolved when the script runs through
+/// `nexumctl capsule env reveal`, so rendered scripts never contain them.
+pub fn capsule_env_exports(
+    capsule_db: &Path,
+    capsule_id: &str,
+    vars: &[CapsuleEnvVar],
+    include_secrets: bool,
+) -> BTreeMap<String, String> {
+    vars.iter()
+        .filter(|var| include_secrets || !var.secret)
+        .map(|var| {
+            let value = if var.secret {
+                format!(
+                    "\"$(nexumctl capsule env reveal --db {} --id {} --key {})\"",
+                    shell_quote(&capsule_db.display().to_string()),
+                    shell_quote(capsule_id),
+                    var.key
+                )
+            } else {
+                shell_quote(&var.value)
+            };
+            (var.key.clone(), value)
+        })
+        .collect()
+}
+
+/// High-risk secret workflows only receive secrets inside an isolated shell.
+pub fn secrets_permitted(mode: CapsuleMode, high_risk_secret_workflow: bool) -> bool {
+    !high_risk_secret_workflow || mode == CapsuleMode::IsolatedNixShell
+}
+
+/// Env keys are shell-safe and cannot shadow the `NEXUM_*` runtime variables.
+pub fn is_valid_env_key(key: &str) -> bool {
+    !key.is_empty()
+        && !key.starts_with("NEXUM_")
+        && !key.starts_with(|ch: char| ch.is_ascii_digit())
+        && key
+            .chars()
+            .all(|ch| ch.is_ascii_uppercase() || ch.is_ascii_digit() || ch == '_')
+}
+
 pub fn terminal_process_label(capsule_id: &str) -> String {
     format!("nexum-terminal-{capsule_id}")
 }
@@ -0,0 +1,345 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    panic::{AssertUnwindSafe, catch_unwind},
+    path::PathBuf,
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    attention::{AttentionEvent, AttentionPolicy, RoutedAttention},
+    capsule::CapsuleState,
+    manifest::{RestoreOverrides, resolve_restore},
+    restore::SignalType,
+    runflow::{RestoreRunInput, RestoreRunSummary, run_restore_flow},
+    store::{CapsuleStore, SceneMember, StoreError},
+};
+
+#[derive(Debug, Clone)]
+pub struct SceneRestoreInput {
+    pub capsule_db: PathBuf,
+    pub scene: String,
+    pub signal: SignalType,
+    pub routing_socket: Option<PathBuf>,
+    pub identity_collision: bool,
+    pub high_risk_secret_workflow: bool,
+    pub tls_dir: PathBuf,
+    pub events_db: PathBuf,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SceneMemberOutcome {
+    pub capsule_id: String,
+    /// Workspace the member was restored onto; `None` when it could not be
+    /// resolved.
+    pub workspace: Option<u16>,
+    pub ok: bool,
+    pub degraded: bool,
+    pub degraded_reason: Option<String>,
+    pub error: Option<String>,
+    pub summary: Option<RestoreRunSummary>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SceneRestoreSummary {
+    pub scene: String,
+    pub restored: u32,
+    pub degraded: u32,
+    pub failed: u32,
+    /// Members grouped by workspace; groups restore in parallel.
+    pub parallel_groups: u32,
+    pub members: Vec<SceneMemberOutcome>,
+    pub attention: RoutedAttention,
+}
+
+#[derive(Debug, Error)]
+pub enum SceneError {
+    #[error("store: {0}")]
+    Store(#[from] StoreError),
+    #[error("scene not found: {0}")]
+    SceneNotFound(String),
+}
+
+/// Restores every member of a scene and folds the results into one summary
+/// with a single attention entry.
+///
+/// Capsules that members depend on, directly or transitively, restore first
+/// and one at a time, in dependency order, so parallel groups never restore a
+/// shared dependency at the same time; a member that others depend on is
+/// restored in that step. The remaining members on the same workspace restore
+/// one after another so their windows land in order; different workspaces
+/// restore in parallel. A member that fails, or whose restore panics, does not
+/// stop the others.
+pub fn restore_scene(input: &SceneRestoreInput) -> Result<SceneRestoreSummary, SceneError> {
+    let mut store = CapsuleStore::open(&input.capsule_db)?;
+    let scene = store
+        .scene(&input.scene)?
+        .ok_or_else(|| SceneError::SceneNotFound(input.scene.clone()))?;
+
+    let mut outcomes = vec![None; scene.members.len()];
+    for capsule_id in dependency_order(&store, &scene.members)? {
+        match scene
+            .members
+            .iter()
+            .position(|member| member.capsule_id == capsule_id)
+        {
+            Some(index) => {
+                let workspace = scene.members[index].workspace;
+                outcomes[index] = Some(
+                    match member_restore_input(&mut store, input, &capsule_id, workspace) {
+                        Ok(run) => restore_member(run),
+                        Err(error) => failed(&capsule_id, None, error),
+                    },
+                );
+            }
+            None => restore_shared_dependency(&mut store, input, &capsule_id),
+        }
+    }
+
+    let mut groups: BTreeMap<u16, Vec<(usize, RestoreRunInput)>> = BTreeMap::new();
+    for (index, member) in scene.members.iter().enumerate() {
+        if outcomes[index].is_some() {
+            continue;
+        }
+        match member_restore_input(&mut store, input, &member.capsule_id, member.workspace) {
+            Ok(run) => groups.entry(run.workspace).or_default().push((index, run)),
+            Err(error) => outcomes[index] = Some(failed(&member.capsule_id, None, error)),
+        }
+    }
+    drop(store);
+    let parallel_groups = groups.len() as u32;
+
+    let restored = std::thread::scope(|scope| {
+        let handles = groups
+            .into_values()
+            .map(|group| {
+                scope.spawn(move || {
+                    group
+                        .into_iter()
+                        .map(|(index, run)| (index, restore_member(run)))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .filter_map(|handle| handle.join().ok())
+            .flatten()
+            .collect::<Vec<_>>()
+    });
+    for (index, outcome) in restored {
+        outcomes[index] = Some(outcome);
+    }
+    let members = outcomes
+        .into_iter()
+        .zip(&scene.members)
+        .map(|(outcome, member)| {
+            outcome.unwrap_or_else(|| {
+                failed(
+                    &member.capsule_id,
+                    member.workspace,
+                    "scene restore thread panicked".to_string(),
+                )
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let restored = members.iter().filter(|member| member.ok).count() as u32;
+    let degraded = members.iter().filter(|member| member.degraded).count() as u32;
+    let failed = members.len() as u32 - restored;
+    let attention = AttentionPolicy.route(&scene_attention(
+        &scene.name,
+        input.signal,
+        &members,
+        restored,
+        degraded,
+        failed,
+    ));
+
+    Ok(SceneRestoreSummary {
+        scene: scene.name,
+        restored,
+        degraded,
+        failed,
+        parallel_groups,
+        members,
+        attention,
+    })
+}
+
+fn member_restore_input(
+    store: &mut CapsuleStore,
+    input: &SceneRestoreInput,
+    capsule_id: &str,
+    workspace: Option<u16>,
+) -> Result<RestoreRunInput, String> {
+    let capsule = store
+        .get(capsule_id)
+        .map_err(|error| error.to_string())?
+        .ok_or_else(|| format!("unknown capsule: {capsule_id}"))?;
+    let resolved = resolve_restore(store, &capsule, RestoreOverrides::default())
+        .map_err(|error| error.to_string())?;
+    Ok(RestoreRunInput {
+        capsule_id: capsule.capsule_id,
+        display_name: resolved.display_name,
+        workspace: workspace.unwrap_or(resolved.workspace),
+        signal: input.signal,
+        terminal_cmd: resolved.terminal_cmd,
+        editor_target: resolved.editor_target,
+        browser_url: resolved.browser_url,
+        route_upstream: resolved.route_upstream,
+        routing_socket: input.routing_socket.clone(),
+        identity_collision: input.identity_collision,
+        high_risk_secret_workflow: input.high_risk_secret_workflow,
+        force_isolated_mode: resolved.force_isolated_mode,
+        capsule_db: Some(input.capsule_db.clone()),
+        tls_dir: input.tls_dir.clone(),
+        events_db: input.events_db.clone(),
+    })
+}
+
+/// Capsules the members depend on, each listed after its own dependencies.
+/// Members only appear when another member depends on them.
+fn dependency_order(
+    store: &CapsuleStore,
+    members: &[SceneMember],
+) -> Result<Vec<String>, StoreError> {
+    fn visit(
+        store: &CapsuleStore,
+        capsule_id: &str,
+        seen: &mut BTreeSet<String>,
+        order: &mut Vec<String>,
+    ) -> Result<(), StoreError> {
+        for dependency in store.dependencies(capsule_id)? {
+            if seen.insert(dependency.depends_on.clone()) {
+                visit(store, &dependency.depends_on, seen, order)?;
+                order.push(dependency.depends_on);
+            }
+        }
+        Ok(())
+    }
+
+    let mut seen = BTreeSet::new();
+    let mut order = Vec::new();
+    for member in members {
+        visit(store, &member.capsule_id, &mut seen, &mut order)?;
+    }
+    Ok(order)
+}
+
+/// Restores a dependency that is not a member when it is not up yet. A
+/// failure is left to each dependent's own dependency check to report.
+fn restore_shared_dependency(
+    store: &mut CapsuleStore,
+    input: &SceneRestoreInput,
+    capsule_id: &str,
+) {
+    let pending = store.get(capsule_id).ok().flatten().is_some_and(|capsule| {
+        !matches!(
+            capsule.state,
+            CapsuleState::Ready | CapsuleState::Degraded | CapsuleState::Archived
+        )
+    });
+    if !pending {
+        return;
+    }
+    if let Ok(run) = member_restore_input(store, input, capsule_id, None) {
+        let _ = run_restore_flow(RestoreRunInput {
+            signal: SignalType::PassiveCompletion,
+            ..run
+        });
+    }
+}
+
+fn restore_member(run: RestoreRunInput) -> SceneMemberOutcome {
+    let capsule_id = run.capsule_id.clone();
+    let workspace = run.workspace;
+    let result = match catch_unwind(AssertUnwindSafe(|| run_restore_flow(run))) {
+        Ok(result) => result,
+        Err(panic) => {
+            let message = panic
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            return failed(
+                &capsule_id,
+                Some(workspace),
+                format!("restore panicked: {message}"),
+            );
+        }
+    };
+    match result {
+        Ok(summary) => SceneMemberOutcome {
+            capsule_id,
+            workspace: Some(workspace),
+            ok: true,
+            degraded: summary.degraded,
+            degraded_reason: summary.degraded_reason.clone(),
+            error: None,
+            summary: Some(summary),
+        },
+        Err(error) => failed(&capsule_id, Some(workspace), error.to_string()),
+    }
+}
+
+fn failed(capsule_id: &str, workspace: Option<u16>, error: String) -> SceneMemberOutcome {
+    SceneMemberOutcome {
+        capsule_id: capsule_id.to_string(),
+        workspace,
+        ok: false,
+        degraded: false,
+        degraded_reason: None,
+        error: Some(error),
+        summary: None,
+    }
+}
+
+/// Failures escalate to a critical failure and degraded members to at least
+/// a decision; the entry points at the first member that needs a look.
+fn scene_attention(
+    scene: &str,
+    signal: SignalType,
+    members: &[SceneMemberOutcome],
+    restored: u32,
+    degraded: u32,
+    failed: u32,
+) -> AttentionEvent {
+    let signal = if failed > 0 {
+        SignalType::CriticalFailure
+    } else if degraded > 0 && signal == SignalType::PassiveCompletion {
+        SignalType::NeedsDecision
+    } else {
+        signal
+    };
+    let focus = members
+        .iter()
+        .find(|member| !member.ok)
+        .or_else(|| members.iter().find(|member| member.degraded))
+        .or(members.first())
+        .map(|member| member.capsule_id.clone())
+        .unwrap_or_default();
+
+    let mut summary =
+        format!("scene {scene}: {restored} restored, {degraded} degraded, {failed} failed");
+    let problems = members
+        .iter()
+        .filter_map(|member| {
+            member
+                .error
+                .as_ref()
+                .or(member.degraded_reason.as_ref())
+                .map(|reason| format!("{}: {reason}", member.capsule_id))
+        })
+        .collect::<Vec<_>>();
+    if !problems.is_empty() {
+        summary.push_str(&format!(" ({})", problems.join("; ")));
+    }
+
+    AttentionEvent {
+        capsule_id: focus,
+        signal,
+        summary,
+    }
+}
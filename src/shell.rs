@@ -115,6 +115,11 @@ pub fn render_shell_script(plan: &NiriShellPlan) -> String {
     lines.join("\n")
 }
 
+/// Wraps `input` in single quotes for POSIX shells.
+pub fn shell_quote(input: &str) -> String {
+    format!("'{}'", escape_single_quotes(input))
+}
+
 fn escape_single_quotes(input: &str) -> String {
     input.replace('\'', "'\"'\"'")
 }
@@ -1,9 +1,225 @@
-use std::path::Path;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
 
-use rusqlite::{Connection, OptionalExtension, params, types::Type};
+use rusqlite::{Connection, OptionalExtension, Transaction, params, types::Type};
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-use crate::capsule::{Capsule, CapsuleMode, CapsuleState, mode_to_str, parse_state, state_to_str};
+use crate::{
+    capsule::{
+        Capsule, CapsuleMode, CapsuleState, InvalidCapsuleId, TransitionError, mode_to_str,
+        parse_state, state_to_str, validate_capsule_id,
+    },
+    crypto::{CryptoError, seal, unseal},
+    db,
+    host_ports::HostPortProbe,
+    labels::{LabelError, Labels, validate_label_key, validate_label_value},
+    migrate::{self, Migration, MigrationError, MigrationPlan, add_column_if_missing},
+    ports::{
+        DEFAULT_PORT_POOL, DEFAULT_PORT_RANGE, DEFAULT_SERVICE, is_valid_service_name, port_env_var,
+    },
+    runtime_meta::is_valid_env_key,
+};
+
+/// Ordered schema history for the capsule database.
+pub const CAPSULE_MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "create capsules and capsule_ports",
+        apply: create_capsule_tables,
+    },
+    Migration {
+        version: 2,
+        description: "add capsules.state",
+        apply: add_state_column,
+    },
+    Migration {
+        version: 3,
+        description: "add capsules.repo_path",
+        apply: add_repo_path_column,
+    },
+    Migration {
+        version: 4,
+        description: "create capsule_transitions",
+        apply: create_transitions_table,
+    },
+    Migration {
+        version: 5,
+        description: "add capsule_ports.service",
+        apply: add_port_service_column,
+    },
+    Migration {
+        version: 6,
+        description: "create port_pools and port_reservations",
+        apply: create_port_pool_tables,
+    },
+    Migration {
+        version: 7,
+        description: "create shared_workspaces",
+        apply: create_shared_workspaces_table,
+    },
+    Migration {
+        version: 8,
+        description: "create capsule_forks",
+        apply: create_forks_table,
+    },
+    Migration {
+        version: 9,
+        description: "create capsule_env",
+        apply: create_env_table,
+    },
+    Migration {
+        version: 10,
+        description: "create capsule_activity",
+        apply: create_activity_table,
+    },
+    Migration {
+        version: 11,
+        description: "create capsule_labels",
+        apply: create_labels_table,
+    },
+    Migration {
+        version: 12,
+        description: "create scenes and scene_members",
+        apply: create_scene_tables,
+    },
+    Migration {
+        version: 13,
+        description: "create capsule_dependencies",
+        apply: create_dependencies_table,
+    },
+];
+
+/// Passphrase used to seal and unseal capsule secrets.
+pub const SECRETS_PASSPHRASE_ENV: &str = "NEXUM_SECRETS_PASSPHRASE";
+/// Shown in place of a secret value in listings, summaries and events.
+pub const SECRET_MASK: &str = "********";
+
+/// One recorded lifecycle state change.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CapsuleTransition {
+    pub capsule_id: String,
+    pub from_state: CapsuleState,
+    pub to_state: CapsuleState,
+    pub component: String,
+    pub reason: String,
+    pub ts_unix_ms: u64,
+}
+
+/// Outcome of comparing an import file with the store, by capsule id.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportPlan {
+    pub creates: Vec<String>,
+    pub updates: Vec<String>,
+    pub unchanged: Vec<String>,
+    pub conflicts: Vec<ImportConflict>,
+}
+
+/// One recorded `capsule_ports` row.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CapsulePort {
+    pub capsule_id: String,
+    pub service: String,
+    pub port: u16,
+}
+
+/// A named port range that allocations are drawn from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PortPool {
+    pub name: String,
+    pub start: u16,
+    pub end: u16,
+}
+
+/// Ports of a pool that are held by capsules or reserved, read from the store.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PortPoolUsage {
+    pub pool: PortPool,
+    pub used: Vec<u16>,
+    pub available: u32,
+}
+
+/// A host port excluded from every pool, e.g. one owned by a system service.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PortReservation {
+    pub port: u16,
+    pub reason: String,
+}
+
+/// A per-capsule environment variable. Secret values are always masked.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CapsuleEnvVar {
+    pub key: String,
+    pub value: String,
+    pub secret: bool,
+}
+
+/// Restore activity recorded by `runflow::run_restore_flow`. Capsules that
+/// were never restored have no timestamp and a zero count.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CapsuleActivity {
+    pub last_restored_unix_ms: Option<u64>,
+    pub restore_count: u32,
+    pub last_signal: Option<String>,
+}
+
+/// Lineage of a capsule created by `fork::fork_capsule`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CapsuleFork {
+    pub capsule_id: String,
+    pub source_id: String,
+    pub branch: String,
+    /// Repository the worktree was added to; used to remove it again.
+    pub source_repo_path: String,
+}
+
+/// A capsule that must be up before `capsule_id` restores. With a `route`, the
+/// dependency is on that named route of `depends_on`, served at
+/// `<route>.<domain>`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CapsuleDependency {
+    pub capsule_id: String,
+    pub depends_on: String,
+    pub route: Option<String>,
+}
+
+/// A named group of capsules restored together by `scene::restore_scene`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Scene {
+    pub name: String,
+    pub members: Vec<SceneMember>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SceneMember {
+    pub capsule_id: String,
+    /// Replaces the capsule's own workspace when restored as part of the scene.
+    pub workspace: Option<u16>,
+}
+
+/// Active capsules placed on the same workspace without it being shared.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorkspaceCollision {
+    pub workspace: u16,
+    pub capsule_ids: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportConflict {
+    pub capsule_id: String,
+    pub code: String,
+    pub message: String,
+}
+
+enum ImportChange {
+    Create,
+    Update(Capsule),
+    Unchanged,
+    Conflict(StoreError),
+}
 
 #[derive(Debug)]
 pub struct CapsuleStore {
@@ -18,6 +234,76 @@ pub enum StoreError {
     Io(#[from] std::io::Error),
     #[error("yaml: {0}")]
     Yaml(#[from] serde_yaml::Error),
+    #[error("migration: {0}")]
+    Migration(#[from] MigrationError),
+    #[error("capsule not found: {0}")]
+    CapsuleNotFound(String),
+    #[error("capsule '{0}' appears more than once in import")]
+    DuplicateImport(String),
+    #[error("{0}")]
+    InvalidCapsuleId(#[from] InvalidCapsuleId),
+    #[error("invalid service name '{0}': use lowercase letters, digits, '-' or '_'")]
+    InvalidServiceName(String),
+    #[error(
+        "service '{service}' of capsule '{capsule_id}' collides with '{existing}': both export {env_var}"
+    )]
+    ServiceEnvCollision {
+        capsule_id: String,
+        service: String,
+        existing: String,
+        env_var: String,
+    },
+    #[error("capsule '{capsule_id}' reached its port quota of {quota}")]
+    PortQuotaExceeded { capsule_id: String, quota: u32 },
+    #[error("invalid port range {start}-{end}")]
+    InvalidPortRange { start: u16, end: u16 },
+    #[error("port pool not found: {0}")]
+    PortPoolNotFound(String),
+    #[error("port {port} is outside port pool '{pool}'")]
+    PortOutsidePool { port: u16, pool: String },
+    #[error("port pool '{name}' overlaps pool '{other}'")]
+    PortPoolOverlap { name: String, other: String },
+    #[error("port range {start}-{end} is outside every configured port pool")]
+    RangeOutsidePool { start: u16, end: u16 },
+    #[error("port {port} is allocated to capsule '{capsule_id}'")]
+    PortAllocated { port: u16, capsule_id: String },
+    #[error("port {port} is reserved: {reason}")]
+    PortReserved { port: u16, reason: String },
+    #[error("workspace {workspace} is already used by {}", capsule_ids.join(", "))]
+    WorkspaceCollision {
+        workspace: u16,
+        capsule_ids: Vec<String>,
+    },
+    #[error("no free workspace left")]
+    NoFreeWorkspace,
+    #[error("invalid env key '{0}': use A-Z, 0-9 and '_', not starting with a digit or NEXUM_")]
+    InvalidEnvKey(String),
+    #[error("{0}")]
+    Label(#[from] LabelError),
+    #[error("invalid scene name '{0}': use lowercase letters, digits, '-' or '_'")]
+    InvalidSceneName(String),
+    #[error("scene '{0}' has no members")]
+    EmptyScene(String),
+    #[error("capsule '{capsule_id}' appears more than once in scene '{scene}'")]
+    DuplicateSceneMember { scene: String, capsule_id: String },
+    #[error("invalid route name '{0}': use lowercase letters, digits, '-' or '_'")]
+    InvalidRouteName(String),
+    #[error("dependency cycle: {}", path.join(" -> "))]
+    DependencyCycle { path: Vec<String> },
+    #[error("capsule '{capsule_id}' is a dependency of {}; remove those dependencies first", dependents.join(", "))]
+    HasDependents {
+        capsule_id: String,
+        dependents: Vec<String>,
+    },
+    #[error("secrets are locked: set {SECRETS_PASSPHRASE_ENV}")]
+    SecretsLocked,
+    #[error("crypto: {0}")]
+    Crypto(#[from] CryptoError),
+    #[error("capsule '{capsule_id}': {source}")]
+    IllegalTransition {
+        capsule_id: String,
+        source: TransitionError,
+    },
     #[error(
         "slug is immutable for capsule '{capsule_id}': existing='{existing_slug}' attempted='{attempted_slug}'"
     )]
@@ -34,74 +320,78 @@ impl CapsuleStore {
             std::fs::create_dir_all(parent)?;
         }
 
-        let conn = Connection::open(path)?;
-        conn.execute_batch(
-            "
-            CREATE TABLE IF NOT EXISTS capsules (
-                capsule_id TEXT PRIMARY KEY,
-                slug TEXT NOT NULL,
-                display_name TEXT NOT NULL,
-                repo_path TEXT NOT NULL DEFAULT '',
-                mode TEXT NOT NULL,
-                state TEXT NOT NULL DEFAULT 'ready',
-                workspace INTEGER NOT NULL
-            );
-            CREATE TABLE IF NOT EXISTS capsule_ports (
-                capsule_id TEXT NOT NULL,
-                port INTEGER NOT NULL PRIMARY KEY
-            );
-            ",
-        )?;
-        add_state_column_if_missing(&conn)?;
-        add_repo_path_column_if_missing(&conn)?;
+        let mut conn = db::open(path)?;
+        migrate::apply(&mut conn, CAPSULE_MIGRATIONS)?;
 
         Ok(Self { conn })
     }
 
-    pub fn upsert(&mut self, capsule: Capsule) -> Result<(), StoreError> {
-        if let Some(existing) = self.get(&capsule.capsule_id)? {
-            if existing.slug != capsule.slug {
-                return Err(StoreError::ImmutableSlug {
-                    capsule_id: capsule.capsule_id.clone(),
-                    existing_slug: existing.slug,
-                    attempted_slug: capsule.slug.clone(),
-                });
-            }
-        }
+    /// Reports pending capsule schema migrations without touching the file.
+    pub fn migration_plan(path: &Path) -> Result<MigrationPlan, StoreError> {
+        Ok(migrate::plan_path(path, CAPSULE_MIGRATIONS)?)
+    }
 
-        self.conn.execute(
-            "
-            INSERT INTO capsules (capsule_id, slug, display_name, repo_path, mode, state, workspace)
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
-            ON CONFLICT(capsule_id) DO UPDATE SET
-                display_name = excluded.display_name,
-                repo_path = excluded.repo_path,
-                mode = excluded.mode,
-                state = excluded.state,
-                workspace = excluded.workspace
-            ",
-            params![
-                capsule.capsule_id,
-                capsule.slug,
-                capsule.display_name,
-                capsule.repo_path,
-                mode_to_str(capsule.mode),
-                state_to_str(capsule.state),
-                capsule.workspace
-            ],
-        )?;
-        Ok(())
+    pub fn schema_version(&self) -> Result<u32, StoreError> {
+        Ok(migrate::schema_version(&self.conn)?)
+    }
+
+    /// Inserts the capsule or updates its stored fields. An update keeps the
+    /// stored lifecycle state; state changes go through `transition_state`.
+    pub fn upsert(&mut self, mut capsule: Capsule) -> Result<(), StoreError> {
+        db::write(&mut self.conn, |tx| {
+            match get_capsule(tx, &capsule.capsule_id)? {
+                Some(existing) => {
+                    check_slug(&existing, &capsule)?;
+                    capsule.state = existing.state;
+                }
+                None => validate_capsule_id(&capsule.capsule_id)?,
+            }
+            write_capsule(tx, &capsule)
+        })
     }
 
     pub fn get(&self, capsule_id: &str) -> Result<Option<Capsule>, StoreError> {
-        self.conn
-            .query_row(
-                "SELECT capsule_id, slug, display_name, repo_path, mode, state, workspace FROM capsules WHERE capsule_id = ?1",
-                params![capsule_id],
-                row_to_capsule,
-            )
-            .optional()
-            .map_err(StoreError::from)
+        get_capsule(&self.conn, capsule_id)
+    }
+
+    /// Classifies each capsule in `capsules` against the store without
+    /// writing anything.
+    pub fn plan_import(&self, capsules: &[Capsule]) -> Result<ImportPlan, StoreError> {
+        let changes = classify_import(&self.conn, capsules)?;
+        Ok(import_plan(capsules, &changes))
+    }
+
+    /// Applies an import in a single transaction. Any conflict aborts the
+    /// whole import and is returned as the corresponding `StoreError`.
+    pub fn import(&mut self, capsules: &[Capsule]) -> Result<ImportPlan, StoreError> {
+        db::write(&mut self.conn, |tx| {
+            let changes = classify_import(tx, capsules)?;
+            let plan = import_plan(capsules, &changes);
+            for (capsule, change) in capsules.iter().zip(changes) {
+                match change {
+                    ImportChange::Conflict(error) => return Err(error),
+                    ImportChange::Unchanged => {}
+                    ImportChange::Create => write_capsule(tx, capsule)?,
+                    ImportChange::Update(existing) => {
+                        write_capsule(tx, capsule)?;
+                        if existing.state != capsule.state {
+                            insert_transition(
+                                tx,
+                                &CapsuleTransition {
+                                    capsule_id: capsule.capsule_id.clone(),
+                                    from_state: existing.state,
+                                    to_state: capsule.state,
+                                    component: "import".to_string(),
+                                    reason: "declarative import".to_string(),
+                                    ts_unix_ms: now_unix_ms(),
+                                },
+                            )?;
+                        }
+                    }
+                }
+            }
+            Ok(plan)
+        })
     }
 
     pub fn list(&self) -> Result<Vec<Capsule>, StoreError> {
@@ -116,40 +406,520 @@ impl CapsuleStore {
         Ok(rows)
     }
 
+    /// Capsules ordered most recently restored first; never-restored capsules
+    /// follow in `capsule_id` order.
+    pub fn list_recent(&self) -> Result<Vec<Capsule>, StoreError> {
+        let mut stmt = self.conn.prepare(
+            "
+            SELECT c.capsule_id, c.slug, c.display_name, c.repo_path, c.mode, c.state, c.workspace
+            FROM capsules c
+            LEFT JOIN capsule_activity a ON a.capsule_id = c.capsule_id
+            ORDER BY a.last_restored_unix_ms IS NULL, a.last_restored_unix_ms DESC, c.capsule_id ASC
+            ",
+        )?;
+        let capsules = stmt
+            .query_map([], row_to_capsule)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(capsules)
+    }
+
+    /// Returns the capsule whose `repo_path` is the longest whole-component
+    /// prefix of `path`. Both sides are canonicalized when they exist, so
+    /// symlinked checkouts resolve to the same capsule.
+    pub fn find_by_path(&self, path: &Path) -> Result<Option<Capsule>, StoreError> {
+        let path = canonical_or_raw(path);
+        let mut best: Option<(usize, Capsule)> = None;
+        for capsule in self.list()? {
+            if capsule.repo_path.is_empty() {
+                continue;
+            }
+            let repo = canonical_or_raw(Path::new(&capsule.repo_path));
+            if !path.starts_with(&repo) {
+                continue;
+            }
+            let depth = repo.components().count();
+            if best
+                .as_ref()
+                .is_none_or(|(best_depth, _)| depth > *best_depth)
+            {
+                best = Some((depth, capsule));
+            }
+        }
+        Ok(best.map(|(_, capsule)| capsule))
+    }
+
     pub fn export_yaml(&self) -> Result<String, StoreError> {
         let capsules = self.list()?;
         Ok(serde_yaml::to_string(&capsules)?)
     }
 
+    /// Moves a capsule to `state` if the lifecycle allows it and records the
+    /// change. Returns `None` when the capsule is already in `state`.
     pub fn transition_state(
         &mut self,
         capsule_id: &str,
         state: CapsuleState,
+        component: &str,
+        reason: &str,
+    ) -> Result<Option<CapsuleTransition>, StoreError> {
+        db::write(&mut self.conn, |tx| {
+            let current = tx
+                .query_row(
+                    "SELECT state FROM capsules WHERE capsule_id = ?1",
+                    params![capsule_id],
+                    |row| row.get::<_, String>(0),
+                )
+                .optional()?
+                .ok_or_else(|| StoreError::CapsuleNotFound(capsule_id.to_string()))?;
+            let from_state = parse_state(&current).unwrap_or(CapsuleState::Ready);
+
+            if !from_state.can_transition_to(state) {
+                return Err(StoreError::IllegalTransition {
+                    capsule_id: capsule_id.to_string(),
+                    source: TransitionError {
+                        from: from_state,
+                        to: state,
+                    },
+                });
+            }
+            if from_state == state {
+                return Ok(None);
+            }
+
+            let transition = CapsuleTransition {
+                capsule_id: capsule_id.to_string(),
+                from_state,
+                to_state: state,
+                component: component.to_string(),
+                reason: reason.to_string(),
+                ts_unix_ms: now_unix_ms(),
+            };
+            tx.execute(
+                "UPDATE capsules SET state = ?1 WHERE capsule_id = ?2",
+                params![state_to_str(state), capsule_id],
+            )?;
+            insert_transition(tx, &transition)?;
+
+            Ok(Some(transition))
+        })
+    }
+
+    /// Removes the capsule together with its ports, transition history and
+    /// scene memberships; scenes left without members are removed too. Fails
+    /// while another capsule depends on it.
+    pub fn delete(&mut self, capsule_id: &str) -> Result<bool, StoreError> {
+        db::write(&mut self.conn, |tx| {
+            let dependents = query_dependents(tx, capsule_id)?;
+            if !dependents.is_empty() {
+                return Err(StoreError::HasDependents {
+                    capsule_id: capsule_id.to_string(),
+                    dependents,
+                });
+            }
+            tx.execute(
+                "DELETE FROM capsule_ports WHERE capsule_id = ?1",
+                params![capsule_id],
+            )?;
+            tx.execute(
+                "DELETE FROM capsule_transitions WHERE capsule_id = ?1",
+                params![capsule_id],
+            )?;
+            tx.execute(
+                "DELETE FROM capsule_forks WHERE capsule_id = ?1",
+                params![capsule_id],
+            )?;
+            tx.execute(
+                "DELETE FROM capsule_env WHERE capsule_id = ?1",
+                params![capsule_id],
+            )?;
+            tx.execute(
+                "DELETE FROM capsule_activity WHERE capsule_id = ?1",
+                params![capsule_id],
+            )?;
+            tx.execute(
+                "DELETE FROM capsule_labels WHERE capsule_id = ?1",
+                params![capsule_id],
+            )?;
+            let scenes = {
+                let mut stmt =
+                    tx.prepare("SELECT DISTINCT scene FROM scene_members WHERE capsule_id = ?1")?;
+                stmt.query_map(params![capsule_id], |row| row.get::<_, String>(0))?
+                    .collect::<Result<Vec<_>, _>>()?
+            };
+            tx.execute(
+                "DELETE FROM scene_members WHERE capsule_id = ?1",
+                params![capsule_id],
+            )?;
+            for scene in scenes {
+                tx.execute(
+                    "DELETE FROM scenes WHERE name = ?1
+                     AND NOT EXISTS (SELECT 1 FROM scene_members WHERE scene = ?1)",
+                    params![scene],
+                )?;
+            }
+            tx.execute(
+                "DELETE FROM capsule_dependencies WHERE capsule_id = ?1",
+                params![capsule_id],
+            )?;
+            let deleted = tx.execute(
+                "DELETE FROM capsules WHERE capsule_id = ?1",
+                params![capsule_id],
+            )?;
+            Ok(deleted == 1)
+        })
+    }
+
+    pub fn set_env(&mut self, capsule_id: &str, key: &str, value: &str) -> Result<(), StoreError> {
+        db::write(&mut self.conn, |tx| {
+            write_env(tx, capsule_id, key, value.as_bytes(), false)
+        })
+    }
+
+    /// Stores `value` sealed with `passphrase`; it is only readable through
+    /// [`CapsuleStore::reveal_secret`].
+    pub fn set_secret(
+        &mut self,
+        capsule_id: &str,
+        key: &str,
+        value: &str,
+        passphrase: &str,
+    ) -> Result<(), StoreError> {
+        let sealed = seal(passphrase, value.as_bytes())?;
+        db::write(&mut self.conn, |tx| {
+            write_env(tx, capsule_id, key, &sealed, true)
+        })
+    }
+
+    pub fn unset_env(&mut self, capsule_id: &str, key: &str) -> Result<bool, StoreError> {
+        let removed = db::write(&mut self.conn, |tx| {
+            tx.execute(
+                "DELETE FROM capsule_env WHERE capsule_id = ?1 AND key = ?2",
+                params![capsule_id, key],
+            )
+        })?;
+        Ok(removed == 1)
+    }
+
+    pub fn list_env(&self, capsule_id: &str) -> Result<Vec<CapsuleEnvVar>, StoreError> {
+        let mut stmt = self.conn.prepare(
+            "SELECT key, value, secret FROM capsule_env WHERE capsule_id = ?1 ORDER BY key ASC",
+        )?;
+        let vars = stmt
+            .query_map(params![capsule_id], |row| {
+                let secret: bool = row.get(2)?;
+                let value = if secret {
+                    SECRET_MASK.to_string()
+                } else {
+                    String::from_utf8_lossy(&row.get::<_, Vec<u8>>(1)?).into_owned()
+                };
+                Ok(CapsuleEnvVar {
+                    key: row.get(0)?,
+                    value,
+                    secret,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(vars)
+    }
+
+    /// Sets and removes labels in one transaction and returns the resulting
+    /// label set. Removing a label that is not set is not an error.
+    pub fn update_labels(
+        &mut self,
+        capsule_id: &str,
+        set: &[(String, String)],
+        remove: &[String],
+    ) -> Result<Labels, StoreError> {
+        for (key, value) in set {
+            validate_label_key(key)?;
+            validate_label_value(value)?;
+        }
+        db::write(&mut self.conn, |tx| {
+            let exists = tx
+                .query_row(
+                    "SELECT 1 FROM capsules WHERE capsule_id = ?1",
+                    params![capsule_id],
+                    |_| Ok(()),
+                )
+                .optional()?
+                .is_some();
+            if !exists {
+                return Err(StoreError::CapsuleNotFound(capsule_id.to_string()));
+            }
+            for key in remove {
+                tx.execute(
+                    "DELETE FROM capsule_labels WHERE capsule_id = ?1 AND key = ?2",
+                    params![capsule_id, key],
+                )?;
+            }
+            for (key, value) in set {
+                tx.execute(
+                    "INSERT INTO capsule_labels (capsule_id, key, value) VALUES (?1, ?2, ?3)
+                     ON CONFLICT(capsule_id, key) DO UPDATE SET value = excluded.value",
+                    params![capsule_id, key, value],
+                )?;
+            }
+            Ok(())
+        })?;
+        self.labels(capsule_id)
+    }
+
+    pub fn labels(&self, capsule_id: &str) -> Result<Labels, StoreError> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT key, value FROM capsule_labels WHERE capsule_id = ?1")?;
+        let labels = stmt
+            .query_map(params![capsule_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<Labels, _>>()?;
+        Ok(labels)
+    }
+
+    /// Labels of every capsule, keyed by capsule id. Capsules without labels
+    /// are absent.
+    pub fn all_labels(&self) -> Result<BTreeMap<String, Labels>, StoreError> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT capsule_id, key, value FROM capsule_labels")?;
+        let mut rows = stmt.query([])?;
+        let mut labels: BTreeMap<String, Labels> = BTreeMap::new();
+        while let Some(row) = rows.next()? {
+            labels
+                .entry(row.get(0)?)
+                .or_default()
+                .insert(row.get(1)?, row.get(2)?);
+        }
+        Ok(labels)
+    }
+
+    /// Declares that `capsule_id` depends on `depends_on`, or on its named
+    /// `route`. Rejects unknown capsules and any edge that would close a cycle.
+    pub fn add_dependency(
+        &mut self,
+        capsule_id: &str,
+        depends_on: &str,
+        route: Option<&str>,
     ) -> Result<(), StoreError> {
-        self.conn.execute(
-            "UPDATE capsules SET state = ?1 WHERE capsule_id = ?2",
-            params![state_to_str(state), capsule_id],
+        if let Some(route) = route
+            && !is_valid_service_name(route)
+        {
+            return Err(StoreError::InvalidRouteName(route.to_string()));
+        }
+        db::write(&mut self.conn, |tx| {
+            for id in [capsule_id, depends_on] {
+                let exists = tx
+                    .query_row(
+                        "SELECT 1 FROM capsules WHERE capsule_id = ?1",
+                        params![id],
+                        |_| Ok(()),
+                    )
+                    .optional()?
+                    .is_some();
+                if !exists {
+                    return Err(StoreError::CapsuleNotFound(id.to_string()));
+                }
+            }
+            let mut edges: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+            {
+                let mut stmt =
+                    tx.prepare("SELECT capsule_id, depends_on FROM capsule_dependencies")?;
+                let mut rows = stmt.query([])?;
+                while let Some(row) = rows.next()? {
+                    edges.entry(row.get(0)?).or_default().insert(row.get(1)?);
+                }
+            }
+            if let Some(path) = dependency_path(&edges, depends_on, capsule_id) {
+                return Err(StoreError::DependencyCycle {
+                    path: std::iter::once(capsule_id.to_string())
+                        .chain(path)
+                        .collect(),
+                });
+            }
+            tx.execute(
+                "INSERT OR IGNORE INTO capsule_dependencies (capsule_id, depends_on, route) VALUES (?1, ?2, ?3)",
+                params![capsule_id, depends_on, route.unwrap_or_default()],
+            )?;
+            Ok(())
+        })
+    }
+
+    pub fn remove_dependency(
+        &mut self,
+        capsule_id: &str,
+        depends_on: &str,
+        route: Option<&str>,
+    ) -> Result<bool, StoreError> {
+        let removed = db::write(&mut self.conn, |tx| {
+            tx.execute(
+                "DELETE FROM capsule_dependencies WHERE capsule_id = ?1 AND depends_on = ?2 AND route = ?3",
+                params![capsule_id, depends_on, route.unwrap_or_default()],
+            )
+        })?;
+        Ok(removed == 1)
+    }
+
+    pub fn dependencies(&self, capsule_id: &str) -> Result<Vec<CapsuleDependency>, StoreError> {
+        let mut stmt = self.conn.prepare(
+            "SELECT capsule_id, depends_on, route FROM capsule_dependencies WHERE capsule_id = ?1 ORDER BY depends_on ASC, route ASC",
         )?;
+        let dependencies = stmt
+            .query_map(params![capsule_id], |row| {
+                let route: String = row.get(2)?;
+                Ok(CapsuleDependency {
+                    capsule_id: row.get(0)?,
+                    depends_on: row.get(1)?,
+                    route: (!route.is_empty()).then_some(route),
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(dependencies)
+    }
+
+    /// IDs of the other capsules that depend on `capsule_id`.
+    pub fn dependents(&self, capsule_id: &str) -> Result<Vec<String>, StoreError> {
+        query_dependents(&self.conn, capsule_id)
+    }
+
+    pub fn reveal_secret(
+        &self,
+        capsule_id: &str,
+        key: &str,
+        passphrase: &str,
+    ) -> Result<Option<String>, StoreError> {
+        let sealed = self
+            .conn
+            .query_row(
+                "SELECT value FROM capsule_env WHERE capsule_id = ?1 AND key = ?2 AND secret = 1",
+                params![capsule_id, key],
+                |row| row.get::<_, Vec<u8>>(0),
+            )
+            .optional()?;
+        match sealed {
+            Some(sealed) => Ok(Some(
+                String::from_utf8_lossy(&unseal(passphrase, &sealed)?).into_owned(),
+            )),
+            None => Ok(None),
+        }
+    }
+
+    /// Registers a forked capsule and its lineage in one transaction.
+    pub fn insert_fork(&mut self, capsule: &Capsule, fork: &CapsuleFork) -> Result<(), StoreError> {
+        validate_capsule_id(&capsule.capsule_id)?;
+        db::write(&mut self.conn, |tx| {
+            write_capsule(tx, capsule)?;
+            tx.execute(
+                "INSERT INTO capsule_forks (capsule_id, source_id, branch, source_repo_path)
+             VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    fork.capsule_id,
+                    fork.source_id,
+                    fork.branch,
+                    fork.source_repo_path
+                ],
+            )?;
+            Ok(())
+        })
+    }
+
+    pub fn fork_of(&self, capsule_id: &str) -> Result<Option<CapsuleFork>, StoreError> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT capsule_id, source_id, branch, source_repo_path FROM capsule_forks WHERE capsule_id = ?1",
+                params![capsule_id],
+                |row| {
+                    Ok(CapsuleFork {
+                        capsule_id: row.get(0)?,
+                        source_id: row.get(1)?,
+                        branch: row.get(2)?,
+                        source_repo_path: row.get(3)?,
+                    })
+                },
+            )
+            .optional()?)
+    }
+
+    /// Bumps the restore count and stamps the time and signal of the restore.
+    pub fn record_restore(
+        &mut self,
+        capsule_id: &str,
+        signal: &str,
+        ts_unix_ms: u64,
+    ) -> Result<(), StoreError> {
+        db::write(&mut self.conn, |tx| {
+            tx.execute(
+                "
+                INSERT INTO capsule_activity (capsule_id, last_restored_unix_ms, restore_count, last_signal)
+                VALUES (?1, ?2, 1, ?3)
+                ON CONFLICT(capsule_id) DO UPDATE SET
+                    last_restored_unix_ms = excluded.last_restored_unix_ms,
+                    restore_count = capsule_activity.restore_count + 1,
+                    last_signal = excluded.last_signal
+                ",
+                params![capsule_id, ts_unix_ms, signal],
+            )
+        })?;
         Ok(())
     }
 
+    pub fn activity(&self, capsule_id: &str) -> Result<CapsuleActivity, StoreError> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT last_restored_unix_ms, restore_count, last_signal FROM capsule_activity WHERE capsule_id = ?1",
+                params![capsule_id],
+                row_to_activity,
+            )
+            .optional()?
+            .unwrap_or_default())
+    }
+
+    pub fn history(&self, capsule_id: &str) -> Result<Vec<CapsuleTransition>, StoreError> {
+        let mut stmt = self.conn.prepare(
+            "
+            SELECT capsule_id, from_state, to_state, component, reason, ts_unix_ms
+            FROM capsule_transitions
+            WHERE capsule_id = ?1
+            ORDER BY id ASC
+            ",
+        )?;
+        let rows = stmt
+            .query_map(params![capsule_id], |row| {
+                let from_state: String = row.get(1)?;
+                let to_state: String = row.get(2)?;
+                Ok(CapsuleTransition {
+                    capsule_id: row.get(0)?,
+                    from_state: parse_state(&from_state).unwrap_or(CapsuleState::Ready),
+                    to_state: parse_state(&to_state).unwrap_or(CapsuleState::Ready),
+                    component: row.get(3)?,
+                    reason: row.get(4)?,
+                    ts_unix_ms: row.get(5)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
     pub fn rename_display_name(
         &mut self,
         capsule_id: &str,
         display_name: &str,
     ) -> Result<(), StoreError> {
-        self.conn.execute(
-            "UPDATE capsules SET display_name = ?1 WHERE capsule_id = ?2",
-            params![display_name, capsule_id],
-        )?;
+        db::write(&mut self.conn, |tx| {
+            tx.execute(
+                "UPDATE capsules SET display_name = ?1 WHERE capsule_id = ?2",
+                params![display_name, capsule_id],
+            )
+        })?;
         Ok(())
     }
 
     pub fn set_repo_path(&mut self, capsule_id: &str, repo_path: &str) -> Result<(), StoreError> {
-        self.conn.execute(
-            "UPDATE capsules SET repo_path = ?1 WHERE capsule_id = ?2",
-            params![repo_path, capsule_id],
-        )?;
+        db::write(&mut self.conn, |tx| {
+            tx.execute(
+                "UPDATE capsules SET repo_path = ?1 WHERE capsule_id = ?2",
+                params![repo_path, capsule_id],
+            )
+        })?;
         Ok(())
     }
 
@@ -163,46 +933,709 @@ impl CapsuleStore {
         Ok(ports)
     }
 
+    pub fn list_service_ports(
+        &self,
+        capsule_id: &str,
+    ) -> Result<BTreeMap<String, u16>, StoreError> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT service, port FROM capsule_ports WHERE capsule_id = ?1")?;
+        let ports = stmt
+            .query_map(params![capsule_id], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, u16>(1)?))
+            })?
+            .collect::<Result<BTreeMap<_, _>, _>>()?;
+        Ok(ports)
+    }
+
     pub fn allocate_port(
         &mut self,
         capsule_id: &str,
         start: u16,
         end: u16,
     ) -> Result<Option<u16>, StoreError> {
-        if let Some(existing) = self
+        self.allocate_service_port(capsule_id, DEFAULT_SERVICE, start, end, u32::MAX)
+    }
+
+    pub fn list_all_ports(&self) -> Result<Vec<CapsulePort>, StoreError> {
+        let mut stmt = self.conn.prepare(
+            "SELECT capsule_id, service, port FROM capsule_ports ORDER BY capsule_id ASC, port ASC",
+        )?;
+        let ports = stmt
+            .query_map([], |row| {
+                Ok(CapsulePort {
+                    capsule_id: row.get(0)?,
+                    service: row.get(1)?,
+                    port: row.get(2)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(ports)
+    }
+
+    /// Lowest workspace that no active capsule occupies and that is not marked
+    /// shared, so automatic placement never lands on a shared workspace.
+    pub fn next_free_workspace(&self) -> Result<u16, StoreError> {
+        let occupied = self.occupied_workspaces()?;
+        let shared = self.shared_workspaces()?;
+        (1..=u16::MAX)
+            .find(|workspace| !occupied.contains_key(workspace) && !shared.contains(workspace))
+            .ok_or(StoreError::NoFreeWorkspace)
+    }
+
+    /// Fails if another active capsule already uses `workspace` and the
+    /// workspace has not been marked shared.
+    pub fn check_workspace(&self, capsule_id: &str, workspace: u16) -> Result<(), StoreError> {
+        if self.shared_workspaces()?.contains(&workspace) {
+            return Ok(());
+        }
+        let capsule_ids = self
+            .occupied_workspaces()?
+            .remove(&workspace)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|id| id != capsule_id)
+            .collect::<Vec<_>>();
+        if capsule_ids.is_empty() {
+            return Ok(());
+        }
+        Err(StoreError::WorkspaceCollision {
+            workspace,
+            capsule_ids,
+        })
+    }
+
+    pub fn workspace_collisions(&self) -> Result<Vec<WorkspaceCollision>, StoreError> {
+        let shared = self.shared_workspaces()?;
+        Ok(self
+            .occupied_workspaces()?
+            .into_iter()
+            .filter(|(workspace, ids)| ids.len() > 1 && !shared.contains(workspace))
+            .map(|(workspace, capsule_ids)| WorkspaceCollision {
+                workspace,
+                capsule_ids,
+            })
+            .collect())
+    }
+
+    pub fn set_workspace_shared(&mut self, workspace: u16, shared: bool) -> Result<(), StoreError> {
+        if shared {
+            db::write(&mut self.conn, |tx| {
+                tx.execute(
+                    "INSERT OR IGNORE INTO shared_workspaces (workspace) VALUES (?1)",
+                    params![workspace],
+                )
+            })?;
+        } else {
+            db::write(&mut self.conn, |tx| {
+                tx.execute(
+                    "DELETE FROM shared_workspaces WHERE workspace = ?1",
+                    params![workspace],
+                )
+            })?;
+        }
+        Ok(())
+    }
+
+    pub fn shared_workspaces(&self) -> Result<BTreeSet<u16>, StoreError> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT workspace FROM shared_workspaces ORDER BY workspace ASC")?;
+        let shared = stmt
+            .query_map([], |row| row.get::<_, u16>(0))?
+            .collect::<Result<BTreeSet<_>, _>>()?;
+        Ok(shared)
+    }
+
+    /// Workspaces held by non-archived capsules, with their capsule ids.
+    fn occupied_workspaces(&self) -> Result<BTreeMap<u16, Vec<String>>, StoreError> {
+        let mut occupied: BTreeMap<u16, Vec<String>> = BTreeMap::new();
+        for capsule in self.list()? {
+            if capsule.state != CapsuleState::Archived {
+                occupied
+                    .entry(capsule.workspace)
+                    .or_default()
+                    .push(capsule.capsule_id);
+            }
+        }
+        Ok(occupied)
+    }
+
+    /// Creates or replaces a scene. Members keep their given order and must
+    /// name existing capsules.
+    pub fn set_scene(&mut self, scene: &Scene) -> Result<(), StoreError> {
+        if !is_valid_service_name(&scene.name) {
+            return Err(StoreError::InvalidSceneName(scene.name.clone()));
+        }
+        if scene.members.is_empty() {
+            return Err(StoreError::EmptyScene(scene.name.clone()));
+        }
+        let mut seen = BTreeSet::new();
+        for member in &scene.members {
+            if !seen.insert(member.capsule_id.as_str()) {
+                return Err(StoreError::DuplicateSceneMember {
+                    scene: scene.name.clone(),
+                    capsule_id: member.capsule_id.clone(),
+                });
+            }
+        }
+        db::write(&mut self.conn, |tx| {
+            for member in &scene.members {
+                let exists = tx
+                    .query_row(
+                        "SELECT 1 FROM capsules WHERE capsule_id = ?1",
+                        params![member.capsule_id],
+                        |_| Ok(()),
+                    )
+                    .optional()?
+                    .is_some();
+                if !exists {
+                    return Err(StoreError::CapsuleNotFound(member.capsule_id.clone()));
+                }
+            }
+            tx.execute(
+                "INSERT OR IGNORE INTO scenes (name) VALUES (?1)",
+                params![scene.name],
+            )?;
+            tx.execute(
+                "DELETE FROM scene_members WHERE scene = ?1",
+                params![scene.name],
+            )?;
+            for (position, member) in scene.members.iter().enumerate() {
+                tx.execute(
+                    "INSERT INTO scene_members (scene, capsule_id, workspace, position) VALUES (?1, ?2, ?3, ?4)",
+                    params![scene.name, member.capsule_id, member.workspace, position as u32],
+                )?;
+            }
+            Ok(())
+        })
+    }
+
+    pub fn scene(&self, name: &str) -> Result<Option<Scene>, StoreError> {
+        let exists = self
             .conn
             .query_row(
-                "SELECT port FROM capsule_ports WHERE capsule_id = ?1 ORDER BY port ASC LIMIT 1",
-                params![capsule_id],
-                |row| row.get::<_, u16>(0),
+                "SELECT 1 FROM scenes WHERE name = ?1",
+                params![name],
+                |_| Ok(()),
             )
             .optional()?
-        {
-            return Ok(Some(existing));
+            .is_some();
+        if !exists {
+            return Ok(None);
         }
+        let mut stmt = self.conn.prepare(
+            "SELECT capsule_id, workspace FROM scene_members WHERE scene = ?1 ORDER BY position ASC",
+        )?;
+        let members = stmt
+            .query_map(params![name], |row| {
+                Ok(SceneMember {
+                    capsule_id: row.get(0)?,
+                    workspace: row.get(1)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Some(Scene {
+            name: name.to_string(),
+            members,
+        }))
+    }
+
+    pub fn list_scenes(&self) -> Result<Vec<Scene>, StoreError> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT name FROM scenes ORDER BY name ASC")?;
+        let names = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut scenes = Vec::with_capacity(names.len());
+        for name in names {
+            scenes.extend(self.scene(&name)?);
+        }
+        Ok(scenes)
+    }
+
+    /// Removes the scene; its member capsules are untouched.
+    pub fn delete_scene(&mut self, name: &str) -> Result<bool, StoreError> {
+        let deleted = db::write(&mut self.conn, |tx| {
+            tx.execute("DELETE FROM scene_members WHERE scene = ?1", params![name])?;
+            tx.execute("DELETE FROM scenes WHERE name = ?1", params![name])
+        })?;
+        Ok(deleted == 1)
+    }
 
-        for candidate in start..=end {
-            let inserted = self.conn.execute(
-                "INSERT OR IGNORE INTO capsule_ports (capsule_id, port) VALUES (?1, ?2)",
-                params![capsule_id, candidate],
+    /// Creates or resizes a pool. Pools may not overlap so a port always
+    /// belongs to at most one of them.
+    pub fn set_port_pool(&mut self, name: &str, start: u16, end: u16) -> Result<(), StoreError> {
+        if start > end {
+            return Err(StoreError::InvalidPortRange { start, end });
+        }
+        db::write(&mut self.conn, |tx| {
+            if let Some(other) = query_port_pools(tx)?
+                .into_iter()
+                .find(|pool| pool.name != name && pool.start <= end && start <= pool.end)
+            {
+                return Err(StoreError::PortPoolOverlap {
+                    name: name.to_string(),
+                    other: other.name,
+                });
+            }
+            tx.execute(
+                "INSERT INTO port_pools (name, start_port, end_port) VALUES (?1, ?2, ?3)
+                 ON CONFLICT(name) DO UPDATE SET start_port = excluded.start_port, end_port = excluded.end_port",
+                params![name, start, end],
             )?;
-            if inserted == 1 {
-                return Ok(Some(candidate));
+            Ok(())
+        })
+    }
+
+    pub fn port_pool(&self, name: &str) -> Result<Option<PortPool>, StoreError> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT name, start_port, end_port FROM port_pools WHERE name = ?1",
+                params![name],
+                row_to_port_pool,
+            )
+            .optional()?)
+    }
+
+    pub fn list_port_pools(&self) -> Result<Vec<PortPool>, StoreError> {
+        query_port_pools(&self.conn)
+    }
+
+    /// Reports which ports of the named pool are allocated or reserved.
+    pub fn pool_usage(&self, name: &str) -> Result<PortPoolUsage, StoreError> {
+        let pool = self
+            .port_pool(name)?
+            .ok_or_else(|| StoreError::PortPoolNotFound(name.to_string()))?;
+        let mut stmt = self.conn.prepare(
+            "SELECT port FROM capsule_ports WHERE port BETWEEN ?1 AND ?2
+             UNION
+             SELECT port FROM port_reservations WHERE port BETWEEN ?1 AND ?2
+             ORDER BY port ASC",
+        )?;
+        let used = stmt
+            .query_map(params![pool.start, pool.end], |row| row.get::<_, u16>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        let available = u32::from(pool.end - pool.start) + 1 - used.len() as u32;
+        Ok(PortPoolUsage {
+            pool,
+            used,
+            available,
+        })
+    }
+
+    /// Excludes `port` from allocation. Fails if a capsule already holds it.
+    pub fn reserve_port(&mut self, port: u16, reason: &str) -> Result<(), StoreError> {
+        db::write(&mut self.conn, |tx| {
+            if let Some(capsule_id) = tx
+                .query_row(
+                    "SELECT capsule_id FROM capsule_ports WHERE port = ?1",
+                    params![port],
+                    |row| row.get::<_, String>(0),
+                )
+                .optional()?
+            {
+                return Err(StoreError::PortAllocated { port, capsule_id });
             }
+            tx.execute(
+                "INSERT INTO port_reservations (port, reason) VALUES (?1, ?2)
+             ON CONFLICT(port) DO UPDATE SET reason = excluded.reason",
+                params![port, reason],
+            )?;
+            Ok(())
+        })
+    }
+
+    pub fn unreserve_port(&mut self, port: u16) -> Result<bool, StoreError> {
+        let removed = db::write(&mut self.conn, |tx| {
+            tx.execute(
+                "DELETE FROM port_reservations WHERE port = ?1",
+                params![port],
+            )
+        })?;
+        Ok(removed == 1)
+    }
+
+    pub fn list_port_reservations(&self) -> Result<Vec<PortReservation>, StoreError> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT port, reason FROM port_reservations ORDER BY port ASC")?;
+        let reservations = stmt
+            .query_map([], |row| {
+                Ok(PortReservation {
+                    port: row.get(0)?,
+                    reason: row.get(1)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(reservations)
+    }
+
+    /// The named pool or, while no pool is configured at all, the default pool
+    /// over `DEFAULT_PORT_RANGE`, so a fresh store can onboard and fork.
+    pub fn effective_port_pool(&self, name: &str) -> Result<PortPool, StoreError> {
+        if let Some(pool) = self.port_pool(name)? {
+            return Ok(pool);
         }
+        if name == DEFAULT_PORT_POOL && self.list_port_pools()?.is_empty() {
+            let (start, end) = DEFAULT_PORT_RANGE;
+            return Ok(PortPool {
+                name: name.to_string(),
+                start,
+                end,
+            });
+        }
+        Err(StoreError::PortPoolNotFound(name.to_string()))
+    }
 
-        Ok(None)
+    /// Allocates the capsule's `service` port from the named pool, see
+    /// `effective_port_pool`.
+    pub fn allocate_pool_port(
+        &mut self,
+        capsule_id: &str,
+        service: &str,
+        pool: &str,
+        quota: u32,
+    ) -> Result<Option<u16>, StoreError> {
+        let pool = self.effective_port_pool(pool)?;
+        self.allocate_service_port(capsule_id, service, pool.start, pool.end, quota)
+    }
+
+    /// Returns the capsule's port for `service`, allocating the lowest port in
+    /// `start..=end` that is neither recorded, reserved nor in use on the host.
+    /// Once pools are configured the range must lie inside one of them.
+    /// `Ok(None)` means the range is exhausted; holding `quota` services
+    /// already is an error.
+    pub fn allocate_service_port(
+        &mut self,
+        capsule_id: &str,
+        service: &str,
+        start: u16,
+        end: u16,
+        quota: u32,
+    ) -> Result<Option<u16>, StoreError> {
+        if !is_valid_service_name(service) {
+            return Err(StoreError::InvalidServiceName(service.to_string()));
+        }
+        if start > end {
+            return Err(StoreError::InvalidPortRange { start, end });
+        }
+        db::write(&mut self.conn, |tx| {
+            let pools = query_port_pools(tx)?;
+            if !pools.is_empty()
+                && !pools
+                    .iter()
+                    .any(|pool| pool.start <= start && end <= pool.end)
+            {
+                return Err(StoreError::RangeOutsidePool { start, end });
+            }
+
+            if let Some(existing) = tx
+                .query_row(
+                    "SELECT port FROM capsule_ports WHERE capsule_id = ?1 AND service = ?2",
+                    params![capsule_id, service],
+                    |row| row.get::<_, u16>(0),
+                )
+                .optional()?
+            {
+                return Ok(Some(existing));
+            }
+
+            let held_services = {
+                let mut stmt =
+                    tx.prepare("SELECT service FROM capsule_ports WHERE capsule_id = ?1")?;
+                stmt.query_map(params![capsule_id], |row| row.get::<_, String>(0))?
+                    .collect::<Result<Vec<_>, _>>()?
+            };
+            let env_var = port_env_var(service);
+            if let Some(existing) = held_services
+                .iter()
+                .find(|held| port_env_var(held) == env_var)
+            {
+                return Err(StoreError::ServiceEnvCollision {
+                    capsule_id: capsule_id.to_string(),
+                    service: service.to_string(),
+                    existing: existing.clone(),
+                    env_var,
+                });
+            }
+
+            let held = held_services.len() as u32;
+            if held >= quota {
+                return Err(StoreError::PortQuotaExceeded {
+                    capsule_id: capsule_id.to_string(),
+                    quota,
+                });
+            }
+
+            let reserved = {
+                let mut stmt = tx.prepare("SELECT port FROM port_reservations")?;
+                stmt.query_map([], |row| row.get::<_, u16>(0))?
+                    .collect::<Result<BTreeSet<_>, _>>()?
+            };
+            let probe = HostPortProbe::snapshot();
+            for candidate in start..=end {
+                if reserved.contains(&candidate) || !probe.is_free(candidate) {
+                    continue;
+                }
+                let inserted = tx.execute(
+                "INSERT OR IGNORE INTO capsule_ports (capsule_id, service, port) VALUES (?1, ?2, ?3)",
+                params![capsule_id, service, candidate],
+            )?;
+                if inserted == 1 {
+                    return Ok(Some(candidate));
+                }
+            }
+
+            Ok(None)
+        })
+    }
+
+    /// Records the fixed `port` a manifest declares for the capsule's
+    /// `service`, replacing any port the service held before. Fails if another
+    /// capsule or service holds the port, it is reserved, or the service would
+    /// export the same port env var as one the capsule already holds.
+    pub fn record_service_port(
+        &mut self,
+        capsule_id: &str,
+        service: &str,
+        port: u16,
+    ) -> Result<(), StoreError> {
+        if !is_valid_service_name(service) {
+            return Err(StoreError::InvalidServiceName(service.to_string()));
+        }
+        db::write(&mut self.conn, |tx| {
+            if let Some((holder, held_service)) = tx
+                .query_row(
+                    "SELECT capsule_id, service FROM capsule_ports WHERE port = ?1",
+                    params![port],
+                    |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+                )
+                .optional()?
+            {
+                if holder == capsule_id && held_service == service {
+                    return Ok(());
+                }
+                return Err(StoreError::PortAllocated {
+                    port,
+                    capsule_id: holder,
+                });
+            }
+            if let Some(reason) = tx
+                .query_row(
+                    "SELECT reason FROM port_reservations WHERE port = ?1",
+                    params![port],
+                    |row| row.get::<_, String>(0),
+                )
+                .optional()?
+            {
+                return Err(StoreError::PortReserved { port, reason });
+            }
+
+            let env_var = port_env_var(service);
+            let existing = {
+                let mut stmt =
+                    tx.prepare("SELECT service FROM capsule_ports WHERE capsule_id = ?1")?;
+                stmt.query_map(params![capsule_id], |row| row.get::<_, String>(0))?
+                    .collect::<Result<Vec<_>, _>>()?
+                    .into_iter()
+                    .find(|held| held != service && port_env_var(held) == env_var)
+            };
+            if let Some(existing) = existing {
+                return Err(StoreError::ServiceEnvCollision {
+                    capsule_id: capsule_id.to_string(),
+                    service: service.to_string(),
+                    existing,
+                    env_var,
+                });
+            }
+
+            tx.execute(
+                "INSERT INTO capsule_ports (capsule_id, service, port) VALUES (?1, ?2, ?3)
+             ON CONFLICT(capsule_id, service) DO UPDATE SET port = excluded.port",
+                params![capsule_id, service, port],
+            )?;
+            Ok(())
+        })
+    }
+
+    pub fn release_service_port(
+        &mut self,
+        capsule_id: &str,
+        service: &str,
+    ) -> Result<u32, StoreError> {
+        let released = db::write(&mut self.conn, |tx| {
+            tx.execute(
+                "DELETE FROM capsule_ports WHERE capsule_id = ?1 AND service = ?2",
+                params![capsule_id, service],
+            )
+        })?;
+        Ok(released as u32)
     }
 
     pub fn release_ports(&mut self, capsule_id: &str) -> Result<u32, StoreError> {
-        let released = self.conn.execute(
-            "DELETE FROM capsule_ports WHERE capsule_id = ?1",
-            params![capsule_id],
-        )?;
+        let released = db::write(&mut self.conn, |tx| {
+            tx.execute(
+                "DELETE FROM capsule_ports WHERE capsule_id = ?1",
+                params![capsule_id],
+            )
+        })?;
         Ok(released as u32)
     }
 }
 
+/// Parses the YAML produced by `CapsuleStore::export_yaml`.
+pub fn parse_capsules_yaml(yaml: &str) -> Result<Vec<Capsule>, StoreError> {
+    Ok(serde_yaml::from_str(yaml)?)
+}
+
+fn classify_import(
+    conn: &Connection,
+    capsules: &[Capsule],
+) -> Result<Vec<ImportChange>, StoreError> {
+    let mut seen = BTreeSet::new();
+    let mut changes = Vec::with_capacity(capsules.len());
+    for capsule in capsules {
+        if !seen.insert(capsule.capsule_id.as_str()) {
+            changes.push(ImportChange::Conflict(StoreError::DuplicateImport(
+                capsule.capsule_id.clone(),
+            )));
+            continue;
+        }
+        let Some(existing) = get_capsule(conn, &capsule.capsule_id)? else {
+            changes.push(match validate_capsule_id(&capsule.capsule_id) {
+                Ok(()) => ImportChange::Create,
+                Err(error) => ImportChange::Conflict(error.into()),
+            });
+            continue;
+        };
+        if let Err(error) = check_slug(&existing, capsule) {
+            changes.push(ImportChange::Conflict(error));
+        } else if !existing.state.can_transition_to(capsule.state) {
+            changes.push(ImportChange::Conflict(StoreError::IllegalTransition {
+                capsule_id: capsule.capsule_id.clone(),
+                source: TransitionError {
+                    from: existing.state,
+                    to: capsule.state,
+                },
+            }));
+        } else if existing == *capsule {
+            changes.push(ImportChange::Unchanged);
+        } else {
+            changes.push(ImportChange::Update(existing));
+        }
+    }
+    Ok(changes)
+}
+
+fn import_plan(capsules: &[Capsule], changes: &[ImportChange]) -> ImportPlan {
+    let mut plan = ImportPlan::default();
+    for (capsule, change) in capsules.iter().zip(changes) {
+        let id = capsule.capsule_id.clone();
+        match change {
+            ImportChange::Create => plan.creates.push(id),
+            ImportChange::Update(_) => plan.updates.push(id),
+            ImportChange::Unchanged => plan.unchanged.push(id),
+            ImportChange::Conflict(error) => plan.conflicts.push(ImportConflict {
+                capsule_id: id,
+                code: conflict_code(error).to_string(),
+                message: error.to_string(),
+            }),
+        }
+    }
+    plan
+}
+
+fn conflict_code(error: &StoreError) -> &'static str {
+    match error {
+        StoreError::ImmutableSlug { .. } => "immutable_slug",
+        StoreError::IllegalTransition { .. } => "illegal_transition",
+        StoreError::DuplicateImport(_) => "duplicate_id",
+        StoreError::InvalidCapsuleId(_) => "invalid_id",
+        _ => "error",
+    }
+}
+
+fn query_dependents(conn: &Connection, capsule_id: &str) -> Result<Vec<String>, StoreError> {
+    let mut stmt = conn.prepare(
+        "SELECT DISTINCT capsule_id FROM capsule_dependencies
+         WHERE depends_on = ?1 AND capsule_id != ?1 ORDER BY capsule_id ASC",
+    )?;
+    let dependents = stmt
+        .query_map(params![capsule_id], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(dependents)
+}
+
+fn check_slug(existing: &Capsule, capsule: &Capsule) -> Result<(), StoreError> {
+    if existing.slug != capsule.slug {
+        return Err(StoreError::ImmutableSlug {
+            capsule_id: capsule.capsule_id.clone(),
+            existing_slug: existing.slug.clone(),
+            attempted_slug: capsule.slug.clone(),
+        });
+    }
+    Ok(())
+}
+
+fn get_capsule(conn: &Connection, capsule_id: &str) -> Result<Option<Capsule>, StoreError> {
+    conn.query_row(
+        "SELECT capsule_id, slug, display_name, repo_path, mode, state, workspace FROM capsules WHERE capsule_id = ?1",
+        params![capsule_id],
+        row_to_capsule,
+    )
+    .optional()
+    .map_err(StoreError::from)
+}
+
+/// Writes the row as given; callers validate the ID of new capsules so rows
+/// predating the ID charset can still be updated.
+fn write_capsule(conn: &Connection, capsule: &Capsule) -> Result<(), StoreError> {
+    conn.execute(
+        "
+        INSERT INTO capsules (capsule_id, slug, display_name, repo_path, mode, state, workspace)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+        ON CONFLICT(capsule_id) DO UPDATE SET
+            display_name = excluded.display_name,
+            repo_path = excluded.repo_path,
+            mode = excluded.mode,
+            state = excluded.state,
+            workspace = excluded.workspace
+        ",
+        params![
+            capsule.capsule_id,
+            capsule.slug,
+            capsule.display_name,
+            capsule.repo_path,
+            mode_to_str(capsule.mode),
+            state_to_str(capsule.state),
+            capsule.workspace
+        ],
+    )?;
+    Ok(())
+}
+
+fn insert_transition(conn: &Connection, transition: &CapsuleTransition) -> Result<(), StoreError> {
+    conn.execute(
+        "
+        INSERT INTO capsule_transitions
+            (capsule_id, from_state, to_state, component, reason, ts_unix_ms)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+        ",
+        params![
+            transition.capsule_id,
+            state_to_str(transition.from_state),
+            state_to_str(transition.to_state),
+            transition.component,
+            transition.reason,
+            transition.ts_unix_ms,
+        ],
+    )?;
+    Ok(())
+}
+
 fn row_to_capsule(row: &rusqlite::Row<'_>) -> rusqlite::Result<Capsule> {
     let mode: String = row.get(4)?;
     let state: String = row.get(5)?;
@@ -220,32 +1653,249 @@ fn row_to_capsule(row: &rusqlite::Row<'_>) -> rusqlite::Result<Capsule> {
     })
 }
 
-fn add_state_column_if_missing(conn: &Connection) -> Result<(), StoreError> {
-    match conn.execute(
-        "ALTER TABLE capsules ADD COLUMN state TEXT NOT NULL DEFAULT 'ready'",
-        [],
-    ) {
-        Ok(_) => Ok(()),
-        Err(rusqlite::Error::SqliteFailure(_, Some(message)))
-            if message.contains("duplicate column name") =>
-        {
-            Ok(())
-        }
-        Err(error) => Err(StoreError::Db(error)),
+fn create_capsule_tables(tx: &Transaction<'_>) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS capsules (
+            capsule_id TEXT PRIMARY KEY,
+            slug TEXT NOT NULL,
+            display_name TEXT NOT NULL,
+            mode TEXT NOT NULL,
+            workspace INTEGER NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS capsule_ports (
+            capsule_id TEXT NOT NULL,
+            port INTEGER NOT NULL PRIMARY KEY
+        );
+        ",
+    )
+}
+
+fn add_state_column(tx: &Transaction<'_>) -> rusqlite::Result<()> {
+    add_column_if_missing(tx, "capsules", "state", "TEXT NOT NULL DEFAULT 'ready'")
+}
+
+fn add_repo_path_column(tx: &Transaction<'_>) -> rusqlite::Result<()> {
+    add_column_if_missing(tx, "capsules", "repo_path", "TEXT NOT NULL DEFAULT ''")
+}
+
+fn create_transitions_table(tx: &Transaction<'_>) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS capsule_transitions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            capsule_id TEXT NOT NULL,
+            from_state TEXT NOT NULL,
+            to_state TEXT NOT NULL,
+            component TEXT NOT NULL,
+            reason TEXT NOT NULL,
+            ts_unix_ms INTEGER NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS capsule_transitions_capsule
+        ON capsule_transitions (capsule_id, id);
+        ",
+    )
+}
+
+fn add_port_service_column(tx: &Transaction<'_>) -> rusqlite::Result<()> {
+    add_column_if_missing(
+        tx,
+        "capsule_ports",
+        "service",
+        "TEXT NOT NULL DEFAULT 'default'",
+    )?;
+    // Older stores could hold several unnamed ports for one capsule; keep the
+    // first as `default` and name the rest after their port.
+    tx.execute_batch(
+        "
+        UPDATE capsule_ports SET service = 'port-' || port
+        WHERE rowid NOT IN (SELECT MIN(rowid) FROM capsule_ports GROUP BY capsule_id);
+        CREATE UNIQUE INDEX IF NOT EXISTS capsule_ports_service
+        ON capsule_ports (capsule_id, service);
+        ",
+    )
+}
+
+fn create_port_pool_tables(tx: &Transaction<'_>) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS port_pools (
+            name TEXT PRIMARY KEY,
+            start_port INTEGER NOT NULL,
+            end_port INTEGER NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS port_reservations (
+            port INTEGER PRIMARY KEY,
+            reason TEXT NOT NULL
+        );
+        ",
+    )
+}
+
+fn write_env(
+    conn: &Connection,
+    capsule_id: &str,
+    key: &str,
+    value: &[u8],
+    secret: bool,
+) -> Result<(), StoreError> {
+    if !is_valid_env_key(key) {
+        return Err(StoreError::InvalidEnvKey(key.to_string()));
     }
+    conn.execute(
+        "INSERT INTO capsule_env (capsule_id, key, value, secret) VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT(capsule_id, key) DO UPDATE SET value = excluded.value, secret = excluded.secret",
+        params![capsule_id, key, value, secret],
+    )?;
+    Ok(())
 }
 
-fn add_repo_path_column_if_missing(conn: &Connection) -> Result<(), StoreError> {
-    match conn.execute(
-        "ALTER TABLE capsules ADD COLUMN repo_path TEXT NOT NULL DEFAULT ''",
-        [],
-    ) {
-        Ok(_) => Ok(()),
-        Err(rusqlite::Error::SqliteFailure(_, Some(message)))
-            if message.contains("duplicate column name") =>
-        {
-            Ok(())
+/// Path of capsule ids from `from` to `to` following dependency edges.
+fn dependency_path(
+    edges: &BTreeMap<String, BTreeSet<String>>,
+    from: &str,
+    to: &str,
+) -> Option<Vec<String>> {
+    let mut stack = vec![vec![from.to_string()]];
+    let mut visited = BTreeSet::new();
+    while let Some(path) = stack.pop() {
+        let last = path.last().expect("paths are never empty");
+        if last == to {
+            return Some(path);
+        }
+        if !visited.insert(last.clone()) {
+            continue;
+        }
+        for next in edges.get(last).into_iter().flatten() {
+            let mut extended = path.clone();
+            extended.push(next.clone());
+            stack.push(extended);
         }
-        Err(error) => Err(StoreError::Db(error)),
     }
+    None
+}
+
+fn create_dependencies_table(tx: &Transaction<'_>) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS capsule_dependencies (
+            capsule_id TEXT NOT NULL,
+            depends_on TEXT NOT NULL,
+            route TEXT NOT NULL DEFAULT '',
+            PRIMARY KEY (capsule_id, depends_on, route)
+        );
+        ",
+    )
+}
+
+fn create_scene_tables(tx: &Transaction<'_>) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS scenes (
+            name TEXT PRIMARY KEY
+        );
+        CREATE TABLE IF NOT EXISTS scene_members (
+            scene TEXT NOT NULL,
+            capsule_id TEXT NOT NULL,
+            workspace INTEGER,
+            position INTEGER NOT NULL,
+            PRIMARY KEY (scene, capsule_id)
+        );
+        ",
+    )
+}
+
+fn create_labels_table(tx: &Transaction<'_>) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS capsule_labels (
+            capsule_id TEXT NOT NULL,
+            key TEXT NOT NULL,
+            value TEXT NOT NULL,
+            PRIMARY KEY (capsule_id, key)
+        );
+        ",
+    )
+}
+
+fn create_activity_table(tx: &Transaction<'_>) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS capsule_activity (
+            capsule_id TEXT PRIMARY KEY,
+            last_restored_unix_ms INTEGER NOT NULL,
+            restore_count INTEGER NOT NULL DEFAULT 0,
+            last_signal TEXT NOT NULL
+        );
+        ",
+    )
+}
+
+fn create_env_table(tx: &Transaction<'_>) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS capsule_env (
+            capsule_id TEXT NOT NULL,
+            key TEXT NOT NULL,
+            value BLOB NOT NULL,
+            secret INTEGER NOT NULL DEFAULT 0,
+            PRIMARY KEY (capsule_id, key)
+        );
+        ",
+    )
+}
+
+fn create_forks_table(tx: &Transaction<'_>) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS capsule_forks (
+            capsule_id TEXT PRIMARY KEY,
+            source_id TEXT NOT NULL,
+            branch TEXT NOT NULL,
+            source_repo_path TEXT NOT NULL
+        );
+        ",
+    )
+}
+
+fn create_shared_workspaces_table(tx: &Transaction<'_>) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS shared_workspaces (workspace INTEGER PRIMARY KEY);",
+    )
+}
+
+fn row_to_activity(row: &rusqlite::Row<'_>) -> rusqlite::Result<CapsuleActivity> {
+    Ok(CapsuleActivity {
+        last_restored_unix_ms: Some(row.get(0)?),
+        restore_count: row.get(1)?,
+        last_signal: Some(row.get(2)?),
+    })
+}
+
+fn query_port_pools(conn: &Connection) -> Result<Vec<PortPool>, StoreError> {
+    let mut stmt =
+        conn.prepare("SELECT name, start_port, end_port FROM port_pools ORDER BY start_port ASC")?;
+    let pools = stmt
+        .query_map([], row_to_port_pool)?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(pools)
+}
+
+fn row_to_port_pool(row: &rusqlite::Row<'_>) -> rusqlite::Result<PortPool> {
+    Ok(PortPool {
+        name: row.get(0)?,
+        start: row.get(1)?,
+        end: row.get(2)?,
+    })
+}
+
+fn canonical_or_raw(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn now_unix_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("clock before epoch")
+        .as_millis() as u64
 }
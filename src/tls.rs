@@ -1,14 +1,57 @@
 use std::{
-    path::Path,
+    collections::BTreeSet,
+    fs::{File, OpenOptions},
+    io::Write,
+    net::IpAddr,
+    path::{Path, PathBuf},
     time::{SystemTime, UNIX_EPOCH},
 };
 
-use rcgen::{CertificateParams, DistinguishedName, DnType, KeyPair};
+use rcgen::{
+    BasicConstraints, CertificateParams, DistinguishedName, DnType, ExtendedKeyUsagePurpose, IsCa,
+    Issuer, KeyPair, KeyUsagePurpose, SanType,
+};
+use ring::{
+    rand::SystemRandom,
+    signature::{
+        ECDSA_P256_SHA256_ASN1, ECDSA_P256_SHA256_ASN1_SIGNING, EcdsaKeyPair, UnparsedPublicKey,
+    },
+};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use thiserror::Error;
+use time::OffsetDateTime;
+use x509_parser::{extensions::GeneralName, pem::parse_x509_pem};
+
+use crate::{
+    capsule::{Capsule, CapsuleState},
+    crypto::{CryptoError, seal, unseal},
+};
 
 const DAY_MS: u64 = 24 * 60 * 60 * 1000;
+/// Finding codes that regenerating the material fixes: missing, corrupt,
+/// mismatched or expired files.
+pub const REPAIRABLE_FINDINGS: [&str; 11] = [
+    "missing_meta",
+    "missing_cert",
+    "missing_key",
+    "corrupt_meta",
+    "corrupt_cert",
+    "corrupt_key",
+    "key_mismatch",
+    "fingerprint_mismatch",
+    "expiry_mismatch",
+    "san_mismatch",
+    "expired",
+];
+const ENCRYPTED_KEY_TAG: &str = "NEXUM ENCRYPTED PRIVATE KEY";
+const CA_DIR: &str = "ca";
+const CA_NAME: &str = "nexum-ca";
+const CA_COMMON_NAME: &str = "Nexum Local CA";
+const CA_VALIDITY_DAYS: u64 = 3650;
+const CLIENTS_DIR: &str = "clients";
+/// When set, newly issued private keys are encrypted at rest with this passphrase.
+pub const KEY_PASSPHRASE_ENV: &str = "NEXUM_TLS_KEY_PASSPHRASE";
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct TlsCertificateRecord {
@@ -18,6 +61,10 @@ pub struct TlsCertificateRecord {
     pub fingerprint_sha256: String,
     pub created_unix_ms: u64,
     pub expires_unix_ms: u64,
+    #[serde(default)]
+    pub subject_alt_names: Vec<String>,
+    #[serde(default)]
+    pub key_encrypted: bool,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -26,6 +73,64 @@ pub struct RotateOutcome {
     pub record: TlsCertificateRecord,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TlsInventoryEntry {
+    pub domain: String,
+    pub capsule_id: Option<String>,
+    pub fingerprint_sha256: String,
+    pub expires_unix_ms: u64,
+    pub remaining_days: u64,
+    pub expired: bool,
+    pub subject_alt_names: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrunedMaterial {
+    pub domain: String,
+    pub capsule_id: Option<String>,
+    pub reason: String,
+    pub files: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PruneReport {
+    pub dry_run: bool,
+    pub kept: u32,
+    pub pruned: Vec<PrunedMaterial>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TlsFinding {
+    pub code: String,
+    pub detail: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TlsVerifyReport {
+    pub domain: String,
+    pub ok: bool,
+    pub findings: Vec<TlsFinding>,
+    pub repaired: bool,
+    pub record: Option<TlsCertificateRecord>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClientCertificateRecord {
+    pub capsule_id: String,
+    pub cert_path: String,
+    pub key_path: String,
+    pub fingerprint_sha256: String,
+    pub ca_fingerprint_sha256: String,
+    pub created_unix_ms: u64,
+    pub expires_unix_ms: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VerifiedClient {
+    pub capsule_id: String,
+    pub fingerprint_sha256: String,
+}
+
 #[derive(Debug, Error)]
 pub enum TlsError {
     #[error("io: {0}")]
@@ -34,22 +139,73 @@ pub enum TlsError {
     Json(#[from] serde_json::Error),
     #[error("rcgen: {0}")]
     Rcgen(#[from] rcgen::Error),
+    #[error("invalid subject alt name: {0}")]
+    InvalidSan(String),
+    #[error("key is encrypted; set {KEY_PASSPHRASE_ENV}")]
+    KeyLocked,
+    #[error("key decryption: {0}")]
+    Crypto(#[from] CryptoError),
+    #[error("pem: {0}")]
+    Pem(#[from] pem::PemError),
+    #[error("client certificate rejected: {0}")]
+    ClientCertRejected(String),
+    #[error("signing failed: {0}")]
+    Signing(String),
 }
 
 pub fn ensure_self_signed_cert(
     dir: &Path,
     domain: &str,
     validity_days: u64,
+) -> Result<TlsCertificateRecord, TlsError> {
+    ensure_cert_with_sans(dir, domain, &[], validity_days)
+}
+
+/// Ensures a certificate for `domain` covering `extra_sans` exists, re-issuing
+/// it whenever the requested SAN set differs from the one on disk. An empty
+/// `extra_sans` keeps whatever SANs the existing certificate already has.
+pub fn ensure_cert_with_sans(
+    dir: &Path,
+    domain: &str,
+    extra_sans: &[String],
+    validity_days: u64,
 ) -> Result<TlsCertificateRecord, TlsError> {
     std::fs::create_dir_all(dir)?;
+    let requested = normalize_sans(domain, extra_sans)?;
+    let _lock = lock_domain(dir, domain)?;
+    let sans = match load_record(dir, domain)? {
+        Some(record) if extra_sans.is_empty() => record_sans(&record),
+        _ => requested,
+    };
+    ensure_locked(dir, domain, &sans, validity_days)
+}
 
-    if let Some(record) = load_record(dir, domain)? {
-        if cert_path(dir, domain).exists() && key_path(dir, domain).exists() {
-            return Ok(record);
-        }
+fn ensure_locked(
+    dir: &Path,
+    domain: &str,
+    sans: &[String],
+    validity_days: u64,
+) -> Result<TlsCertificateRecord, TlsError> {
+    if let Some(record) = load_record(dir, domain)?
+        && cert_path(dir, domain).exists()
+        && key_path(dir, domain).exists()
+        && san_set(&record_sans(&record)) == san_set(sans)
+    {
+        return Ok(record);
     }
 
-    generate_and_store(dir, domain, validity_days)
+    generate_and_store(dir, domain, sans, validity_days)
+}
+
+/// SANs a capsule certificate should carry: the capsule domain, a wildcard
+/// for its service subdomains, and the loopback addresses.
+pub fn capsule_subject_alt_names(domain: &str) -> Vec<String> {
+    vec![
+        domain.to_string(),
+        format!("*.{domain}"),
+        "127.0.0.1".to_string(),
+        "::1".to_string(),
+    ]
 }
 
 pub fn rotate_if_expiring(
@@ -57,17 +213,17 @@ pub fn rotate_if_expiring(
     domain: &str,
     threshold_days: u64,
 ) -> Result<RotateOutcome, TlsError> {
-    let current = ensure_self_signed_cert(dir, domain, 30)?;
-    let now = now_unix_ms();
-
-    let remaining_days = if current.expires_unix_ms > now {
-        (current.expires_unix_ms - now) / DAY_MS
-    } else {
-        0
+    std::fs::create_dir_all(dir)?;
+    let _lock = lock_domain(dir, domain)?;
+    let sans = match load_record(dir, domain)? {
+        Some(record) => record_sans(&record),
+        None => vec![domain.to_string()],
     };
+    let current = ensure_locked(dir, domain, &sans, 30)?;
+    let now = now_unix_ms();
 
-    if remaining_days <= threshold_days {
-        let rotated = generate_and_store(dir, domain, 30)?;
+    if remaining_days(current.expires_unix_ms, now) <= threshold_days {
+        let rotated = generate_and_store(dir, domain, &record_sans(&current), 30)?;
         return Ok(RotateOutcome {
             rotated: true,
             record: rotated,
@@ -80,46 +236,469 @@ pub fn rotate_if_expiring(
     })
 }
 
+pub fn list_records(dir: &Path) -> Result<Vec<TlsCertificateRecord>, TlsError> {
+    let mut records = Vec::new();
+    for domain in material_domains(dir)? {
+        if let Some(record) = load_record(dir, &domain)? {
+            records.push(record);
+        }
+    }
+    Ok(records)
+}
+
+pub fn inventory(dir: &Path, capsules: &[Capsule]) -> Result<Vec<TlsInventoryEntry>, TlsError> {
+    let now = now_unix_ms();
+    let entries = list_records(dir)?
+        .into_iter()
+        .map(|record| TlsInventoryEntry {
+            capsule_id: owning_capsule(&record.domain, capsules).map(|c| c.capsule_id.clone()),
+            remaining_days: remaining_days(record.expires_unix_ms, now),
+            expired: record.expires_unix_ms <= now,
+            subject_alt_names: record_sans(&record),
+            domain: record.domain,
+            fingerprint_sha256: record.fingerprint_sha256,
+            expires_unix_ms: record.expires_unix_ms,
+        })
+        .collect();
+    Ok(entries)
+}
+
+pub fn rotate_all_expiring(
+    dir: &Path,
+    threshold_days: u64,
+) -> Result<Vec<RotateOutcome>, TlsError> {
+    list_records(dir)?
+        .into_iter()
+        .map(|record| rotate_if_expiring(dir, &record.domain, threshold_days))
+        .collect()
+}
+
+/// Removes certificate material whose domain belongs to no capsule or only to
+/// archived ones. With `dry_run` the report is computed but nothing is deleted.
+pub fn prune_orphans(
+    dir: &Path,
+    capsules: &[Capsule],
+    dry_run: bool,
+) -> Result<PruneReport, TlsError> {
+    let mut kept = 0u32;
+    let mut pruned = Vec::new();
+
+    for domain in material_domains(dir)? {
+        // Slugs are not unique: one live capsule on the domain keeps it.
+        let owners = capsules
+            .iter()
+            .filter(|capsule| serves_domain(capsule, &domain))
+            .collect::<Vec<_>>();
+        if owners
+            .iter()
+            .any(|capsule| capsule.state != CapsuleState::Archived)
+        {
+            kept += 1;
+            continue;
+        }
+        let owner = owners.first();
+        let reason = match owner {
+            None => "unknown_capsule",
+            Some(_) => "archived_capsule",
+        };
+
+        let files = material_paths(dir, &domain)
+            .into_iter()
+            .filter(|path| path.exists())
+            .collect::<Vec<_>>();
+        if !dry_run {
+            for path in &files {
+                std::fs::remove_file(path)?;
+            }
+        }
+
+        pruned.push(PrunedMaterial {
+            capsule_id: owner.map(|capsule| capsule.capsule_id.clone()),
+            domain,
+            reason: reason.to_string(),
+            files: files
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect(),
+        });
+    }
+
+    Ok(PruneReport {
+        dry_run,
+        kept,
+        pruned,
+    })
+}
+
+/// Removes the server material owned by `capsule` together with its client
+/// certificate. Slugs are not unique, so material for a domain that any other
+/// capsule in `capsules` also resolves to is left in place. With `dry_run` the
+/// same report is returned without deleting.
+pub fn retire_capsule_material(
+    dir: &Path,
+    capsule: &Capsule,
+    capsules: &[Capsule],
+    dry_run: bool,
+) -> Result<Vec<PrunedMaterial>, TlsError> {
+    let mut retired = Vec::new();
+
+    for domain in material_domains(dir)? {
+        let shared = capsules
+            .iter()
+            .any(|other| other.capsule_id != capsule.capsule_id && serves_domain(other, &domain));
+        if !serves_domain(capsule, &domain) || shared {
+            continue;
+        }
+        let files = remove_existing(&material_paths(dir, &domain), dry_run)?;
+        retired.push(PrunedMaterial {
+            domain,
+            capsule_id: Some(capsule.capsule_id.clone()),
+            reason: "capsule_retired".to_string(),
+            files,
+        });
+    }
+
+    let clients_dir = dir.join(CLIENTS_DIR);
+    let files = remove_existing(&material_paths(&clients_dir, &capsule.capsule_id), dry_run)?;
+    if !files.is_empty() {
+        retired.push(PrunedMaterial {
+            domain: capsule.capsule_id.clone(),
+            capsule_id: Some(capsule.capsule_id.clone()),
+            reason: "client_certificate_retired".to_string(),
+            files,
+        });
+    }
+
+    Ok(retired)
+}
+
+/// Parses the stored PEMs and cross-checks them against the metadata. Findings
+/// are always reported; material is only regenerated when `repair` is set and
+/// every finding is one of [`REPAIRABLE_FINDINGS`].
+pub fn verify(dir: &Path, domain: &str, repair: bool) -> Result<TlsVerifyReport, TlsError> {
+    let _lock = if repair {
+        Some(lock_domain(dir, domain)?)
+    } else {
+        None
+    };
+    let (findings, record) = inspect_material(dir, domain);
+    let repairable = findings
+        .iter()
+        .all(|finding| REPAIRABLE_FINDINGS.contains(&finding.code.as_str()));
+    if findings.is_empty() || !repair || !repairable {
+        return Ok(TlsVerifyReport {
+            domain: domain.to_string(),
+            ok: findings.is_empty(),
+            findings,
+            repaired: false,
+            record,
+        });
+    }
+
+    let (sans, validity_days) = match &record {
+        Some(record) => (
+            record_sans(record),
+            (record
+                .expires_unix_ms
+                .saturating_sub(record.created_unix_ms)
+                / DAY_MS)
+                .max(1),
+        ),
+        None => (vec![domain.to_string()], 30),
+    };
+    let regenerated = generate_and_store(dir, domain, &sans, validity_days)?;
+
+    Ok(TlsVerifyReport {
+        domain: domain.to_string(),
+        ok: true,
+        findings,
+        repaired: true,
+        record: Some(regenerated),
+    })
+}
+
+pub fn verify_all(dir: &Path, repair: bool) -> Result<Vec<TlsVerifyReport>, TlsError> {
+    material_domains(dir)?
+        .iter()
+        .map(|domain| verify(dir, domain, repair))
+        .collect()
+}
+
+/// Ensures the local CA used to sign capsule client certificates exists under
+/// `<dir>/ca`.
+pub fn ensure_local_ca(dir: &Path) -> Result<TlsCertificateRecord, TlsError> {
+    let ca_dir = dir.join(CA_DIR);
+    std::fs::create_dir_all(&ca_dir)?;
+    let _lock = lock_domain(&ca_dir, CA_NAME)?;
+
+    if let Some(record) = load_record(&ca_dir, CA_NAME)?
+        && cert_path(&ca_dir, CA_NAME).exists()
+        && key_path(&ca_dir, CA_NAME).exists()
+    {
+        return Ok(record);
+    }
+
+    let mut distinguished_name = DistinguishedName::new();
+    distinguished_name.push(DnType::CommonName, CA_COMMON_NAME);
+    let mut params = CertificateParams::new(Vec::<String>::new())?;
+    params.distinguished_name = distinguished_name;
+    params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    params.key_usages = vec![
+        KeyUsagePurpose::KeyCertSign,
+        KeyUsagePurpose::CrlSign,
+        KeyUsagePurpose::DigitalSignature,
+    ];
+    let created_unix_ms = now_unix_ms();
+    let expires_unix_ms = created_unix_ms.saturating_add(CA_VALIDITY_DAYS * DAY_MS);
+    params.not_before = to_offset_date_time(created_unix_ms);
+    params.not_after = to_offset_date_time(expires_unix_ms);
+
+    let key_pair = KeyPair::generate()?;
+    let cert_pem = params.self_signed(&key_pair)?.pem();
+    let key_encrypted = write_key_and_cert(&ca_dir, CA_NAME, &key_pair, &cert_pem)?;
+
+    let record = TlsCertificateRecord {
+        domain: CA_NAME.to_string(),
+        cert_path: cert_path(&ca_dir, CA_NAME).display().to_string(),
+        key_path: key_path(&ca_dir, CA_NAME).display().to_string(),
+        fingerprint_sha256: sha256_hex(cert_pem.as_bytes()),
+        created_unix_ms,
+        expires_unix_ms,
+        subject_alt_names: Vec::new(),
+        key_encrypted,
+    };
+    write_atomic(
+        &meta_path(&ca_dir, CA_NAME),
+        &serde_json::to_vec_pretty(&record)?,
+        0o644,
+    )?;
+    Ok(record)
+}
+
+/// Issues (or reuses) a client certificate for `capsule_id` signed by the local
+/// CA. The capsule id is carried in the subject common name.
+pub fn ensure_client_cert(
+    dir: &Path,
+    capsule_id: &str,
+    validity_days: u64,
+) -> Result<ClientCertificateRecord, TlsError> {
+    let ca = ensure_local_ca(dir)?;
+    let clients_dir = dir.join(CLIENTS_DIR);
+    std::fs::create_dir_all(&clients_dir)?;
+    let _lock = lock_domain(&clients_dir, capsule_id)?;
+
+    let meta = meta_path(&clients_dir, capsule_id);
+    if meta.exists() && cert_path(&clients_dir, capsule_id).exists() {
+        let record: ClientCertificateRecord = serde_json::from_slice(&std::fs::read(&meta)?)?;
+        if record.ca_fingerprint_sha256 == ca.fingerprint_sha256
+            && record.expires_unix_ms > now_unix_ms()
+        {
+            return Ok(record);
+        }
+    }
+
+    let ca_key = read_key_pair(Path::new(&ca.key_path))?;
+    let issuer = Issuer::from_ca_cert_pem(&std::fs::read_to_string(&ca.cert_path)?, ca_key)?;
+
+    let mut distinguished_name = DistinguishedName::new();
+    distinguished_name.push(DnType::CommonName, capsule_id);
+    let mut params = CertificateParams::new(Vec::<String>::new())?;
+    params.distinguished_name = distinguished_name;
+    params.subject_alt_names = vec![SanType::URI(
+        format!("urn:nexum:capsule:{capsule_id}")
+            .try_into()
+            .map_err(|_| TlsError::InvalidSan(capsule_id.to_string()))?,
+    )];
+    params.key_usages = vec![KeyUsagePurpose::DigitalSignature];
+    params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ClientAuth];
+    let created_unix_ms = now_unix_ms();
+    let expires_unix_ms = created_unix_ms.saturating_add(validity_days.saturating_mul(DAY_MS));
+    params.not_before = to_offset_date_time(created_unix_ms);
+    params.not_after = to_offset_date_time(expires_unix_ms);
+
+    let key_pair = KeyPair::generate()?;
+    let cert_pem = params.signed_by(&key_pair, &issuer)?.pem();
+    write_key_and_cert(&clients_dir, capsule_id, &key_pair, &cert_pem)?;
+
+    let record = ClientCertificateRecord {
+        capsule_id: capsule_id.to_string(),
+        cert_path: cert_path(&clients_dir, capsule_id).display().to_string(),
+        key_path: key_path(&clients_dir, capsule_id).display().to_string(),
+        fingerprint_sha256: sha256_hex(cert_pem.as_bytes()),
+        ca_fingerprint_sha256: ca.fingerprint_sha256,
+        created_unix_ms,
+        expires_unix_ms,
+    };
+    write_atomic(&meta, &serde_json::to_vec_pretty(&record)?, 0o644)?;
+    Ok(record)
+}
+
+/// Verifies a presented client certificate against the local CA and returns
+/// the capsule it identifies.
+pub fn verify_client_cert(dir: &Path, cert_pem: &str) -> Result<VerifiedClient, TlsError> {
+    let reject = |reason: &str| TlsError::ClientCertRejected(reason.to_string());
+
+    let ca_pem = std::fs::read(cert_path(&dir.join(CA_DIR), CA_NAME))
+        .map_err(|_| reject("local CA not initialised"))?;
+    let (_, ca_pem) = parse_x509_pem(&ca_pem).map_err(|_| reject("local CA is corrupt"))?;
+    let ca = ca_pem
+        .parse_x509()
+        .map_err(|_| reject("local CA is corrupt"))?;
+
+    let (_, client_pem) =
+        parse_x509_pem(cert_pem.as_bytes()).map_err(|_| reject("malformed certificate"))?;
+    let client = client_pem
+        .parse_x509()
+        .map_err(|_| reject("malformed certificate"))?;
+
+    client
+        .verify_signature(Some(ca.public_key()))
+        .map_err(|_| reject("not signed by the local CA"))?;
+    if !client.validity().is_valid() {
+        return Err(reject("certificate outside its validity window"));
+    }
+    let client_auth = matches!(
+        client.extended_key_usage(),
+        Ok(Some(extension)) if extension.value.client_auth
+    );
+    if !client_auth {
+        return Err(reject("certificate is not valid for client auth"));
+    }
+
+    let capsule_id = client
+        .subject()
+        .iter_common_name()
+        .next()
+        .and_then(|name| name.as_str().ok())
+        .ok_or_else(|| reject("certificate has no capsule common name"))?;
+
+    Ok(VerifiedClient {
+        capsule_id: capsule_id.to_string(),
+        fingerprint_sha256: sha256_hex(cert_pem.as_bytes()),
+    })
+}
+
+/// Signs `message` with a client private key, proving possession of the key
+/// behind its certificate.
+pub fn sign_with_client_key(key_path: &Path, message: &[u8]) -> Result<Vec<u8>, TlsError> {
+    let key_pair = read_key_pair(key_path)?;
+    let rng = SystemRandom::new();
+    let signer = EcdsaKeyPair::from_pkcs8(
+        &ECDSA_P256_SHA256_ASN1_SIGNING,
+        &key_pair.serialize_der(),
+        &rng,
+    )
+    .map_err(|error| TlsError::Signing(error.to_string()))?;
+    let signature = signer
+        .sign(&rng, message)
+        .map_err(|error| TlsError::Signing(error.to_string()))?;
+    Ok(signature.as_ref().to_vec())
+}
+
+/// Checks that `signature` over `message` was made with the private key
+/// matching the public key in `cert_pem`.
+pub fn verify_client_signature(
+    cert_pem: &str,
+    message: &[u8],
+    signature: &[u8],
+) -> Result<(), TlsError> {
+    let reject = |reason: &str| TlsError::ClientCertRejected(reason.to_string());
+    let (_, pem) =
+        parse_x509_pem(cert_pem.as_bytes()).map_err(|_| reject("malformed certificate"))?;
+    let cert = pem
+        .parse_x509()
+        .map_err(|_| reject("malformed certificate"))?;
+    UnparsedPublicKey::new(
+        &ECDSA_P256_SHA256_ASN1,
+        cert.public_key().subject_public_key.data.as_ref(),
+    )
+    .verify(message, signature)
+    .map_err(|_| reject("signature does not match certificate key"))
+}
+
+/// Resolves the capsule owning `domain`: either the capsule domain itself or
+/// one of its service subdomains.
+pub fn owning_capsule<'a>(domain: &str, capsules: &'a [Capsule]) -> Option<&'a Capsule> {
+    capsules
+        .iter()
+        .find(|capsule| serves_domain(capsule, domain))
+}
+
+fn serves_domain(capsule: &Capsule, domain: &str) -> bool {
+    let capsule_domain = capsule.domain();
+    domain == capsule_domain || domain.ends_with(&format!(".{capsule_domain}"))
+}
+
 fn generate_and_store(
     dir: &Path,
     domain: &str,
+    sans: &[String],
     validity_days: u64,
 ) -> Result<TlsCertificateRecord, TlsError> {
     let mut distinguished_name = DistinguishedName::new();
     distinguished_name.push(DnType::CommonName, domain);
 
-    let mut params = CertificateParams::new(vec![domain.to_string()])?;
+    let mut params = CertificateParams::new(Vec::<String>::new())?;
     params.distinguished_name = distinguished_name;
+    params.subject_alt_names = sans
+        .iter()
+        .map(|san| to_san_type(san))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let created_unix_ms = now_unix_ms();
+    let expires_unix_ms = created_unix_ms.saturating_add(validity_days.saturating_mul(DAY_MS));
+    params.not_before = to_offset_date_time(created_unix_ms);
+    params.not_after = to_offset_date_time(expires_unix_ms);
 
     let key_pair = KeyPair::generate()?;
     let cert = params.self_signed(&key_pair)?;
 
     let cert_pem = cert.pem();
-    let key_pem = key_pair.serialize_pem();
-
-    let cert_path = cert_path(dir, domain);
-    let key_path = key_path(dir, domain);
-
-    std::fs::write(&cert_path, cert_pem.as_bytes())?;
-    std::fs::write(&key_path, key_pem.as_bytes())?;
-
-    let created_unix_ms = now_unix_ms();
-    let expires_unix_ms = created_unix_ms.saturating_add(validity_days.saturating_mul(DAY_MS));
+    let key_encrypted = write_key_and_cert(dir, domain, &key_pair, &cert_pem)?;
 
     let record = TlsCertificateRecord {
         domain: domain.to_string(),
-        cert_path: cert_path.display().to_string(),
-        key_path: key_path.display().to_string(),
+        cert_path: cert_path(dir, domain).display().to_string(),
+        key_path: key_path(dir, domain).display().to_string(),
         fingerprint_sha256: sha256_hex(cert_pem.as_bytes()),
         created_unix_ms,
         expires_unix_ms,
+        subject_alt_names: sans.to_vec(),
+        key_encrypted,
     };
 
-    std::fs::write(meta_path(dir, domain), serde_json::to_vec_pretty(&record)?)?;
+    write_atomic(
+        &meta_path(dir, domain),
+        &serde_json::to_vec_pretty(&record)?,
+        0o644,
+    )?;
 
     Ok(record)
 }
 
+/// Writes key then cert; the caller's metadata rename is the commit point, so
+/// a crash in between leaves stale metadata that `verify` reports. Returns
+/// whether the key was encrypted at rest.
+fn write_key_and_cert(
+    dir: &Path,
+    name: &str,
+    key_pair: &KeyPair,
+    cert_pem: &str,
+) -> Result<bool, TlsError> {
+    let passphrase = key_passphrase();
+    let key_pem = match &passphrase {
+        Some(passphrase) => pem::encode(&pem::Pem::new(
+            ENCRYPTED_KEY_TAG,
+            seal(passphrase, key_pair.serialize_pem().as_bytes())?,
+        )),
+        None => key_pair.serialize_pem(),
+    };
+
+    write_atomic(&key_path(dir, name), key_pem.as_bytes(), 0o600)?;
+    write_atomic(&cert_path(dir, name), cert_pem.as_bytes(), 0o644)?;
+    Ok(passphrase.is_some())
+}
+
 fn load_record(dir: &Path, domain: &str) -> Result<Option<TlsCertificateRecord>, TlsError> {
     let path = meta_path(dir, domain);
     if !path.exists() {
@@ -130,15 +709,326 @@ fn load_record(dir: &Path, domain: &str) -> Result<Option<TlsCertificateRecord>,
     Ok(Some(serde_json::from_slice(&bytes)?))
 }
 
-fn cert_path(dir: &Path, domain: &str) -> std::path::PathBuf {
+fn inspect_material(dir: &Path, domain: &str) -> (Vec<TlsFinding>, Option<TlsCertificateRecord>) {
+    let mut findings = Vec::new();
+    let mut finding = |code: &str, detail: String| {
+        findings.push(TlsFinding {
+            code: code.to_string(),
+            detail,
+        })
+    };
+
+    let record = match load_record(dir, domain) {
+        Ok(Some(record)) => Some(record),
+        Ok(None) => {
+            finding("missing_meta", meta_path(dir, domain).display().to_string());
+            None
+        }
+        Err(error) => {
+            finding("corrupt_meta", error.to_string());
+            None
+        }
+    };
+
+    let cert_pem = match std::fs::read(cert_path(dir, domain)) {
+        Ok(bytes) => Some(bytes),
+        Err(error) => {
+            finding("missing_cert", error.to_string());
+            None
+        }
+    };
+    let key_pair = match read_key_pair(&key_path(dir, domain)) {
+        Ok(key_pair) => Some(key_pair),
+        Err(TlsError::Io(error)) => {
+            finding("missing_key", error.to_string());
+            None
+        }
+        Err(TlsError::KeyLocked) => {
+            finding("key_locked", TlsError::KeyLocked.to_string());
+            None
+        }
+        Err(error) => {
+            finding("corrupt_key", error.to_string());
+            None
+        }
+    };
+
+    let Some(cert_pem) = cert_pem else {
+        return (findings, record);
+    };
+    let pem = match parse_x509_pem(&cert_pem) {
+        Ok((_, pem)) => pem,
+        Err(error) => {
+            finding("corrupt_cert", error.to_string());
+            return (findings, record);
+        }
+    };
+    let cert = match pem.parse_x509() {
+        Ok(cert) => cert,
+        Err(error) => {
+            finding("corrupt_cert", error.to_string());
+            return (findings, record);
+        }
+    };
+
+    if let Some(key_pair) = &key_pair
+        && cert.public_key().subject_public_key.data.as_ref() != key_pair.public_key_raw()
+    {
+        finding(
+            "key_mismatch",
+            "private key does not pair with certificate public key".to_string(),
+        );
+    }
+
+    let Some(record) = record else {
+        return (findings, None);
+    };
+
+    let actual_fingerprint = sha256_hex(&cert_pem);
+    if record.fingerprint_sha256 != actual_fingerprint {
+        finding(
+            "fingerprint_mismatch",
+            format!(
+                "meta={} cert={}",
+                record.fingerprint_sha256, actual_fingerprint
+            ),
+        );
+    }
+
+    let cert_expires_secs = cert.validity().not_after.timestamp();
+    if (record.expires_unix_ms / 1000) as i64 != cert_expires_secs {
+        finding(
+            "expiry_mismatch",
+            format!(
+                "meta={} cert={}",
+                record.expires_unix_ms,
+                cert_expires_secs.saturating_mul(1000)
+            ),
+        );
+    }
+
+    let now_secs = (now_unix_ms() / 1000) as i64;
+    if cert_expires_secs <= now_secs {
+        finding(
+            "expired",
+            format!("not_after={}", cert_expires_secs.saturating_mul(1000)),
+        );
+    }
+
+    let cert_sans = match cert.subject_alternative_name() {
+        Ok(Some(extension)) => extension
+            .value
+            .general_names
+            .iter()
+            .filter_map(general_name_to_string)
+            .collect::<Vec<_>>(),
+        Ok(None) => Vec::new(),
+        Err(error) => {
+            finding("corrupt_cert", error.to_string());
+            Vec::new()
+        }
+    };
+    let recorded_sans = record_sans(&record);
+    if san_set(&recorded_sans) != san_set(&cert_sans) {
+        finding(
+            "san_mismatch",
+            format!(
+                "meta=[{}] cert=[{}]",
+                recorded_sans.join(","),
+                cert_sans.join(",")
+            ),
+        );
+    }
+
+    (findings, Some(record))
+}
+
+/// Reads a private key written by this module, decrypting it with the
+/// passphrase from [`KEY_PASSPHRASE_ENV`] when it was stored encrypted.
+pub fn read_key_pair(path: &Path) -> Result<KeyPair, TlsError> {
+    let content = std::fs::read_to_string(path)?;
+    let parsed = pem::parse(&content)?;
+    if parsed.tag() != ENCRYPTED_KEY_TAG {
+        return Ok(KeyPair::from_pem(&content)?);
+    }
+
+    let passphrase = key_passphrase().ok_or(TlsError::KeyLocked)?;
+    let plaintext = unseal(&passphrase, parsed.contents())?;
+    let plaintext = String::from_utf8(plaintext).map_err(|_| CryptoError::Open)?;
+    Ok(KeyPair::from_pem(&plaintext)?)
+}
+
+fn key_passphrase() -> Option<String> {
+    std::env::var(KEY_PASSPHRASE_ENV)
+        .ok()
+        .filter(|value| !value.is_empty())
+}
+
+fn lock_domain(dir: &Path, domain: &str) -> Result<File, TlsError> {
+    std::fs::create_dir_all(dir)?;
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(dir.join(format!(".{domain}.lock")))?;
+    file.lock()?;
+    Ok(file)
+}
+
+fn write_atomic(path: &Path, bytes: &[u8], mode: u32) -> Result<(), TlsError> {
+    let temp_path = temporary_path(path);
+    let write_result = (|| -> Result<(), TlsError> {
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(mode);
+        }
+        #[cfg(not(unix))]
+        let _ = mode;
+        let mut file = options.open(&temp_path)?;
+        file.write_all(bytes)?;
+        file.sync_all()?;
+        std::fs::rename(&temp_path, path)?;
+        Ok(())
+    })();
+
+    if write_result.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+    }
+
+    write_result
+}
+
+fn temporary_path(path: &Path) -> PathBuf {
+    let file_name = path
+        .file_name()
+        .and_then(|value| value.to_str())
+        .unwrap_or("material");
+    let stamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|value| value.as_nanos())
+        .unwrap_or(0);
+    path.with_file_name(format!(".{file_name}.tmp.{}.{}", std::process::id(), stamp))
+}
+
+fn general_name_to_string(name: &GeneralName<'_>) -> Option<String> {
+    match name {
+        GeneralName::DNSName(dns) => Some(dns.to_string()),
+        GeneralName::IPAddress(bytes) => match bytes.len() {
+            4 => Some(IpAddr::from(<[u8; 4]>::try_from(*bytes).ok()?).to_string()),
+            16 => Some(IpAddr::from(<[u8; 16]>::try_from(*bytes).ok()?).to_string()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn to_offset_date_time(unix_ms: u64) -> OffsetDateTime {
+    OffsetDateTime::from_unix_timestamp((unix_ms / 1000) as i64)
+        .unwrap_or(OffsetDateTime::UNIX_EPOCH)
+}
+
+fn normalize_sans(domain: &str, extra_sans: &[String]) -> Result<Vec<String>, TlsError> {
+    let mut sans = vec![domain.to_string()];
+    for san in extra_sans {
+        let san = san.trim();
+        if san.is_empty() {
+            return Err(TlsError::InvalidSan(san.to_string()));
+        }
+        if !sans.iter().any(|existing| existing == san) {
+            sans.push(san.to_string());
+        }
+    }
+    for san in &sans {
+        to_san_type(san)?;
+    }
+    Ok(sans)
+}
+
+fn to_san_type(san: &str) -> Result<SanType, TlsError> {
+    if let Ok(ip) = san.parse::<IpAddr>() {
+        return Ok(SanType::IpAddress(ip));
+    }
+    let name = san
+        .to_string()
+        .try_into()
+        .map_err(|_| TlsError::InvalidSan(san.to_string()))?;
+    Ok(SanType::DnsName(name))
+}
+
+fn record_sans(record: &TlsCertificateRecord) -> Vec<String> {
+    if record.subject_alt_names.is_empty() {
+        return vec![record.domain.clone()];
+    }
+    record.subject_alt_names.clone()
+}
+
+fn san_set(sans: &[String]) -> BTreeSet<&str> {
+    sans.iter().map(String::as_str).collect()
+}
+
+fn remaining_days(expires_unix_ms: u64, now: u64) -> u64 {
+    expires_unix_ms.saturating_sub(now) / DAY_MS
+}
+
+fn material_domains(dir: &Path) -> Result<Vec<String>, TlsError> {
+    let mut domains = BTreeSet::new();
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    for entry in std::fs::read_dir(dir)? {
+        let name = entry?.file_name();
+        let Some(name) = name.to_str() else {
+            continue;
+        };
+        for suffix in [".crt.pem", ".key.pem", ".meta.json"] {
+            if let Some(domain) = name.strip_suffix(suffix)
+                && !domain.starts_with('.')
+            {
+                domains.insert(domain.to_string());
+            }
+        }
+    }
+
+    Ok(domains.into_iter().collect())
+}
+
+fn remove_existing(paths: &[PathBuf], dry_run: bool) -> Result<Vec<String>, TlsError> {
+    let existing = paths
+        .iter()
+        .filter(|path| path.exists())
+        .collect::<Vec<_>>();
+    if !dry_run {
+        for path in &existing {
+            std::fs::remove_file(path)?;
+        }
+    }
+    Ok(existing
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect())
+}
+
+fn material_paths(dir: &Path, domain: &str) -> Vec<PathBuf> {
+    vec![
+        cert_path(dir, domain),
+        key_path(dir, domain),
+        meta_path(dir, domain),
+    ]
+}
+
+fn cert_path(dir: &Path, domain: &str) -> PathBuf {
     dir.join(format!("{domain}.crt.pem"))
 }
 
-fn key_path(dir: &Path, domain: &str) -> std::path::PathBuf {
+fn key_path(dir: &Path, domain: &str) -> PathBuf {
     dir.join(format!("{domain}.key.pem"))
 }
 
-fn meta_path(dir: &Path, domain: &str) -> std::path::PathBuf {
+fn meta_path(dir: &Path, domain: &str) -> PathBuf {
     dir.join(format!("{domain}.meta.json"))
 }
 
@@ -149,7 +1039,7 @@ fn now_unix_ms() -> u64 {
         .as_millis() as u64
 }
 
-fn sha256_hex(bytes: &[u8]) -> String {
+pub(crate) fn sha256_hex(bytes: &[u8]) -> String {
     let mut hasher = Sha256::new();
     hasher.update(bytes);
     let digest = hasher.finalize();
@@ -1,3 +1,5 @@
+#![allow(clippy::default_constructed_unit_structs)]
+
 use nexum::{
     attention::{AttentionChannel, AttentionEvent, AttentionPolicy, AttentionPriority},
     restore::SignalType,
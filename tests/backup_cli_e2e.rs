@@ -0,0 +1,125 @@
+use std::process::Command;
+
+use serde_json::Value;
+use tempfile::tempdir;
+
+#[test]
+fn nexumctl_backup_create_then_restore() {
+    let dir = tempdir().unwrap();
+    let capsule_db = dir.path().join("capsules.sqlite3");
+    let flags = dir.path().join("flags.toml");
+    let bundle = dir.path().join("bundle.tar");
+    let nexumctl = assert_cmd::cargo::cargo_bin!("nexumctl");
+
+    let created = Command::new(nexumctl)
+        .arg("capsule")
+        .arg("create")
+        .arg("--db")
+        .arg(&capsule_db)
+        .arg("--id")
+        .arg("cap-cli-backup")
+        .arg("--name")
+        .arg("Backup CLI")
+        .arg("--mode")
+        .arg("host_default")
+        .arg("--workspace")
+        .arg("5")
+        .output()
+        .unwrap();
+    assert!(created.status.success());
+    let flagged = Command::new(nexumctl)
+        .arg("flags")
+        .arg("set")
+        .arg("--file")
+        .arg(&flags)
+        .arg("--shadow")
+        .arg("true")
+        .output()
+        .unwrap();
+    assert!(flagged.status.success());
+
+    let backup = Command::new(nexumctl)
+        .arg("backup")
+        .arg("create")
+        .arg("--out")
+        .arg(&bundle)
+        .arg("--capsule-db")
+        .arg(&capsule_db)
+        .arg("--flags")
+        .arg(&flags)
+        .arg("--events-db")
+        .arg(dir.path().join("missing-events.sqlite3"))
+        .output()
+        .unwrap();
+    assert!(backup.status.success());
+    let backup_json: Value = serde_json::from_slice(&backup.stdout).unwrap();
+    let files = backup_json["manifest"]["files"].as_array().unwrap();
+    assert_eq!(files.len(), 2);
+    assert_eq!(files[0]["path"], "capsules.sqlite3");
+    assert_eq!(files[1]["path"], "flags.toml");
+    assert_eq!(
+        backup_json["manifest"]["events_schema_version"],
+        Value::Null
+    );
+
+    let restore_dir = dir.path().join("restored");
+    let restore = |dry_run: &str| {
+        Command::new(nexumctl)
+            .arg("backup")
+            .arg("restore")
+            .arg("--bundle")
+            .arg(&bundle)
+            .arg("--capsule-db")
+            .arg(restore_dir.join("capsules.sqlite3"))
+            .arg("--flags")
+            .arg(restore_dir.join("flags.toml"))
+            .arg("--dry-run")
+            .arg(dry_run)
+            .output()
+            .unwrap()
+    };
+    let dry_run = restore("true");
+    assert!(dry_run.status.success());
+    assert!(!restore_dir.exists());
+
+    let restored = restore("false");
+    assert!(restored.status.success());
+    let restored_json: Value = serde_json::from_slice(&restored.stdout).unwrap();
+    assert_eq!(restored_json["restored"][0]["component"], "capsule_db");
+    assert_eq!(restored_json["restored"][1]["component"], "flags");
+
+    let listed = Command::new(nexumctl)
+        .arg("capsule")
+        .arg("list")
+        .arg("--db")
+        .arg(restore_dir.join("capsules.sqlite3"))
+        .output()
+        .unwrap();
+    assert!(listed.status.success());
+    assert!(
+        String::from_utf8(listed.stdout)
+            .unwrap()
+            .contains("cap-cli-backup")
+    );
+    assert!(
+        std::fs::read_to_string(restore_dir.join("flags.toml"))
+            .unwrap()
+            .contains("shadow_mode = true")
+    );
+
+    let missing_target = Command::new(nexumctl)
+        .arg("backup")
+        .arg("restore")
+        .arg("--bundle")
+        .arg(&bundle)
+        .arg("--capsule-db")
+        .arg(restore_dir.join("capsules.sqlite3"))
+        .output()
+        .unwrap();
+    assert!(!missing_target.status.success());
+    assert!(
+        String::from_utf8(missing_target.stderr)
+            .unwrap()
+            .contains("no target path was given")
+    );
+}
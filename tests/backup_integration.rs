@@ -0,0 +1,236 @@
+use nexum::{
+    backup::{BackupComponent, BackupError, BackupPaths, create_backup, restore_backup},
+    capsule::{Capsule, CapsuleMode},
+    crypto::to_hex,
+    events::{EventStore, RuntimeEvent},
+    flags::{CutoverFlags, FlagName},
+    store::CapsuleStore,
+    tls::{ensure_self_signed_cert, list_records},
+};
+use sha2::{Digest, Sha256};
+use tempfile::tempdir;
+
+fn state_paths(root: &std::path::Path) -> BackupPaths {
+    BackupPaths {
+        capsule_db: Some(root.join("capsules.sqlite3")),
+        events_db: Some(root.join("events.sqlite3")),
+        flags: Some(root.join("flags.toml")),
+        tls_dir: Some(root.join("tls")),
+    }
+}
+
+fn seed_state(paths: &BackupPaths) {
+    let mut store = CapsuleStore::open(paths.capsule_db.as_ref().unwrap()).unwrap();
+    store
+        .upsert(Capsule::new(
+            "cap-backup",
+            "Backup Core",
+            CapsuleMode::HostDefault,
+            3,
+        ))
+        .unwrap();
+    store.allocate_port("cap-backup", 6600, 6610).unwrap();
+
+    let mut events = EventStore::open(paths.events_db.as_ref().unwrap()).unwrap();
+    events
+        .append(RuntimeEvent {
+            capsule_id: "cap-backup".to_string(),
+            component: "restore".to_string(),
+            level: "info".to_string(),
+            message: "restored".to_string(),
+            ts_unix_ms: 1,
+        })
+        .unwrap();
+
+    let mut flags = CutoverFlags::default();
+    flags.set(FlagName::RoutingControlPlane, true);
+    flags.save(paths.flags.as_ref().unwrap()).unwrap();
+
+    ensure_self_signed_cert(
+        paths.tls_dir.as_ref().unwrap(),
+        "backup-core.nexum.local",
+        30,
+    )
+    .unwrap();
+}
+
+#[test]
+fn backup_round_trips_all_state_onto_a_fresh_machine() {
+    let source = tempdir().unwrap();
+    let paths = state_paths(source.path());
+    seed_state(&paths);
+
+    let bundle = source.path().join("bundle.tar");
+    let report = create_backup(&paths, &bundle).unwrap();
+    assert!(report.manifest.capsule_schema_version.is_some());
+    assert!(report.manifest.events_schema_version.is_some());
+    assert!(
+        report
+            .manifest
+            .files
+            .iter()
+            .any(|file| file.component == BackupComponent::Tls)
+    );
+
+    let target = tempdir().unwrap();
+    let targets = state_paths(target.path());
+    let dry_run = restore_backup(&bundle, &targets, true).unwrap();
+    assert_eq!(dry_run.restored.len(), 4);
+    assert!(!targets.capsule_db.as_ref().unwrap().exists());
+
+    restore_backup(&bundle, &targets, false).unwrap();
+    let store = CapsuleStore::open(targets.capsule_db.as_ref().unwrap()).unwrap();
+    assert_eq!(store.get("cap-backup").unwrap().unwrap().workspace, 3);
+    assert_eq!(store.list_ports("cap-backup").unwrap(), vec![6600]);
+    let events = EventStore::open(targets.events_db.as_ref().unwrap()).unwrap();
+    assert_eq!(events.list_for_capsule("cap-backup").unwrap().len(), 1);
+    let flags = CutoverFlags::load_or_default(targets.flags.as_ref().unwrap()).unwrap();
+    assert!(flags.routing_control_plane);
+    let records = list_records(targets.tls_dir.as_ref().unwrap()).unwrap();
+    assert!(
+        records
+            .iter()
+            .any(|record| record.domain == "backup-core.nexum.local")
+    );
+}
+
+#[test]
+fn restore_rolls_back_changes_made_after_the_backup() {
+    let dir = tempdir().unwrap();
+    let paths = state_paths(dir.path());
+    seed_state(&paths);
+    let bundle = dir.path().join("bundle.tar");
+    create_backup(&paths, &bundle).unwrap();
+
+    {
+        let mut store = CapsuleStore::open(paths.capsule_db.as_ref().unwrap()).unwrap();
+        store
+            .upsert(Capsule::new(
+                "cap-after",
+                "After Backup",
+                CapsuleMode::HostDefault,
+                4,
+            ))
+            .unwrap();
+        ensure_self_signed_cert(paths.tls_dir.as_ref().unwrap(), "after.nexum.local", 30).unwrap();
+    }
+
+    restore_backup(&bundle, &paths, false).unwrap();
+    let store = CapsuleStore::open(paths.capsule_db.as_ref().unwrap()).unwrap();
+    assert!(store.get("cap-after").unwrap().is_none());
+    assert!(store.get("cap-backup").unwrap().is_some());
+    let records = list_records(paths.tls_dir.as_ref().unwrap()).unwrap();
+    assert!(
+        records
+            .iter()
+            .all(|record| record.domain != "after.nexum.local")
+    );
+}
+
+#[test]
+fn restore_rejects_tampered_bundles_and_missing_targets() {
+    let dir = tempdir().unwrap();
+    let paths = state_paths(dir.path());
+    seed_state(&paths);
+    let bundle = dir.path().join("bundle.tar");
+    create_backup(&paths, &bundle).unwrap();
+
+    let missing_target = restore_backup(
+        &bundle,
+        &BackupPaths {
+            tls_dir: None,
+            ..state_paths(dir.path())
+        },
+        true,
+    )
+    .unwrap_err();
+    assert!(matches!(missing_target, BackupError::MissingTarget("tls")));
+
+    let mut bytes = std::fs::read(&bundle).unwrap();
+    let flags_at = bytes
+        .windows(b"routing_control_plane = true".len())
+        .position(|window| window == b"routing_control_plane = true")
+        .unwrap();
+    bytes[flags_at + b"routing_control_plane = ".len()] = b'T';
+    let tampered = dir.path().join("tampered.tar");
+    std::fs::write(&tampered, bytes).unwrap();
+
+    let target = tempdir().unwrap();
+    let err = restore_backup(&tampered, &state_paths(target.path()), false).unwrap_err();
+    assert!(matches!(err, BackupError::ChecksumMismatch(path) if path == "flags.toml"));
+    assert!(!target.path().join("capsules.sqlite3").exists());
+}
+
+/// Byte range of the tar entry `name`'s data in `bundle`.
+fn tar_entry(bundle: &[u8], name: &str) -> std::ops::Range<usize> {
+    let mut offset = 0;
+    while offset + 512 <= bundle.len() {
+        let header = &bundle[offset..offset + 512];
+        let entry = String::from_utf8_lossy(&header[..100])
+            .trim_end_matches('\0')
+            .to_string();
+        let size = usize::from_str_radix(
+            String::from_utf8_lossy(&header[124..136])
+                .trim_matches(|ch: char| ch == '\0' || ch == ' '),
+            8,
+        )
+        .unwrap();
+        let data = offset + 512;
+        if entry == name {
+            return data..data + size;
+        }
+        offset = data + size.div_ceil(512) * 512;
+    }
+    panic!("entry {name} not in bundle");
+}
+
+#[test]
+fn restore_stages_every_database_before_replacing_any() {
+    let dir = tempdir().unwrap();
+    let paths = state_paths(dir.path());
+    seed_state(&paths);
+    let bundle = dir.path().join("bundle.tar");
+    create_backup(&paths, &bundle).unwrap();
+
+    let mut bytes = std::fs::read(&bundle).unwrap();
+    let events = tar_entry(&bytes, "events.sqlite3");
+    let old_sha = to_hex(&Sha256::digest(&bytes[events.clone()]));
+    bytes[events.start..events.start + 16].copy_from_slice(b"not a database!\0");
+    let new_sha = to_hex(&Sha256::digest(&bytes[events.clone()]));
+    let manifest = tar_entry(&bytes, "manifest.json");
+    let rewritten = String::from_utf8(bytes[manifest.clone()].to_vec())
+        .unwrap()
+        .replace(&old_sha, &new_sha);
+    bytes[manifest].copy_from_slice(rewritten.as_bytes());
+    let corrupt = dir.path().join("corrupt-events.tar");
+    std::fs::write(&corrupt, bytes).unwrap();
+
+    {
+        let mut store = CapsuleStore::open(paths.capsule_db.as_ref().unwrap()).unwrap();
+        store
+            .upsert(Capsule::new(
+                "cap-after",
+                "After Backup",
+                CapsuleMode::HostDefault,
+                4,
+            ))
+            .unwrap();
+    }
+
+    let err = restore_backup(&corrupt, &paths, false).unwrap_err();
+    assert!(
+        matches!(err, BackupError::Db(_) | BackupError::CorruptDatabase(_)),
+        "{err:?}"
+    );
+    let store = CapsuleStore::open(paths.capsule_db.as_ref().unwrap()).unwrap();
+    assert!(store.get("cap-after").unwrap().is_some());
+    let leftovers = std::fs::read_dir(dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .filter(|name| name.contains(".restore."))
+        .collect::<Vec<_>>();
+    assert!(
+        leftovers.is_empty(),
+        "staged files left behind: {leftovers:?}"
+    );
+}
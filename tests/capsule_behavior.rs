@@ -1,6 +1,9 @@
 use proptest::prelude::*;
 
-use nexum::capsule::{Capsule, CapsuleMode, normalize_slug};
+use nexum::capsule::{
+    Capsule, CapsuleMode, CapsuleState, GENERATED_ID_PREFIX, generate_capsule_id, normalize_slug,
+    ulid, validate_capsule_id,
+};
 
 #[test]
 fn slug_is_normalized_for_domain_identity() {
@@ -21,6 +24,56 @@ fn capsule_slug_is_immutable_after_rename() {
     assert_eq!(capsule.domain(), "billing-api.nexum.local");
 }
 
+#[test]
+fn lifecycle_rejects_illegal_jumps_but_allows_unarchive() {
+    let mut capsule = Capsule::new("cap-3", "Lifecycle", CapsuleMode::HostDefault, 2);
+
+    capsule.transition_state(CapsuleState::Archived).unwrap();
+    let error = capsule
+        .transition_state(CapsuleState::Restoring)
+        .unwrap_err();
+    assert_eq!(error.from, CapsuleState::Archived);
+    assert_eq!(error.to, CapsuleState::Restoring);
+    assert_eq!(capsule.state, CapsuleState::Archived);
+
+    capsule.transition_state(CapsuleState::Ready).unwrap();
+    capsule.transition_state(CapsuleState::Restoring).unwrap();
+    assert!(capsule.transition_state(CapsuleState::Archived).is_err());
+    capsule.transition_state(CapsuleState::Degraded).unwrap();
+}
+
+#[test]
+fn capsule_ids_are_limited_to_the_documented_charset() {
+    for valid in ["cap-1", "billing_api", "0abc", &"a".repeat(64)] {
+        assert!(validate_capsule_id(valid).is_ok(), "{valid}");
+    }
+    for invalid in [
+        "",
+        "-cap",
+        "_cap",
+        "Cap-1",
+        "cap 1",
+        "cap.1",
+        "cap/1",
+        &"a".repeat(65),
+    ] {
+        assert!(validate_capsule_id(invalid).is_err(), "{invalid}");
+    }
+}
+
+#[test]
+fn generated_ids_are_valid_ulids_that_sort_by_time() {
+    let id = generate_capsule_id().unwrap();
+    assert!(id.starts_with(GENERATED_ID_PREFIX));
+    assert_eq!(id.len(), GENERATED_ID_PREFIX.len() + 26);
+    assert!(validate_capsule_id(&id).is_ok());
+    assert_ne!(id, generate_capsule_id().unwrap());
+
+    assert_eq!(ulid(0, [0; 10]), "0".repeat(26));
+    assert_eq!(ulid(1, [0; 10]), "00000000010000000000000000");
+    assert!(ulid(1_700_000_000_000, [0xff; 10]) < ulid(1_700_000_000_001, [0; 10]));
+}
+
 proptest! {
     #[test]
     fn normalized_slugs_are_dns_safe(input in "[A-Za-z0-9 _./-]{1,48}") {
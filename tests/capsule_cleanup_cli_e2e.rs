@@ -0,0 +1,116 @@
+use std::{process::Command, time::Duration};
+
+use serde_json::Value;
+use tempfile::tempdir;
+
+#[test]
+fn nexumctl_capsule_archive_removes_routes_through_daemon() {
+    let dir = tempdir().unwrap();
+    let socket = dir.path().join("nexumd.sock");
+    let db = dir.path().join("capsules.sqlite3");
+    let nexumctl = assert_cmd::cargo::cargo_bin!("nexumctl");
+
+    let mut daemon = Command::new(assert_cmd::cargo::cargo_bin!("nexumd"))
+        .arg("serve")
+        .arg("--socket")
+        .arg(&socket)
+        .spawn()
+        .unwrap();
+    for _ in 0..40 {
+        if socket.exists() {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(25));
+    }
+
+    let create = Command::new(nexumctl)
+        .arg("capsule")
+        .arg("create")
+        .arg("--db")
+        .arg(&db)
+        .arg("--id")
+        .arg("cap-archive-cli")
+        .arg("--name")
+        .arg("Archive Cli")
+        .arg("--workspace")
+        .arg("8")
+        .arg("--mode")
+        .arg("host_default")
+        .output()
+        .unwrap();
+    assert!(create.status.success());
+
+    let register = Command::new(nexumctl)
+        .arg("routing")
+        .arg("register")
+        .arg("--socket")
+        .arg(&socket)
+        .arg("--capsule-id")
+        .arg("cap-archive-cli")
+        .arg("--domain")
+        .arg("archive-cli.nexum.local")
+        .arg("--upstream")
+        .arg("127.0.0.1:4820")
+        .output()
+        .unwrap();
+    assert!(register.status.success());
+
+    let archive = |dry_run: &str| {
+        Command::new(nexumctl)
+            .arg("capsule")
+            .arg("archive")
+            .arg("--db")
+            .arg(&db)
+            .arg("--id")
+            .arg("cap-archive-cli")
+            .arg("--socket")
+            .arg(&socket)
+            .arg("--dry-run")
+            .arg(dry_run)
+            .output()
+            .unwrap()
+    };
+
+    let preview = archive("true");
+    assert!(preview.status.success());
+    let preview_json: Value = serde_json::from_slice(&preview.stdout).unwrap();
+    assert_eq!(preview_json["dry_run"], Value::Bool(true));
+    assert_eq!(
+        preview_json["removed_routes"],
+        serde_json::json!(["archive-cli.nexum.local"])
+    );
+
+    let applied = archive("false");
+    assert!(applied.status.success());
+    let applied_json: Value = serde_json::from_slice(&applied.stdout).unwrap();
+    assert_eq!(
+        applied_json["removed_routes"],
+        preview_json["removed_routes"]
+    );
+    assert_eq!(applied_json["routing_error"], Value::Null);
+
+    let resolve = Command::new(nexumctl)
+        .arg("routing")
+        .arg("resolve")
+        .arg("--socket")
+        .arg(&socket)
+        .arg("--domain")
+        .arg("archive-cli.nexum.local")
+        .output()
+        .unwrap();
+    let resolve_json: Value = serde_json::from_slice(&resolve.stdout).unwrap();
+    assert_eq!(resolve_json["route"], Value::Null);
+
+    let list = Command::new(nexumctl)
+        .arg("capsule")
+        .arg("list")
+        .arg("--db")
+        .arg(&db)
+        .output()
+        .unwrap();
+    let list_json: Value = serde_json::from_slice(&list.stdout).unwrap();
+    assert_eq!(list_json[0]["state"], Value::String("archived".into()));
+
+    daemon.kill().unwrap();
+    let _ = daemon.wait();
+}
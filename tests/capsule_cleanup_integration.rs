@@ -0,0 +1,221 @@
+use nexum::{
+    capsule::{Capsule, CapsuleMode, CapsuleState},
+    cleanup::{CleanupAction, CleanupError, CleanupInput, cleanup_capsule},
+    events::{EventStore, RuntimeEvent},
+    identity::profile_dir_for_capsule,
+    store::{CapsuleStore, StoreError},
+    tls::{ensure_client_cert, ensure_self_signed_cert},
+};
+use tempfile::tempdir;
+
+fn seed(dir: &std::path::Path, capsule_id: &str) -> Capsule {
+    let capsule = Capsule::new(capsule_id, capsule_id, CapsuleMode::HostDefault, 3);
+    let mut store = CapsuleStore::open(&dir.join("capsules.sqlite3")).unwrap();
+    store.upsert(capsule.clone()).unwrap();
+    store.allocate_port(capsule_id, 4600, 4610).unwrap();
+
+    let tls_dir = dir.join("tls");
+    ensure_self_signed_cert(&tls_dir, &capsule.domain(), 30).unwrap();
+    ensure_self_signed_cert(&tls_dir, "other.nexum.local", 30).unwrap();
+    ensure_client_cert(&tls_dir, capsule_id, 30).unwrap();
+
+    let mut events = EventStore::open(&dir.join("events.sqlite3")).unwrap();
+    events
+        .append(RuntimeEvent {
+            capsule_id: capsule_id.into(),
+            component: "restore".into(),
+            level: "info".into(),
+            message: "ok".into(),
+            ts_unix_ms: 1,
+        })
+        .unwrap();
+    capsule
+}
+
+fn input(
+    dir: &std::path::Path,
+    capsule_id: &str,
+    action: CleanupAction,
+    dry_run: bool,
+) -> CleanupInput {
+    CleanupInput {
+        capsule_db: dir.join("capsules.sqlite3"),
+        capsule_id: capsule_id.into(),
+        action,
+        routing_socket: None,
+        tls_dir: Some(dir.join("tls")),
+        events_db: Some(dir.join("events.sqlite3")),
+        dry_run,
+    }
+}
+
+#[test]
+fn archive_releases_ports_and_retires_tls_but_keeps_record() {
+    let dir = tempdir().unwrap();
+    let capsule = seed(dir.path(), "cap-cleanup-archive");
+
+    let preview = cleanup_capsule(&input(
+        dir.path(),
+        "cap-cleanup-archive",
+        CleanupAction::Archive,
+        true,
+    ))
+    .unwrap();
+    assert_eq!(preview.released_ports, vec![4600]);
+    assert_eq!(preview.retired_tls.len(), 2);
+    assert!(
+        dir.path()
+            .join("tls")
+            .join(format!("{}.crt.pem", capsule.domain()))
+            .exists()
+    );
+
+    let report = cleanup_capsule(&input(
+        dir.path(),
+        "cap-cleanup-archive",
+        CleanupAction::Archive,
+        false,
+    ))
+    .unwrap();
+    assert_eq!(report.released_ports, preview.released_ports);
+    assert_eq!(report.retired_tls, preview.retired_tls);
+    assert_eq!(report.removed_events, 0);
+
+    let store = CapsuleStore::open(&dir.path().join("capsules.sqlite3")).unwrap();
+    let archived = store.get("cap-cleanup-archive").unwrap().unwrap();
+    assert_eq!(archived.state, CapsuleState::Archived);
+    assert!(store.list_ports("cap-cleanup-archive").unwrap().is_empty());
+    assert!(
+        !dir.path()
+            .join("tls")
+            .join(format!("{}.crt.pem", capsule.domain()))
+            .exists()
+    );
+    assert!(dir.path().join("tls/other.nexum.local.crt.pem").exists());
+}
+
+#[test]
+fn archive_keeps_tls_material_when_another_capsule_shares_the_domain() {
+    let dir = tempdir().unwrap();
+    let capsule = seed(dir.path(), "cap-cleanup-shared");
+    let twin = Capsule::new(
+        "cap-cleanup-twin",
+        "cap-cleanup-shared",
+        CapsuleMode::HostDefault,
+        4,
+    );
+    assert_eq!(twin.domain(), capsule.domain());
+    CapsuleStore::open(&dir.path().join("capsules.sqlite3"))
+        .unwrap()
+        .upsert(twin)
+        .unwrap();
+
+    let report = cleanup_capsule(&input(
+        dir.path(),
+        "cap-cleanup-shared",
+        CleanupAction::Archive,
+        false,
+    ))
+    .unwrap();
+
+    assert_eq!(report.retired_tls.len(), 1);
+    assert_eq!(report.retired_tls[0].reason, "client_certificate_retired");
+    assert!(
+        dir.path()
+            .join("tls")
+            .join(format!("{}.crt.pem", capsule.domain()))
+            .exists()
+    );
+}
+
+#[test]
+fn delete_removes_record_profile_and_events() {
+    let dir = tempdir().unwrap();
+    seed(dir.path(), "cap-cleanup-delete");
+    let profile = profile_dir_for_capsule("cap-cleanup-delete");
+    std::fs::create_dir_all(&profile).unwrap();
+    std::fs::write(profile.join("user.js"), "").unwrap();
+
+    let preview = cleanup_capsule(&input(
+        dir.path(),
+        "cap-cleanup-delete",
+        CleanupAction::Delete,
+        true,
+    ))
+    .unwrap();
+    assert_eq!(preview.removed_events, 1);
+    assert!(preview.removed_profile_dir.is_some());
+    assert!(profile.exists());
+
+    let report = cleanup_capsule(&input(
+        dir.path(),
+        "cap-cleanup-delete",
+        CleanupAction::Delete,
+        false,
+    ))
+    .unwrap();
+    assert_eq!(report.removed_events, 1);
+    assert_eq!(report.removed_profile_dir, preview.removed_profile_dir);
+    assert!(!profile.exists());
+
+    let store = CapsuleStore::open(&dir.path().join("capsules.sqlite3")).unwrap();
+    assert!(store.get("cap-cleanup-delete").unwrap().is_none());
+    let events = EventStore::open(&dir.path().join("events.sqlite3")).unwrap();
+    assert!(
+        events
+            .list_for_capsule("cap-cleanup-delete")
+            .unwrap()
+            .is_empty()
+    );
+
+    assert!(matches!(
+        cleanup_capsule(&input(
+            dir.path(),
+            "cap-cleanup-delete",
+            CleanupAction::Delete,
+            false,
+        )),
+        Err(CleanupError::CapsuleNotFound(_))
+    ));
+}
+
+#[test]
+fn delete_is_refused_while_another_capsule_depends_on_it() {
+    let dir = tempdir().unwrap();
+    let capsule = seed(dir.path(), "cap-cleanup-shared");
+    let mut store = CapsuleStore::open(&dir.path().join("capsules.sqlite3")).unwrap();
+    store
+        .upsert(Capsule::new(
+            "cap-cleanup-front",
+            "cap-cleanup-front",
+            CapsuleMode::HostDefault,
+            4,
+        ))
+        .unwrap();
+    store
+        .add_dependency("cap-cleanup-front", "cap-cleanup-shared", None)
+        .unwrap();
+
+    for dry_run in [true, false] {
+        let error = cleanup_capsule(&input(
+            dir.path(),
+            "cap-cleanup-shared",
+            CleanupAction::Delete,
+            dry_run,
+        ))
+        .unwrap_err();
+        assert!(matches!(
+            error,
+            CleanupError::Store(StoreError::HasDependents { ref dependents, .. })
+                if dependents == &vec!["cap-cleanup-front".to_string()]
+        ));
+    }
+    assert!(store.get("cap-cleanup-shared").unwrap().is_some());
+    assert_eq!(store.list_ports("cap-cleanup-shared").unwrap(), vec![4600]);
+    assert!(
+        dir.path()
+            .join("tls")
+            .join(format!("{}.crt.pem", capsule.domain()))
+            .exists()
+    );
+}
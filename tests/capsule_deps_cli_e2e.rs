@@ -0,0 +1,72 @@
+use std::process::Command;
+
+use serde_json::Value;
+use tempfile::tempdir;
+
+fn run(args: &[&str]) -> std::process::Output {
+    Command::new(assert_cmd::cargo::cargo_bin!("nexumctl"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn nexumctl_capsule_deps_declares_and_rejects_cycles() {
+    let dir = tempdir().unwrap();
+    let capsule_db = dir.path().join("capsules.sqlite3");
+    let db = capsule_db.to_str().unwrap();
+
+    for (id, workspace) in [("cap-web", "1"), ("cap-api", "2")] {
+        let created = run(&[
+            "capsule",
+            "create",
+            "--db",
+            db,
+            "--id",
+            id,
+            "--name",
+            id,
+            "--workspace",
+            workspace,
+            "--mode",
+            "host_default",
+        ]);
+        assert!(created.status.success());
+    }
+
+    let added = run(&[
+        "capsule", "deps", "add", "--db", db, "--id", "cap-web", "--on", "cap-api", "--route",
+        "graphql",
+    ]);
+    assert!(added.status.success());
+    let added: Value = serde_json::from_slice(&added.stdout).unwrap();
+    assert_eq!(
+        added,
+        serde_json::json!([{"capsule_id": "cap-web", "depends_on": "cap-api", "route": "graphql"}])
+    );
+
+    let cycle = run(&[
+        "capsule", "deps", "add", "--db", db, "--id", "cap-api", "--on", "cap-web",
+    ]);
+    assert!(!cycle.status.success());
+    assert!(
+        String::from_utf8(cycle.stderr)
+            .unwrap()
+            .contains("dependency cycle: cap-api -> cap-web -> cap-api")
+    );
+
+    let listed = run(&["capsule", "list", "--db", db]);
+    let listed: Value = serde_json::from_slice(&listed.stdout).unwrap();
+    assert_eq!(listed[1]["capsule_id"], "cap-web");
+    assert_eq!(listed[1]["dependencies"][0]["route"], "graphql");
+
+    let removed = run(&[
+        "capsule", "deps", "remove", "--db", db, "--id", "cap-web", "--on", "cap-api", "--route",
+        "graphql",
+    ]);
+    assert!(removed.status.success());
+    let removed: Value = serde_json::from_slice(&removed.stdout).unwrap();
+    assert_eq!(removed["removed"], true);
+    let deps = run(&["capsule", "deps", "list", "--db", db, "--id", "cap-web"]);
+    assert_eq!(String::from_utf8(deps.stdout).unwrap().trim(), "[]");
+}
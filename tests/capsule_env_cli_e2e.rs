@@ -0,0 +1,134 @@
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+use serde_json::Value;
+use tempfile::tempdir;
+
+#[test]
+fn nexumctl_capsule_env_masks_secrets_and_reveals_with_passphrase() {
+    let dir = tempdir().unwrap();
+    let db = dir.path().join("capsules.sqlite3");
+    let nexumctl = assert_cmd::cargo::cargo_bin!("nexumctl");
+
+    let create = Command::new(nexumctl)
+        .arg("capsule")
+        .arg("create")
+        .arg("--db")
+        .arg(&db)
+        .arg("--id")
+        .arg("cap-env-cli")
+        .arg("--name")
+        .arg("Env Cli")
+        .arg("--mode")
+        .arg("host_default")
+        .output()
+        .unwrap();
+    assert!(create.status.success());
+
+    let on_argv = Command::new(nexumctl)
+        .env("NEXUM_SECRETS_PASSPHRASE", "hunter2")
+        .arg("capsule")
+        .arg("env")
+        .arg("set")
+        .arg("--db")
+        .arg(&db)
+        .arg("--id")
+        .arg("cap-env-cli")
+        .arg("--key")
+        .arg("API_KEY")
+        .arg("--value")
+        .arg("sk-cli-789")
+        .arg("--secret")
+        .arg("true")
+        .output()
+        .unwrap();
+    assert!(!on_argv.status.success());
+    assert!(
+        String::from_utf8(on_argv.stderr)
+            .unwrap()
+            .contains("--value is not accepted with --secret true")
+    );
+
+    let locked = Command::new(nexumctl)
+        .env_remove("NEXUM_SECRETS_PASSPHRASE")
+        .env("CLI_API_KEY", "sk-cli-789")
+        .arg("capsule")
+        .arg("env")
+        .arg("set")
+        .arg("--db")
+        .arg(&db)
+        .arg("--id")
+        .arg("cap-env-cli")
+        .arg("--key")
+        .arg("API_KEY")
+        .arg("--value-env")
+        .arg("CLI_API_KEY")
+        .arg("--secret")
+        .arg("true")
+        .output()
+        .unwrap();
+    assert!(!locked.status.success());
+    assert!(
+        String::from_utf8(locked.stderr)
+            .unwrap()
+            .contains("secrets are locked")
+    );
+
+    let mut secret = Command::new(nexumctl)
+        .env("NEXUM_SECRETS_PASSPHRASE", "hunter2")
+        .arg("capsule")
+        .arg("env")
+        .arg("set")
+        .arg("--db")
+        .arg(&db)
+        .arg("--id")
+        .arg("cap-env-cli")
+        .arg("--key")
+        .arg("API_KEY")
+        .arg("--value-stdin")
+        .arg("true")
+        .arg("--secret")
+        .arg("true")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    secret
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"sk-cli-789\n")
+        .unwrap();
+    assert!(secret.wait_with_output().unwrap().status.success());
+
+    let listed = Command::new(nexumctl)
+        .arg("capsule")
+        .arg("list")
+        .arg("--db")
+        .arg(&db)
+        .output()
+        .unwrap();
+    assert!(listed.status.success());
+    assert!(!String::from_utf8_lossy(&listed.stdout).contains("sk-cli-789"));
+    let json: Value = serde_json::from_slice(&listed.stdout).unwrap();
+    assert_eq!(json[0]["env"][0]["key"], "API_KEY");
+    assert_eq!(json[0]["env"][0]["value"], "********");
+
+    let revealed = Command::new(nexumctl)
+        .env("NEXUM_SECRETS_PASSPHRASE", "hunter2")
+        .arg("capsule")
+        .arg("env")
+        .arg("reveal")
+        .arg("--db")
+        .arg(&db)
+        .arg("--id")
+        .arg("cap-env-cli")
+        .arg("--key")
+        .arg("API_KEY")
+        .output()
+        .unwrap();
+    assert!(revealed.status.success());
+    assert_eq!(String::from_utf8(revealed.stdout).unwrap(), "sk-cli-789");
+}
@@ -0,0 +1,120 @@
+use std::process::Command;
+
+use serde_json::Value;
+use tempfile::tempdir;
+
+#[test]
+fn nexumctl_capsule_fork_and_unfork_round_trip() {
+    let dir = tempdir().unwrap();
+    let db = dir.path().join("capsules.sqlite3");
+    let repo = dir.path().join("shop");
+    let nexumctl = assert_cmd::cargo::cargo_bin!("nexumctl");
+
+    std::fs::create_dir_all(&repo).unwrap();
+    for args in [
+        vec!["init", "--quiet"],
+        vec![
+            "-c",
+            "user.name=nexum",
+            "-c",
+            "user.email=nexum@example.invalid",
+            "commit",
+            "--quiet",
+            "--allow-empty",
+            "-m",
+            "init",
+        ],
+    ] {
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(&repo)
+            .args(args)
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+
+    let create = Command::new(nexumctl)
+        .arg("capsule")
+        .arg("create")
+        .arg("--db")
+        .arg(&db)
+        .arg("--id")
+        .arg("cap-shop")
+        .arg("--name")
+        .arg("Shop")
+        .arg("--mode")
+        .arg("host_default")
+        .arg("--repo-path")
+        .arg(&repo)
+        .output()
+        .unwrap();
+    assert!(create.status.success());
+
+    let pool = Command::new(nexumctl)
+        .arg("capsule")
+        .arg("ports")
+        .arg("set-pool")
+        .arg("--db")
+        .arg(&db)
+        .arg("--start")
+        .arg("6700")
+        .arg("--end")
+        .arg("6710")
+        .output()
+        .unwrap();
+    assert!(pool.status.success());
+
+    let fork = Command::new(nexumctl)
+        .arg("capsule")
+        .arg("fork")
+        .arg("--db")
+        .arg(&db)
+        .arg("--from")
+        .arg("cap-shop")
+        .arg("--branch")
+        .arg("checkout-v2")
+        .output()
+        .unwrap();
+    assert!(fork.status.success());
+    let fork_json: Value = serde_json::from_slice(&fork.stdout).unwrap();
+    assert_eq!(fork_json["capsule_id"], "cap-shop-checkout-v2");
+    assert_eq!(fork_json["domain"], "shop-checkout-v2.nexum.local");
+    assert_eq!(fork_json["workspace"], 2);
+    assert!(fork_json["service_ports"]["default"].is_u64());
+    let worktree = dir.path().join("shop-checkout-v2");
+    assert!(worktree.is_dir());
+
+    let unfork = Command::new(nexumctl)
+        .arg("capsule")
+        .arg("unfork")
+        .arg("--db")
+        .arg(&db)
+        .arg("--id")
+        .arg("cap-shop-checkout-v2")
+        .arg("--socket")
+        .arg(dir.path().join("missing.sock"))
+        .output()
+        .unwrap();
+    assert!(unfork.status.success());
+    let unfork_json: Value = serde_json::from_slice(&unfork.stdout).unwrap();
+    assert_eq!(unfork_json["removed_worktree"], Value::Bool(true));
+    assert_eq!(unfork_json["cleanup"]["action"], "delete");
+    assert!(!worktree.exists());
+
+    let not_fork = Command::new(nexumctl)
+        .arg("capsule")
+        .arg("unfork")
+        .arg("--db")
+        .arg(&db)
+        .arg("--id")
+        .arg("cap-shop")
+        .output()
+        .unwrap();
+    assert!(!not_fork.status.success());
+    assert!(
+        String::from_utf8(not_fork.stderr)
+            .unwrap()
+            .contains("capsule 'cap-shop' is not a fork")
+    );
+}
@@ -0,0 +1,194 @@
+use std::{path::Path, process::Command};
+
+use nexum::{
+    capsule::{Capsule, CapsuleMode},
+    fork::{ForkError, ForkInput, UnforkInput, fork_capsule, unfork_capsule},
+    store::CapsuleStore,
+};
+use tempfile::tempdir;
+
+fn git(repo: &Path, args: &[&str]) {
+    let status = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args([
+            "-c",
+            "user.name=nexum",
+            "-c",
+            "user.email=nexum@example.invalid",
+        ])
+        .args(args)
+        .status()
+        .unwrap();
+    assert!(status.success(), "git {args:?} failed");
+}
+
+fn seed(dir: &Path) -> std::path::PathBuf {
+    let repo = dir.join("billing");
+    std::fs::create_dir_all(&repo).unwrap();
+    git(&repo, &["init", "--quiet"]);
+    git(&repo, &["commit", "--quiet", "--allow-empty", "-m", "init"]);
+
+    let mut store = CapsuleStore::open(&dir.join("capsules.sqlite3")).unwrap();
+    store
+        .upsert(
+            Capsule::new("cap-billing", "Billing API", CapsuleMode::HostDefault, 1)
+                .with_repo_path(&repo.display().to_string()),
+        )
+        .unwrap();
+    store.set_port_pool("default", 6600, 6620).unwrap();
+    store
+        .allocate_pool_port("cap-billing", "web", "default", 4)
+        .unwrap();
+    store
+        .allocate_pool_port("cap-billing", "api", "default", 4)
+        .unwrap();
+    repo
+}
+
+fn fork_input(dir: &Path, branch: &str) -> ForkInput {
+    ForkInput {
+        capsule_db: dir.join("capsules.sqlite3"),
+        source_id: "cap-billing".into(),
+        branch: branch.into(),
+        pool: "default".into(),
+    }
+}
+
+#[test]
+fn fork_creates_worktree_capsule_with_fresh_ports_and_workspace() {
+    let dir = tempdir().unwrap();
+    let repo = seed(dir.path());
+
+    let report = fork_capsule(&fork_input(dir.path(), "feature/retry")).unwrap();
+    assert_eq!(report.capsule_id, "cap-billing-feature-retry");
+    assert_eq!(report.slug, "billing-api-feature-retry");
+    assert_eq!(report.domain, "billing-api-feature-retry.nexum.local");
+    assert_eq!(report.workspace, 2);
+    assert!(report.created_branch);
+    assert_eq!(
+        report.repo_path,
+        dir.path()
+            .join("billing-feature-retry")
+            .display()
+            .to_string()
+    );
+    assert!(Path::new(&report.repo_path).join(".git").exists());
+    assert_eq!(
+        report.service_ports.keys().collect::<Vec<_>>(),
+        vec!["api", "web"]
+    );
+    assert!(report.service_ports.values().all(|port| *port > 6601));
+
+    let store = CapsuleStore::open(&dir.path().join("capsules.sqlite3")).unwrap();
+    let fork = store.fork_of(&report.capsule_id).unwrap().unwrap();
+    assert_eq!(fork.source_id, "cap-billing");
+    assert_eq!(fork.branch, "feature/retry");
+    assert_eq!(fork.source_repo_path, repo.display().to_string());
+
+    assert!(matches!(
+        fork_capsule(&fork_input(dir.path(), "feature/retry")),
+        Err(ForkError::CapsuleExists(_))
+    ));
+}
+
+#[test]
+fn unfork_removes_worktree_and_capsule_but_keeps_branch() {
+    let dir = tempdir().unwrap();
+    let repo = seed(dir.path());
+    let report = fork_capsule(&fork_input(dir.path(), "spike")).unwrap();
+
+    assert!(matches!(
+        unfork_capsule(&UnforkInput {
+            capsule_db: dir.path().join("capsules.sqlite3"),
+            capsule_id: "cap-billing".into(),
+            routing_socket: None,
+            tls_dir: None,
+            events_db: None,
+            force: false,
+            dry_run: false,
+        }),
+        Err(ForkError::NotAFork(_))
+    ));
+
+    let unforked = unfork_capsule(&UnforkInput {
+        capsule_db: dir.path().join("capsules.sqlite3"),
+        capsule_id: report.capsule_id.clone(),
+        routing_socket: None,
+        tls_dir: None,
+        events_db: None,
+        force: false,
+        dry_run: false,
+    })
+    .unwrap();
+    assert!(unforked.removed_worktree);
+    assert_eq!(unforked.cleanup.released_ports.len(), 2);
+    assert!(!Path::new(&report.repo_path).exists());
+
+    let store = CapsuleStore::open(&dir.path().join("capsules.sqlite3")).unwrap();
+    assert!(store.get(&report.capsule_id).unwrap().is_none());
+    assert!(store.fork_of(&report.capsule_id).unwrap().is_none());
+    git(
+        &repo,
+        &["rev-parse", "--verify", "--quiet", "refs/heads/spike"],
+    );
+}
+
+#[test]
+fn failed_fork_removes_the_worktree_and_the_branch_it_created() {
+    let dir = tempdir().unwrap();
+    let repo = seed(dir.path());
+    let mut store = CapsuleStore::open(&dir.path().join("capsules.sqlite3")).unwrap();
+    for port in 6602..=6620 {
+        store.reserve_port(port, "busy").unwrap();
+    }
+
+    assert!(matches!(
+        fork_capsule(&fork_input(dir.path(), "doomed")),
+        Err(ForkError::PortsExhausted(_))
+    ));
+    assert!(!dir.path().join("billing-doomed").exists());
+    assert!(store.get("cap-billing-doomed").unwrap().is_none());
+    let branch = Command::new("git")
+        .arg("-C")
+        .arg(&repo)
+        .args(["rev-parse", "--verify", "--quiet", "refs/heads/doomed"])
+        .status()
+        .unwrap();
+    assert!(!branch.success());
+
+    git(&repo, &["branch", "kept"]);
+    assert!(fork_capsule(&fork_input(dir.path(), "kept")).is_err());
+    git(
+        &repo,
+        &["rev-parse", "--verify", "--quiet", "refs/heads/kept"],
+    );
+}
+
+#[test]
+fn unfork_refuses_a_dirty_worktree_before_deleting_the_capsule() {
+    let dir = tempdir().unwrap();
+    seed(dir.path());
+    let report = fork_capsule(&fork_input(dir.path(), "wip")).unwrap();
+    std::fs::write(Path::new(&report.repo_path).join("notes.txt"), "draft").unwrap();
+    let unfork = |force| {
+        unfork_capsule(&UnforkInput {
+            capsule_db: dir.path().join("capsules.sqlite3"),
+            capsule_id: report.capsule_id.clone(),
+            routing_socket: None,
+            tls_dir: None,
+            events_db: None,
+            force,
+            dry_run: false,
+        })
+    };
+
+    assert!(matches!(unfork(false), Err(ForkError::DirtyWorktree(_))));
+    let store = CapsuleStore::open(&dir.path().join("capsules.sqlite3")).unwrap();
+    assert!(store.get(&report.capsule_id).unwrap().is_some());
+    assert!(Path::new(&report.repo_path).join("notes.txt").exists());
+
+    assert!(unfork(true).unwrap().removed_worktree);
+    assert!(store.get(&report.capsule_id).unwrap().is_none());
+    assert!(!Path::new(&report.repo_path).exists());
+}
@@ -0,0 +1,147 @@
+use std::process::Command;
+
+use serde_json::Value;
+use tempfile::tempdir;
+
+fn run(args: &[&str]) -> std::process::Output {
+    Command::new(assert_cmd::cargo::cargo_bin!("nexumctl"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn capsule_ids(stdout: &[u8]) -> Vec<String> {
+    let payload: Value = serde_json::from_slice(stdout).unwrap();
+    payload
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|capsule| capsule["capsule_id"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[test]
+fn nexumctl_capsule_labels_drive_selectors() {
+    let dir = tempdir().unwrap();
+    let capsule_db = dir.path().join("capsules.sqlite3");
+    let db = capsule_db.to_str().unwrap();
+    let events_db = dir.path().join("events.sqlite3");
+    let tls_dir = dir.path().join("tls");
+
+    for (id, workspace) in [("cap-infra", "1"), ("cap-old", "2"), ("cap-pay", "3")] {
+        let created = run(&[
+            "capsule",
+            "create",
+            "--db",
+            db,
+            "--id",
+            id,
+            "--name",
+            id,
+            "--workspace",
+            workspace,
+            "--mode",
+            "host_default",
+        ]);
+        assert!(created.status.success());
+    }
+    for (id, set) in [
+        ("cap-infra", "team=infra,tier=web"),
+        ("cap-old", "team=infra"),
+        ("cap-pay", "team=payments"),
+    ] {
+        let labeled = run(&["capsule", "label", "--db", db, "--id", id, "--set", set]);
+        assert!(labeled.status.success());
+    }
+    let removed = run(&[
+        "capsule",
+        "label",
+        "--db",
+        db,
+        "--id",
+        "cap-infra",
+        "--remove",
+        "tier",
+    ]);
+    assert!(removed.status.success());
+    let removed: Value = serde_json::from_slice(&removed.stdout).unwrap();
+    assert_eq!(removed["labels"], serde_json::json!({"team": "infra"}));
+
+    let archived = run(&[
+        "capsule",
+        "set-state",
+        "--db",
+        db,
+        "--id",
+        "cap-old",
+        "--state",
+        "archived",
+    ]);
+    assert!(archived.status.success());
+
+    let listed = run(&[
+        "capsule",
+        "list",
+        "--db",
+        db,
+        "--selector",
+        "team=infra,!archived",
+    ]);
+    assert!(listed.status.success());
+    assert_eq!(capsule_ids(&listed.stdout), vec!["cap-infra"]);
+    let listed: Value = serde_json::from_slice(&listed.stdout).unwrap();
+    assert_eq!(listed[0]["labels"]["team"], "infra");
+
+    let status = run(&[
+        "supervisor",
+        "status",
+        "--capsule-db",
+        db,
+        "--events-db",
+        events_db.to_str().unwrap(),
+        "--flags-file",
+        dir.path().join("flags.toml").to_str().unwrap(),
+        "--selector",
+        "team=infra",
+    ]);
+    assert!(status.status.success());
+    let status: Value = serde_json::from_slice(&status.stdout).unwrap();
+    assert_eq!(status["total_capsules"], 2);
+    assert_eq!(status["archived_capsules"], 1);
+
+    let events = r#"[{"capsule_id":"cap-infra","signal":"needs_decision","upstream":"127.0.0.1:5400"},{"capsule_id":"cap-pay","signal":"critical_failure","upstream":"127.0.0.1:5401"}]"#;
+    let batch = run(&[
+        "stead",
+        "dispatch-batch",
+        "--capsule-db",
+        db,
+        "--events-json",
+        events,
+        "--tls-dir",
+        tls_dir.to_str().unwrap(),
+        "--events-db",
+        events_db.to_str().unwrap(),
+        "--dry-run",
+        "true",
+        "--selector",
+        "team=infra",
+    ]);
+    assert!(batch.status.success());
+    let batch: Value = serde_json::from_slice(&batch.stdout).unwrap();
+    assert_eq!(batch["processed"], 1);
+    assert_eq!(batch["results"][0]["capsule_id"], "cap-infra");
+    assert_eq!(batch["skipped"], serde_json::json!(["cap-pay"]));
+    assert_eq!(batch["attention_plan"]["blocking"], 0);
+
+    let invalid = run(&["capsule", "list", "--db", db, "--selector", "Team=infra"]);
+    assert!(!invalid.status.success());
+    assert!(
+        String::from_utf8(invalid.stderr)
+            .unwrap()
+            .contains("invalid selector term 'Team=infra'")
+    );
+    let reserved = run(&[
+        "capsule", "label", "--db", db, "--id", "cap-pay", "--set", "state=x",
+    ]);
+    assert!(!reserved.status.success());
+}
@@ -144,3 +144,555 @@ fn nexumctl_reports_workspace_parse_error_with_flag_context() {
     let stderr = String::from_utf8(created.stderr).unwrap();
     assert!(stderr.contains("failed to parse --workspace as u16"));
 }
+
+#[test]
+fn nexumctl_rejects_illegal_transition_and_prints_history() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = dir.path().join("capsules.sqlite3");
+    let nexumctl = assert_cmd::cargo::cargo_bin!("nexumctl");
+
+    let created = Command::new(nexumctl)
+        .arg("capsule")
+        .arg("create")
+        .arg("--db")
+        .arg(&db)
+        .arg("--id")
+        .arg("cap-cli-history")
+        .arg("--name")
+        .arg("History Capsule")
+        .arg("--workspace")
+        .arg("6")
+        .arg("--mode")
+        .arg("host_default")
+        .output()
+        .unwrap();
+    assert!(created.status.success());
+
+    let archived = Command::new(nexumctl)
+        .arg("capsule")
+        .arg("set-state")
+        .arg("--db")
+        .arg(&db)
+        .arg("--id")
+        .arg("cap-cli-history")
+        .arg("--state")
+        .arg("archived")
+        .arg("--reason")
+        .arg("project finished")
+        .output()
+        .unwrap();
+    assert!(archived.status.success());
+
+    let restoring = Command::new(nexumctl)
+        .arg("capsule")
+        .arg("set-state")
+        .arg("--db")
+        .arg(&db)
+        .arg("--id")
+        .arg("cap-cli-history")
+        .arg("--state")
+        .arg("restoring")
+        .output()
+        .unwrap();
+    assert!(!restoring.status.success());
+    let stderr = String::from_utf8(restoring.stderr).unwrap();
+    assert!(stderr.contains("illegal capsule state transition: archived -> restoring"));
+
+    let history = Command::new(nexumctl)
+        .arg("capsule")
+        .arg("history")
+        .arg("--db")
+        .arg(&db)
+        .arg("--id")
+        .arg("cap-cli-history")
+        .output()
+        .unwrap();
+    assert!(history.status.success());
+    let payload: serde_json::Value = serde_json::from_slice(&history.stdout).unwrap();
+    let entries = payload.as_array().unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0]["from_state"], "ready");
+    assert_eq!(entries[0]["to_state"], "archived");
+    assert_eq!(entries[0]["component"], "nexumctl");
+    assert_eq!(entries[0]["reason"], "project finished");
+}
+
+#[test]
+fn nexumctl_unarchive_moves_only_archived_capsules_back_to_ready() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = dir.path().join("capsules.sqlite3");
+    let nexumctl = assert_cmd::cargo::cargo_bin!("nexumctl");
+    let run = |args: &[&str]| {
+        Command::new(nexumctl)
+            .args(args)
+            .arg("--db")
+            .arg(&db)
+            .output()
+            .unwrap()
+    };
+
+    let created = run(&[
+        "capsule",
+        "create",
+        "--id",
+        "cap-cli-unarchive",
+        "--name",
+        "Unarchive Capsule",
+        "--workspace",
+        "7",
+        "--mode",
+        "host_default",
+    ]);
+    assert!(created.status.success());
+
+    let not_archived = run(&["capsule", "unarchive", "--id", "cap-cli-unarchive"]);
+    assert!(!not_archived.status.success());
+    assert!(
+        String::from_utf8(not_archived.stderr)
+            .unwrap()
+            .contains("capsule is not archived: cap-cli-unarchive")
+    );
+
+    let archived = run(&[
+        "capsule",
+        "set-state",
+        "--id",
+        "cap-cli-unarchive",
+        "--state",
+        "archived",
+    ]);
+    assert!(archived.status.success());
+
+    let unarchived = run(&["capsule", "unarchive", "--id", "cap-cli-unarchive"]);
+    assert!(unarchived.status.success());
+    assert_eq!(
+        String::from_utf8(unarchived.stdout).unwrap().trim(),
+        "unarchived cap-cli-unarchive"
+    );
+
+    let history = run(&["capsule", "history", "--id", "cap-cli-unarchive"]);
+    let payload: serde_json::Value = serde_json::from_slice(&history.stdout).unwrap();
+    let last = payload.as_array().unwrap().last().unwrap().clone();
+    assert_eq!(last["from_state"], "archived");
+    assert_eq!(last["to_state"], "ready");
+    assert_eq!(last["reason"], "unarchived");
+}
+
+#[test]
+fn nexumctl_imports_exported_capsules_with_plan_then_apply() {
+    let dir = tempfile::tempdir().unwrap();
+    let source = dir.path().join("source.sqlite3");
+    let target = dir.path().join("target.sqlite3");
+    let file = dir.path().join("capsules.yaml");
+    let nexumctl = assert_cmd::cargo::cargo_bin!("nexumctl");
+
+    let created = Command::new(nexumctl)
+        .arg("capsule")
+        .arg("create")
+        .arg("--db")
+        .arg(&source)
+        .arg("--id")
+        .arg("cap-cli-import")
+        .arg("--name")
+        .arg("Import Capsule")
+        .arg("--workspace")
+        .arg("7")
+        .arg("--mode")
+        .arg("host_default")
+        .output()
+        .unwrap();
+    assert!(created.status.success());
+
+    let exported = Command::new(nexumctl)
+        .arg("capsule")
+        .arg("export")
+        .arg("--db")
+        .arg(&source)
+        .arg("--format")
+        .arg("yaml")
+        .output()
+        .unwrap();
+    assert!(exported.status.success());
+    std::fs::write(&file, &exported.stdout).unwrap();
+
+    let import = |apply: &str| {
+        Command::new(nexumctl)
+            .arg("capsule")
+            .arg("import")
+            .arg("--db")
+            .arg(&target)
+            .arg("--file")
+            .arg(&file)
+            .arg("--apply")
+            .arg(apply)
+            .output()
+            .unwrap()
+    };
+
+    let planned = import("false");
+    assert!(planned.status.success());
+    let planned_json: serde_json::Value = serde_json::from_slice(&planned.stdout).unwrap();
+    assert_eq!(planned_json["applied"], false);
+    assert_eq!(
+        planned_json["creates"],
+        serde_json::json!(["cap-cli-import"])
+    );
+
+    let applied = import("true");
+    assert!(applied.status.success());
+    let applied_json: serde_json::Value = serde_json::from_slice(&applied.stdout).unwrap();
+    assert_eq!(applied_json["applied"], true);
+
+    let listed = Command::new(nexumctl)
+        .arg("capsule")
+        .arg("list")
+        .arg("--db")
+        .arg(&target)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(listed.stdout).unwrap();
+    assert!(stdout.contains("Import Capsule"));
+
+    let yaml = std::fs::read_to_string(&file)
+        .unwrap()
+        .replace("slug: import-capsule", "slug: other-slug");
+    std::fs::write(&file, yaml).unwrap();
+    let conflicted = import("true");
+    assert!(!conflicted.status.success());
+    let conflicted_json: serde_json::Value = serde_json::from_slice(&conflicted.stdout).unwrap();
+    assert_eq!(conflicted_json["applied"], false);
+    assert_eq!(conflicted_json["conflicts"][0]["code"], "immutable_slug");
+}
+
+#[test]
+fn nexumctl_create_assigns_free_workspace_and_rejects_collisions() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = dir.path().join("capsules.sqlite3");
+    let nexumctl = assert_cmd::cargo::cargo_bin!("nexumctl");
+
+    let first = Command::new(nexumctl)
+        .arg("capsule")
+        .arg("create")
+        .arg("--db")
+        .arg(&db)
+        .arg("--id")
+        .arg("cap-ws-a")
+        .arg("--name")
+        .arg("Workspace A")
+        .arg("--mode")
+        .arg("host_default")
+        .arg("--json")
+        .arg("true")
+        .output()
+        .unwrap();
+    assert!(first.status.success());
+    let first_json: serde_json::Value = serde_json::from_slice(&first.stdout).unwrap();
+    assert_eq!(first_json["capsule_id"], "cap-ws-a");
+    assert_eq!(first_json["workspace"], 1);
+
+    let colliding = Command::new(nexumctl)
+        .arg("capsule")
+        .arg("create")
+        .arg("--db")
+        .arg(&db)
+        .arg("--id")
+        .arg("cap-ws-b")
+        .arg("--name")
+        .arg("Workspace B")
+        .arg("--workspace")
+        .arg("1")
+        .arg("--mode")
+        .arg("host_default")
+        .output()
+        .unwrap();
+    assert!(!colliding.status.success());
+    assert!(
+        String::from_utf8(colliding.stderr)
+            .unwrap()
+            .contains("workspace 1 is already used by cap-ws-a")
+    );
+
+    let shared = Command::new(nexumctl)
+        .arg("capsule")
+        .arg("share-workspace")
+        .arg("--db")
+        .arg(&db)
+        .arg("--workspace")
+        .arg("1")
+        .arg("--shared")
+        .arg("true")
+        .output()
+        .unwrap();
+    assert!(shared.status.success());
+
+    let sharing = Command::new(nexumctl)
+        .arg("capsule")
+        .arg("create")
+        .arg("--db")
+        .arg(&db)
+        .arg("--id")
+        .arg("cap-ws-b")
+        .arg("--name")
+        .arg("Workspace B")
+        .arg("--workspace")
+        .arg("1")
+        .arg("--mode")
+        .arg("host_default")
+        .output()
+        .unwrap();
+    assert!(sharing.status.success());
+
+    let workspaces = Command::new(nexumctl)
+        .arg("capsule")
+        .arg("workspaces")
+        .arg("--db")
+        .arg(&db)
+        .output()
+        .unwrap();
+    assert!(workspaces.status.success());
+    let json: serde_json::Value = serde_json::from_slice(&workspaces.stdout).unwrap();
+    assert_eq!(json["next_free"], 2);
+    assert_eq!(json["shared"], serde_json::json!([1]));
+    assert_eq!(json["collisions"], serde_json::json!([]));
+}
+
+#[test]
+fn nexumctl_create_again_without_workspace_keeps_the_existing_one() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = dir.path().join("capsules.sqlite3");
+    let nexumctl = assert_cmd::cargo::cargo_bin!("nexumctl");
+    let create = |id: &str, name: &str| {
+        let output = Command::new(nexumctl)
+            .arg("capsule")
+            .arg("create")
+            .arg("--db")
+            .arg(&db)
+            .arg("--id")
+            .arg(id)
+            .arg("--name")
+            .arg(name)
+            .arg("--mode")
+            .arg("host_default")
+            .arg("--json")
+            .arg("true")
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        serde_json::from_slice::<serde_json::Value>(&output.stdout).unwrap()["workspace"].clone()
+    };
+
+    assert_eq!(create("cap-keep-a", "Keep A"), 1);
+    assert_eq!(create("cap-keep-b", "Keep B"), 2);
+    assert_eq!(create("cap-keep-a", "Keep A"), 1);
+}
+
+#[test]
+fn nexumctl_capsule_which_resolves_working_directory() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = dir.path().join("capsules.sqlite3");
+    let repo = dir.path().join("orders");
+    std::fs::create_dir_all(repo.join("web")).unwrap();
+    let nexumctl = assert_cmd::cargo::cargo_bin!("nexumctl");
+
+    let created = Command::new(nexumctl)
+        .arg("capsule")
+        .arg("create")
+        .arg("--db")
+        .arg(&db)
+        .arg("--id")
+        .arg("cap-which")
+        .arg("--name")
+        .arg("Orders")
+        .arg("--mode")
+        .arg("host_default")
+        .arg("--repo-path")
+        .arg(&repo)
+        .output()
+        .unwrap();
+    assert!(created.status.success());
+
+    let from_cwd = Command::new(nexumctl)
+        .current_dir(repo.join("web"))
+        .arg("capsule")
+        .arg("which")
+        .arg("--db")
+        .arg(&db)
+        .output()
+        .unwrap();
+    assert!(from_cwd.status.success());
+    let json: serde_json::Value = serde_json::from_slice(&from_cwd.stdout).unwrap();
+    assert_eq!(json["capsule_id"], "cap-which");
+    assert_eq!(json["domain"], "orders.nexum.local");
+
+    let unmatched = Command::new(nexumctl)
+        .arg("capsule")
+        .arg("which")
+        .arg("--db")
+        .arg(&db)
+        .arg("--path")
+        .arg(dir.path())
+        .output()
+        .unwrap();
+    assert_eq!(unmatched.status.code(), Some(1));
+    assert!(unmatched.stdout.is_empty());
+}
+
+#[test]
+fn nexumctl_capsule_list_sorts_by_recent_restore() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = dir.path().join("capsules.sqlite3");
+    let nexumctl = assert_cmd::cargo::cargo_bin!("nexumctl");
+
+    for (id, workspace) in [("cap-recent-a", "1"), ("cap-recent-b", "2")] {
+        let created = Command::new(nexumctl)
+            .arg("capsule")
+            .arg("create")
+            .arg("--db")
+            .arg(&db)
+            .arg("--id")
+            .arg(id)
+            .arg("--name")
+            .arg(id)
+            .arg("--mode")
+            .arg("host_default")
+            .arg("--workspace")
+            .arg(workspace)
+            .output()
+            .unwrap();
+        assert!(created.status.success());
+    }
+
+    let restored = Command::new(nexumctl)
+        .arg("run")
+        .arg("restore-capsule")
+        .arg("--capsule-db")
+        .arg(&db)
+        .arg("--capsule-id")
+        .arg("cap-recent-b")
+        .arg("--signal")
+        .arg("needs_decision")
+        .arg("--upstream")
+        .arg("127.0.0.1:4730")
+        .arg("--terminal")
+        .arg("cd /workspace/recent")
+        .arg("--editor")
+        .arg("/workspace/recent")
+        .arg("--tls-dir")
+        .arg(dir.path().join("tls"))
+        .arg("--events-db")
+        .arg(dir.path().join("events.sqlite3"))
+        .output()
+        .unwrap();
+    assert!(restored.status.success());
+
+    let list = |sort: &str| {
+        Command::new(nexumctl)
+            .arg("capsule")
+            .arg("list")
+            .arg("--db")
+            .arg(&db)
+            .arg("--sort")
+            .arg(sort)
+            .output()
+            .unwrap()
+    };
+    let recent = list("recent");
+    assert!(recent.status.success());
+    let json: serde_json::Value = serde_json::from_slice(&recent.stdout).unwrap();
+    assert_eq!(json[0]["capsule_id"], "cap-recent-b");
+    assert_eq!(json[0]["restore_count"], 1);
+    assert_eq!(json[0]["last_signal"], "needs_decision");
+    assert!(json[0]["last_restored_unix_ms"].as_u64().unwrap() > 0);
+    assert_eq!(json[1]["capsule_id"], "cap-recent-a");
+    assert_eq!(json[1]["restore_count"], 0);
+    assert_eq!(json[1]["last_restored_unix_ms"], serde_json::Value::Null);
+
+    let by_id = list("id");
+    let json: serde_json::Value = serde_json::from_slice(&by_id.stdout).unwrap();
+    assert_eq!(json[0]["capsule_id"], "cap-recent-a");
+
+    let invalid = list("oldest");
+    assert!(!invalid.status.success());
+    assert!(
+        String::from_utf8(invalid.stderr)
+            .unwrap()
+            .contains("invalid sort: oldest")
+    );
+}
+
+#[test]
+fn nexumctl_capsule_create_generates_ids_and_onboards_repos() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = dir.path().join("capsules.sqlite3");
+    let repo = dir.path().join("inventory");
+    std::fs::create_dir_all(&repo).unwrap();
+    let nexumctl = assert_cmd::cargo::cargo_bin!("nexumctl");
+
+    let generated = Command::new(nexumctl)
+        .arg("capsule")
+        .arg("create")
+        .arg("--db")
+        .arg(&db)
+        .arg("--name")
+        .arg("Generated")
+        .arg("--mode")
+        .arg("host_default")
+        .output()
+        .unwrap();
+    assert!(generated.status.success());
+    let stdout = String::from_utf8(generated.stdout).unwrap();
+    assert!(stdout.starts_with("created cap-"));
+    assert_eq!(stdout.split_whitespace().count(), 2);
+
+    let invalid = Command::new(nexumctl)
+        .arg("capsule")
+        .arg("create")
+        .arg("--db")
+        .arg(&db)
+        .arg("--id")
+        .arg("Bad Id")
+        .arg("--name")
+        .arg("Bad")
+        .arg("--mode")
+        .arg("host_default")
+        .output()
+        .unwrap();
+    assert!(!invalid.status.success());
+    assert!(
+        String::from_utf8(invalid.stderr)
+            .unwrap()
+            .contains("invalid capsule id 'Bad Id'")
+    );
+
+    let pool = Command::new(nexumctl)
+        .arg("capsule")
+        .arg("ports")
+        .arg("set-pool")
+        .arg("--db")
+        .arg(&db)
+        .arg("--name")
+        .arg("default")
+        .arg("--start")
+        .arg("6750")
+        .arg("--end")
+        .arg("6760")
+        .output()
+        .unwrap();
+    assert!(pool.status.success());
+
+    let onboarded = Command::new(nexumctl)
+        .arg("capsule")
+        .arg("create")
+        .arg("--db")
+        .arg(&db)
+        .arg("--from-repo")
+        .arg(&repo)
+        .output()
+        .unwrap();
+    assert!(onboarded.status.success());
+    let json: serde_json::Value = serde_json::from_slice(&onboarded.stdout).unwrap();
+    assert_eq!(json["display_name"], "inventory");
+    assert_eq!(json["name_source"], "directory");
+    assert_eq!(json["workspace"], 2);
+    assert_eq!(json["service_ports"]["default"], 6750);
+}
@@ -0,0 +1,195 @@
+use std::{path::Path, process::Command};
+
+use nexum::{
+    capsule::{Capsule, CapsuleMode, GENERATED_ID_PREFIX},
+    onboard::{FromRepoInput, NameSource, OnboardError, create_from_repo, repo_name_from_remote},
+    ports::DEFAULT_PORT_RANGE,
+    store::{CapsuleStore, StoreError},
+};
+use tempfile::tempdir;
+
+fn git(repo: &Path, args: &[&str]) {
+    let status = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(args)
+        .status()
+        .unwrap();
+    assert!(status.success(), "git {args:?} failed");
+}
+
+fn input(dir: &Path, repo: &Path) -> FromRepoInput {
+    FromRepoInput {
+        capsule_db: dir.join("capsules.sqlite3"),
+        repo_path: repo.to_path_buf(),
+        capsule_id: None,
+        display_name: None,
+        mode: None,
+        workspace: None,
+        pool: "default".into(),
+    }
+}
+
+fn seed_pool(dir: &Path, start: u16, end: u16) {
+    let mut store = CapsuleStore::open(&dir.join("capsules.sqlite3")).unwrap();
+    store.set_port_pool("default", start, end).unwrap();
+}
+
+#[test]
+fn repo_names_are_taken_from_common_remote_url_shapes() {
+    for url in [
+        "git@github.com:acme/billing-api.git",
+        "https://github.com/acme/billing-api.git",
+        "https://github.com/acme/billing-api/",
+        "ssh://git@host:2222/acme/billing-api",
+        "/srv/git/billing-api.git",
+    ] {
+        assert_eq!(
+            repo_name_from_remote(url).as_deref(),
+            Some("billing-api"),
+            "{url}"
+        );
+    }
+    assert_eq!(repo_name_from_remote(".git"), None);
+}
+
+#[test]
+fn create_from_repo_derives_name_from_git_remote_and_allocates_a_port() {
+    let dir = tempdir().unwrap();
+    let repo = dir.path().join("checkout");
+    std::fs::create_dir_all(&repo).unwrap();
+    git(&repo, &["init", "--quiet"]);
+    git(
+        &repo,
+        &[
+            "remote",
+            "add",
+            "origin",
+            "git@github.com:acme/orders-web.git",
+        ],
+    );
+    seed_pool(dir.path(), 6700, 6710);
+
+    let report = create_from_repo(&input(dir.path(), &repo)).unwrap();
+    assert!(report.capsule_id.starts_with(GENERATED_ID_PREFIX));
+    assert_eq!(report.display_name, "orders-web");
+    assert_eq!(report.name_source, NameSource::GitRemote);
+    assert_eq!(report.domain, "orders-web.nexum.local");
+    assert_eq!(report.mode, CapsuleMode::HostDefault);
+    assert_eq!(report.workspace, 1);
+    assert_eq!(report.service_ports.get("default"), Some(&6700));
+
+    let store = CapsuleStore::open(&dir.path().join("capsules.sqlite3")).unwrap();
+    let stored = store.get(&report.capsule_id).unwrap().unwrap();
+    assert_eq!(
+        stored.repo_path,
+        repo.canonicalize().unwrap().display().to_string()
+    );
+
+    let again = create_from_repo(&input(dir.path(), &repo)).unwrap_err();
+    assert!(matches!(again, OnboardError::RepoRegistered { .. }));
+}
+
+#[test]
+fn create_from_repo_uses_the_default_range_before_any_pool_is_configured() {
+    let dir = tempdir().unwrap();
+    let repo = dir.path().join("fresh");
+    std::fs::create_dir_all(&repo).unwrap();
+
+    let report = create_from_repo(&input(dir.path(), &repo)).unwrap();
+    let port = report.service_ports["default"];
+    assert!((DEFAULT_PORT_RANGE.0..=DEFAULT_PORT_RANGE.1).contains(&port));
+
+    let missing_pool = create_from_repo(&FromRepoInput {
+        pool: "staging".into(),
+        ..input(dir.path(), &dir.path().join("other"))
+    });
+    assert!(missing_pool.is_err());
+}
+
+#[test]
+fn create_from_repo_prefers_manifest_and_records_fixed_ports() {
+    let dir = tempdir().unwrap();
+    let repo = dir.path().join("search");
+    std::fs::create_dir_all(&repo).unwrap();
+    std::fs::write(
+        repo.join("nexum.toml"),
+        r#"
+display_name = "Search Core"
+mode = "isolated_nix_shell"
+workspace = 7
+
+[services.web]
+[services.api]
+[services.db]
+port = 5432
+"#,
+    )
+    .unwrap();
+    seed_pool(dir.path(), 6720, 6730);
+
+    let report = create_from_repo(&FromRepoInput {
+        capsule_id: Some("cap-search".into()),
+        ..input(dir.path(), &repo)
+    })
+    .unwrap();
+    assert_eq!(report.capsule_id, "cap-search");
+    assert_eq!(report.display_name, "Search Core");
+    assert_eq!(report.name_source, NameSource::Manifest);
+    assert_eq!(report.mode, CapsuleMode::IsolatedNixShell);
+    assert_eq!(report.workspace, 7);
+    assert!(report.manifest_path.is_some());
+    assert_eq!(
+        report.service_ports.keys().collect::<Vec<_>>(),
+        vec!["api", "db", "web"]
+    );
+    assert_eq!(report.service_ports.get("db"), Some(&5432));
+}
+
+#[test]
+fn create_from_repo_validates_ids_and_rolls_back_when_ports_run_out() {
+    let dir = tempdir().unwrap();
+    let repo = dir.path().join("ledger");
+    std::fs::create_dir_all(&repo).unwrap();
+    seed_pool(dir.path(), 6740, 6740);
+    {
+        let mut store = CapsuleStore::open(&dir.path().join("capsules.sqlite3")).unwrap();
+        store
+            .upsert(Capsule::new(
+                "cap-holder",
+                "Holder",
+                CapsuleMode::HostDefault,
+                1,
+            ))
+            .unwrap();
+        store
+            .allocate_pool_port("cap-holder", "web", "default", 4)
+            .unwrap();
+    }
+
+    let invalid = create_from_repo(&FromRepoInput {
+        capsule_id: Some("Ledger".into()),
+        ..input(dir.path(), &repo)
+    })
+    .unwrap_err();
+    assert!(matches!(invalid, OnboardError::InvalidCapsuleId(_)));
+
+    let exhausted = create_from_repo(&FromRepoInput {
+        capsule_id: Some("cap-ledger".into()),
+        ..input(dir.path(), &repo)
+    })
+    .unwrap_err();
+    assert!(matches!(exhausted, OnboardError::PortsExhausted(_)));
+    let store = CapsuleStore::open(&dir.path().join("capsules.sqlite3")).unwrap();
+    assert!(store.get("cap-ledger").unwrap().is_none());
+
+    let missing_pool = create_from_repo(&FromRepoInput {
+        pool: "staging".into(),
+        ..input(dir.path(), &repo)
+    })
+    .unwrap_err();
+    assert!(matches!(
+        missing_pool,
+        OnboardError::Store(StoreError::PortPoolNotFound(_))
+    ));
+}
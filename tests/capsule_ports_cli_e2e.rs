@@ -64,3 +64,233 @@ fn nexumctl_can_allocate_and_release_capsule_ports() {
     let release_stdout = String::from_utf8(released.stdout).unwrap();
     assert!(release_stdout.contains("\"released\":1"));
 }
+
+#[test]
+fn nexumctl_allocates_named_service_ports_and_exports_env() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = dir.path().join("capsules.sqlite3");
+    let tls_dir = dir.path().join("tls");
+    let events_db = dir.path().join("events.sqlite3");
+    let nexumctl = assert_cmd::cargo::cargo_bin!("nexumctl");
+
+    let created = Command::new(nexumctl)
+        .arg("capsule")
+        .arg("create")
+        .arg("--db")
+        .arg(&db)
+        .arg("--id")
+        .arg("cap-cli-services")
+        .arg("--name")
+        .arg("Service Capsule")
+        .arg("--workspace")
+        .arg("11")
+        .arg("--mode")
+        .arg("host_default")
+        .arg("--repo-path")
+        .arg("/workspace/services")
+        .output()
+        .unwrap();
+    assert!(created.status.success());
+
+    let allocate = |service: &str| {
+        Command::new(nexumctl)
+            .arg("capsule")
+            .arg("allocate-port")
+            .arg("--db")
+            .arg(&db)
+            .arg("--id")
+            .arg("cap-cli-services")
+            .arg("--start")
+            .arg("6400")
+            .arg("--end")
+            .arg("6410")
+            .arg("--service")
+            .arg(service)
+            .arg("--quota")
+            .arg("2")
+            .output()
+            .unwrap()
+    };
+
+    let web = allocate("web");
+    assert!(web.status.success());
+    let web_json: serde_json::Value = serde_json::from_slice(&web.stdout).unwrap();
+    assert_eq!(web_json["port"], 6400);
+    assert_eq!(web_json["env"], "NEXUM_PORT_WEB");
+    assert!(allocate("api").status.success());
+
+    let over_quota = allocate("db");
+    assert!(!over_quota.status.success());
+    assert!(String::from_utf8_lossy(&over_quota.stderr).contains("port quota of 2"));
+
+    let restore = Command::new(nexumctl)
+        .arg("run")
+        .arg("restore-capsule")
+        .arg("--capsule-db")
+        .arg(&db)
+        .arg("--capsule-id")
+        .arg("cap-cli-services")
+        .arg("--signal")
+        .arg("needs_decision")
+        .arg("--upstream")
+        .arg("127.0.0.1:6400")
+        .arg("--tls-dir")
+        .arg(&tls_dir)
+        .arg("--events-db")
+        .arg(&events_db)
+        .output()
+        .unwrap();
+    assert!(restore.status.success());
+    let summary: serde_json::Value = serde_json::from_slice(&restore.stdout).unwrap();
+    let script = summary["shell_script"].as_str().unwrap();
+    assert!(script.contains("export NEXUM_PORT_WEB=6400"));
+    assert!(script.contains("export NEXUM_PORT_API=6401"));
+}
+
+#[test]
+fn nexumctl_ports_audit_flags_ports_held_outside_the_capsule() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = dir.path().join("capsules.sqlite3");
+    let nexumctl = assert_cmd::cargo::cargo_bin!("nexumctl");
+    let port = std::net::TcpListener::bind("0.0.0.0:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+
+    let allocated = Command::new(nexumctl)
+        .arg("capsule")
+        .arg("allocate-port")
+        .arg("--db")
+        .arg(&db)
+        .arg("--id")
+        .arg("cap-cli-audit")
+        .arg("--start")
+        .arg(port.to_string())
+        .arg("--end")
+        .arg(port.to_string())
+        .arg("--service")
+        .arg("web")
+        .output()
+        .unwrap();
+    assert!(allocated.status.success());
+    let allocated_json: serde_json::Value = serde_json::from_slice(&allocated.stdout).unwrap();
+    assert_eq!(allocated_json["port"], port);
+
+    let audit = || {
+        Command::new(nexumctl)
+            .arg("capsule")
+            .arg("ports")
+            .arg("audit")
+            .arg("--db")
+            .arg(&db)
+            .output()
+            .unwrap()
+    };
+
+    let clean = audit();
+    assert!(clean.status.success());
+
+    let squatter = std::net::TcpListener::bind(("0.0.0.0", port)).unwrap();
+    let conflicted = audit();
+    assert!(!conflicted.status.success());
+    let payload: serde_json::Value = serde_json::from_slice(&conflicted.stdout).unwrap();
+    assert_eq!(payload["checked"], 1);
+    assert_eq!(payload["conflicts"][0]["capsule_id"], "cap-cli-audit");
+    assert_eq!(payload["conflicts"][0]["service"], "web");
+    assert_eq!(payload["conflicts"][0]["port"], port);
+    assert_eq!(payload["conflicts"][0]["status"], "conflict");
+    assert_eq!(payload["unknown_owners"], serde_json::json!([]));
+    assert_eq!(
+        payload["conflicts"][0]["owners"][0]["pid"],
+        std::process::id()
+    );
+    drop(squatter);
+}
+
+#[test]
+fn nexumctl_allocates_from_configured_pool_and_reservations() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = dir.path().join("capsules.sqlite3");
+    let nexumctl = assert_cmd::cargo::cargo_bin!("nexumctl");
+
+    let pool = Command::new(nexumctl)
+        .arg("capsule")
+        .arg("ports")
+        .arg("set-pool")
+        .arg("--db")
+        .arg(&db)
+        .arg("--start")
+        .arg("6500")
+        .arg("--end")
+        .arg("6504")
+        .output()
+        .unwrap();
+    assert!(pool.status.success());
+
+    let reserved = Command::new(nexumctl)
+        .arg("capsule")
+        .arg("ports")
+        .arg("reserve")
+        .arg("--db")
+        .arg(&db)
+        .arg("--port")
+        .arg("6500")
+        .arg("--reason")
+        .arg("system proxy")
+        .output()
+        .unwrap();
+    assert!(reserved.status.success());
+
+    let allocated = Command::new(nexumctl)
+        .arg("capsule")
+        .arg("allocate-port")
+        .arg("--db")
+        .arg(&db)
+        .arg("--id")
+        .arg("cap-cli-pool")
+        .arg("--service")
+        .arg("web")
+        .output()
+        .unwrap();
+    assert!(allocated.status.success());
+    let alloc_stdout = String::from_utf8(allocated.stdout).unwrap();
+    assert!(alloc_stdout.contains("\"port\":6501"));
+
+    let outside = Command::new(nexumctl)
+        .arg("capsule")
+        .arg("allocate-port")
+        .arg("--db")
+        .arg(&db)
+        .arg("--id")
+        .arg("cap-cli-pool")
+        .arg("--service")
+        .arg("api")
+        .arg("--start")
+        .arg("9000")
+        .arg("--end")
+        .arg("9001")
+        .output()
+        .unwrap();
+    assert!(!outside.status.success());
+    assert!(
+        String::from_utf8(outside.stderr)
+            .unwrap()
+            .contains("outside every configured port pool")
+    );
+
+    let pools = Command::new(nexumctl)
+        .arg("capsule")
+        .arg("ports")
+        .arg("pools")
+        .arg("--db")
+        .arg(&db)
+        .output()
+        .unwrap();
+    assert!(pools.status.success());
+    let json: serde_json::Value = serde_json::from_slice(&pools.stdout).unwrap();
+    assert_eq!(json["pools"][0]["name"], "default");
+    assert_eq!(json["pools"][0]["available"], 3);
+    assert_eq!(json["reservations"][0]["port"], 6500);
+    assert_eq!(json["reservations"][0]["reason"], "system proxy");
+}
@@ -0,0 +1,17 @@
+use nexum::crypto::{CryptoError, seal, unseal};
+
+#[test]
+fn sealed_payload_round_trips_with_same_passphrase() {
+    let sealed = seal("hunter2", b"private material").unwrap();
+
+    assert_ne!(&sealed[..], b"private material");
+    assert_eq!(unseal("hunter2", &sealed).unwrap(), b"private material");
+}
+
+#[test]
+fn unseal_rejects_wrong_passphrase_and_truncation() {
+    let sealed = seal("hunter2", b"private material").unwrap();
+
+    assert_eq!(unseal("hunter3", &sealed), Err(CryptoError::Open));
+    assert_eq!(unseal("hunter2", &sealed[..8]), Err(CryptoError::Truncated));
+}
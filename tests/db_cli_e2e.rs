@@ -0,0 +1,65 @@
+use std::process::Command;
+
+use serde_json::Value;
+use tempfile::tempdir;
+
+#[test]
+fn nexumctl_db_migrate_dry_run_then_apply() {
+    let dir = tempdir().unwrap();
+    let capsule_db = dir.path().join("capsules.sqlite3");
+    let events_db = dir.path().join("events.sqlite3");
+    let nexumctl = assert_cmd::cargo::cargo_bin!("nexumctl");
+
+    let dry_run = Command::new(nexumctl)
+        .arg("db")
+        .arg("migrate")
+        .arg("--capsule-db")
+        .arg(&capsule_db)
+        .arg("--events-db")
+        .arg(&events_db)
+        .arg("--dry-run")
+        .arg("true")
+        .output()
+        .unwrap();
+    assert!(dry_run.status.success());
+    let dry_run_json: Value = serde_json::from_slice(&dry_run.stdout).unwrap();
+    assert_eq!(dry_run_json["dry_run"], Value::Bool(true));
+    assert_eq!(dry_run_json["databases"][0]["database"], "capsules");
+    assert_eq!(dry_run_json["databases"][0]["current_version"], 0);
+    assert!(
+        !dry_run_json["databases"][0]["pending"]
+            .as_array()
+            .unwrap()
+            .is_empty()
+    );
+    assert_eq!(dry_run_json["databases"][1]["database"], "events");
+    assert!(!capsule_db.exists());
+    assert!(!events_db.exists());
+
+    let apply = Command::new(nexumctl)
+        .arg("db")
+        .arg("migrate")
+        .arg("--capsule-db")
+        .arg(&capsule_db)
+        .arg("--events-db")
+        .arg(&events_db)
+        .output()
+        .unwrap();
+    assert!(apply.status.success());
+    assert!(capsule_db.exists());
+
+    let after = Command::new(nexumctl)
+        .arg("db")
+        .arg("migrate")
+        .arg("--capsule-db")
+        .arg(&capsule_db)
+        .arg("--dry-run")
+        .arg("true")
+        .output()
+        .unwrap();
+    assert!(after.status.success());
+    let after_json: Value = serde_json::from_slice(&after.stdout).unwrap();
+    let entry = &after_json["databases"][0];
+    assert_eq!(entry["current_version"], entry["target_version"]);
+    assert!(entry["pending"].as_array().unwrap().is_empty());
+}
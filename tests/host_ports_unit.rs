@@ -0,0 +1,59 @@
+use std::net::TcpListener;
+
+use nexum::host_ports::{HostPortProbe, PortOwner, PortStatus, parse_proc_net_tcp, port_status};
+
+#[test]
+fn parses_only_listening_sockets_from_proc_net_tcp() {
+    let contents = "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 00000000:07E8 00000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 662 1 0000000039187ad1 100 0 0 10 0
+   1: 0100007F:D376 0100007F:BC8F 01 00000000:00000000 02:000000DD 00000000     0        0 35193 2 000000000e461de7 20 4 0 18 8
+   2: 00000000000000000000000000000000:10E1 00000000000000000000000000000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 991 1 0000000000000000 100 0 0 10 0
+";
+    let listeners = parse_proc_net_tcp(contents);
+
+    assert_eq!(
+        listeners
+            .iter()
+            .map(|listener| (listener.port, listener.inode))
+            .collect::<Vec<_>>(),
+        vec![(2024, 662), (4321, 991)]
+    );
+}
+
+#[test]
+fn probe_reports_ports_bound_by_this_process_as_busy() {
+    let listener = TcpListener::bind("0.0.0.0:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    assert!(!HostPortProbe::snapshot().is_free(port));
+    drop(listener);
+}
+
+#[test]
+fn owners_that_cannot_be_identified_are_reported_as_unknown() {
+    let owner = |capsule_id: Option<&str>, environ_readable| PortOwner {
+        pid: 42,
+        command: "server".to_string(),
+        capsule_id: capsule_id.map(ToString::to_string),
+        environ_readable,
+    };
+
+    assert_eq!(port_status("cap-a", &[owner(Some("cap-a"), true)]), None);
+    assert_eq!(
+        port_status("cap-a", &[owner(None, true)]),
+        Some(PortStatus::Conflict)
+    );
+    assert_eq!(
+        port_status("cap-a", &[owner(Some("cap-b"), true), owner(None, false)]),
+        Some(PortStatus::Conflict)
+    );
+    assert_eq!(port_status("cap-a", &[]), Some(PortStatus::OwnerUnknown));
+    assert_eq!(
+        port_status("cap-a", &[owner(None, false)]),
+        Some(PortStatus::OwnerUnknown)
+    );
+    assert_eq!(
+        port_status("cap-a", &[owner(Some("cap-a"), true), owner(None, false)]),
+        Some(PortStatus::OwnerUnknown)
+    );
+}
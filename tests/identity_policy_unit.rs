@@ -1,4 +1,6 @@
-use nexum::identity::{browser_launch_command, profile_dir_for_capsule};
+use nexum::identity::{
+    browser_launch_command, certutil_available, profile_dir_for_capsule, provision_profile_trust,
+};
 
 #[test]
 fn domain_isolation_uses_default_browser_command_when_no_collision() {
@@ -15,3 +17,42 @@ fn collision_activates_profile_fallback_command() {
     assert!(launch.contains(profile.to_str().unwrap()));
     assert!(launch.contains("https://alpha.nexum.local"));
 }
+
+#[test]
+fn profile_trust_provisioning_copies_cert_and_quotes_import_command() {
+    let dir = tempfile::tempdir().unwrap();
+    let cert = dir.path().join("alpha.nexum.local.crt.pem");
+    std::fs::write(&cert, "-----BEGIN CERTIFICATE-----\n").unwrap();
+    let profile = dir.path().join("my profile");
+
+    let trust = provision_profile_trust(&profile, &cert, "alpha.nexum.local").unwrap();
+
+    let trusted = profile.join("nexum-trust/alpha.nexum.local.crt.pem");
+    assert!(trusted.exists());
+    let user_js = std::fs::read_to_string(profile.join("user.js")).unwrap();
+    assert!(user_js.contains("user_pref(\"security.enterprise_roots.enabled\", true);"));
+    let policies: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(&trust.policies_path).unwrap()).unwrap();
+    assert_eq!(
+        policies["policies"]["Certificates"]["Install"][0],
+        serde_json::Value::String(trusted.display().to_string())
+    );
+    assert_eq!(trust.warnings.is_empty(), certutil_available());
+    assert!(trust.certutil_command.contains("else echo"));
+    assert!(trust.certutil_command.contains("certutil -A"));
+    assert!(
+        trust
+            .certutil_command
+            .contains("-n 'alpha.nexum.local' -t P,,")
+    );
+    assert!(
+        trust
+            .certutil_command
+            .contains(&format!("-d 'sql:{}'", profile.display()))
+    );
+    assert!(
+        trust
+            .certutil_command
+            .contains(&format!("-i '{}'", trusted.display()))
+    );
+}
@@ -0,0 +1,108 @@
+use std::collections::BTreeMap;
+
+use nexum::{
+    capsule::{Capsule, CapsuleMode, CapsuleState},
+    labels::{LabelError, Requirement, Selector, parse_label, validate_label_key},
+};
+
+fn labels(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+    pairs
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+#[test]
+fn selector_parses_every_term_kind() {
+    let selector = "team=infra, tier!=db,owner,!frozen,!archived"
+        .parse::<Selector>()
+        .unwrap();
+    assert_eq!(
+        selector.requirements,
+        vec![
+            Requirement::Equals {
+                key: "team".to_string(),
+                value: "infra".to_string(),
+            },
+            Requirement::NotEquals {
+                key: "tier".to_string(),
+                value: "db".to_string(),
+            },
+            Requirement::Exists {
+                key: "owner".to_string(),
+            },
+            Requirement::Absent {
+                key: "frozen".to_string(),
+            },
+            Requirement::NotEquals {
+                key: "state".to_string(),
+                value: "archived".to_string(),
+            },
+        ]
+    );
+    assert_eq!(
+        selector.to_string(),
+        "team=infra,tier!=db,owner,!frozen,state!=archived"
+    );
+    assert!("".parse::<Selector>().unwrap().requirements.is_empty());
+}
+
+#[test]
+fn selector_rejects_malformed_terms() {
+    for input in ["Team=infra", "team=in fra", "=infra", "!", "team==x"] {
+        assert!(
+            matches!(
+                input.parse::<Selector>(),
+                Err(LabelError::InvalidSelector(_))
+            ),
+            "{input}"
+        );
+    }
+}
+
+#[test]
+fn selector_matches_labels_and_capsule_fields() {
+    let mut capsule = Capsule::new("cap-infra", "Infra", CapsuleMode::HostDefault, 1);
+    let set = labels(&[("team", "infra"), ("tier", "web")]);
+    let select =
+        |input: &str, capsule: &Capsule| input.parse::<Selector>().unwrap().matches(capsule, &set);
+
+    assert!(select("team=infra,!archived", &capsule));
+    assert!(select("tier!=db,mode=host_default,state=ready", &capsule));
+    assert!(select("", &capsule));
+    assert!(!select("team=payments", &capsule));
+    assert!(!select("owner", &capsule));
+    assert!(!select("!tier", &capsule));
+
+    capsule.state = CapsuleState::Archived;
+    assert!(!select("team=infra,!archived", &capsule));
+    assert!(select("archived", &capsule));
+}
+
+#[test]
+fn label_keys_exclude_reserved_names() {
+    assert_eq!(
+        parse_label("team=infra").unwrap(),
+        ("team".to_string(), "infra".to_string())
+    );
+    assert_eq!(
+        parse_label("nexum.dev/owner=").unwrap(),
+        ("nexum.dev/owner".to_string(), String::new())
+    );
+    assert!(matches!(
+        parse_label("team"),
+        Err(LabelError::InvalidLabel(_))
+    ));
+    assert!(matches!(
+        validate_label_key("state"),
+        Err(LabelError::ReservedKey(_))
+    ));
+    assert!(matches!(
+        validate_label_key("archived"),
+        Err(LabelError::ReservedKey(_))
+    ));
+    assert!(matches!(
+        validate_label_key("-team"),
+        Err(LabelError::InvalidKey(_))
+    ));
+}
@@ -0,0 +1,197 @@
+use std::path::Path;
+
+use nexum::{
+    capsule::{Capsule, CapsuleMode},
+    manifest::{CapsuleManifest, ManifestError, RestoreOverrides, resolve_restore},
+    store::{CapsuleStore, StoreError},
+};
+use tempfile::{TempDir, tempdir};
+
+fn open_store() -> (TempDir, CapsuleStore) {
+    let dir = tempdir().unwrap();
+    let store = CapsuleStore::open(&dir.path().join("capsules.sqlite3")).unwrap();
+    (dir, store)
+}
+
+#[test]
+fn manifest_parses_surfaces_and_services() {
+    let manifest = CapsuleManifest::parse(
+        r#"
+display_name = "Search Core"
+mode = "isolated_nix_shell"
+workspace = 4
+
+[surfaces]
+terminal = ["nix develop"]
+editor = "."
+
+[services.web]
+port = 4302
+
+[services.api]
+"#,
+        Path::new("nexum.toml"),
+    )
+    .unwrap();
+
+    assert_eq!(manifest.display_name.as_deref(), Some("Search Core"));
+    assert_eq!(manifest.mode, Some(CapsuleMode::IsolatedNixShell));
+    assert_eq!(manifest.services.len(), 2);
+    assert_eq!(
+        manifest.default_upstream().as_deref(),
+        Some("127.0.0.1:4302")
+    );
+}
+
+#[test]
+fn manifest_rejects_unknown_keys() {
+    let error =
+        CapsuleManifest::parse("terminal = \"vim\"\n", Path::new("repo/nexum.toml")).unwrap_err();
+    assert!(matches!(error, ManifestError::Parse { path, .. } if path == "repo/nexum.toml"));
+}
+
+#[test]
+fn overrides_take_precedence_over_manifest_and_manifest_over_capsule() {
+    let repo = tempdir().unwrap();
+    std::fs::write(
+        repo.path().join("nexum.toml"),
+        "mode = \"isolated_nix_shell\"\nworkspace = 9\nupstream = \"127.0.0.1:5000\"\n",
+    )
+    .unwrap();
+    let capsule = Capsule::new("cap-manifest", "Manifest", CapsuleMode::HostDefault, 2)
+        .with_repo_path(repo.path().to_str().unwrap());
+    let (_db, mut store) = open_store();
+
+    let merged = resolve_restore(&mut store, &capsule, RestoreOverrides::default()).unwrap();
+    assert_eq!(merged.workspace, 9);
+    assert_eq!(merged.route_upstream, "127.0.0.1:5000");
+    assert!(merged.force_isolated_mode);
+    assert_eq!(merged.display_name, "Manifest");
+    assert!(merged.manifest_path.is_some());
+
+    let overridden = resolve_restore(
+        &mut store,
+        &capsule,
+        RestoreOverrides {
+            upstream: Some("127.0.0.1:6000".into()),
+            force_isolated_mode: Some(false),
+            ..RestoreOverrides::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(overridden.route_upstream, "127.0.0.1:6000");
+    assert!(!overridden.force_isolated_mode);
+}
+
+#[test]
+fn missing_upstream_is_reported_without_manifest() {
+    let capsule = Capsule::new("cap-bare", "Bare", CapsuleMode::HostDefault, 1)
+        .with_repo_path("/workspace/does-not-exist");
+    let (_db, mut store) = open_store();
+    assert!(matches!(
+        resolve_restore(&mut store, &capsule, RestoreOverrides::default()),
+        Err(ManifestError::MissingUpstream)
+    ));
+}
+
+#[test]
+fn manifest_display_name_wins_but_cannot_change_the_slug() {
+    let repo = tempdir().unwrap();
+    let capsule = Capsule::new("cap-named", "search core", CapsuleMode::HostDefault, 2)
+        .with_repo_path(repo.path().to_str().unwrap());
+    let (_db, mut store) = open_store();
+
+    std::fs::write(
+        repo.path().join("nexum.toml"),
+        "display_name = \"Search Core\"\nupstream = \"127.0.0.1:5000\"\n",
+    )
+    .unwrap();
+    let resolved = resolve_restore(&mut store, &capsule, RestoreOverrides::default()).unwrap();
+    assert_eq!(resolved.display_name, "Search Core");
+
+    std::fs::write(
+        repo.path().join("nexum.toml"),
+        "display_name = \"Billing\"\nupstream = \"127.0.0.1:5000\"\n",
+    )
+    .unwrap();
+    assert!(matches!(
+        resolve_restore(&mut store, &capsule, RestoreOverrides::default()),
+        Err(ManifestError::Store(StoreError::ImmutableSlug { .. }))
+    ));
+}
+
+#[test]
+fn manifest_workspace_is_checked_for_collisions() {
+    let repo = tempdir().unwrap();
+    std::fs::write(
+        repo.path().join("nexum.toml"),
+        "workspace = 3\nupstream = \"127.0.0.1:5000\"\n",
+    )
+    .unwrap();
+    let (_db, mut store) = open_store();
+    store
+        .upsert(Capsule::new(
+            "cap-other",
+            "Other",
+            CapsuleMode::HostDefault,
+            3,
+        ))
+        .unwrap();
+    let capsule = Capsule::new("cap-moving", "Moving", CapsuleMode::HostDefault, 1)
+        .with_repo_path(repo.path().to_str().unwrap());
+    store.upsert(capsule.clone()).unwrap();
+
+    assert!(matches!(
+        resolve_restore(&mut store, &capsule, RestoreOverrides::default()),
+        Err(ManifestError::Store(StoreError::WorkspaceCollision {
+            workspace: 3,
+            ..
+        }))
+    ));
+}
+
+#[test]
+fn fixed_service_ports_are_recorded_in_the_store() {
+    let repo = tempdir().unwrap();
+    std::fs::write(
+        repo.path().join("nexum.toml"),
+        "[services.web]\nport = 4302\n",
+    )
+    .unwrap();
+    let (_db, mut store) = open_store();
+    let capsule = Capsule::new("cap-fixed", "Fixed", CapsuleMode::HostDefault, 1)
+        .with_repo_path(repo.path().to_str().unwrap());
+    store.upsert(capsule.clone()).unwrap();
+
+    let resolved = resolve_restore(&mut store, &capsule, RestoreOverrides::default()).unwrap();
+    assert_eq!(resolved.route_upstream, "127.0.0.1:4302");
+    assert_eq!(
+        store.list_service_ports("cap-fixed").unwrap().get("web"),
+        Some(&4302)
+    );
+
+    let other = Capsule::new("cap-rival", "Rival", CapsuleMode::HostDefault, 2)
+        .with_repo_path(repo.path().to_str().unwrap());
+    store.upsert(other.clone()).unwrap();
+    assert!(matches!(
+        resolve_restore(&mut store, &other, RestoreOverrides::default()),
+        Err(ManifestError::Store(StoreError::PortAllocated {
+            port: 4302,
+            ..
+        }))
+    ));
+
+    store.reserve_port(4400, "system").unwrap();
+    std::fs::write(
+        repo.path().join("nexum.toml"),
+        "[services.web]\nport = 4400\n",
+    )
+    .unwrap();
+    assert!(matches!(
+        resolve_restore(&mut store, &capsule, RestoreOverrides::default()),
+        Err(ManifestError::Store(StoreError::PortReserved {
+            port: 4400,
+            ..
+        }))
+    ));
+}
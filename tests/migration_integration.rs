@@ -0,0 +1,169 @@
+use nexum::{
+    events::{EVENT_MIGRATIONS, EventError, EventStore},
+    migrate::MigrationError,
+    store::{CAPSULE_MIGRATIONS, CapsuleStore, StoreError},
+};
+use rusqlite::Connection;
+use tempfile::tempdir;
+
+#[test]
+fn fresh_databases_reach_latest_schema_version() {
+    let dir = tempdir().unwrap();
+    let capsule_db = dir.path().join("capsules.sqlite3");
+    let events_db = dir.path().join("events.sqlite3");
+
+    let store = CapsuleStore::open(&capsule_db).unwrap();
+    assert_eq!(
+        store.schema_version().unwrap(),
+        CAPSULE_MIGRATIONS.last().unwrap().version
+    );
+    let events = EventStore::open(&events_db).unwrap();
+    assert_eq!(
+        events.schema_version().unwrap(),
+        EVENT_MIGRATIONS.last().unwrap().version
+    );
+
+    assert!(
+        CapsuleStore::migration_plan(&capsule_db)
+            .unwrap()
+            .pending
+            .is_empty()
+    );
+    assert!(
+        EventStore::migration_plan(&events_db)
+            .unwrap()
+            .pending
+            .is_empty()
+    );
+}
+
+#[test]
+fn unversioned_legacy_capsule_database_is_upgraded_in_place() {
+    let dir = tempdir().unwrap();
+    let db = dir.path().join("capsules.sqlite3");
+    {
+        let conn = Connection::open(&db).unwrap();
+        conn.execute_batch(
+            "
+            CREATE TABLE capsules (
+                capsule_id TEXT PRIMARY KEY,
+                slug TEXT NOT NULL,
+                display_name TEXT NOT NULL,
+                mode TEXT NOT NULL,
+                state TEXT NOT NULL DEFAULT 'ready',
+                workspace INTEGER NOT NULL
+            );
+            CREATE TABLE capsule_ports (
+                capsule_id TEXT NOT NULL,
+                port INTEGER NOT NULL PRIMARY KEY
+            );
+            INSERT INTO capsules (capsule_id, slug, display_name, mode, state, workspace)
+            VALUES ('cap-legacy', 'legacy', 'Legacy', 'host_default', 'degraded', 3);
+            ",
+        )
+        .unwrap();
+    }
+
+    let plan = CapsuleStore::migration_plan(&db).unwrap();
+    assert_eq!(plan.current_version, 0);
+    assert_eq!(plan.pending.len(), CAPSULE_MIGRATIONS.len());
+
+    let store = CapsuleStore::open(&db).unwrap();
+    let capsule = store.get("cap-legacy").unwrap().unwrap();
+    assert_eq!(capsule.slug, "legacy");
+    assert_eq!(capsule.repo_path, "");
+    assert_eq!(capsule.workspace, 3);
+    assert_eq!(
+        store.schema_version().unwrap(),
+        CAPSULE_MIGRATIONS.last().unwrap().version
+    );
+}
+
+#[test]
+fn databases_from_newer_versions_are_refused() {
+    let dir = tempdir().unwrap();
+    let capsule_db = dir.path().join("capsules.sqlite3");
+    let events_db = dir.path().join("events.sqlite3");
+    for path in [&capsule_db, &events_db] {
+        Connection::open(path)
+            .unwrap()
+            .pragma_update(None, "user_version", 999)
+            .unwrap();
+    }
+
+    assert!(matches!(
+        CapsuleStore::open(&capsule_db),
+        Err(StoreError::Migration(MigrationError::NewerSchema {
+            found: 999,
+            ..
+        }))
+    ));
+    assert!(matches!(
+        EventStore::open(&events_db),
+        Err(EventError::Migration(MigrationError::NewerSchema {
+            found: 999,
+            ..
+        }))
+    ));
+}
+
+#[test]
+fn migration_plan_does_not_create_missing_database() {
+    let dir = tempdir().unwrap();
+    let db = dir.path().join("missing.sqlite3");
+
+    let plan = CapsuleStore::migration_plan(&db).unwrap();
+    assert_eq!(plan.current_version, 0);
+    assert_eq!(
+        plan.target_version,
+        CAPSULE_MIGRATIONS.last().unwrap().version
+    );
+    assert!(!db.exists());
+}
+
+#[test]
+fn legacy_multi_port_rows_receive_distinct_service_names() {
+    let dir = tempdir().unwrap();
+    let db = dir.path().join("capsules.sqlite3");
+    {
+        let conn = Connection::open(&db).unwrap();
+        conn.execute_batch(
+            "
+            CREATE TABLE capsule_ports (
+                capsule_id TEXT NOT NULL,
+                port INTEGER NOT NULL PRIMARY KEY
+            );
+            INSERT INTO capsule_ports (capsule_id, port) VALUES ('cap-legacy', 4400);
+            INSERT INTO capsule_ports (capsule_id, port) VALUES ('cap-legacy', 4401);
+            ",
+        )
+        .unwrap();
+    }
+
+    let store = CapsuleStore::open(&db).unwrap();
+    let ports = store.list_service_ports("cap-legacy").unwrap();
+    assert_eq!(ports.get("default"), Some(&4400));
+    assert_eq!(ports.get("port-4401"), Some(&4401));
+}
+
+#[test]
+fn capsules_with_pre_charset_ids_can_still_be_updated() {
+    let dir = tempdir().unwrap();
+    let db = dir.path().join("capsules.sqlite3");
+    let mut store = CapsuleStore::open(&db).unwrap();
+    Connection::open(&db)
+        .unwrap()
+        .execute_batch(
+            "INSERT INTO capsules (capsule_id, slug, display_name, repo_path, mode, state, workspace)
+             VALUES ('Legacy One', 'legacy-one', 'Legacy One', '', 'host_default', 'ready', 2);",
+        )
+        .unwrap();
+
+    let mut capsule = store.get("Legacy One").unwrap().unwrap();
+    capsule.workspace = 5;
+    store.upsert(capsule).unwrap();
+    assert_eq!(store.get("Legacy One").unwrap().unwrap().workspace, 5);
+
+    let exported = store.list().unwrap();
+    assert!(store.plan_import(&exported).unwrap().conflicts.is_empty());
+}
@@ -1,14 +1,26 @@
-use nexum::ports::PortAllocator;
+use nexum::{
+    ports::PortAllocator,
+    store::{CapsuleStore, StoreError},
+};
+use tempfile::{TempDir, tempdir};
+
+fn store_with_pool(start: u16, end: u16) -> (TempDir, CapsuleStore) {
+    let dir = tempdir().unwrap();
+    let mut store = CapsuleStore::open(&dir.path().join("capsules.sqlite3")).unwrap();
+    store.set_port_pool("default", start, end).unwrap();
+    (dir, store)
+}
 
 #[test]
 fn allocates_unique_ports_across_multiple_capsules() {
-    let mut allocator = PortAllocator::new(4300, 4310);
-    allocator.reserve(4300);
-    allocator.reserve(4301);
+    let (_dir, mut store) = store_with_pool(4300, 4310);
+    let mut allocator = PortAllocator::open(&mut store, "default").unwrap();
+    allocator.reserve(4300, "system").unwrap();
+    allocator.reserve(4301, "system").unwrap();
 
     let allocated = ["cap-a", "cap-b", "cap-c", "cap-d", "cap-e"]
         .into_iter()
-        .map(|id| allocator.allocate(id).expect("port expected"))
+        .map(|id| allocator.allocate(id).unwrap().expect("port expected"))
         .collect::<Vec<_>>();
 
     assert_eq!(allocated, vec![4302, 4303, 4304, 4305, 4306]);
@@ -16,34 +28,112 @@ fn allocates_unique_ports_across_multiple_capsules() {
 
 #[test]
 fn allocation_is_stable_for_same_capsule_and_reusable_after_release() {
-    let mut allocator = PortAllocator::new(5000, 5003);
+    let (_dir, mut store) = store_with_pool(5000, 5003);
+    let mut allocator = PortAllocator::open(&mut store, "default").unwrap();
 
-    let first = allocator.allocate("cap-a").unwrap();
-    let second = allocator.allocate("cap-a").unwrap();
+    let first = allocator.allocate("cap-a").unwrap().unwrap();
+    let second = allocator.allocate("cap-a").unwrap().unwrap();
     assert_eq!(first, second);
 
-    allocator.release("cap-a");
-    let reused = allocator.allocate("cap-b").unwrap();
+    assert_eq!(allocator.release("cap-a").unwrap(), 1);
+    let reused = allocator.allocate("cap-b").unwrap().unwrap();
     assert_eq!(reused, first);
 }
 
 #[test]
 fn returns_none_when_range_is_exhausted() {
-    let mut allocator = PortAllocator::new(7000, 7001);
-    assert_eq!(allocator.allocate("cap-a"), Some(7000));
-    assert_eq!(allocator.allocate("cap-b"), Some(7001));
-    assert_eq!(allocator.allocate("cap-c"), None);
+    let (_dir, mut store) = store_with_pool(7000, 7001);
+    let mut allocator = PortAllocator::open(&mut store, "default").unwrap();
+    assert_eq!(allocator.allocate("cap-a").unwrap(), Some(7000));
+    assert_eq!(allocator.allocate("cap-b").unwrap(), Some(7001));
+    assert_eq!(allocator.allocate("cap-c").unwrap(), None);
 }
 
 #[test]
-#[should_panic(expected = "port range start must be <= end")]
 fn rejects_invalid_port_range_configuration() {
-    let _ = PortAllocator::new(7100, 7099);
+    let dir = tempdir().unwrap();
+    let mut store = CapsuleStore::open(&dir.path().join("capsules.sqlite3")).unwrap();
+    assert!(matches!(
+        store.set_port_pool("default", 7100, 7099),
+        Err(StoreError::InvalidPortRange {
+            start: 7100,
+            end: 7099
+        })
+    ));
+    assert!(matches!(
+        PortAllocator::open(&mut store, "default"),
+        Err(StoreError::PortPoolNotFound(_))
+    ));
 }
 
 #[test]
-#[should_panic(expected = "reserved port out of range")]
 fn rejects_reserving_out_of_range_port() {
-    let mut allocator = PortAllocator::new(7200, 7201);
-    allocator.reserve(7300);
+    let (_dir, mut store) = store_with_pool(7200, 7201);
+    let mut allocator = PortAllocator::open(&mut store, "default").unwrap();
+    assert!(matches!(
+        allocator.reserve(7300, "system"),
+        Err(StoreError::PortOutsidePool { port: 7300, .. })
+    ));
+    assert!(store.list_port_reservations().unwrap().is_empty());
+}
+
+#[test]
+fn allocates_distinct_ports_per_service_up_to_quota() {
+    let (_dir, mut store) = store_with_pool(7300, 7310);
+    let mut allocator = PortAllocator::open(&mut store, "default")
+        .unwrap()
+        .with_quota(2);
+
+    assert_eq!(
+        allocator.allocate_service("cap-a", "web").unwrap(),
+        Some(7300)
+    );
+    assert_eq!(
+        allocator.allocate_service("cap-a", "api").unwrap(),
+        Some(7301)
+    );
+    assert_eq!(
+        allocator.allocate_service("cap-a", "web").unwrap(),
+        Some(7300)
+    );
+    assert!(matches!(
+        allocator.allocate_service("cap-a", "db"),
+        Err(StoreError::PortQuotaExceeded { quota: 2, .. })
+    ));
+    assert_eq!(allocator.service_ports("cap-a").unwrap().len(), 2);
+
+    allocator.release("cap-a").unwrap();
+    assert!(allocator.used_ports().unwrap().is_empty());
+}
+
+#[test]
+fn allocator_reads_and_writes_through_the_store() {
+    let (dir, mut store) = store_with_pool(7400, 7404);
+    store.reserve_port(7400, "system").unwrap();
+    store
+        .allocate_service_port("cap-view", "web", 7400, 7404, 4)
+        .unwrap();
+
+    {
+        let mut allocator = PortAllocator::open(&mut store, "default").unwrap();
+        assert_eq!(allocator.range(), (7400, 7404));
+        assert_eq!(
+            allocator.service_ports("cap-view").unwrap().get("web"),
+            Some(&7401)
+        );
+        assert_eq!(
+            allocator.allocate_service("cap-view", "api").unwrap(),
+            Some(7402)
+        );
+        allocator.reserve(7404, "dns").unwrap();
+    }
+
+    let reopened = CapsuleStore::open(&dir.path().join("capsules.sqlite3")).unwrap();
+    assert_eq!(
+        reopened.list_service_ports("cap-view").unwrap().get("api"),
+        Some(&7402)
+    );
+    let usage = reopened.pool_usage("default").unwrap();
+    assert_eq!(usage.used, vec![7400, 7401, 7402, 7404]);
+    assert_eq!(usage.available, 1);
 }
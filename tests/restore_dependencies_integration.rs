@@ -0,0 +1,213 @@
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+    time::Duration,
+};
+
+use nexum::{
+    capsule::{Capsule, CapsuleMode, CapsuleState},
+    restore::SignalType,
+    routing::{RouteCommand, RouteOutcome, send_command},
+    runflow::{RestoreRunInput, run_restore_flow},
+    store::CapsuleStore,
+};
+use tempfile::tempdir;
+
+fn wait_for_socket(socket: &Path) {
+    for _ in 0..40 {
+        if socket.exists() {
+            return;
+        }
+        std::thread::sleep(Duration::from_millis(25));
+    }
+    panic!("socket not ready: {}", socket.display());
+}
+
+fn seed(root: &Path) -> PathBuf {
+    let capsule_db = root.join("capsules.sqlite3");
+    let api_repo = root.join("api");
+    std::fs::create_dir_all(&api_repo).unwrap();
+    std::fs::write(
+        api_repo.join("nexum.toml"),
+        "upstream = \"127.0.0.1:5710\"\n\n[services.graphql]\nport = 5711\n",
+    )
+    .unwrap();
+
+    let mut store = CapsuleStore::open(&capsule_db).unwrap();
+    store
+        .upsert(
+            Capsule::new("cap-api", "Dep Api", CapsuleMode::HostDefault, 1)
+                .with_repo_path(&api_repo.display().to_string()),
+        )
+        .unwrap();
+    store
+        .upsert(Capsule::new(
+            "cap-front",
+            "Dep Front",
+            CapsuleMode::HostDefault,
+            2,
+        ))
+        .unwrap();
+    store.add_dependency("cap-front", "cap-api", None).unwrap();
+    capsule_db
+}
+
+fn front_input(root: &Path, capsule_db: &Path, socket: Option<PathBuf>) -> RestoreRunInput {
+    RestoreRunInput {
+        capsule_id: "cap-front".into(),
+        display_name: "Dep Front".into(),
+        workspace: 2,
+        signal: SignalType::NeedsDecision,
+        terminal_cmd: "cd /workspace/front && nix develop".into(),
+        editor_target: "/workspace/front".into(),
+        browser_url: "https://dep-front.nexum.local".into(),
+        route_upstream: "127.0.0.1:5720".into(),
+        routing_socket: socket,
+        identity_collision: false,
+        high_risk_secret_workflow: false,
+        force_isolated_mode: false,
+        capsule_db: Some(capsule_db.to_path_buf()),
+        tls_dir: root.join("tls"),
+        events_db: root.join("events.sqlite3"),
+    }
+}
+
+#[test]
+fn restore_brings_up_stalled_dependencies_first() {
+    let dir = tempdir().unwrap();
+    let capsule_db = seed(dir.path());
+    CapsuleStore::open(&capsule_db)
+        .unwrap()
+        .transition_state(
+            "cap-api",
+            CapsuleState::Restoring,
+            "runflow",
+            "restore started",
+        )
+        .unwrap();
+
+    let summary = run_restore_flow(front_input(dir.path(), &capsule_db, None)).unwrap();
+    assert!(!summary.degraded);
+    assert_eq!(summary.dependencies.len(), 1);
+    assert!(summary.dependencies[0].restored);
+    assert_eq!(summary.dependencies[0].state, CapsuleState::Ready);
+    assert_eq!(summary.events_written, 4);
+
+    let store = CapsuleStore::open(&capsule_db).unwrap();
+    assert_eq!(
+        store.get("cap-api").unwrap().unwrap().state,
+        CapsuleState::Ready
+    );
+    assert_eq!(store.activity("cap-api").unwrap().restore_count, 1);
+}
+
+#[test]
+fn archived_dependency_degrades_the_dependent_without_unarchiving() {
+    let dir = tempdir().unwrap();
+    let capsule_db = seed(dir.path());
+    CapsuleStore::open(&capsule_db)
+        .unwrap()
+        .transition_state("cap-api", CapsuleState::Archived, "test", "parked")
+        .unwrap();
+
+    let summary = run_restore_flow(front_input(dir.path(), &capsule_db, None)).unwrap();
+    assert!(summary.degraded);
+    assert_eq!(
+        summary.degraded_reason.as_deref(),
+        Some("dependency_archived: cap-api")
+    );
+    assert!(!summary.dependencies[0].restored);
+
+    let store = CapsuleStore::open(&capsule_db).unwrap();
+    assert_eq!(
+        store.get("cap-api").unwrap().unwrap().state,
+        CapsuleState::Archived
+    );
+}
+
+#[test]
+fn degraded_dependency_degrades_the_dependent_restore() {
+    let dir = tempdir().unwrap();
+    let capsule_db = seed(dir.path());
+    CapsuleStore::open(&capsule_db)
+        .unwrap()
+        .transition_state(
+            "cap-api",
+            CapsuleState::Degraded,
+            "runflow",
+            "route_unavailable: daemon down",
+        )
+        .unwrap();
+
+    let summary = run_restore_flow(front_input(dir.path(), &capsule_db, None)).unwrap();
+    assert!(summary.degraded);
+    assert_eq!(
+        summary.degraded_reason.as_deref(),
+        Some("dependency_degraded: cap-api: route_unavailable: daemon down")
+    );
+    assert!(!summary.dependencies[0].restored);
+
+    let store = CapsuleStore::open(&capsule_db).unwrap();
+    let front = store.get("cap-front").unwrap().unwrap();
+    assert_eq!(front.state, CapsuleState::Degraded);
+    assert_eq!(
+        store.history("cap-front").unwrap().last().unwrap().reason,
+        "dependency_degraded: cap-api: route_unavailable: daemon down"
+    );
+}
+
+#[test]
+fn restore_registers_named_dependency_routes_with_the_daemon() {
+    let dir = tempdir().unwrap();
+    let capsule_db = seed(dir.path());
+    let socket = dir.path().join("nexumd.sock");
+    CapsuleStore::open(&capsule_db)
+        .unwrap()
+        .add_dependency("cap-front", "cap-api", Some("graphql"))
+        .unwrap();
+
+    let mut daemon = Command::new(assert_cmd::cargo::cargo_bin!("nexumd"))
+        .arg("serve")
+        .arg("--socket")
+        .arg(&socket)
+        .spawn()
+        .unwrap();
+    wait_for_socket(&socket);
+
+    let summary =
+        run_restore_flow(front_input(dir.path(), &capsule_db, Some(socket.clone()))).unwrap();
+    assert!(!summary.degraded, "{:?}", summary.degraded_reason);
+    let domains = summary
+        .dependencies
+        .iter()
+        .map(|dependency| dependency.domain.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        domains,
+        vec!["dep-api.nexum.local", "graphql.dep-api.nexum.local"]
+    );
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_io()
+        .enable_time()
+        .build()
+        .unwrap();
+    let resolved = runtime
+        .block_on(send_command(
+            &socket,
+            RouteCommand::Resolve {
+                domain: "graphql.dep-api.nexum.local".into(),
+            },
+        ))
+        .unwrap();
+    match resolved {
+        RouteOutcome::Resolved { route: Some(route) } => {
+            assert_eq!(route.capsule_id, "cap-api");
+            assert_eq!(route.upstream, "127.0.0.1:5711");
+        }
+        other => panic!("unexpected outcome: {other:?}"),
+    }
+
+    daemon.kill().unwrap();
+    let _ = daemon.wait();
+}
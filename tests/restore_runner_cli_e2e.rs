@@ -192,6 +192,14 @@ fn nexumctl_run_restore_uses_profile_fallback_when_identity_collision_flag_is_se
     let script = value["shell_script"].as_str().unwrap();
     assert!(script.contains("firefox --profile"));
     assert!(script.contains("runner-collision.nexum.local"));
+    assert!(script.contains("certutil -A -d 'sql:/tmp/nexum/profiles/cap-run-cli-collision'"));
+    assert!(std::path::Path::new("/tmp/nexum/profiles/cap-run-cli-collision/user.js").exists());
+    assert!(
+        std::path::Path::new(
+            "/tmp/nexum/profiles/cap-run-cli-collision/nexum-trust/runner-collision.nexum.local.crt.pem"
+        )
+        .exists()
+    );
     assert_eq!(
         value["run_mode"],
         Value::String("isolated_nix_shell".into())
@@ -348,6 +356,17 @@ fn nexumctl_run_restore_marks_capsule_ready_in_store_on_success() {
         .unwrap();
     assert!(set_archived.status.success());
 
+    let unarchive = Command::new(nexumctl)
+        .arg("capsule")
+        .arg("unarchive")
+        .arg("--db")
+        .arg(&capsule_db)
+        .arg("--id")
+        .arg("cap-state-ready")
+        .output()
+        .unwrap();
+    assert!(unarchive.status.success());
+
     let restore = Command::new(nexumctl)
         .arg("run")
         .arg("restore")
@@ -565,3 +584,73 @@ fn nexumctl_run_restore_capsule_requires_explicit_surfaces_when_repo_path_missin
     assert!(stderr.contains("--terminal"));
     assert!(stderr.contains("--editor"));
 }
+
+#[test]
+fn nexumctl_run_restore_capsule_merges_repo_manifest_under_cli_flags() {
+    let dir = tempdir().unwrap();
+    let capsule_db = dir.path().join("capsules.sqlite3");
+    let tls_dir = dir.path().join("tls");
+    let events_db = dir.path().join("events.sqlite3");
+    let repo = dir.path().join("repo");
+    std::fs::create_dir_all(&repo).unwrap();
+    std::fs::write(
+        repo.join("nexum.toml"),
+        r#"
+upstream = "127.0.0.1:4790"
+
+[surfaces]
+terminal = ["nix develop", "cargo watch -x test"]
+editor = "src"
+browser = "https://manifest.nexum.local/docs"
+"#,
+    )
+    .unwrap();
+    let nexumctl = assert_cmd::cargo::cargo_bin!("nexumctl");
+
+    let create = Command::new(nexumctl)
+        .arg("capsule")
+        .arg("create")
+        .arg("--db")
+        .arg(&capsule_db)
+        .arg("--id")
+        .arg("cap-restore-manifest")
+        .arg("--name")
+        .arg("Restore Manifest")
+        .arg("--workspace")
+        .arg("18")
+        .arg("--mode")
+        .arg("host_default")
+        .arg("--repo-path")
+        .arg(&repo)
+        .output()
+        .unwrap();
+    assert!(create.status.success());
+
+    let out = Command::new(nexumctl)
+        .arg("run")
+        .arg("restore-capsule")
+        .arg("--capsule-db")
+        .arg(&capsule_db)
+        .arg("--capsule-id")
+        .arg("cap-restore-manifest")
+        .arg("--signal")
+        .arg("needs_decision")
+        .arg("--browser")
+        .arg("https://override.nexum.local")
+        .arg("--tls-dir")
+        .arg(&tls_dir)
+        .arg("--events-db")
+        .arg(&events_db)
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    let value: Value = serde_json::from_slice(&out.stdout).unwrap();
+    let script = value["shell_script"].as_str().unwrap();
+    assert!(script.contains(&format!(
+        "cd {} && nix develop && cargo watch -x test",
+        repo.display()
+    )));
+    assert!(script.contains(&format!("code {}", repo.join("src").display())));
+    assert!(script.contains("xdg-open https://override.nexum.local"));
+    assert!(!script.contains("manifest.nexum.local/docs"));
+}
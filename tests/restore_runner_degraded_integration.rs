@@ -1,6 +1,10 @@
+use std::path::Path;
+
 use nexum::{
+    capsule::{Capsule, CapsuleMode, CapsuleState},
     restore::SignalType,
-    runflow::{RestoreRunInput, run_restore_flow},
+    runflow::{RestoreRunInput, RunFlowError, run_restore_flow},
+    store::{CapsuleStore, StoreError},
 };
 use tempfile::tempdir;
 
@@ -37,3 +41,80 @@ fn run_restore_flow_degrades_when_routing_socket_is_unavailable() {
             .contains("route_unavailable")
     );
 }
+
+fn stored_capsule_input(root: &Path, capsule_id: &str) -> RestoreRunInput {
+    let capsule_db = root.join("capsules.sqlite3");
+    CapsuleStore::open(&capsule_db)
+        .unwrap()
+        .upsert(Capsule::new(
+            capsule_id,
+            "Stored Restore",
+            CapsuleMode::HostDefault,
+            14,
+        ))
+        .unwrap();
+
+    RestoreRunInput {
+        capsule_id: capsule_id.into(),
+        display_name: "Stored Restore".into(),
+        workspace: 14,
+        signal: SignalType::NeedsDecision,
+        terminal_cmd: "cd /workspace/stored && nix develop".into(),
+        editor_target: "/workspace/stored".into(),
+        browser_url: "https://stored-restore.nexum.local".into(),
+        route_upstream: "127.0.0.1:4910".into(),
+        routing_socket: None,
+        identity_collision: false,
+        high_risk_secret_workflow: false,
+        force_isolated_mode: false,
+        capsule_db: Some(capsule_db),
+        tls_dir: root.join("tls"),
+        events_db: root.join("events.sqlite3"),
+    }
+}
+
+#[test]
+fn run_restore_flow_rejects_archived_capsules() {
+    let dir = tempdir().unwrap();
+    let input = stored_capsule_input(dir.path(), "cap-archived-1");
+    let capsule_db = input.capsule_db.clone().unwrap();
+    CapsuleStore::open(&capsule_db)
+        .unwrap()
+        .transition_state("cap-archived-1", CapsuleState::Archived, "test", "parked")
+        .unwrap();
+
+    let error = run_restore_flow(input).unwrap_err();
+
+    assert!(matches!(
+        error,
+        RunFlowError::Store(StoreError::IllegalTransition { .. })
+    ));
+    let store = CapsuleStore::open(&capsule_db).unwrap();
+    assert_eq!(
+        store.get("cap-archived-1").unwrap().unwrap().state,
+        CapsuleState::Archived
+    );
+}
+
+#[test]
+fn run_restore_flow_degrades_capsule_when_restore_fails_midway() {
+    let dir = tempdir().unwrap();
+    let mut input = stored_capsule_input(dir.path(), "cap-midway-1");
+    let capsule_db = input.capsule_db.clone().unwrap();
+    let blocked_tls_dir = dir.path().join("tls-is-a-file");
+    std::fs::write(&blocked_tls_dir, "").unwrap();
+    input.tls_dir = blocked_tls_dir;
+
+    let error = run_restore_flow(input).unwrap_err();
+
+    assert!(matches!(error, RunFlowError::Tls(_)));
+    let store = CapsuleStore::open(&capsule_db).unwrap();
+    assert_eq!(
+        store.get("cap-midway-1").unwrap().unwrap().state,
+        CapsuleState::Degraded
+    );
+    let history = store.history("cap-midway-1").unwrap();
+    let last = history.last().unwrap();
+    assert_eq!(last.from_state, CapsuleState::Restoring);
+    assert!(last.reason.starts_with("tls:"));
+}
@@ -1,7 +1,9 @@
 use nexum::{
+    capsule::{Capsule, CapsuleMode},
     events::EventStore,
     restore::SignalType,
     runflow::{RestoreRunInput, run_restore_flow},
+    store::CapsuleStore,
 };
 use tempfile::tempdir;
 
@@ -49,3 +51,120 @@ fn run_restore_flow_returns_script_and_persists_events() {
     let events = store.list_for_capsule("cap-run-1").unwrap();
     assert_eq!(events.len(), 3);
 }
+
+#[test]
+fn run_restore_flow_exports_capsule_env_without_leaking_secrets() {
+    let dir = tempdir().unwrap();
+    let capsule_db = dir.path().join("capsules.sqlite3");
+    let events_db = dir.path().join("events.sqlite3");
+
+    let mut store = CapsuleStore::open(&capsule_db).unwrap();
+    store
+        .upsert(Capsule::new(
+            "cap-run-env",
+            "Runner Env",
+            CapsuleMode::HostDefault,
+            9,
+        ))
+        .unwrap();
+    store
+        .set_env("cap-run-env", "DATABASE_URL", "postgres://localhost/app")
+        .unwrap();
+    store
+        .set_secret("cap-run-env", "API_KEY", "sk-live-456", "hunter2")
+        .unwrap();
+
+    let summary = run_restore_flow(RestoreRunInput {
+        capsule_id: "cap-run-env".into(),
+        display_name: "Runner Env".into(),
+        workspace: 9,
+        signal: SignalType::PassiveCompletion,
+        terminal_cmd: "cd /workspace/env && nix develop".into(),
+        editor_target: "/workspace/env".into(),
+        browser_url: "https://runner-env.nexum.local".into(),
+        route_upstream: "127.0.0.1:4701".into(),
+        routing_socket: None,
+        identity_collision: false,
+        high_risk_secret_workflow: false,
+        force_isolated_mode: false,
+        capsule_db: Some(capsule_db.clone()),
+        tls_dir: dir.path().join("tls"),
+        events_db: events_db.clone(),
+    })
+    .unwrap();
+
+    assert!(
+        summary
+            .shell_script
+            .contains("export DATABASE_URL='postgres://localhost/app'")
+    );
+    assert!(
+        summary
+            .shell_script
+            .contains("export API_KEY=\"$(nexumctl capsule env reveal")
+    );
+    assert!(
+        !serde_json::to_string(&summary)
+            .unwrap()
+            .contains("sk-live-456")
+    );
+    assert_eq!(summary.events_written, 4);
+
+    let events = EventStore::open(&events_db)
+        .unwrap()
+        .list_for_capsule("cap-run-env")
+        .unwrap();
+    let env_event = events
+        .iter()
+        .find(|event| event.component == "env")
+        .unwrap();
+    assert_eq!(
+        env_event.message,
+        "env injected: API_KEY=********, DATABASE_URL"
+    );
+}
+
+#[test]
+fn run_restore_flow_records_capsule_activity() {
+    let dir = tempdir().unwrap();
+    let capsule_db = dir.path().join("capsules.sqlite3");
+
+    let mut store = CapsuleStore::open(&capsule_db).unwrap();
+    store
+        .upsert(Capsule::new(
+            "cap-run-activity",
+            "Runner Activity",
+            CapsuleMode::HostDefault,
+            10,
+        ))
+        .unwrap();
+    assert_eq!(store.activity("cap-run-activity").unwrap().restore_count, 0);
+
+    let restore = |signal| {
+        run_restore_flow(RestoreRunInput {
+            capsule_id: "cap-run-activity".into(),
+            display_name: "Runner Activity".into(),
+            workspace: 10,
+            signal,
+            terminal_cmd: "cd /workspace/activity".into(),
+            editor_target: "/workspace/activity".into(),
+            browser_url: "https://runner-activity.nexum.local".into(),
+            route_upstream: "127.0.0.1:4702".into(),
+            routing_socket: None,
+            identity_collision: false,
+            high_risk_secret_workflow: false,
+            force_isolated_mode: false,
+            capsule_db: Some(capsule_db.clone()),
+            tls_dir: dir.path().join("tls"),
+            events_db: dir.path().join("events.sqlite3"),
+        })
+        .unwrap()
+    };
+    restore(SignalType::NeedsDecision);
+    restore(SignalType::CriticalFailure);
+
+    let activity = store.activity("cap-run-activity").unwrap();
+    assert_eq!(activity.restore_count, 2);
+    assert_eq!(activity.last_signal.as_deref(), Some("critical_failure"));
+    assert!(activity.last_restored_unix_ms.unwrap() > 0);
+}
@@ -81,3 +81,168 @@ fn nexumctl_routing_health_register_resolve_remove() {
     daemon.kill().unwrap();
     let _ = daemon.wait();
 }
+
+#[test]
+fn nexumctl_routing_caller_policy_uses_capsule_client_certs() {
+    let dir = tempdir().unwrap();
+    let socket = dir.path().join("nexumd.sock");
+    let tls_dir = dir.path().join("tls");
+    let policy_file = dir.path().join("state").join("policies.json");
+    let nexumctl = assert_cmd::cargo::cargo_bin!("nexumctl");
+
+    let issue = Command::new(nexumctl)
+        .arg("tls")
+        .arg("issue-client")
+        .arg("--dir")
+        .arg(&tls_dir)
+        .arg("--capsule-id")
+        .arg("cap-web")
+        .output()
+        .unwrap();
+    assert!(issue.status.success());
+    let issued: Value = serde_json::from_slice(&issue.stdout).unwrap();
+    let cert_path = issued["cert_path"].as_str().unwrap().to_string();
+    let key_path = issued["key_path"].as_str().unwrap().to_string();
+
+    let mut daemon = Command::new(assert_cmd::cargo::cargo_bin!("nexumd"))
+        .arg("serve")
+        .arg("--socket")
+        .arg(&socket)
+        .arg("--client-ca-dir")
+        .arg(&tls_dir)
+        .arg("--policy-file")
+        .arg(&policy_file)
+        .spawn()
+        .unwrap();
+
+    for _ in 0..40 {
+        if socket.exists() {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(25));
+    }
+
+    let register = Command::new(nexumctl)
+        .arg("routing")
+        .arg("register")
+        .arg("--socket")
+        .arg(&socket)
+        .arg("--capsule-id")
+        .arg("cap-api")
+        .arg("--domain")
+        .arg("api.nexum.local")
+        .arg("--upstream")
+        .arg("127.0.0.1:4810")
+        .output()
+        .unwrap();
+    assert!(register.status.success());
+
+    let policy = Command::new(nexumctl)
+        .arg("routing")
+        .arg("set-policy")
+        .arg("--socket")
+        .arg(&socket)
+        .arg("--domain")
+        .arg("api.nexum.local")
+        .arg("--allow")
+        .arg("cap-web")
+        .output()
+        .unwrap();
+    assert!(policy.status.success());
+    let policy_json: Value = serde_json::from_slice(&policy.stdout).unwrap();
+    assert_eq!(policy_json["kind"], Value::String("policy_updated".into()));
+
+    let anonymous = Command::new(nexumctl)
+        .arg("routing")
+        .arg("authorize")
+        .arg("--socket")
+        .arg(&socket)
+        .arg("--domain")
+        .arg("api.nexum.local")
+        .output()
+        .unwrap();
+    let anonymous_json: Value = serde_json::from_slice(&anonymous.stdout).unwrap();
+    assert_eq!(
+        anonymous_json["code"],
+        Value::String("client_cert_required".into())
+    );
+
+    let authorized = Command::new(nexumctl)
+        .arg("routing")
+        .arg("authorize")
+        .arg("--socket")
+        .arg(&socket)
+        .arg("--domain")
+        .arg("api.nexum.local")
+        .arg("--client-cert")
+        .arg(&cert_path)
+        .arg("--client-key")
+        .arg(&key_path)
+        .output()
+        .unwrap();
+    assert!(authorized.status.success());
+    let authorized_json: Value = serde_json::from_slice(&authorized.stdout).unwrap();
+    assert_eq!(
+        authorized_json["kind"],
+        Value::String("caller_authorized".into())
+    );
+    assert_eq!(
+        authorized_json["headers"]["x-nexum-capsule-id"],
+        Value::String("cap-web".into())
+    );
+
+    daemon.kill().unwrap();
+    let _ = daemon.wait();
+    assert!(policy_file.exists());
+
+    let _ = std::fs::remove_file(&socket);
+    let mut restarted = Command::new(assert_cmd::cargo::cargo_bin!("nexumd"))
+        .arg("serve")
+        .arg("--socket")
+        .arg(&socket)
+        .arg("--client-ca-dir")
+        .arg(&tls_dir)
+        .arg("--policy-file")
+        .arg(&policy_file)
+        .spawn()
+        .unwrap();
+    for _ in 0..40 {
+        if socket.exists() {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(25));
+    }
+
+    let reregister = Command::new(nexumctl)
+        .arg("routing")
+        .arg("register")
+        .arg("--socket")
+        .arg(&socket)
+        .arg("--capsule-id")
+        .arg("cap-api")
+        .arg("--domain")
+        .arg("api.nexum.local")
+        .arg("--upstream")
+        .arg("127.0.0.1:4810")
+        .output()
+        .unwrap();
+    assert!(reregister.status.success());
+
+    let after_restart = Command::new(nexumctl)
+        .arg("routing")
+        .arg("authorize")
+        .arg("--socket")
+        .arg(&socket)
+        .arg("--domain")
+        .arg("api.nexum.local")
+        .output()
+        .unwrap();
+    let after_restart_json: Value = serde_json::from_slice(&after_restart.stdout).unwrap();
+    assert_eq!(
+        after_restart_json["code"],
+        Value::String("client_cert_required".into())
+    );
+
+    restarted.kill().unwrap();
+    let _ = restarted.wait();
+}
@@ -1,4 +1,14 @@
-use nexum::routing::{RouteCommand, RouteOutcome, RouterState};
+use std::path::Path;
+
+use nexum::{
+    crypto::to_hex,
+    routing::{
+        CAPSULE_ID_HEADER, MAX_PENDING_CHALLENGES, RouteCommand, RouteOutcome, RouterState,
+        RoutingError, caller_proof_message,
+    },
+    tls::{ClientCertificateRecord, ensure_client_cert, sign_with_client_key},
+};
+use tempfile::tempdir;
 
 #[test]
 fn register_and_resolve_roundtrip() {
@@ -69,3 +79,275 @@ fn list_routes_is_sorted_by_domain() {
         other => panic!("unexpected outcome: {other:?}"),
     }
 }
+
+fn api_router(ca_dir: &Path) -> RouterState {
+    let mut state = RouterState::with_caller_attestation(Some(ca_dir.to_path_buf()), false);
+    state.handle(RouteCommand::Register {
+        capsule_id: "cap-api".into(),
+        domain: "api.nexum.local".into(),
+        upstream: "127.0.0.1:4400".into(),
+    });
+    state
+}
+
+fn challenge(state: &mut RouterState, domain: &str) -> String {
+    match state.handle(RouteCommand::CallerChallenge {
+        domain: domain.into(),
+    }) {
+        RouteOutcome::CallerChallenge { nonce, .. } => nonce,
+        other => panic!("unexpected outcome: {other:?}"),
+    }
+}
+
+fn signed_authorize(
+    domain: &str,
+    client: &ClientCertificateRecord,
+    signing_key: &str,
+    nonce: String,
+) -> RouteCommand {
+    let signature = sign_with_client_key(
+        Path::new(signing_key),
+        caller_proof_message(domain, &nonce).as_bytes(),
+    )
+    .unwrap();
+    RouteCommand::AuthorizeCaller {
+        domain: domain.into(),
+        client_cert_pem: Some(std::fs::read_to_string(&client.cert_path).unwrap()),
+        nonce: Some(nonce),
+        signature: Some(to_hex(&signature)),
+    }
+}
+
+fn error_code(outcome: RouteOutcome) -> String {
+    match outcome {
+        RouteOutcome::Error { code, .. } => code,
+        other => panic!("unexpected outcome: {other:?}"),
+    }
+}
+
+#[test]
+fn caller_policy_admits_only_allowed_capsule_certificates() {
+    let dir = tempdir().unwrap();
+    let allowed = ensure_client_cert(dir.path(), "cap-web", 30).unwrap();
+    let denied = ensure_client_cert(dir.path(), "cap-other", 30).unwrap();
+    let mut state = api_router(dir.path());
+
+    let open = state.handle(RouteCommand::AuthorizeCaller {
+        domain: "api.nexum.local".into(),
+        client_cert_pem: None,
+        nonce: None,
+        signature: None,
+    });
+    assert!(matches!(
+        open,
+        RouteOutcome::CallerAuthorized {
+            caller_capsule_id: None,
+            ..
+        }
+    ));
+
+    let policy = state.handle(RouteCommand::SetCallerPolicy {
+        domain: "api.nexum.local".into(),
+        allowed_callers: vec!["cap-web".into()],
+    });
+    assert!(matches!(policy, RouteOutcome::PolicyUpdated { .. }));
+
+    let anonymous = state.handle(RouteCommand::AuthorizeCaller {
+        domain: "api.nexum.local".into(),
+        client_cert_pem: None,
+        nonce: None,
+        signature: None,
+    });
+    assert_eq!(error_code(anonymous), "client_cert_required");
+
+    let nonce = challenge(&mut state, "api.nexum.local");
+    let forbidden = state.handle(signed_authorize(
+        "api.nexum.local",
+        &denied,
+        &denied.key_path,
+        nonce,
+    ));
+    assert_eq!(error_code(forbidden), "caller_forbidden");
+
+    let nonce = challenge(&mut state, "api.nexum.local");
+    let admitted = state.handle(signed_authorize(
+        "api.nexum.local",
+        &allowed,
+        &allowed.key_path,
+        nonce,
+    ));
+    match admitted {
+        RouteOutcome::CallerAuthorized {
+            upstream,
+            caller_capsule_id,
+            headers,
+            ..
+        } => {
+            assert_eq!(upstream, "127.0.0.1:4400");
+            assert_eq!(caller_capsule_id.as_deref(), Some("cap-web"));
+            assert_eq!(
+                headers.get(CAPSULE_ID_HEADER).map(String::as_str),
+                Some("cap-web")
+            );
+        }
+        other => panic!("unexpected outcome: {other:?}"),
+    }
+}
+
+#[test]
+fn presented_certificate_requires_proof_of_key_possession() {
+    let dir = tempdir().unwrap();
+    let web = ensure_client_cert(dir.path(), "cap-web", 30).unwrap();
+    let other = ensure_client_cert(dir.path(), "cap-other", 30).unwrap();
+    let mut state = api_router(dir.path());
+
+    let unsigned = state.handle(RouteCommand::AuthorizeCaller {
+        domain: "api.nexum.local".into(),
+        client_cert_pem: Some(std::fs::read_to_string(&web.cert_path).unwrap()),
+        nonce: None,
+        signature: None,
+    });
+    assert_eq!(error_code(unsigned), "client_proof_required");
+
+    let nonce = challenge(&mut state, "api.nexum.local");
+    let impostor = state.handle(signed_authorize(
+        "api.nexum.local",
+        &web,
+        &other.key_path,
+        nonce,
+    ));
+    assert_eq!(error_code(impostor), "client_proof_invalid");
+
+    let nonce = challenge(&mut state, "api.nexum.local");
+    let first = state.handle(signed_authorize(
+        "api.nexum.local",
+        &web,
+        &web.key_path,
+        nonce.clone(),
+    ));
+    assert!(matches!(first, RouteOutcome::CallerAuthorized { .. }));
+    let replayed = state.handle(signed_authorize(
+        "api.nexum.local",
+        &web,
+        &web.key_path,
+        nonce,
+    ));
+    assert_eq!(error_code(replayed), "client_proof_invalid");
+}
+
+#[test]
+fn caller_policy_survives_router_restart_with_policy_file() {
+    let dir = tempdir().unwrap();
+    let policy_file = dir.path().join("state").join("policies.json");
+    let register = RouteCommand::Register {
+        capsule_id: "cap-api".into(),
+        domain: "api.nexum.local".into(),
+        upstream: "127.0.0.1:4400".into(),
+    };
+
+    let mut first = RouterState::default()
+        .with_policy_file(policy_file.clone())
+        .unwrap();
+    first.handle(register.clone());
+    first.handle(RouteCommand::SetCallerPolicy {
+        domain: "api.nexum.local".into(),
+        allowed_callers: vec!["cap-web".into()],
+    });
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(dir.path().join("state"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o700);
+    }
+
+    let mut restarted = RouterState::default()
+        .with_policy_file(policy_file)
+        .unwrap();
+    restarted.handle(register);
+    let anonymous = restarted.handle(RouteCommand::AuthorizeCaller {
+        domain: "api.nexum.local".into(),
+        client_cert_pem: None,
+        nonce: None,
+        signature: None,
+    });
+    assert_eq!(error_code(anonymous), "client_cert_required");
+}
+
+#[test]
+fn reregistering_a_route_keeps_its_caller_policy() {
+    let mut state = RouterState::default();
+    let register = RouteCommand::Register {
+        capsule_id: "cap-api".into(),
+        domain: "api.nexum.local".into(),
+        upstream: "127.0.0.1:4400".into(),
+    };
+    state.handle(register.clone());
+    state.handle(RouteCommand::SetCallerPolicy {
+        domain: "api.nexum.local".into(),
+        allowed_callers: vec!["cap-web".into()],
+    });
+    state.handle(register);
+
+    match state.handle(RouteCommand::Resolve {
+        domain: "api.nexum.local".into(),
+    }) {
+        RouteOutcome::Resolved { route } => {
+            assert_eq!(route.unwrap().allowed_callers, vec!["cap-web".to_string()]);
+        }
+        other => panic!("unexpected outcome: {other:?}"),
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn policy_file_in_a_shared_directory_is_refused() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempdir().unwrap();
+    let shared = dir.path().join("shared");
+    std::fs::create_dir(&shared).unwrap();
+    std::fs::set_permissions(&shared, std::fs::Permissions::from_mode(0o1777)).unwrap();
+
+    let result = RouterState::default().with_policy_file(shared.join("policies.json"));
+    assert!(matches!(result, Err(RoutingError::InsecurePolicyDir(_))));
+}
+
+#[test]
+fn caller_policy_changes_are_limited_to_the_owner_uid() {
+    let mut state = RouterState::default().with_owner_uid(1000);
+    state.handle(RouteCommand::Register {
+        capsule_id: "cap-api".into(),
+        domain: "api.nexum.local".into(),
+        upstream: "127.0.0.1:4400".into(),
+    });
+    let set_policy = RouteCommand::SetCallerPolicy {
+        domain: "api.nexum.local".into(),
+        allowed_callers: vec!["cap-web".into()],
+    };
+
+    let foreign = state.handle_from(Some(1001), set_policy.clone());
+    assert_eq!(error_code(foreign), "policy_forbidden");
+    let unknown = state.handle_from(None, set_policy.clone());
+    assert_eq!(error_code(unknown), "policy_forbidden");
+
+    let owner = state.handle_from(Some(1000), set_policy);
+    assert!(matches!(owner, RouteOutcome::PolicyUpdated { .. }));
+}
+
+#[test]
+fn pending_challenges_are_capped() {
+    let dir = tempdir().unwrap();
+    let mut state = api_router(dir.path());
+    for _ in 0..MAX_PENDING_CHALLENGES {
+        challenge(&mut state, "api.nexum.local");
+    }
+
+    let refused = state.handle(RouteCommand::CallerChallenge {
+        domain: "api.nexum.local".into(),
+    });
+    assert_eq!(error_code(refused), "challenge_limit");
+}
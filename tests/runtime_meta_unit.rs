@@ -1,6 +1,12 @@
+use std::path::Path;
+
 use nexum::{
     capsule::{Capsule, CapsuleMode},
-    runtime_meta::{capsule_runtime_env, terminal_process_label},
+    runtime_meta::{
+        capsule_env_exports, capsule_runtime_env, is_valid_env_key, secrets_permitted,
+        service_port_env, terminal_process_label,
+    },
+    store::{CapsuleEnvVar, SECRET_MASK},
 };
 
 #[test]
@@ -21,3 +27,56 @@ fn terminal_process_label_includes_capsule_id() {
     let label = terminal_process_label("cap-meta-1");
     assert_eq!(label, "nexum-terminal-cap-meta-1");
 }
+
+#[test]
+fn service_ports_are_exported_as_uppercase_env_vars() {
+    let ports = [("web".to_string(), 4302), ("admin-ui".to_string(), 4303)]
+        .into_iter()
+        .collect();
+    let env = service_port_env(&ports);
+
+    assert_eq!(env.get("NEXUM_PORT_WEB"), Some(&"4302".to_string()));
+    assert_eq!(env.get("NEXUM_PORT_ADMIN_UI"), Some(&"4303".to_string()));
+}
+
+#[test]
+fn capsule_env_exports_quote_values_and_defer_secrets() {
+    let vars = vec![
+        CapsuleEnvVar {
+            key: "API_KEY".into(),
+            value: SECRET_MASK.into(),
+            secret: true,
+        },
+        CapsuleEnvVar {
+            key: "GREETING".into(),
+            value: "it's on".into(),
+            secret: false,
+        },
+    ];
+
+    let env = capsule_env_exports(Path::new("/var/nexum/capsules.db"), "cap-env", &vars, true);
+    assert_eq!(env.get("GREETING"), Some(&"'it'\"'\"'s on'".to_string()));
+    assert_eq!(
+        env.get("API_KEY"),
+        Some(
+            &"\"$(nexumctl capsule env reveal --db '/var/nexum/capsules.db' --id 'cap-env' --key API_KEY)\""
+                .to_string()
+        )
+    );
+
+    let withheld = capsule_env_exports(Path::new("/db"), "cap-env", &vars, false);
+    assert!(!withheld.contains_key("API_KEY"));
+    assert!(withheld.contains_key("GREETING"));
+}
+
+#[test]
+fn secrets_require_isolation_only_for_high_risk_workflows() {
+    assert!(secrets_permitted(CapsuleMode::HostDefault, false));
+    assert!(!secrets_permitted(CapsuleMode::HostDefault, true));
+    assert!(secrets_permitted(CapsuleMode::IsolatedNixShell, true));
+
+    assert!(is_valid_env_key("DATABASE_URL"));
+    assert!(!is_valid_env_key("NEXUM_CAPSULE_ID"));
+    assert!(!is_valid_env_key("1PASSWORD"));
+    assert!(!is_valid_env_key("lower"));
+}
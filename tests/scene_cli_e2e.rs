@@ -0,0 +1,115 @@
+use std::process::Command;
+
+use serde_json::Value;
+use tempfile::tempdir;
+
+fn run(args: &[&str]) -> std::process::Output {
+    Command::new(assert_cmd::cargo::cargo_bin!("nexumctl"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn nexumctl_scene_restores_members_with_one_summary() {
+    let dir = tempdir().unwrap();
+    let capsule_db = dir.path().join("capsules.sqlite3");
+    let db = capsule_db.to_str().unwrap();
+    let tls_dir = dir.path().join("tls");
+    let events_db = dir.path().join("events.sqlite3");
+
+    for (id, workspace, port) in [("cap-front", "1", 5610), ("cap-back", "2", 5620)] {
+        let repo = dir.path().join(id);
+        std::fs::create_dir_all(&repo).unwrap();
+        std::fs::write(
+            repo.join("nexum.toml"),
+            format!("upstream = \"127.0.0.1:{port}\"\n"),
+        )
+        .unwrap();
+        let created = run(&[
+            "capsule",
+            "create",
+            "--db",
+            db,
+            "--id",
+            id,
+            "--name",
+            id,
+            "--workspace",
+            workspace,
+            "--mode",
+            "host_default",
+            "--repo-path",
+            repo.to_str().unwrap(),
+        ]);
+        assert!(created.status.success());
+    }
+
+    let set = run(&[
+        "scene",
+        "set",
+        "--db",
+        db,
+        "--name",
+        "fullstack",
+        "--members",
+        "cap-front:7,cap-back",
+    ]);
+    assert!(set.status.success());
+    let shown = run(&["scene", "show", "--db", db, "--name", "fullstack"]);
+    assert!(shown.status.success());
+    let shown: Value = serde_json::from_slice(&shown.stdout).unwrap();
+    assert_eq!(shown["members"][0]["workspace"], 7);
+    assert_eq!(shown["members"][0]["workspace_override"], 7);
+    assert_eq!(shown["members"][1]["workspace"], 2);
+    assert_eq!(shown["members"][1]["workspace_override"], Value::Null);
+
+    let restored = run(&[
+        "run",
+        "restore-scene",
+        "--capsule-db",
+        db,
+        "--scene",
+        "fullstack",
+        "--signal",
+        "needs_decision",
+        "--tls-dir",
+        tls_dir.to_str().unwrap(),
+        "--events-db",
+        events_db.to_str().unwrap(),
+    ]);
+    assert!(restored.status.success(), "{restored:?}");
+    let summary: Value = serde_json::from_slice(&restored.stdout).unwrap();
+    assert_eq!(summary["restored"], 2);
+    assert_eq!(summary["failed"], 0);
+    assert_eq!(summary["parallel_groups"], 2);
+    assert_eq!(summary["members"][0]["capsule_id"], "cap-front");
+    assert_eq!(summary["members"][0]["workspace"], 7);
+    assert_eq!(summary["attention"]["priority"], "active");
+    assert_eq!(
+        summary["attention"]["summary"],
+        "scene fullstack: 2 restored, 0 degraded, 0 failed"
+    );
+
+    let missing = run(&[
+        "scene",
+        "set",
+        "--db",
+        db,
+        "--name",
+        "broken",
+        "--members",
+        "cap-front,cap-ghost",
+    ]);
+    assert!(!missing.status.success());
+    assert!(
+        String::from_utf8(missing.stderr)
+            .unwrap()
+            .contains("capsule not found: cap-ghost")
+    );
+
+    let deleted = run(&["scene", "delete", "--db", db, "--name", "fullstack"]);
+    assert!(deleted.status.success());
+    let listed = run(&["scene", "list", "--db", db]);
+    assert_eq!(String::from_utf8(listed.stdout).unwrap().trim(), "[]");
+}
@@ -0,0 +1,230 @@
+use std::path::Path;
+
+use nexum::{
+    attention::AttentionPriority,
+    capsule::{Capsule, CapsuleMode, CapsuleState},
+    restore::SignalType,
+    scene::{SceneError, SceneRestoreInput, restore_scene},
+    store::{CapsuleStore, Scene, SceneMember},
+};
+use tempfile::tempdir;
+
+fn repo_with_manifest(root: &Path, name: &str, port: u16) -> String {
+    let repo = root.join(name);
+    std::fs::create_dir_all(&repo).unwrap();
+    std::fs::write(
+        repo.join("nexum.toml"),
+        format!("upstream = \"127.0.0.1:{port}\"\n"),
+    )
+    .unwrap();
+    repo.display().to_string()
+}
+
+fn seed_scene(root: &Path) -> std::path::PathBuf {
+    let capsule_db = root.join("capsules.sqlite3");
+    let mut store = CapsuleStore::open(&capsule_db).unwrap();
+    store
+        .upsert(
+            Capsule::new("cap-web", "Scene Web", CapsuleMode::HostDefault, 1)
+                .with_repo_path(&repo_with_manifest(root, "web", 5510)),
+        )
+        .unwrap();
+    store
+        .upsert(
+            Capsule::new("cap-api", "Scene Api", CapsuleMode::HostDefault, 2)
+                .with_repo_path(&repo_with_manifest(root, "api", 5520)),
+        )
+        .unwrap();
+    store
+        .upsert(Capsule::new(
+            "cap-infra",
+            "Scene Infra",
+            CapsuleMode::HostDefault,
+            3,
+        ))
+        .unwrap();
+    store
+        .set_scene(&Scene {
+            name: "stack".to_string(),
+            members: vec![
+                SceneMember {
+                    capsule_id: "cap-web".to_string(),
+                    workspace: Some(2),
+                },
+                SceneMember {
+                    capsule_id: "cap-api".to_string(),
+                    workspace: None,
+                },
+                SceneMember {
+                    capsule_id: "cap-infra".to_string(),
+                    workspace: None,
+                },
+            ],
+        })
+        .unwrap();
+    capsule_db
+}
+
+fn input(root: &Path, capsule_db: &Path, signal: SignalType) -> SceneRestoreInput {
+    SceneRestoreInput {
+        capsule_db: capsule_db.to_path_buf(),
+        scene: "stack".to_string(),
+        signal,
+        routing_socket: None,
+        identity_collision: false,
+        high_risk_secret_workflow: false,
+        tls_dir: root.join("tls"),
+        events_db: root.join("events.sqlite3"),
+    }
+}
+
+#[test]
+fn restore_scene_restores_members_and_escalates_failures() {
+    let dir = tempdir().unwrap();
+    let capsule_db = seed_scene(dir.path());
+
+    let summary = restore_scene(&input(
+        dir.path(),
+        &capsule_db,
+        SignalType::PassiveCompletion,
+    ))
+    .unwrap();
+    assert_eq!(summary.scene, "stack");
+    assert_eq!(
+        (summary.restored, summary.degraded, summary.failed),
+        (2, 0, 1)
+    );
+    assert_eq!(summary.parallel_groups, 1);
+    let members = summary
+        .members
+        .iter()
+        .map(|member| (member.capsule_id.as_str(), member.workspace, member.ok))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        members,
+        vec![
+            ("cap-web", Some(2), true),
+            ("cap-api", Some(2), true),
+            ("cap-infra", None, false),
+        ]
+    );
+    assert_eq!(summary.attention.capsule_id, "cap-infra");
+    assert_eq!(summary.attention.priority, AttentionPriority::Blocking);
+    assert!(
+        summary
+            .attention
+            .summary
+            .starts_with("scene stack: 2 restored, 0 degraded, 1 failed (cap-infra: ")
+    );
+
+    let store = CapsuleStore::open(&capsule_db).unwrap();
+    assert_eq!(
+        store.get("cap-web").unwrap().unwrap().state,
+        CapsuleState::Ready
+    );
+    assert_eq!(store.activity("cap-api").unwrap().restore_count, 1);
+}
+
+#[test]
+fn restore_scene_reports_degraded_members_once() {
+    let dir = tempdir().unwrap();
+    let capsule_db = seed_scene(dir.path());
+    CapsuleStore::open(&capsule_db)
+        .unwrap()
+        .set_scene(&Scene {
+            name: "stack".to_string(),
+            members: vec![
+                SceneMember {
+                    capsule_id: "cap-web".to_string(),
+                    workspace: None,
+                },
+                SceneMember {
+                    capsule_id: "cap-api".to_string(),
+                    workspace: None,
+                },
+            ],
+        })
+        .unwrap();
+
+    let summary = restore_scene(&SceneRestoreInput {
+        routing_socket: Some(dir.path().join("missing.sock")),
+        ..input(dir.path(), &capsule_db, SignalType::PassiveCompletion)
+    })
+    .unwrap();
+    assert_eq!(
+        (summary.restored, summary.degraded, summary.failed),
+        (2, 2, 0)
+    );
+    assert_eq!(summary.parallel_groups, 2);
+    assert_eq!(summary.attention.capsule_id, "cap-web");
+    assert_eq!(summary.attention.priority, AttentionPriority::Active);
+    assert!(summary.attention.summary.contains("route_unavailable"));
+
+    let err = restore_scene(&SceneRestoreInput {
+        scene: "missing".to_string(),
+        ..input(dir.path(), &capsule_db, SignalType::NeedsDecision)
+    })
+    .unwrap_err();
+    assert!(matches!(err, SceneError::SceneNotFound(name) if name == "missing"));
+}
+
+#[test]
+fn restore_scene_restores_shared_dependencies_once_before_members() {
+    let dir = tempdir().unwrap();
+    let capsule_db = seed_scene(dir.path());
+    let mut store = CapsuleStore::open(&capsule_db).unwrap();
+    store
+        .upsert(
+            Capsule::new("cap-db", "Scene Db", CapsuleMode::HostDefault, 4)
+                .with_repo_path(&repo_with_manifest(dir.path(), "db", 5530)),
+        )
+        .unwrap();
+    store
+        .transition_state("cap-db", CapsuleState::Restoring, "test", "stalled")
+        .unwrap();
+    store.add_dependency("cap-web", "cap-db", None).unwrap();
+    store.add_dependency("cap-api", "cap-db", None).unwrap();
+    store.add_dependency("cap-web", "cap-api", None).unwrap();
+    store
+        .set_scene(&Scene {
+            name: "stack".to_string(),
+            members: vec![
+                SceneMember {
+                    capsule_id: "cap-web".to_string(),
+                    workspace: None,
+                },
+                SceneMember {
+                    capsule_id: "cap-api".to_string(),
+                    workspace: None,
+                },
+            ],
+        })
+        .unwrap();
+
+    let summary = restore_scene(&input(
+        dir.path(),
+        &capsule_db,
+        SignalType::PassiveCompletion,
+    ))
+    .unwrap();
+    assert_eq!(
+        (summary.restored, summary.degraded, summary.failed),
+        (2, 0, 0)
+    );
+    assert_eq!(summary.parallel_groups, 1);
+    assert_eq!(
+        summary
+            .members
+            .iter()
+            .map(|member| member.capsule_id.as_str())
+            .collect::<Vec<_>>(),
+        vec!["cap-web", "cap-api"]
+    );
+
+    assert_eq!(store.activity("cap-db").unwrap().restore_count, 1);
+    assert_eq!(store.activity("cap-api").unwrap().restore_count, 1);
+    assert_eq!(
+        store.get("cap-db").unwrap().unwrap().state,
+        CapsuleState::Ready
+    );
+}
@@ -0,0 +1,124 @@
+use std::{collections::BTreeSet, process::Command};
+
+use nexum::{
+    capsule::{Capsule, CapsuleMode},
+    events::EventStore,
+    store::CapsuleStore,
+};
+use serde_json::Value;
+use tempfile::tempdir;
+
+const CAPSULES: u16 = 12;
+const SERVICES: [&str; 2] = ["web", "api"];
+
+#[test]
+fn concurrent_writer_processes_share_capsule_and_event_databases() {
+    let dir = tempdir().unwrap();
+    let capsule_db = dir.path().join("capsules.sqlite3");
+    let events_db = dir.path().join("events.sqlite3");
+
+    {
+        let mut store = CapsuleStore::open(&capsule_db).unwrap();
+        store.set_port_pool("default", 21000, 21199).unwrap();
+        for idx in 0..CAPSULES {
+            store
+                .upsert(Capsule::new(
+                    &format!("cap-stress-{idx}"),
+                    &format!("Stress {idx}"),
+                    CapsuleMode::HostDefault,
+                    idx + 1,
+                ))
+                .unwrap();
+        }
+    }
+    EventStore::open(&events_db).unwrap();
+
+    let nexumctl = assert_cmd::cargo::cargo_bin!("nexumctl");
+    let mut allocations = Vec::new();
+    let mut restores = Vec::new();
+
+    for idx in 0..CAPSULES {
+        for service in SERVICES {
+            let nexumctl = nexumctl.to_path_buf();
+            let capsule_db = capsule_db.clone();
+            allocations.push(std::thread::spawn(move || {
+                Command::new(nexumctl)
+                    .arg("capsule")
+                    .arg("allocate-port")
+                    .arg("--db")
+                    .arg(&capsule_db)
+                    .arg("--id")
+                    .arg(format!("cap-stress-{idx}"))
+                    .arg("--service")
+                    .arg(service)
+                    .output()
+                    .unwrap()
+            }));
+        }
+
+        let nexumctl = nexumctl.to_path_buf();
+        let capsule_db = capsule_db.clone();
+        let events_db = events_db.clone();
+        let tls_dir = dir.path().join(format!("tls-{idx}"));
+        restores.push(std::thread::spawn(move || {
+            Command::new(nexumctl)
+                .arg("run")
+                .arg("restore-capsule")
+                .arg("--capsule-db")
+                .arg(&capsule_db)
+                .arg("--capsule-id")
+                .arg(format!("cap-stress-{idx}"))
+                .arg("--signal")
+                .arg("passive_completion")
+                .arg("--upstream")
+                .arg(format!("127.0.0.1:{}", 22000 + idx))
+                .arg("--terminal")
+                .arg(format!("cd /workspace/stress-{idx}"))
+                .arg("--editor")
+                .arg(format!("/workspace/stress-{idx}"))
+                .arg("--tls-dir")
+                .arg(&tls_dir)
+                .arg("--events-db")
+                .arg(&events_db)
+                .output()
+                .unwrap()
+        }));
+    }
+
+    for handle in allocations {
+        let out = handle.join().unwrap();
+        assert!(
+            out.status.success(),
+            "allocate-port failed: {}",
+            String::from_utf8_lossy(&out.stderr)
+        );
+        let value: Value = serde_json::from_slice(&out.stdout).unwrap();
+        assert!(value["port"].is_u64());
+    }
+
+    let mut events_written = 0;
+    for handle in restores {
+        let out = handle.join().unwrap();
+        assert!(
+            out.status.success(),
+            "restore-capsule failed: {}",
+            String::from_utf8_lossy(&out.stderr)
+        );
+        let value: Value = serde_json::from_slice(&out.stdout).unwrap();
+        events_written += value["events_written"].as_u64().unwrap() as usize;
+    }
+
+    let store = CapsuleStore::open(&capsule_db).unwrap();
+    let events = EventStore::open(&events_db).unwrap();
+    let mut ports = BTreeSet::new();
+    let mut recorded_events = 0;
+    for idx in 0..CAPSULES {
+        let capsule_id = format!("cap-stress-{idx}");
+        let service_ports = store.list_service_ports(&capsule_id).unwrap();
+        assert_eq!(service_ports.len(), SERVICES.len());
+        ports.extend(service_ports.into_values());
+        recorded_events += events.list_for_capsule(&capsule_id).unwrap().len();
+    }
+    assert_eq!(ports.len(), CAPSULES as usize * SERVICES.len());
+    assert_eq!(recorded_events, events_written);
+}
@@ -1,6 +1,9 @@
 use nexum::{
-    capsule::{Capsule, CapsuleMode},
-    store::{CapsuleStore, StoreError},
+    capsule::{Capsule, CapsuleMode, CapsuleState, TransitionError},
+    store::{
+        CapsuleActivity, CapsuleStore, PortPool, SECRET_MASK, Scene, SceneMember, StoreError,
+        WorkspaceCollision, parse_capsules_yaml,
+    },
 };
 use tempfile::tempdir;
 
@@ -73,7 +76,12 @@ fn store_supports_lifecycle_state_transition() {
         .unwrap();
 
     store
-        .transition_state("cap-store-4", nexum::capsule::CapsuleState::Degraded)
+        .transition_state(
+            "cap-store-4",
+            nexum::capsule::CapsuleState::Degraded,
+            "test",
+            "probe failed",
+        )
         .unwrap();
 
     let loaded = store.get("cap-store-4").unwrap().unwrap();
@@ -156,3 +164,686 @@ fn store_rejects_slug_change_for_existing_capsule() {
     let error = store.upsert(capsule).unwrap_err();
     assert!(matches!(error, StoreError::ImmutableSlug { .. }));
 }
+
+#[test]
+fn store_rejects_illegal_transitions_and_records_history() {
+    let dir = tempdir().unwrap();
+    let db = dir.path().join("capsules.sqlite3");
+
+    let mut store = CapsuleStore::open(&db).unwrap();
+    store
+        .upsert(Capsule::new(
+            "cap-store-7",
+            "History Capsule",
+            CapsuleMode::HostDefault,
+            5,
+        ))
+        .unwrap();
+
+    store
+        .transition_state("cap-store-7", CapsuleState::Archived, "test", "retired")
+        .unwrap();
+    let rejected = store.transition_state("cap-store-7", CapsuleState::Restoring, "test", "oops");
+    assert!(matches!(
+        rejected,
+        Err(StoreError::IllegalTransition {
+            source: TransitionError {
+                from: CapsuleState::Archived,
+                to: CapsuleState::Restoring,
+            },
+            ..
+        })
+    ));
+    assert_eq!(
+        store.get("cap-store-7").unwrap().unwrap().state,
+        CapsuleState::Archived
+    );
+
+    let unchanged = store
+        .transition_state("cap-store-7", CapsuleState::Archived, "test", "again")
+        .unwrap();
+    assert!(unchanged.is_none());
+
+    let history = store.history("cap-store-7").unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].from_state, CapsuleState::Ready);
+    assert_eq!(history[0].to_state, CapsuleState::Archived);
+    assert_eq!(history[0].component, "test");
+    assert_eq!(history[0].reason, "retired");
+
+    assert!(matches!(
+        store.transition_state("cap-missing", CapsuleState::Ready, "test", "none"),
+        Err(StoreError::CapsuleNotFound(id)) if id == "cap-missing"
+    ));
+}
+
+#[test]
+fn upsert_keeps_the_stored_state_of_an_existing_capsule() {
+    let dir = tempdir().unwrap();
+    let mut store = CapsuleStore::open(&dir.path().join("capsules.sqlite3")).unwrap();
+    let capsule = Capsule::new("cap-store-8", "Parked", CapsuleMode::HostDefault, 6);
+    store.upsert(capsule.clone()).unwrap();
+    store
+        .transition_state("cap-store-8", CapsuleState::Archived, "test", "parked")
+        .unwrap();
+
+    let mut renamed = capsule.with_repo_path("/workspace/parked");
+    renamed.state = CapsuleState::Restoring;
+    store.upsert(renamed).unwrap();
+
+    let stored = store.get("cap-store-8").unwrap().unwrap();
+    assert_eq!(stored.state, CapsuleState::Archived);
+    assert_eq!(stored.repo_path, "/workspace/parked");
+    assert_eq!(store.history("cap-store-8").unwrap().len(), 1);
+}
+
+#[test]
+fn import_plans_and_applies_exported_capsules() {
+    let dir = tempdir().unwrap();
+    let source = dir.path().join("source.sqlite3");
+    let target = dir.path().join("target.sqlite3");
+
+    let mut source_store = CapsuleStore::open(&source).unwrap();
+    source_store
+        .upsert(Capsule::new(
+            "cap-imp-1",
+            "Import One",
+            CapsuleMode::HostDefault,
+            1,
+        ))
+        .unwrap();
+    source_store
+        .upsert(
+            Capsule::new("cap-imp-2", "Import Two", CapsuleMode::IsolatedNixShell, 2)
+                .with_repo_path("/workspace/import-two"),
+        )
+        .unwrap();
+    let capsules = parse_capsules_yaml(&source_store.export_yaml().unwrap()).unwrap();
+
+    let mut target_store = CapsuleStore::open(&target).unwrap();
+    target_store
+        .upsert(Capsule::new(
+            "cap-imp-1",
+            "Import One",
+            CapsuleMode::HostDefault,
+            9,
+        ))
+        .unwrap();
+
+    let plan = target_store.plan_import(&capsules).unwrap();
+    assert_eq!(plan.creates, vec!["cap-imp-2"]);
+    assert_eq!(plan.updates, vec!["cap-imp-1"]);
+    assert!(plan.conflicts.is_empty());
+    assert_eq!(target_store.get("cap-imp-1").unwrap().unwrap().workspace, 9);
+
+    target_store.import(&capsules).unwrap();
+    assert_eq!(target_store.list().unwrap(), source_store.list().unwrap());
+
+    let replanned = target_store.plan_import(&capsules).unwrap();
+    assert_eq!(replanned.unchanged, vec!["cap-imp-1", "cap-imp-2"]);
+}
+
+#[test]
+fn import_with_slug_conflict_applies_nothing() {
+    let dir = tempdir().unwrap();
+    let db = dir.path().join("capsules.sqlite3");
+
+    let mut store = CapsuleStore::open(&db).unwrap();
+    store
+        .upsert(Capsule::new(
+            "cap-imp-3",
+            "Original",
+            CapsuleMode::HostDefault,
+            1,
+        ))
+        .unwrap();
+    let capsules = vec![
+        Capsule::new("cap-imp-4", "Fresh", CapsuleMode::HostDefault, 2),
+        Capsule::new("cap-imp-3", "Renamed Slug", CapsuleMode::HostDefault, 1),
+    ];
+
+    let plan = store.plan_import(&capsules).unwrap();
+    assert_eq!(plan.conflicts.len(), 1);
+    assert_eq!(plan.conflicts[0].capsule_id, "cap-imp-3");
+    assert_eq!(plan.conflicts[0].code, "immutable_slug");
+
+    assert!(matches!(
+        store.import(&capsules),
+        Err(StoreError::ImmutableSlug { capsule_id, .. }) if capsule_id == "cap-imp-3"
+    ));
+    assert!(store.get("cap-imp-4").unwrap().is_none());
+}
+
+#[test]
+fn store_allocates_named_service_ports_within_quota() {
+    let dir = tempdir().unwrap();
+    let db = dir.path().join("capsules.sqlite3");
+
+    let mut store = CapsuleStore::open(&db).unwrap();
+    let web = store
+        .allocate_service_port("cap-svc", "web", 6300, 6310, 2)
+        .unwrap();
+    let api = store
+        .allocate_service_port("cap-svc", "api", 6300, 6310, 2)
+        .unwrap();
+    assert_eq!(web, Some(6300));
+    assert_eq!(api, Some(6301));
+    assert_eq!(
+        store
+            .allocate_service_port("cap-svc", "web", 6300, 6310, 2)
+            .unwrap(),
+        web
+    );
+
+    assert!(matches!(
+        store.allocate_service_port("cap-svc", "db", 6300, 6310, 2),
+        Err(StoreError::PortQuotaExceeded { quota: 2, .. })
+    ));
+    assert!(matches!(
+        store.allocate_service_port("cap-svc", "Web Admin", 6300, 6310, 2),
+        Err(StoreError::InvalidServiceName(_))
+    ));
+
+    let ports = store.list_service_ports("cap-svc").unwrap();
+    assert_eq!(ports.get("web"), Some(&6300));
+    assert_eq!(ports.get("api"), Some(&6301));
+
+    assert_eq!(store.release_service_port("cap-svc", "api").unwrap(), 1);
+    assert_eq!(store.list_ports("cap-svc").unwrap(), vec![6300]);
+}
+
+#[test]
+fn store_rejects_service_names_sharing_an_env_var() {
+    let dir = tempdir().unwrap();
+    let mut store = CapsuleStore::open(&dir.path().join("capsules.sqlite3")).unwrap();
+
+    store
+        .allocate_service_port("cap-env", "admin-api", 6320, 6330, 4)
+        .unwrap();
+    match store.allocate_service_port("cap-env", "admin_api", 6320, 6330, 4) {
+        Err(StoreError::ServiceEnvCollision {
+            existing, env_var, ..
+        }) => {
+            assert_eq!(existing, "admin-api");
+            assert_eq!(env_var, "NEXUM_PORT_ADMIN_API");
+        }
+        other => panic!("unexpected result: {other:?}"),
+    }
+
+    assert!(
+        store
+            .allocate_service_port("cap-other", "admin_api", 6320, 6330, 4)
+            .unwrap()
+            .is_some()
+    );
+}
+
+#[test]
+fn store_skips_ports_already_bound_on_the_host() {
+    let dir = tempdir().unwrap();
+    let db = dir.path().join("capsules.sqlite3");
+    let busy = std::net::TcpListener::bind("0.0.0.0:0").unwrap();
+    let port = busy.local_addr().unwrap().port();
+
+    let mut store = CapsuleStore::open(&db).unwrap();
+    assert_eq!(
+        store
+            .allocate_service_port("cap-host", "web", port, port, 4)
+            .unwrap(),
+        None
+    );
+
+    drop(busy);
+    assert_eq!(
+        store
+            .allocate_service_port("cap-host", "web", port, port, 4)
+            .unwrap(),
+        Some(port)
+    );
+}
+
+#[test]
+fn store_allocates_from_persisted_pool_skipping_reservations() {
+    let dir = tempdir().unwrap();
+    let db = dir.path().join("capsules.sqlite3");
+
+    {
+        let mut store = CapsuleStore::open(&db).unwrap();
+        store.set_port_pool("default", 6400, 6409).unwrap();
+        store.reserve_port(6400, "system proxy").unwrap();
+    }
+
+    let mut store = CapsuleStore::open(&db).unwrap();
+    assert_eq!(
+        store.port_pool("default").unwrap(),
+        Some(PortPool {
+            name: "default".to_string(),
+            start: 6400,
+            end: 6409,
+        })
+    );
+    assert_eq!(
+        store
+            .allocate_pool_port("cap-pool", "web", "default", 4)
+            .unwrap(),
+        Some(6401)
+    );
+    assert!(matches!(
+        store.allocate_pool_port("cap-pool", "api", "missing", 4),
+        Err(StoreError::PortPoolNotFound(_))
+    ));
+    assert!(matches!(
+        store.allocate_service_port("cap-pool", "api", 7000, 7005, 4),
+        Err(StoreError::RangeOutsidePool {
+            start: 7000,
+            end: 7005
+        })
+    ));
+    assert!(matches!(
+        store.reserve_port(6401, "taken"),
+        Err(StoreError::PortAllocated { port: 6401, .. })
+    ));
+    assert!(matches!(
+        store.set_port_pool("extra", 6405, 6420),
+        Err(StoreError::PortPoolOverlap { .. })
+    ));
+
+    assert!(store.unreserve_port(6400).unwrap());
+    assert_eq!(
+        store
+            .allocate_pool_port("cap-pool", "api", "default", 4)
+            .unwrap(),
+        Some(6400)
+    );
+}
+
+#[test]
+fn store_assigns_free_workspaces_and_reports_collisions() {
+    let dir = tempdir().unwrap();
+    let db = dir.path().join("capsules.sqlite3");
+
+    let mut store = CapsuleStore::open(&db).unwrap();
+    assert_eq!(store.next_free_workspace().unwrap(), 1);
+    store
+        .upsert(Capsule::new(
+            "cap-ws-1",
+            "Ws One",
+            CapsuleMode::HostDefault,
+            1,
+        ))
+        .unwrap();
+    store
+        .upsert(Capsule::new(
+            "cap-ws-2",
+            "Ws Two",
+            CapsuleMode::HostDefault,
+            2,
+        ))
+        .unwrap();
+    store.set_workspace_shared(3, true).unwrap();
+    assert_eq!(store.next_free_workspace().unwrap(), 4);
+
+    assert!(store.check_workspace("cap-ws-1", 1).is_ok());
+    assert!(store.check_workspace("cap-ws-new", 3).is_ok());
+    assert!(matches!(
+        store.check_workspace("cap-ws-new", 2),
+        Err(StoreError::WorkspaceCollision { workspace: 2, .. })
+    ));
+
+    store
+        .upsert(Capsule::new(
+            "cap-ws-3",
+            "Ws Three",
+            CapsuleMode::HostDefault,
+            2,
+        ))
+        .unwrap();
+    assert_eq!(
+        store.workspace_collisions().unwrap(),
+        vec![WorkspaceCollision {
+            workspace: 2,
+            capsule_ids: vec!["cap-ws-2".to_string(), "cap-ws-3".to_string()],
+        }]
+    );
+
+    store
+        .transition_state("cap-ws-3", CapsuleState::Archived, "test", "parked")
+        .unwrap();
+    assert!(store.workspace_collisions().unwrap().is_empty());
+
+    store
+        .transition_state("cap-ws-3", CapsuleState::Ready, "test", "back")
+        .unwrap();
+    store.set_workspace_shared(2, true).unwrap();
+    assert!(store.workspace_collisions().unwrap().is_empty());
+}
+
+#[test]
+fn store_keeps_capsule_env_and_seals_secrets() {
+    let dir = tempdir().unwrap();
+    let db = dir.path().join("capsules.sqlite3");
+
+    let mut store = CapsuleStore::open(&db).unwrap();
+    store
+        .upsert(Capsule::new("cap-env", "Env", CapsuleMode::HostDefault, 1))
+        .unwrap();
+    store.set_env("cap-env", "LOG_LEVEL", "debug").unwrap();
+    store
+        .set_secret("cap-env", "API_KEY", "sk-live-123", "hunter2")
+        .unwrap();
+    assert!(matches!(
+        store.set_env("cap-env", "NEXUM_CAPSULE_ID", "x"),
+        Err(StoreError::InvalidEnvKey(_))
+    ));
+
+    let listed = store.list_env("cap-env").unwrap();
+    assert_eq!(listed.len(), 2);
+    assert_eq!(listed[0].key, "API_KEY");
+    assert_eq!(listed[0].value, SECRET_MASK);
+    assert!(listed[0].secret);
+    assert_eq!(listed[1].value, "debug");
+
+    let raw = std::fs::read(&db).unwrap();
+    assert!(!raw.windows(11).any(|window| window == b"sk-live-123"));
+
+    assert_eq!(
+        store
+            .reveal_secret("cap-env", "API_KEY", "hunter2")
+            .unwrap(),
+        Some("sk-live-123".to_string())
+    );
+    assert!(matches!(
+        store.reveal_secret("cap-env", "API_KEY", "wrong"),
+        Err(StoreError::Crypto(_))
+    ));
+    assert_eq!(
+        store
+            .reveal_secret("cap-env", "LOG_LEVEL", "hunter2")
+            .unwrap(),
+        None
+    );
+
+    assert!(store.unset_env("cap-env", "LOG_LEVEL").unwrap());
+    store.delete("cap-env").unwrap();
+    assert!(store.list_env("cap-env").unwrap().is_empty());
+}
+
+#[test]
+fn store_finds_capsule_by_longest_repo_path_prefix() {
+    let dir = tempdir().unwrap();
+    let db = dir.path().join("capsules.sqlite3");
+    let mono = dir.path().join("mono");
+    let service = mono.join("services/billing");
+    std::fs::create_dir_all(service.join("src")).unwrap();
+    std::fs::create_dir_all(dir.path().join("mono-docs")).unwrap();
+
+    let mut store = CapsuleStore::open(&db).unwrap();
+    store
+        .upsert(
+            Capsule::new("cap-mono", "Mono", CapsuleMode::HostDefault, 1)
+                .with_repo_path(&mono.display().to_string()),
+        )
+        .unwrap();
+    store
+        .upsert(
+            Capsule::new("cap-billing", "Billing", CapsuleMode::HostDefault, 2)
+                .with_repo_path(&service.display().to_string()),
+        )
+        .unwrap();
+
+    let find = |path: &std::path::Path| {
+        store
+            .find_by_path(path)
+            .unwrap()
+            .map(|capsule| capsule.capsule_id)
+    };
+    assert_eq!(find(&service.join("src")), Some("cap-billing".to_string()));
+    assert_eq!(find(&mono.join("services")), Some("cap-mono".to_string()));
+    assert_eq!(find(&mono), Some("cap-mono".to_string()));
+    assert_eq!(find(&dir.path().join("mono-docs")), None);
+    assert_eq!(find(dir.path()), None);
+}
+
+#[test]
+fn store_orders_capsules_by_most_recent_restore() {
+    let dir = tempdir().unwrap();
+    let db = dir.path().join("capsules.sqlite3");
+
+    let mut store = CapsuleStore::open(&db).unwrap();
+    for (id, workspace) in [("cap-a", 1), ("cap-b", 2), ("cap-c", 3), ("cap-d", 4)] {
+        store
+            .upsert(Capsule::new(id, id, CapsuleMode::HostDefault, workspace))
+            .unwrap();
+    }
+    store
+        .record_restore("cap-c", "needs_decision", 1_000)
+        .unwrap();
+    store
+        .record_restore("cap-b", "passive_completion", 2_000)
+        .unwrap();
+    store
+        .record_restore("cap-c", "critical_failure", 3_000)
+        .unwrap();
+
+    let recent = store
+        .list_recent()
+        .unwrap()
+        .into_iter()
+        .map(|capsule| capsule.capsule_id)
+        .collect::<Vec<_>>();
+    assert_eq!(recent, vec!["cap-c", "cap-b", "cap-a", "cap-d"]);
+    assert_eq!(
+        store.activity("cap-c").unwrap(),
+        CapsuleActivity {
+            last_restored_unix_ms: Some(3_000),
+            restore_count: 2,
+            last_signal: Some("critical_failure".to_string()),
+        }
+    );
+    assert_eq!(store.activity("cap-a").unwrap(), CapsuleActivity::default());
+
+    store.delete("cap-c").unwrap();
+    assert_eq!(store.activity("cap-c").unwrap(), CapsuleActivity::default());
+}
+
+#[test]
+fn store_rejects_capsule_ids_outside_the_charset() {
+    let dir = tempdir().unwrap();
+    let mut store = CapsuleStore::open(&dir.path().join("capsules.sqlite3")).unwrap();
+
+    let err = store
+        .upsert(Capsule::new(
+            "Cap One",
+            "Cap One",
+            CapsuleMode::HostDefault,
+            1,
+        ))
+        .unwrap_err();
+    assert!(matches!(err, StoreError::InvalidCapsuleId(_)));
+    assert!(store.list().unwrap().is_empty());
+
+    let capsules = vec![
+        Capsule::new("cap-ok", "Ok", CapsuleMode::HostDefault, 1),
+        Capsule::new("cap/bad", "Bad", CapsuleMode::HostDefault, 2),
+    ];
+    let plan = store.plan_import(&capsules).unwrap();
+    assert_eq!(plan.creates, vec!["cap-ok"]);
+    assert_eq!(plan.conflicts[0].capsule_id, "cap/bad");
+    assert_eq!(plan.conflicts[0].code, "invalid_id");
+    assert!(store.import(&capsules).is_err());
+    assert!(store.get("cap-ok").unwrap().is_none());
+}
+
+#[test]
+fn store_updates_and_cascades_capsule_labels() {
+    let dir = tempdir().unwrap();
+    let mut store = CapsuleStore::open(&dir.path().join("capsules.sqlite3")).unwrap();
+    for (id, workspace) in [("cap-a", 1), ("cap-b", 2)] {
+        store
+            .upsert(Capsule::new(id, id, CapsuleMode::HostDefault, workspace))
+            .unwrap();
+    }
+
+    let set = |pairs: &[(&str, &str)]| {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect::<Vec<_>>()
+    };
+    store
+        .update_labels("cap-a", &set(&[("team", "infra"), ("tier", "web")]), &[])
+        .unwrap();
+    store
+        .update_labels("cap-b", &set(&[("team", "payments")]), &[])
+        .unwrap();
+    let labels = store
+        .update_labels("cap-a", &set(&[("team", "core")]), &["tier".to_string()])
+        .unwrap();
+    assert_eq!(
+        labels.into_iter().collect::<Vec<_>>(),
+        vec![("team".to_string(), "core".to_string())]
+    );
+    assert_eq!(store.all_labels().unwrap().len(), 2);
+
+    let err = store
+        .update_labels("cap-a", &set(&[("state", "x")]), &[])
+        .unwrap_err();
+    assert!(matches!(err, StoreError::Label(_)));
+    let err = store
+        .update_labels("cap-missing", &set(&[("team", "x")]), &[])
+        .unwrap_err();
+    assert!(matches!(err, StoreError::CapsuleNotFound(_)));
+
+    store.delete("cap-a").unwrap();
+    assert!(store.labels("cap-a").unwrap().is_empty());
+    assert_eq!(
+        store.all_labels().unwrap().into_keys().collect::<Vec<_>>(),
+        vec!["cap-b"]
+    );
+}
+
+#[test]
+fn store_keeps_scenes_in_member_order() {
+    let dir = tempdir().unwrap();
+    let mut store = CapsuleStore::open(&dir.path().join("capsules.sqlite3")).unwrap();
+    for (id, workspace) in [("cap-a", 1), ("cap-b", 2)] {
+        store
+            .upsert(Capsule::new(id, id, CapsuleMode::HostDefault, workspace))
+            .unwrap();
+    }
+    let member = |capsule_id: &str, workspace: Option<u16>| SceneMember {
+        capsule_id: capsule_id.to_string(),
+        workspace,
+    };
+    let scene = Scene {
+        name: "stack".to_string(),
+        members: vec![member("cap-b", Some(4)), member("cap-a", None)],
+    };
+    store.set_scene(&scene).unwrap();
+    assert_eq!(store.scene("stack").unwrap(), Some(scene));
+
+    let invalid = |name: &str, members: Vec<SceneMember>| Scene {
+        name: name.to_string(),
+        members,
+    };
+    assert!(matches!(
+        store.set_scene(&invalid("Stack", vec![member("cap-a", None)])),
+        Err(StoreError::InvalidSceneName(_))
+    ));
+    assert!(matches!(
+        store.set_scene(&invalid("empty", Vec::new())),
+        Err(StoreError::EmptyScene(_))
+    ));
+    assert!(matches!(
+        store.set_scene(&invalid(
+            "dupes",
+            vec![member("cap-a", None), member("cap-a", Some(1))]
+        )),
+        Err(StoreError::DuplicateSceneMember { .. })
+    ));
+    assert!(matches!(
+        store.set_scene(&invalid("ghost", vec![member("cap-ghost", None)])),
+        Err(StoreError::CapsuleNotFound(_))
+    ));
+    assert_eq!(store.list_scenes().unwrap().len(), 1);
+
+    store.delete("cap-b").unwrap();
+    assert_eq!(
+        store.scene("stack").unwrap().unwrap().members,
+        vec![member("cap-a", None)]
+    );
+    assert!(store.delete_scene("stack").unwrap());
+    assert!(store.scene("stack").unwrap().is_none());
+    assert!(store.get("cap-a").unwrap().is_some());
+
+    store
+        .set_scene(&invalid("solo", vec![member("cap-a", None)]))
+        .unwrap();
+    store.delete("cap-a").unwrap();
+    assert!(store.scene("solo").unwrap().is_none());
+    assert!(store.list_scenes().unwrap().is_empty());
+}
+
+#[test]
+fn store_rejects_dependency_cycles() {
+    let dir = tempdir().unwrap();
+    let mut store = CapsuleStore::open(&dir.path().join("capsules.sqlite3")).unwrap();
+    for (id, workspace) in [("cap-a", 1), ("cap-b", 2), ("cap-c", 3)] {
+        store
+            .upsert(Capsule::new(id, id, CapsuleMode::HostDefault, workspace))
+            .unwrap();
+    }
+
+    store.add_dependency("cap-a", "cap-b", None).unwrap();
+    store.add_dependency("cap-a", "cap-b", Some("api")).unwrap();
+    store.add_dependency("cap-b", "cap-c", None).unwrap();
+    let err = store.add_dependency("cap-c", "cap-a", None).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "dependency cycle: cap-c -> cap-a -> cap-b -> cap-c"
+    );
+    assert!(matches!(
+        store.add_dependency("cap-a", "cap-a", Some("web")),
+        Err(StoreError::DependencyCycle { .. })
+    ));
+    assert!(matches!(
+        store.add_dependency("cap-a", "cap-c", Some("Web")),
+        Err(StoreError::InvalidRouteName(_))
+    ));
+    assert!(matches!(
+        store.add_dependency("cap-a", "cap-ghost", None),
+        Err(StoreError::CapsuleNotFound(_))
+    ));
+
+    let dependencies = store.dependencies("cap-a").unwrap();
+    assert_eq!(
+        dependencies
+            .iter()
+            .map(|dependency| dependency.route.as_deref())
+            .collect::<Vec<_>>(),
+        vec![None, Some("api")]
+    );
+    assert!(
+        store
+            .remove_dependency("cap-a", "cap-b", Some("api"))
+            .unwrap()
+    );
+    assert!(
+        !store
+            .remove_dependency("cap-a", "cap-b", Some("api"))
+            .unwrap()
+    );
+
+    assert!(matches!(
+        store.delete("cap-c"),
+        Err(StoreError::HasDependents { dependents, .. }) if dependents == vec!["cap-b"]
+    ));
+    assert!(store.get("cap-c").unwrap().is_some());
+    assert_eq!(store.dependents("cap-b").unwrap(), vec!["cap-a"]);
+    store.delete("cap-a").unwrap();
+    assert!(store.dependents("cap-b").unwrap().is_empty());
+    assert_eq!(store.dependencies("cap-b").unwrap().len(), 1);
+    store
+        .add_dependency("cap-b", "cap-a", Some("web"))
+        .unwrap_err();
+}
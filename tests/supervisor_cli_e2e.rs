@@ -1,6 +1,10 @@
 use std::process::Command;
 
-use nexum::events::{EventStore, RuntimeEvent};
+use nexum::{
+    capsule::{Capsule, CapsuleMode},
+    events::{EventStore, RuntimeEvent},
+    store::CapsuleStore,
+};
 use serde_json::Value;
 use tempfile::tempdir;
 
@@ -228,3 +232,49 @@ fn nexumctl_supervisor_blockers_lists_degraded_or_critical_capsules() {
         Value::String("critical_events_threshold".into())
     );
 }
+
+#[test]
+fn nexumctl_supervisor_status_reports_workspace_collisions() {
+    let dir = tempdir().unwrap();
+    let capsule_db = dir.path().join("capsules.sqlite3");
+    let events_db = dir.path().join("events.sqlite3");
+    let flags_file = dir.path().join("flags.toml");
+
+    let mut store = CapsuleStore::open(&capsule_db).unwrap();
+    store
+        .upsert(Capsule::new(
+            "cap-col-1",
+            "Col One",
+            CapsuleMode::HostDefault,
+            7,
+        ))
+        .unwrap();
+    store
+        .upsert(Capsule::new(
+            "cap-col-2",
+            "Col Two",
+            CapsuleMode::HostDefault,
+            7,
+        ))
+        .unwrap();
+
+    let out = Command::new(assert_cmd::cargo::cargo_bin!("nexumctl"))
+        .arg("supervisor")
+        .arg("status")
+        .arg("--capsule-db")
+        .arg(&capsule_db)
+        .arg("--events-db")
+        .arg(&events_db)
+        .arg("--flags-file")
+        .arg(&flags_file)
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+
+    let json: Value = serde_json::from_slice(&out.stdout).unwrap();
+    assert_eq!(json["workspace_collisions"][0]["workspace"], 7);
+    assert_eq!(
+        json["workspace_collisions"][0]["capsule_ids"],
+        serde_json::json!(["cap-col-1", "cap-col-2"])
+    );
+}
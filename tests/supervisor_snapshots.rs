@@ -1,3 +1,5 @@
+#![allow(clippy::field_reassign_with_default)]
+
 use std::process::Command;
 
 use nexum::{
@@ -32,7 +34,10 @@ fn seed_supervisor_fixtures(
         ))
         .unwrap();
     store
-        .transition_state("cap-snap-2", CapsuleState::Degraded)
+        .transition_state("cap-snap-2", CapsuleState::Degraded, "test", "seeded")
+        .unwrap();
+    store
+        .record_restore("cap-snap-1", "needs_decision", 1000)
         .unwrap();
 
     let mut flags = CutoverFlags::default();
@@ -58,3 +58,259 @@ fn nexumctl_tls_ensure_and_rotate_work() {
         rotated["record"]["fingerprint_sha256"]
     );
 }
+
+#[test]
+fn nexumctl_tls_ensure_accepts_extra_sans() {
+    let dir = tempdir().unwrap();
+    let nexumctl = assert_cmd::cargo::cargo_bin!("nexumctl");
+
+    let ensure = Command::new(nexumctl)
+        .arg("tls")
+        .arg("ensure")
+        .arg("--dir")
+        .arg(dir.path())
+        .arg("--domain")
+        .arg("shop.nexum.local")
+        .arg("--sans")
+        .arg("*.shop.nexum.local,127.0.0.1,::1")
+        .output()
+        .unwrap();
+    assert!(ensure.status.success());
+    let record: Value = serde_json::from_slice(&ensure.stdout).unwrap();
+
+    assert_eq!(
+        record["subject_alt_names"],
+        serde_json::json!(["shop.nexum.local", "*.shop.nexum.local", "127.0.0.1", "::1"])
+    );
+}
+
+#[test]
+fn nexumctl_tls_list_and_prune_cross_check_capsule_store() {
+    let dir = tempdir().unwrap();
+    let tls_dir = dir.path().join("tls");
+    let db = dir.path().join("capsules.sqlite3");
+    let nexumctl = assert_cmd::cargo::cargo_bin!("nexumctl");
+
+    let created = Command::new(nexumctl)
+        .arg("capsule")
+        .arg("create")
+        .arg("--db")
+        .arg(&db)
+        .arg("--id")
+        .arg("cap-tls-owner")
+        .arg("--name")
+        .arg("Owner")
+        .arg("--workspace")
+        .arg("3")
+        .arg("--mode")
+        .arg("host_default")
+        .output()
+        .unwrap();
+    assert!(created.status.success());
+
+    for domain in ["owner.nexum.local", "stale.nexum.local"] {
+        let ensure = Command::new(nexumctl)
+            .arg("tls")
+            .arg("ensure")
+            .arg("--dir")
+            .arg(&tls_dir)
+            .arg("--domain")
+            .arg(domain)
+            .output()
+            .unwrap();
+        assert!(ensure.status.success());
+    }
+
+    let listed = Command::new(nexumctl)
+        .arg("tls")
+        .arg("list")
+        .arg("--dir")
+        .arg(&tls_dir)
+        .arg("--capsule-db")
+        .arg(&db)
+        .output()
+        .unwrap();
+    assert!(listed.status.success());
+    let entries: Value = serde_json::from_slice(&listed.stdout).unwrap();
+    assert_eq!(entries[0]["domain"], "owner.nexum.local");
+    assert_eq!(entries[0]["capsule_id"], "cap-tls-owner");
+    assert_eq!(entries[1]["capsule_id"], Value::Null);
+
+    let dry_run = Command::new(nexumctl)
+        .arg("tls")
+        .arg("prune")
+        .arg("--dir")
+        .arg(&tls_dir)
+        .arg("--capsule-db")
+        .arg(&db)
+        .arg("--dry-run")
+        .arg("true")
+        .output()
+        .unwrap();
+    assert!(dry_run.status.success());
+    let preview: Value = serde_json::from_slice(&dry_run.stdout).unwrap();
+    assert_eq!(preview["pruned"][0]["domain"], "stale.nexum.local");
+    assert!(tls_dir.join("stale.nexum.local.crt.pem").exists());
+
+    let pruned = Command::new(nexumctl)
+        .arg("tls")
+        .arg("prune")
+        .arg("--dir")
+        .arg(&tls_dir)
+        .arg("--capsule-db")
+        .arg(&db)
+        .output()
+        .unwrap();
+    assert!(pruned.status.success());
+    assert!(!tls_dir.join("stale.nexum.local.crt.pem").exists());
+    assert!(tls_dir.join("owner.nexum.local.crt.pem").exists());
+
+    let rotate_all = Command::new(nexumctl)
+        .arg("tls")
+        .arg("rotate-all")
+        .arg("--dir")
+        .arg(&tls_dir)
+        .arg("--threshold-days")
+        .arg("1")
+        .output()
+        .unwrap();
+    assert!(rotate_all.status.success());
+    let rotated: Value = serde_json::from_slice(&rotate_all.stdout).unwrap();
+    assert_eq!(rotated["checked"], 1);
+    assert_eq!(rotated["rotated"], 0);
+}
+
+#[test]
+fn nexumctl_tls_verify_fails_on_mismatch_and_repairs_on_request() {
+    let dir = tempdir().unwrap();
+    let nexumctl = assert_cmd::cargo::cargo_bin!("nexumctl");
+
+    let ensure = Command::new(nexumctl)
+        .arg("tls")
+        .arg("ensure")
+        .arg("--dir")
+        .arg(dir.path())
+        .arg("--domain")
+        .arg("audit.nexum.local")
+        .output()
+        .unwrap();
+    assert!(ensure.status.success());
+    std::fs::remove_file(dir.path().join("audit.nexum.local.key.pem")).unwrap();
+
+    let verify = Command::new(nexumctl)
+        .arg("tls")
+        .arg("verify")
+        .arg("--dir")
+        .arg(dir.path())
+        .output()
+        .unwrap();
+    assert!(!verify.status.success());
+    let reports: Value = serde_json::from_slice(&verify.stdout).unwrap();
+    assert_eq!(reports[0]["findings"][0]["code"], "missing_key");
+
+    let repair = Command::new(nexumctl)
+        .arg("tls")
+        .arg("verify")
+        .arg("--dir")
+        .arg(dir.path())
+        .arg("--domain")
+        .arg("audit.nexum.local")
+        .arg("--repair")
+        .arg("true")
+        .output()
+        .unwrap();
+    assert!(repair.status.success());
+    let repaired: Value = serde_json::from_slice(&repair.stdout).unwrap();
+    assert_eq!(repaired[0]["repaired"], Value::Bool(true));
+    assert!(dir.path().join("audit.nexum.local.key.pem").exists());
+}
+
+#[test]
+fn nexumctl_tls_encrypts_keys_with_passphrase() {
+    let dir = tempdir().unwrap();
+    let nexumctl = assert_cmd::cargo::cargo_bin!("nexumctl");
+
+    let ensure = Command::new(nexumctl)
+        .env("NEXUM_TLS_KEY_PASSPHRASE", "correct horse")
+        .arg("tls")
+        .arg("ensure")
+        .arg("--dir")
+        .arg(dir.path())
+        .arg("--domain")
+        .arg("vault.nexum.local")
+        .output()
+        .unwrap();
+    assert!(ensure.status.success());
+    let record: Value = serde_json::from_slice(&ensure.stdout).unwrap();
+    assert_eq!(record["key_encrypted"], Value::Bool(true));
+    let key = std::fs::read_to_string(dir.path().join("vault.nexum.local.key.pem")).unwrap();
+    assert!(key.contains("BEGIN NEXUM ENCRYPTED PRIVATE KEY"));
+
+    let unlocked = Command::new(nexumctl)
+        .env("NEXUM_TLS_KEY_PASSPHRASE", "correct horse")
+        .arg("tls")
+        .arg("verify")
+        .arg("--dir")
+        .arg(dir.path())
+        .output()
+        .unwrap();
+    assert!(unlocked.status.success());
+
+    let locked = Command::new(nexumctl)
+        .env_remove("NEXUM_TLS_KEY_PASSPHRASE")
+        .arg("tls")
+        .arg("verify")
+        .arg("--dir")
+        .arg(dir.path())
+        .output()
+        .unwrap();
+    assert!(!locked.status.success());
+    let reports: Value = serde_json::from_slice(&locked.stdout).unwrap();
+    assert_eq!(reports[0]["findings"][0]["code"], "key_locked");
+}
+
+#[test]
+fn nexumctl_tls_repair_keeps_encrypted_key_without_passphrase() {
+    let dir = tempdir().unwrap();
+    let nexumctl = assert_cmd::cargo::cargo_bin!("nexumctl");
+
+    let ensure = Command::new(nexumctl)
+        .env("NEXUM_TLS_KEY_PASSPHRASE", "correct horse")
+        .arg("tls")
+        .arg("ensure")
+        .arg("--dir")
+        .arg(dir.path())
+        .arg("--domain")
+        .arg("locked.nexum.local")
+        .output()
+        .unwrap();
+    assert!(ensure.status.success());
+    let key_path = dir.path().join("locked.nexum.local.key.pem");
+    let key = std::fs::read_to_string(&key_path).unwrap();
+
+    let repair = Command::new(nexumctl)
+        .env_remove("NEXUM_TLS_KEY_PASSPHRASE")
+        .arg("tls")
+        .arg("verify")
+        .arg("--dir")
+        .arg(dir.path())
+        .arg("--repair")
+        .arg("true")
+        .output()
+        .unwrap();
+    assert!(!repair.status.success());
+    let reports: Value = serde_json::from_slice(&repair.stdout).unwrap();
+    assert_eq!(reports[0]["findings"][0]["code"], "key_locked");
+    assert_eq!(reports[0]["repaired"], Value::Bool(false));
+    assert_eq!(std::fs::read_to_string(&key_path).unwrap(), key);
+
+    let unlocked = Command::new(nexumctl)
+        .env("NEXUM_TLS_KEY_PASSPHRASE", "correct horse")
+        .arg("tls")
+        .arg("verify")
+        .arg("--dir")
+        .arg(dir.path())
+        .output()
+        .unwrap();
+    assert!(unlocked.status.success());
+}
@@ -1,4 +1,11 @@
-use nexum::tls::{ensure_self_signed_cert, rotate_if_expiring};
+use nexum::{
+    capsule::{Capsule, CapsuleMode, CapsuleState},
+    tls::{
+        capsule_subject_alt_names, ensure_cert_with_sans, ensure_client_cert,
+        ensure_self_signed_cert, inventory, prune_orphans, rotate_all_expiring, rotate_if_expiring,
+        verify, verify_client_cert,
+    },
+};
 use tempfile::tempdir;
 
 #[test]
@@ -35,3 +42,274 @@ fn does_not_rotate_when_not_expiring() {
     assert!(!rotate.rotated);
     assert_eq!(first.fingerprint_sha256, rotate.record.fingerprint_sha256);
 }
+
+#[test]
+fn reissues_cert_when_requested_san_set_changes() {
+    let dir = tempdir().unwrap();
+    let domain = "multi.nexum.local";
+
+    let single = ensure_self_signed_cert(dir.path(), domain, 30).unwrap();
+    assert_eq!(single.subject_alt_names, vec![domain.to_string()]);
+
+    let sans = capsule_subject_alt_names(domain);
+    let multi = ensure_cert_with_sans(dir.path(), domain, &sans, 30).unwrap();
+    assert_ne!(single.fingerprint_sha256, multi.fingerprint_sha256);
+    assert_eq!(multi.subject_alt_names, sans);
+
+    let mut reordered = sans.clone();
+    reordered.reverse();
+    let reused = ensure_cert_with_sans(dir.path(), domain, &reordered, 30).unwrap();
+    assert_eq!(multi.fingerprint_sha256, reused.fingerprint_sha256);
+}
+
+#[test]
+fn ensure_without_sans_keeps_the_existing_san_set() {
+    let dir = tempdir().unwrap();
+    let domain = "keep.nexum.local";
+    let sans = capsule_subject_alt_names(domain);
+
+    let restored = ensure_cert_with_sans(dir.path(), domain, &sans, 30).unwrap();
+    let plain = ensure_self_signed_cert(dir.path(), domain, 30).unwrap();
+    let unlisted = ensure_cert_with_sans(dir.path(), domain, &[], 30).unwrap();
+    assert_eq!(plain.fingerprint_sha256, restored.fingerprint_sha256);
+    assert_eq!(unlisted.fingerprint_sha256, restored.fingerprint_sha256);
+    assert_eq!(plain.subject_alt_names, sans);
+
+    let narrowed = ensure_cert_with_sans(dir.path(), domain, &[domain.to_string()], 30).unwrap();
+    assert_eq!(narrowed.subject_alt_names, vec![domain.to_string()]);
+}
+
+#[test]
+fn rotation_preserves_recorded_sans() {
+    let dir = tempdir().unwrap();
+    let domain = "wild.nexum.local";
+    let sans = capsule_subject_alt_names(domain);
+
+    ensure_cert_with_sans(dir.path(), domain, &sans, 1).unwrap();
+    let rotate = rotate_if_expiring(dir.path(), domain, 2).unwrap();
+
+    assert!(rotate.rotated);
+    assert_eq!(rotate.record.subject_alt_names, sans);
+}
+
+#[test]
+fn inventory_resolves_owning_capsule_and_prune_removes_orphans() {
+    let dir = tempdir().unwrap();
+    let live = Capsule::new("cap-live", "Live", CapsuleMode::HostDefault, 1);
+    let mut archived = Capsule::new("cap-old", "Old", CapsuleMode::HostDefault, 2);
+    archived.transition_state(CapsuleState::Archived).unwrap();
+    let capsules = vec![live.clone(), archived.clone()];
+
+    ensure_self_signed_cert(dir.path(), &live.domain(), 30).unwrap();
+    ensure_self_signed_cert(dir.path(), "api.live.nexum.local", 30).unwrap();
+    ensure_self_signed_cert(dir.path(), &archived.domain(), 30).unwrap();
+    ensure_self_signed_cert(dir.path(), "ghost.nexum.local", 30).unwrap();
+
+    let listed = inventory(dir.path(), &capsules).unwrap();
+    assert_eq!(listed.len(), 4);
+    let api = listed
+        .iter()
+        .find(|entry| entry.domain == "api.live.nexum.local")
+        .unwrap();
+    assert_eq!(api.capsule_id.as_deref(), Some("cap-live"));
+    assert!(api.remaining_days >= 29);
+
+    let preview = prune_orphans(dir.path(), &capsules, true).unwrap();
+    assert_eq!(preview.kept, 2);
+    assert_eq!(preview.pruned.len(), 2);
+    assert!(dir.path().join("ghost.nexum.local.crt.pem").exists());
+
+    let pruned = prune_orphans(dir.path(), &capsules, false).unwrap();
+    let reasons = pruned
+        .pruned
+        .iter()
+        .map(|entry| (entry.domain.as_str(), entry.reason.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        reasons,
+        vec![
+            ("ghost.nexum.local", "unknown_capsule"),
+            ("old.nexum.local", "archived_capsule"),
+        ]
+    );
+    assert!(!dir.path().join("ghost.nexum.local.crt.pem").exists());
+    assert!(!dir.path().join("old.nexum.local.meta.json").exists());
+    assert!(dir.path().join("live.nexum.local.key.pem").exists());
+}
+
+#[test]
+fn prune_keeps_material_shared_with_a_live_capsule() {
+    let dir = tempdir().unwrap();
+    let mut archived = Capsule::new("cap-old-shared", "Shared", CapsuleMode::HostDefault, 1);
+    archived.transition_state(CapsuleState::Archived).unwrap();
+    let live = Capsule::new("cap-new-shared", "Shared", CapsuleMode::HostDefault, 2);
+    ensure_self_signed_cert(dir.path(), &live.domain(), 30).unwrap();
+
+    let report = prune_orphans(dir.path(), &[archived, live], false).unwrap();
+
+    assert_eq!(report.kept, 1);
+    assert!(report.pruned.is_empty());
+    assert!(dir.path().join("shared.nexum.local.crt.pem").exists());
+}
+
+#[test]
+fn rotate_all_only_rotates_expiring_material() {
+    let dir = tempdir().unwrap();
+
+    ensure_self_signed_cert(dir.path(), "soon.nexum.local", 1).unwrap();
+    ensure_self_signed_cert(dir.path(), "later.nexum.local", 30).unwrap();
+
+    let outcomes = rotate_all_expiring(dir.path(), 2).unwrap();
+    let rotated = outcomes
+        .iter()
+        .filter(|outcome| outcome.rotated)
+        .map(|outcome| outcome.record.domain.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(rotated, vec!["soon.nexum.local"]);
+}
+
+#[test]
+fn verify_accepts_freshly_issued_material() {
+    let dir = tempdir().unwrap();
+    let domain = "clean.nexum.local";
+    ensure_cert_with_sans(dir.path(), domain, &capsule_subject_alt_names(domain), 30).unwrap();
+
+    let report = verify(dir.path(), domain, false).unwrap();
+    assert!(report.ok, "{:?}", report.findings);
+    assert!(report.findings.is_empty());
+}
+
+#[test]
+fn verify_detects_tampering_and_repairs_only_on_request() {
+    let dir = tempdir().unwrap();
+    let domain = "tamper.nexum.local";
+    let original = ensure_self_signed_cert(dir.path(), domain, 30).unwrap();
+    ensure_self_signed_cert(dir.path(), "donor.nexum.local", 30).unwrap();
+
+    std::fs::copy(
+        dir.path().join("donor.nexum.local.key.pem"),
+        dir.path().join("tamper.nexum.local.key.pem"),
+    )
+    .unwrap();
+    let mut meta = original.clone();
+    meta.fingerprint_sha256 = "deadbeef".into();
+    meta.expires_unix_ms += 86_400_000;
+    std::fs::write(
+        dir.path().join("tamper.nexum.local.meta.json"),
+        serde_json::to_vec_pretty(&meta).unwrap(),
+    )
+    .unwrap();
+
+    let report = verify(dir.path(), domain, false).unwrap();
+    let codes = report
+        .findings
+        .iter()
+        .map(|finding| finding.code.as_str())
+        .collect::<Vec<_>>();
+    assert!(!report.ok);
+    assert!(!report.repaired);
+    assert_eq!(
+        codes,
+        vec!["key_mismatch", "fingerprint_mismatch", "expiry_mismatch"]
+    );
+
+    let repaired = verify(dir.path(), domain, true).unwrap();
+    assert!(repaired.ok);
+    assert!(repaired.repaired);
+    assert_ne!(
+        repaired.record.unwrap().fingerprint_sha256,
+        original.fingerprint_sha256
+    );
+    assert!(verify(dir.path(), domain, false).unwrap().ok);
+}
+
+#[test]
+fn verify_reports_expired_certificate_and_repairs_it() {
+    let dir = tempdir().unwrap();
+    let domain = "stale.nexum.local";
+    ensure_self_signed_cert(dir.path(), domain, 0).unwrap();
+
+    let report = verify(dir.path(), domain, false).unwrap();
+    assert!(!report.ok);
+    assert_eq!(report.findings[0].code, "expired");
+
+    let repaired = verify(dir.path(), domain, true).unwrap();
+    assert!(repaired.repaired);
+    assert!(verify(dir.path(), domain, false).unwrap().ok);
+}
+
+#[test]
+fn verify_reports_corrupt_certificate() {
+    let dir = tempdir().unwrap();
+    let domain = "corrupt.nexum.local";
+    ensure_self_signed_cert(dir.path(), domain, 30).unwrap();
+    std::fs::write(dir.path().join("corrupt.nexum.local.crt.pem"), "not a pem").unwrap();
+
+    let report = verify(dir.path(), domain, false).unwrap();
+    assert_eq!(report.findings[0].code, "corrupt_cert");
+}
+
+#[cfg(unix)]
+#[test]
+fn private_keys_are_written_owner_only_without_temp_leftovers() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempdir().unwrap();
+    ensure_self_signed_cert(dir.path(), "perm.nexum.local", 30).unwrap();
+
+    let mode = std::fs::metadata(dir.path().join("perm.nexum.local.key.pem"))
+        .unwrap()
+        .permissions()
+        .mode();
+    assert_eq!(mode & 0o777, 0o600);
+
+    let leftovers = std::fs::read_dir(dir.path())
+        .unwrap()
+        .filter_map(|entry| entry.unwrap().file_name().into_string().ok())
+        .filter(|name| name.contains(".tmp."))
+        .count();
+    assert_eq!(leftovers, 0);
+}
+
+#[test]
+fn concurrent_ensure_calls_converge_on_one_certificate() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_path_buf();
+
+    let handles = (0..8)
+        .map(|_| {
+            let path = path.clone();
+            std::thread::spawn(move || {
+                ensure_self_signed_cert(&path, "race.nexum.local", 30)
+                    .unwrap()
+                    .fingerprint_sha256
+            })
+        })
+        .collect::<Vec<_>>();
+    let mut fingerprints = handles
+        .into_iter()
+        .map(|handle| handle.join().unwrap())
+        .collect::<Vec<_>>();
+    fingerprints.dedup();
+
+    assert_eq!(fingerprints.len(), 1);
+    assert!(verify(dir.path(), "race.nexum.local", false).unwrap().ok);
+}
+
+#[test]
+fn client_certificates_identify_their_capsule_and_foreign_certs_are_rejected() {
+    let dir = tempdir().unwrap();
+
+    let first = ensure_client_cert(dir.path(), "cap-caller", 30).unwrap();
+    let again = ensure_client_cert(dir.path(), "cap-caller", 30).unwrap();
+    assert_eq!(first.fingerprint_sha256, again.fingerprint_sha256);
+
+    let pem = std::fs::read_to_string(&first.cert_path).unwrap();
+    let verified = verify_client_cert(dir.path(), &pem).unwrap();
+    assert_eq!(verified.capsule_id, "cap-caller");
+    assert_eq!(verified.fingerprint_sha256, first.fingerprint_sha256);
+
+    let foreign = ensure_self_signed_cert(dir.path(), "cap-caller.nexum.local", 30).unwrap();
+    let foreign_pem = std::fs::read_to_string(&foreign.cert_path).unwrap();
+    assert!(verify_client_cert(dir.path(), &foreign_pem).is_err());
+}
@@ -9,6 +9,12 @@ fn snapshot_tls_record_contract() {
         fingerprint_sha256: "abc123".into(),
         created_unix_ms: 1,
         expires_unix_ms: 2,
+        subject_alt_names: vec![
+            "agent.nexum.local".into(),
+            "*.agent.nexum.local".into(),
+            "127.0.0.1".into(),
+        ],
+        key_encrypted: false,
     };
 
     insta::assert_yaml_snapshot!("tls_record_contract", record);